@@ -1,3 +1,3 @@
-const API_LOGIN_PATH = '/api/login';
+const API_LOGIN_PATH = '/api/sessions';
 
 export {API_LOGIN_PATH};
@@ -0,0 +1,83 @@
+// タイムラインクエリのベンチマーク
+// cargo bench --bench timeline で実行する
+// 環境変数RUITTER_DATABASE_URLを指定するとそのDBで計測する(未指定ならSQLiteのインメモリDB)
+// MySQLで計測する場合は空のテスト用DBを指定すること
+use ruitter::models::{
+    create_tokio_runtime, FollowRelation, Role, User, UserTweet, DATABASE_URL_ENV,
+};
+use ruitter::repositories::{connect, SharedRepository};
+use std::time::{Duration, Instant};
+
+// 計測するフォロイー数
+const FOLLOWEE_COUNTS: [usize; 4] = [0, 1, 1_000, 50_000];
+// 1ケースあたりの計測回数
+const ITERATIONS: u32 = 20;
+
+fn main() -> anyhow::Result<()> {
+    let tokio_rt = create_tokio_runtime();
+    tokio_rt.block_on(run())
+}
+
+async fn run() -> anyhow::Result<()> {
+    let url = std::env::var(DATABASE_URL_ENV).unwrap_or_else(|_| "sqlite::memory:".to_string());
+    let (repository, _session_store) = connect(&url).await?;
+    repository.setup_tables().await?;
+
+    // ツイートを1件ずつ持つフォロイー候補、計測ケースが進むごとに追加していく
+    let mut followee_ids = Vec::new();
+    for (case, target) in FOLLOWEE_COUNTS.into_iter().enumerate() {
+        while followee_ids.len() < target {
+            let followee_id =
+                insert_user(&repository, &format!("followee{}", followee_ids.len())).await?;
+            repository
+                .insert_user_tweet(&UserTweet {
+                    id: None,
+                    user_id: followee_id,
+                    content: format!("bench tweet {}", followee_id),
+                    in_reply_to_id: None,
+                })
+                .await?;
+            followee_ids.push(followee_id);
+        }
+        // 計測ケースごとにフォロワーを作り、先頭target人をフォローさせる
+        let follower_id = insert_user(&repository, &format!("follower{}", case)).await?;
+        for followee_id in &followee_ids[..target] {
+            repository
+                .insert_follow_relation(&FollowRelation {
+                    id: None,
+                    followee_id: *followee_id,
+                    follower_id,
+                })
+                .await?;
+        }
+
+        let mut elapsed = Duration::ZERO;
+        let mut items = 0;
+        for _ in 0..ITERATIONS {
+            let started = Instant::now();
+            items = repository.timeline(follower_id).await?.len();
+            elapsed += started.elapsed();
+        }
+        println!(
+            "followees={:>6} items={:>6} mean={:?}",
+            target,
+            items,
+            elapsed / ITERATIONS
+        );
+    }
+    Ok(())
+}
+
+async fn insert_user(repository: &SharedRepository, name: &str) -> anyhow::Result<u64> {
+    let id = repository
+        .insert_user(&User {
+            id: None,
+            name: name.to_string(),
+            password_hash: None,
+            role: Role::User,
+            is_suspended: false,
+            is_protected: false,
+        })
+        .await?;
+    Ok(id)
+}
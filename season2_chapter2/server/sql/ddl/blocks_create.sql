@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS blocks (
+  id SERIAL,
+  blocker_id BIGINT UNSIGNED NOT NULL, -- ブロックしたユーザのID
+  blocked_id BIGINT UNSIGNED NOT NULL, -- ブロックされたユーザのID
+  FOREIGN KEY (blocker_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (blocked_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX blocks__blocker_id__blocked_id ON blocks (blocker_id, blocked_id);
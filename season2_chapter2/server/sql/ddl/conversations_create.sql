@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS conversations (
+  id SERIAL,
+  user1_id BIGINT UNSIGNED NOT NULL, -- 参加者のうちIDが小さい方のユーザID
+  user2_id BIGINT UNSIGNED NOT NULL, -- 参加者のうちIDが大きい方のユーザID
+  FOREIGN KEY (user1_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (user2_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- 同じ2人の会話は1つだけ
+CREATE UNIQUE INDEX conversations__user1_id__user2_id ON conversations (user1_id, user2_id);
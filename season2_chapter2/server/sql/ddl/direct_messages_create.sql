@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS direct_messages (
+  id SERIAL,
+  conversation_id BIGINT UNSIGNED NOT NULL, -- 会話ID
+  sender_id BIGINT UNSIGNED NOT NULL, -- 送信したユーザのID
+  content VARCHAR(1000) NOT NULL, -- 本文
+  is_read BOOLEAN NOT NULL DEFAULT FALSE, -- 受信者が既読にしたかどうか
+  FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE,
+  FOREIGN KEY (sender_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS hashtag_buckets (
+  tag VARCHAR(140) NOT NULL, -- 小文字にしたタグ名
+  bucket_start BIGINT NOT NULL, -- バケットの開始時刻(UNIXタイムスタンプ)
+  count BIGINT UNSIGNED NOT NULL, -- バケット内でタグを含むツイートの数
+  PRIMARY KEY (tag, bucket_start)
+);
+
+-- 期間ごとの集計と古いバケットの削除に使う
+CREATE INDEX hashtag_buckets__bucket_start ON hashtag_buckets (bucket_start);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS home_timeline_entries (
+  id SERIAL,
+  user_id BIGINT UNSIGNED NOT NULL, -- タイムラインを持つユーザのID
+  tweet_id BIGINT UNSIGNED NOT NULL, -- 配られたツイートのID
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (tweet_id) REFERENCES user_tweets(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX home_timeline_entries__user_id__tweet_id ON home_timeline_entries (user_id, tweet_id);
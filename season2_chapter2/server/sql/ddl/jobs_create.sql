@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS jobs (
+  id SERIAL,
+  kind VARCHAR(32) NOT NULL, -- ジョブの種類
+  payload TEXT NOT NULL, -- ジョブの引数(JSON)
+  unique_key VARCHAR(255) NULL, -- 定期ジョブの重複登録を防ぐキー
+  status VARCHAR(16) NOT NULL DEFAULT 'pending', -- pending, running, done, failed
+  run_at DATETIME NOT NULL, -- 実行予定日時(UTC)
+  locked_by VARCHAR(64) NULL, -- 実行中のワーカーが取得時に書き込むトークン
+  locked_until DATETIME NULL, -- この日時を過ぎても実行中ならワーカーが落ちたとみなして再実行する
+  attempts INT UNSIGNED NOT NULL DEFAULT 0, -- 実行回数
+  last_error TEXT NULL -- 最後に失敗したときのエラー
+);
+
+CREATE UNIQUE INDEX jobs__unique_key ON jobs (unique_key);
+CREATE INDEX jobs__status__run_at ON jobs (status, run_at);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS likes (
+  id SERIAL,
+  user_id BIGINT UNSIGNED NOT NULL, -- いいねしたユーザのID
+  user_tweet_id BIGINT UNSIGNED NOT NULL, -- いいねされたツイートのID
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (user_tweet_id) REFERENCES user_tweets(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX likes__user_id__user_tweet_id ON likes (user_id, user_tweet_id);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS link_previews (
+  id SERIAL,
+  url_hash CHAR(64) NOT NULL, -- URLのSHA-256(16進数)、URLは長くなりうるのでこちらで一意にする
+  url TEXT NOT NULL,
+  title VARCHAR(255) NULL, -- og:title、なければtitle要素
+  description VARCHAR(1024) NULL, -- og:description、なければdescription
+  image_url TEXT NULL, -- og:image
+  fetched_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX link_previews__url_hash ON link_previews (url_hash);
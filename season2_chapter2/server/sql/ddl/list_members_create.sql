@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS list_members (
+  id SERIAL,
+  list_id BIGINT UNSIGNED NOT NULL, -- リストのID
+  user_id BIGINT UNSIGNED NOT NULL, -- リストに追加されたユーザのID
+  FOREIGN KEY (list_id) REFERENCES lists(id) ON DELETE CASCADE,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX list_members__list_id__user_id ON list_members (list_id, user_id);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS lists (
+  id SERIAL,
+  owner_id BIGINT UNSIGNED NOT NULL, -- リストを作成したユーザのID
+  name VARCHAR(64) NOT NULL, -- リスト名
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX lists__owner_id ON lists (owner_id);
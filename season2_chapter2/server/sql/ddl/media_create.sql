@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS media (
+  id SERIAL,
+  user_id BIGINT UNSIGNED NOT NULL, -- アップロードしたユーザのID
+  content_type VARCHAR(255) NOT NULL, -- 本体のMIMEタイプ
+  byte_size BIGINT UNSIGNED NOT NULL, -- 本体のバイト数
+  blob_key VARCHAR(255) NOT NULL, -- 本体の保存先キー
+  thumbnail_key VARCHAR(255) NOT NULL, -- サムネイルの保存先キー
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
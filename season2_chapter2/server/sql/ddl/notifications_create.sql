@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS notifications (
+  id SERIAL,
+  user_id BIGINT UNSIGNED NOT NULL, -- 通知先ユーザのID
+  kind VARCHAR(16) NOT NULL, -- follow, reply, like, mention
+  actor_id BIGINT UNSIGNED NOT NULL, -- 通知のきっかけになったユーザのID
+  target_id BIGINT UNSIGNED NOT NULL, -- 通知対象のユーザIDもしくはツイートID
+  is_read BOOLEAN NOT NULL DEFAULT FALSE, -- 既読かどうか
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (actor_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX notifications__user_id__is_read ON notifications (user_id, is_read);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS oidc_identities (
+  id SERIAL,
+  issuer VARCHAR(255) NOT NULL, -- IdPのissuer
+  subject VARCHAR(255) NOT NULL, -- IdP内のユーザID(IDトークンのsub)
+  user_id BIGINT UNSIGNED NOT NULL, -- 紐付いたユーザのID
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX oidc_identities__issuer_subject ON oidc_identities (issuer, subject);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS reports (
+  id SERIAL,
+  reporter_id BIGINT UNSIGNED NOT NULL, -- 通報したユーザのID
+  user_tweet_id BIGINT UNSIGNED NOT NULL, -- 通報されたツイートのID
+  reason VARCHAR(255) NOT NULL, -- 通報理由
+  is_resolved BOOLEAN NOT NULL DEFAULT FALSE, -- モデレーターが対応済みにしたかどうか
+  FOREIGN KEY (reporter_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (user_tweet_id) REFERENCES user_tweets(id) ON DELETE CASCADE
+);
+
+-- 同じユーザが同じツイートを重ねて通報しても1件にする
+CREATE UNIQUE INDEX reports__reporter_id__user_tweet_id ON reports (reporter_id, user_tweet_id);
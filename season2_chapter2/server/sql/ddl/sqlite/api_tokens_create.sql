@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS api_tokens (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id INTEGER NOT NULL, -- トークンの持ち主のID
+  name VARCHAR(255) NOT NULL, -- 用途がわかるようにユーザが付ける名前
+  token_hash CHAR(64) NOT NULL, -- トークンのSHA-256(16進数)
+  scopes VARCHAR(32) NOT NULL, -- カンマ区切りの権限(read, write)
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_used_at DATETIME NULL, -- 最後に使われた日時(UTC)
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS api_tokens__token_hash ON api_tokens (token_hash);
+CREATE INDEX IF NOT EXISTS api_tokens__user_id ON api_tokens (user_id);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS blocks (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  blocker_id INTEGER NOT NULL, -- ブロックしたユーザのID
+  blocked_id INTEGER NOT NULL, -- ブロックされたユーザのID
+  FOREIGN KEY (blocker_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (blocked_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS blocks__blocker_id__blocked_id ON blocks (blocker_id, blocked_id);
+CREATE INDEX IF NOT EXISTS blocks__blocked_id ON blocks (blocked_id);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS direct_messages (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  conversation_id INTEGER NOT NULL, -- 会話ID
+  sender_id INTEGER NOT NULL, -- 送信したユーザのID
+  content VARCHAR(1000) NOT NULL, -- 本文
+  is_read BOOLEAN NOT NULL DEFAULT FALSE, -- 受信者が既読にしたかどうか
+  FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE,
+  FOREIGN KEY (sender_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS direct_messages__conversation_id ON direct_messages (conversation_id);
+CREATE INDEX IF NOT EXISTS direct_messages__sender_id ON direct_messages (sender_id);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS follow_relations (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  followee_id INTEGER NOT NULL, -- フォローされる側のID
+  follower_id INTEGER NOT NULL, -- フォローする側のID
+  FOREIGN KEY (followee_id) REFERENCES users(id) ON DELETE CASCADE, -- フォロウィーユーザ削除時にフォロー関係削除
+  FOREIGN KEY (follower_id) REFERENCES users(id) ON DELETE CASCADE -- フォロワーユーザ削除時にフォロー関係削除
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS follow_relations__follower_id__followee_id ON follow_relations (follower_id, followee_id);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS follow_requests (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  requester_id INTEGER NOT NULL, -- フォローを申請したユーザのID
+  target_id INTEGER NOT NULL, -- 申請された非公開アカウントのユーザID
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (requester_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (target_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS follow_requests__requester_id__target_id ON follow_requests (requester_id, target_id);
+CREATE INDEX IF NOT EXISTS follow_requests__target_id ON follow_requests (target_id);
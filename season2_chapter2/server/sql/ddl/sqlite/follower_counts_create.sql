@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS follower_counts (
+  user_id INTEGER NOT NULL PRIMARY KEY,
+  follower_count INTEGER NOT NULL, -- follow_relationsでfollowee_idがuser_idの行数
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- 既存のフォロー関係から数える、テーブルを作った最初の1回だけ行う
+INSERT INTO follower_counts (user_id, follower_count)
+SELECT followee_id, COUNT(*) FROM follow_relations
+WHERE NOT EXISTS (SELECT 1 FROM follower_counts)
+GROUP BY followee_id;
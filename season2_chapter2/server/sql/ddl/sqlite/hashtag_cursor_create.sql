@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS hashtag_cursor (
+  id INTEGER PRIMARY KEY, -- 常に1の1行だけを持つ
+  last_tweet_id INTEGER NOT NULL -- ハッシュタグを集計済みの最後のツイートID
+);
+
+INSERT OR IGNORE INTO hashtag_cursor (id, last_tweet_id) VALUES (1, 0);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id INTEGER NOT NULL, -- リクエストしたユーザのID、キーはユーザごとに一意
+  idempotency_key VARCHAR(255) NOT NULL, -- Idempotency-Keyヘッダの値
+  request_hash CHAR(64) NOT NULL, -- メソッド、クエリ文字列を含むパス、ボディのSHA-256(16進数)
+  status_code INTEGER NULL, -- 処理中はNULL
+  response_headers TEXT NULL, -- 保存したレスポンスヘッダ、[名前, 値]の配列のJSON
+  response_body BLOB NULL,
+  expires_at DATETIME NOT NULL, -- この日時(UTC)を過ぎたら同じキーで新しいリクエストとして処理する
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idempotency_keys__user_id__idempotency_key ON idempotency_keys (user_id, idempotency_key);
+CREATE INDEX IF NOT EXISTS idempotency_keys__expires_at ON idempotency_keys (expires_at);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS likes (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id INTEGER NOT NULL, -- いいねしたユーザのID
+  user_tweet_id INTEGER NOT NULL, -- いいねされたツイートのID
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (user_tweet_id) REFERENCES user_tweets(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS likes__user_id__user_tweet_id ON likes (user_id, user_tweet_id);
+CREATE INDEX IF NOT EXISTS likes__user_tweet_id ON likes (user_tweet_id);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS list_members (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  list_id INTEGER NOT NULL, -- リストのID
+  user_id INTEGER NOT NULL, -- リストに追加されたユーザのID
+  FOREIGN KEY (list_id) REFERENCES lists(id) ON DELETE CASCADE,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS list_members__list_id__user_id ON list_members (list_id, user_id);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS moderation_logs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  actor_id INTEGER NULL, -- 操作した管理者もしくはモデレーターのID
+  action VARCHAR(32) NOT NULL, -- 操作の種類
+  target_id INTEGER NOT NULL, -- 操作対象のユーザID、ツイートIDもしくは通報ID
+  detail VARCHAR(255) NULL, -- 変更後のロールなどの補足
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- 監査ログは操作したユーザが削除されても残す
+  FOREIGN KEY (actor_id) REFERENCES users(id) ON DELETE SET NULL
+);
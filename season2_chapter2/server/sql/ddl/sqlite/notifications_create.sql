@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS notifications (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id INTEGER NOT NULL, -- 通知先ユーザのID
+  kind VARCHAR(16) NOT NULL, -- follow, reply, like, mention
+  actor_id INTEGER NOT NULL, -- 通知のきっかけになったユーザのID
+  target_id INTEGER NOT NULL, -- 通知対象のユーザIDもしくはツイートID
+  is_read BOOLEAN NOT NULL DEFAULT FALSE, -- 既読かどうか
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (actor_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS notifications__user_id__is_read ON notifications (user_id, is_read);
+CREATE INDEX IF NOT EXISTS notifications__actor_id ON notifications (actor_id);
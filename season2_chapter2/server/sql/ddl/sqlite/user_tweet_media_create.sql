@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS user_tweet_media (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_tweet_id INTEGER NOT NULL, -- ツイートID
+  media_id INTEGER NOT NULL, -- メディアID
+  position INTEGER NOT NULL, -- ツイート内での並び順
+  FOREIGN KEY (user_tweet_id) REFERENCES user_tweets(id) ON DELETE CASCADE,
+  FOREIGN KEY (media_id) REFERENCES media(id) ON DELETE CASCADE
+);
+
+-- 1つのメディアは1つのツイートにしか添付できない
+CREATE UNIQUE INDEX IF NOT EXISTS user_tweet_media__media_id ON user_tweet_media (media_id);
+CREATE INDEX IF NOT EXISTS user_tweet_media__user_tweet_id ON user_tweet_media (user_tweet_id);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS user_tweets (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id INTEGER NOT NULL, -- ユーザーID
+  content VARCHAR(140), -- メモ内容
+  in_reply_to_id INTEGER NULL, -- 返信先のツイートID
+  is_hidden BOOLEAN NOT NULL DEFAULT FALSE, -- モデレーターが非表示にしたかどうか
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP, -- 投稿日時
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (in_reply_to_id) REFERENCES user_tweets(id) ON DELETE SET NULL
+);
+
+-- MySQLは外部キーに自動でINDEXを張るがSQLiteは張らないので明示する
+CREATE INDEX IF NOT EXISTS user_tweets__user_id ON user_tweets (user_id);
+CREATE INDEX IF NOT EXISTS user_tweets__in_reply_to_id ON user_tweets (in_reply_to_id);
+CREATE INDEX IF NOT EXISTS user_tweets__user_id__created_at ON user_tweets (user_id, created_at);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS webhooks (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  url TEXT NOT NULL, -- 配信先のURL
+  secret CHAR(64) NOT NULL, -- 署名の鍵、配信のたびに使うのでハッシュ化せずに持つ
+  events VARCHAR(255) NOT NULL, -- カンマ区切りのイベントの種類
+  created_by INTEGER NULL, -- 登録した管理者のID
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS user_tweet_media (
+  id SERIAL,
+  user_tweet_id BIGINT UNSIGNED NOT NULL, -- ツイートID
+  media_id BIGINT UNSIGNED NOT NULL, -- メディアID
+  position INT UNSIGNED NOT NULL, -- ツイート内での並び順
+  FOREIGN KEY (user_tweet_id) REFERENCES user_tweets(id) ON DELETE CASCADE,
+  FOREIGN KEY (media_id) REFERENCES media(id) ON DELETE CASCADE
+);
+
+-- 1つのメディアは1つのツイートにしか添付できない
+CREATE UNIQUE INDEX user_tweet_media__media_id ON user_tweet_media (media_id);
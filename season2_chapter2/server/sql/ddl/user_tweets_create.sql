@@ -2,5 +2,12 @@ CREATE TABLE IF NOT EXISTS user_tweets (
   id SERIAL,
   user_id BIGINT UNSIGNED NOT NULL, -- ユーザーID
   content VARCHAR(140), -- メモ内容
-  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+  in_reply_to_id BIGINT UNSIGNED NULL, -- 返信先のツイートID
+  is_hidden BOOLEAN NOT NULL DEFAULT FALSE, -- モデレーターが非表示にしたかどうか
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP, -- 投稿日時
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  FOREIGN KEY (in_reply_to_id) REFERENCES user_tweets(id) ON DELETE SET NULL
 );
+
+-- おすすめユーザの直近の投稿数の集計に使う
+CREATE INDEX user_tweets__user_id__created_at ON user_tweets (user_id, created_at);
@@ -1,6 +1,10 @@
 CREATE TABLE IF NOT EXISTS users (
   id SERIAL,
-  name VARCHAR(255) NOT NULL -- ユーザー名
+  name VARCHAR(255) NOT NULL, -- ユーザー名
+  password_hash VARCHAR(255) NULL, -- パスワードのハッシュ、パスワード導入前のユーザはNULL
+  role VARCHAR(16) NOT NULL DEFAULT 'user', -- user, moderator, admin
+  is_suspended BOOLEAN NOT NULL DEFAULT FALSE, -- 管理者に凍結されたかどうか
+  is_protected BOOLEAN NOT NULL DEFAULT FALSE -- ツイートをフォロワーにだけ公開するかどうか
 );
 
 CREATE UNIQUE INDEX users__name ON users (name);
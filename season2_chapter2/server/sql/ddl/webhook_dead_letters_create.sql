@@ -0,0 +1,13 @@
+-- 再試行しても配信できなかったイベント、Webhookを削除しても調査と再送のために残す
+CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+  id SERIAL,
+  delivery_id BIGINT UNSIGNED NOT NULL,
+  webhook_id BIGINT UNSIGNED NOT NULL,
+  url TEXT NOT NULL, -- 配信しようとしたURL
+  event VARCHAR(32) NOT NULL,
+  payload TEXT NOT NULL,
+  last_error TEXT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX webhook_dead_letters__delivery_id ON webhook_dead_letters (delivery_id);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+  id SERIAL,
+  webhook_id BIGINT UNSIGNED NOT NULL,
+  event VARCHAR(32) NOT NULL, -- イベントの種類
+  payload TEXT NOT NULL, -- 送るJSON
+  status VARCHAR(16) NOT NULL DEFAULT 'pending', -- pending, delivered, dead_lettered
+  attempts INT UNSIGNED NOT NULL DEFAULT 0, -- 送信を試みた回数
+  response_status SMALLINT UNSIGNED NULL, -- 最後の送信のレスポンスのステータスコード
+  last_error TEXT NULL, -- 最後の送信の失敗理由
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  delivered_at TIMESTAMP NULL,
+  FOREIGN KEY (webhook_id) REFERENCES webhooks(id) ON DELETE CASCADE
+);
+
+CREATE INDEX webhook_deliveries__webhook_id ON webhook_deliveries (webhook_id);
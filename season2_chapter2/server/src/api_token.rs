@@ -0,0 +1,17 @@
+// ボットやCLI向けの個人アクセストークンの生成とハッシュ化
+// トークンは十分な長さの乱数なので、パスワードと違いソルトなしのSHA-256でハッシュ化して検索に使う
+use rand::RngCore as _;
+use sha2::{Digest as _, Sha256};
+
+// トークンの接頭辞、ログやリポジトリに誤って載ったときに見つけやすくする
+pub const API_TOKEN_PREFIX: &str = "rtr_";
+
+pub fn generate_api_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", API_TOKEN_PREFIX, hex::encode(bytes))
+}
+
+pub fn hash_api_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
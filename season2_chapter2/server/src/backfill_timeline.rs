@@ -0,0 +1,21 @@
+// src/backfill_timeline.rs
+// fan-out-on-writeを有効にする前に投稿されたツイートをホームタイムラインに書き込む
+use ruitter::config::ServerConfig;
+use ruitter::models::{create_tokio_runtime, database_url};
+use ruitter::repositories::connect;
+
+fn main() -> anyhow::Result<()> {
+    let tokio_rt = create_tokio_runtime();
+    tokio_rt.block_on(run())
+}
+
+async fn run() -> anyhow::Result<()> {
+    let (repository, _session_store) = connect(&database_url()).await?;
+    // ハイブリッド方式の場合は閾値を超える投稿者のツイートは配らない
+    let config = ServerConfig::from_env()?;
+    let inserted = repository
+        .backfill_home_timelines(config.timeline_mode.fan_out_threshold())
+        .await?;
+    println!("backfilled {} home timeline entries", inserted);
+    Ok(())
+}
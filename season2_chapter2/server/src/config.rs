@@ -0,0 +1,274 @@
+// 環境変数から読み込むサーバ設定
+use crate::oidc::{JwksSource, OidcConfig};
+use crate::repositories::ReadRetry;
+use sqlx::pool::PoolOptions;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+// 書き込んだセッションの読み出しをプライマリに送る秒数を指定する環境変数名
+// 読み取り専用のレプリカ(RUITTER_REPLICA_DATABASE_URL)を使う場合のみ有効
+pub const STICKY_PRIMARY_SECONDS_ENV: &str = "RUITTER_STICKY_PRIMARY_SECONDS";
+const DEFAULT_STICKY_PRIMARY_SECONDS: u64 = 5;
+
+// タイムラインの組み立て方を指定する環境変数名
+// "read" | "write" | "hybrid:<フォロワー数の閾値>" を受け付ける
+pub const TIMELINE_MODE_ENV: &str = "RUITTER_TIMELINE_MODE";
+
+// ビルド済みフロントエンド(front/dist/app)のディレクトリを指定する環境変数名
+// 指定するとAPI以外のパスで静的ファイルを配信する
+pub const STATIC_DIR_ENV: &str = "RUITTER_STATIC_DIR";
+
+// アップロードされたメディアを保存するディレクトリを指定する環境変数名
+pub const MEDIA_DIR_ENV: &str = "RUITTER_MEDIA_DIR";
+const DEFAULT_MEDIA_DIR: &str = "media";
+
+// おすすめユーザの結果をキャッシュする秒数を指定する環境変数名、0でキャッシュしない
+pub const RECOMMENDATION_CACHE_SECONDS_ENV: &str = "RUITTER_RECOMMENDATION_CACHE_SECONDS";
+const DEFAULT_RECOMMENDATION_CACHE_SECONDS: u64 = 300;
+
+// トレンドの結果をキャッシュする秒数を指定する環境変数名、0でキャッシュしない
+pub const TREND_CACHE_SECONDS_ENV: &str = "RUITTER_TREND_CACHE_SECONDS";
+const DEFAULT_TREND_CACHE_SECONDS: u64 = 60;
+
+// OIDCログインの設定の環境変数名、RUITTER_OIDC_ISSUERを指定した場合のみ有効にする
+// RUITTER_OIDC_JWKSはhttp(s)のURLかJWKSを保存したファイルのパス
+pub const OIDC_ISSUER_ENV: &str = "RUITTER_OIDC_ISSUER";
+pub const OIDC_CLIENT_ID_ENV: &str = "RUITTER_OIDC_CLIENT_ID";
+pub const OIDC_CLIENT_SECRET_ENV: &str = "RUITTER_OIDC_CLIENT_SECRET";
+pub const OIDC_AUTHORIZATION_ENDPOINT_ENV: &str = "RUITTER_OIDC_AUTHORIZATION_ENDPOINT";
+pub const OIDC_TOKEN_ENDPOINT_ENV: &str = "RUITTER_OIDC_TOKEN_ENDPOINT";
+pub const OIDC_JWKS_ENV: &str = "RUITTER_OIDC_JWKS";
+pub const OIDC_REDIRECT_URI_ENV: &str = "RUITTER_OIDC_REDIRECT_URI";
+
+// "true"を指定するとツイート中のURLのリンクプレビューをバックグラウンドジョブで取得する
+pub const LINK_PREVIEWS_ENV: &str = "RUITTER_LINK_PREVIEWS";
+
+// DBの接続プールと再試行の設定の環境変数名
+pub const DB_MAX_CONNECTIONS_ENV: &str = "RUITTER_DB_MAX_CONNECTIONS";
+pub const DB_MIN_CONNECTIONS_ENV: &str = "RUITTER_DB_MIN_CONNECTIONS";
+pub const DB_ACQUIRE_TIMEOUT_SECONDS_ENV: &str = "RUITTER_DB_ACQUIRE_TIMEOUT_SECONDS";
+pub const DB_IDLE_TIMEOUT_SECONDS_ENV: &str = "RUITTER_DB_IDLE_TIMEOUT_SECONDS";
+pub const DB_MAX_LIFETIME_SECONDS_ENV: &str = "RUITTER_DB_MAX_LIFETIME_SECONDS";
+pub const DB_READ_RETRIES_ENV: &str = "RUITTER_DB_READ_RETRIES";
+pub const DB_RETRY_BACKOFF_MILLIS_ENV: &str = "RUITTER_DB_RETRY_BACKOFF_MILLIS";
+
+// タイムラインの組み立て方
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TimelineMode {
+    // 読み出しのたびにフォロイーのツイートを結合する(従来の方式)
+    #[default]
+    FanOutOnRead,
+    // 投稿時にフォロワー全員のホームタイムラインへ書き込み、読み出しはそれを引くだけにする
+    FanOutOnWrite,
+    // フォロワー数が閾値以下の投稿者は書き込み時に配り、閾値を超える投稿者は読み出し時に結合する
+    Hybrid {
+        follower_threshold: u64,
+    },
+}
+
+impl TimelineMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(Self::FanOutOnRead),
+            "write" => Some(Self::FanOutOnWrite),
+            _ => {
+                let threshold = value.strip_prefix("hybrid:")?.parse().ok()?;
+                Some(Self::Hybrid {
+                    follower_threshold: threshold,
+                })
+            }
+        }
+    }
+
+    // ホームタイムラインのテーブルを使うかどうか
+    pub fn uses_home_timeline(&self) -> bool {
+        !matches!(self, Self::FanOutOnRead)
+    }
+
+    // 書き込み時に配らず読み出し時に結合する投稿者のフォロワー数の閾値
+    // Noneなら全投稿者を書き込み時に配る
+    pub fn fan_out_threshold(&self) -> Option<u64> {
+        match self {
+            Self::Hybrid { follower_threshold } => Some(*follower_threshold),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub timeline_mode: TimelineMode,
+    pub static_dir: Option<PathBuf>,
+    pub media_dir: PathBuf,
+    pub recommendation_cache_ttl: Duration,
+    pub trend_cache_ttl: Duration,
+    pub oidc: Option<OidcConfig>,
+    // ツイート中のURLのリンクプレビューを取得するかどうか
+    pub link_previews: bool,
+    // 書き込んだセッションの読み出しをプライマリに送る時間、レプリカへの反映の遅れより長くする
+    pub sticky_primary_window: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            timeline_mode: TimelineMode::default(),
+            static_dir: None,
+            media_dir: PathBuf::from(DEFAULT_MEDIA_DIR),
+            recommendation_cache_ttl: Duration::from_secs(DEFAULT_RECOMMENDATION_CACHE_SECONDS),
+            trend_cache_ttl: Duration::from_secs(DEFAULT_TREND_CACHE_SECONDS),
+            oidc: None,
+            link_previews: false,
+            sticky_primary_window: Duration::from_secs(DEFAULT_STICKY_PRIMARY_SECONDS),
+        }
+    }
+}
+
+impl ServerConfig {
+    // 環境変数から設定を読み込む、未指定の項目はデフォルト値にする
+    pub fn from_env() -> anyhow::Result<Self> {
+        let timeline_mode = match std::env::var(TIMELINE_MODE_ENV) {
+            Ok(value) => TimelineMode::parse(&value)
+                .ok_or_else(|| anyhow::anyhow!("invalid {}: {}", TIMELINE_MODE_ENV, value))?,
+            Err(_) => TimelineMode::default(),
+        };
+        let static_dir = std::env::var_os(STATIC_DIR_ENV).map(PathBuf::from);
+        let media_dir = std::env::var_os(MEDIA_DIR_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_MEDIA_DIR));
+        let recommendation_cache_seconds = match std::env::var(RECOMMENDATION_CACHE_SECONDS_ENV) {
+            Ok(value) => value.parse().map_err(|_| {
+                anyhow::anyhow!("invalid {}: {}", RECOMMENDATION_CACHE_SECONDS_ENV, value)
+            })?,
+            Err(_) => DEFAULT_RECOMMENDATION_CACHE_SECONDS,
+        };
+        let sticky_primary_seconds = match std::env::var(STICKY_PRIMARY_SECONDS_ENV) {
+            Ok(value) => value.parse().map_err(|_| {
+                anyhow::anyhow!("invalid {}: {}", STICKY_PRIMARY_SECONDS_ENV, value)
+            })?,
+            Err(_) => DEFAULT_STICKY_PRIMARY_SECONDS,
+        };
+        Ok(Self {
+            timeline_mode,
+            static_dir,
+            media_dir,
+            recommendation_cache_ttl: Duration::from_secs(recommendation_cache_seconds),
+            trend_cache_ttl: Duration::from_secs(env_or(
+                TREND_CACHE_SECONDS_ENV,
+                DEFAULT_TREND_CACHE_SECONDS,
+            )?),
+            oidc: oidc_config_from_env()?,
+            link_previews: std::env::var(LINK_PREVIEWS_ENV).is_ok_and(|value| value == "true"),
+            sticky_primary_window: Duration::from_secs(sticky_primary_seconds),
+        })
+    }
+}
+
+fn oidc_config_from_env() -> anyhow::Result<Option<OidcConfig>> {
+    let issuer = match std::env::var(OIDC_ISSUER_ENV) {
+        Ok(issuer) => issuer,
+        Err(_) => return Ok(None),
+    };
+    let required = |name: &str| {
+        std::env::var(name).map_err(|_| anyhow::anyhow!("{} is required for oidc login", name))
+    };
+    let authorization_endpoint = required(OIDC_AUTHORIZATION_ENDPOINT_ENV)?;
+    reqwest::Url::parse(&authorization_endpoint).map_err(|_| {
+        anyhow::anyhow!(
+            "invalid {}: {}",
+            OIDC_AUTHORIZATION_ENDPOINT_ENV,
+            authorization_endpoint
+        )
+    })?;
+    Ok(Some(OidcConfig {
+        issuer,
+        client_id: required(OIDC_CLIENT_ID_ENV)?,
+        client_secret: std::env::var(OIDC_CLIENT_SECRET_ENV).ok(),
+        authorization_endpoint,
+        token_endpoint: required(OIDC_TOKEN_ENDPOINT_ENV)?,
+        jwks: JwksSource::parse(&required(OIDC_JWKS_ENV)?),
+        redirect_uri: required(OIDC_REDIRECT_URI_ENV)?,
+    }))
+}
+
+// DBの接続プールと、接続の失敗で参照系のクエリを再試行する設定
+#[derive(Clone, Debug)]
+pub struct DatabaseConfig {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    // プールから接続を取得するまで待つ時間、DBが落ちているときにハンドラを待たせすぎないよう短めにする
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Duration,
+    // DBの再起動やフェイルオーバーの後に古い接続を使い続けないよう、一定時間で張り直す
+    pub max_lifetime: Duration,
+    pub read_retry: ReadRetry,
+    // trueなら起動時に接続せず、最初のクエリで接続する
+    // DBが落ちていてもサーバを起動し、DBが戻れば以降のリクエストから処理できるようにする
+    pub lazy_connect: bool,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(5),
+            idle_timeout: Duration::from_secs(600),
+            max_lifetime: Duration::from_secs(1800),
+            read_retry: ReadRetry::default(),
+            lazy_connect: false,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    // 環境変数から設定を読み込む、未指定の項目はデフォルト値にする
+    pub fn from_env() -> anyhow::Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            max_connections: env_or(DB_MAX_CONNECTIONS_ENV, default.max_connections)?,
+            min_connections: env_or(DB_MIN_CONNECTIONS_ENV, default.min_connections)?,
+            acquire_timeout: Duration::from_secs(env_or(
+                DB_ACQUIRE_TIMEOUT_SECONDS_ENV,
+                default.acquire_timeout.as_secs(),
+            )?),
+            idle_timeout: Duration::from_secs(env_or(
+                DB_IDLE_TIMEOUT_SECONDS_ENV,
+                default.idle_timeout.as_secs(),
+            )?),
+            max_lifetime: Duration::from_secs(env_or(
+                DB_MAX_LIFETIME_SECONDS_ENV,
+                default.max_lifetime.as_secs(),
+            )?),
+            read_retry: ReadRetry {
+                retries: env_or(DB_READ_RETRIES_ENV, default.read_retry.retries)?,
+                backoff: Duration::from_millis(env_or(
+                    DB_RETRY_BACKOFF_MILLIS_ENV,
+                    default.read_retry.backoff.as_millis() as u64,
+                )?),
+            },
+            lazy_connect: default.lazy_connect,
+        })
+    }
+
+    pub fn pool_options<DB: sqlx::Database>(&self) -> PoolOptions<DB> {
+        PoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+            // DBの再起動で切れた接続をハンドラに渡さないよう、取得時に確かめる
+            .test_before_acquire(true)
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> anyhow::Result<T> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid {}: {}", name, value)),
+        Err(_) => Ok(default),
+    }
+}
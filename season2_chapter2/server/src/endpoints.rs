@@ -1,39 +1,97 @@
+use crate::api_token::hash_api_token;
+use crate::config::{ServerConfig, TimelineMode};
+use crate::i18n::{Messages, SharedMessages};
+use crate::media::{LocalFsBlobStore, SharedBlobStore, MAX_MEDIA_PER_TWEET};
+use crate::oidc::{OidcClient, SharedOidcClient};
+use crate::openapi::openapi;
+use crate::password::{hash_password, verify_password};
+use crate::render::{extract_entities, EntityKind};
+use crate::unfurl::MAX_UNFURL_URLS;
+use crate::webhooks::emit as emit_webhook;
 // データモデルの読み込み
-use crate::models::{timeline, FollowRelation, User, UserTweet};
+use crate::models::{
+    ApiTokenScope, Block, FollowRelation, FollowRequest, Job, JobKind, NotificationKind, Role,
+    ScheduledTweetPayload, TimelineItem, UnfurlLinksPayload, User, UserTweet, WebhookEvent,
+};
+// データアクセスはリポジトリのトレイト経由で行う
+use crate::repositories::{AppSessionStore, SharedRepository};
+use crate::static_files::serve_static;
 use async_session::{Session, SessionStore as _};
-// セッション情報をMySQLに保存するライブラリ
-use async_sqlx_session::MySqlSessionStore;
 use axum::{
-    extract::{Extension, FromRequest, Json, RequestParts},
-    http::StatusCode,
+    body::Body,
+    extract::{Extension, FromRequest, Json, Path, RequestParts},
+    handler::Handler as _,
+    http::{header, Method, Request, StatusCode},
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
+use chrono::{DateTime, Utc};
+use errors::{ApiError, ErrorCode};
+use recommendations::RecommendationCache;
+use sticky_primary::StickyPrimary;
+use trends::TrendCache;
 // クライアントクッキーを制御する便利なライブラリ
 use axum_extra::extract::cookie::{Cookie, CookieJar};
-use sqlx::{MySql, Pool};
 use std::sync::Arc;
 
+pub mod accounts;
+pub mod admin;
+pub mod api_tokens;
+pub mod direct_messages;
+pub mod errors;
+pub mod follow_requests;
+pub mod idempotency;
+pub mod lists;
+pub mod media;
+pub mod notifications;
+pub mod oidc;
+pub mod recommendations;
+pub mod sticky_primary;
+pub mod trends;
+pub mod webhooks;
+
 // ユーザ新規作成APIのリクエストJSONのスキーマ
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct CreateUserParams {
     pub name: String,
+    // 省略するとパスワードなしのユーザになる(パスワード導入前との互換のため)
+    #[serde(default)]
+    pub password: Option<String>,
 }
 
 // ユーザ新規作成API
+#[utoipa::path(
+    post,
+    path = "/api/users",
+    request_body = CreateUserParams,
+    responses(
+        (status = 201, description = "ユーザ作成成功"),
+        (status = 400, description = "ユーザ名重複、空のパスワードなど"),
+    )
+)]
 pub(crate) async fn create_user(
     Json(payload): Json<CreateUserParams>,
-    arc_pool: Extension<Arc<Pool<MySql>>>,
+    repository: Extension<SharedRepository>,
 ) -> impl IntoResponse {
+    if payload.password.as_deref() == Some("") {
+        return StatusCode::BAD_REQUEST;
+    }
     let user = User {
         id: None,
         name: payload.name,
+        password_hash: payload.password.as_deref().map(hash_password),
+        role: Role::User,
+        is_suspended: false,
+        is_protected: false,
     };
     // ユーザ登録を試みる
-    match user.insert(&arc_pool).await {
+    match repository.insert_user(&user).await {
         // 成功したらHTTPステータスコード201を返す
-        Ok(_res) => StatusCode::CREATED,
+        Ok(id) => {
+            user_created(&repository, id, &user.name).await;
+            StatusCode::CREATED
+        }
         // 失敗したらHTTPステータスコード400を返す
         // ユーザ名重複やサーバ接続エラーなど
         // より精緻にステータスコードを分けることもできる
@@ -42,72 +100,198 @@ pub(crate) async fn create_user(
 }
 
 // ログインAPI
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct CreateSessionParams {
     pub name: String,
+    // パスワードを設定したユーザでは必須
+    #[serde(default)]
+    pub password: Option<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/sessions",
+    request_body = CreateSessionParams,
+    responses(
+        (status = 201, description = "ログイン成功、Set-Cookieでセッションキーを返す"),
+        (status = 400, description = "ユーザ名が存在しない、もしくはパスワードが一致しない"),
+        (status = 403, description = "アカウントが凍結されている"),
+        (status = 503, description = "DBに接続できない"),
+    )
+)]
 pub(crate) async fn create_session(
     Json(payload): Json<CreateSessionParams>,
-    arc_pool: Extension<Arc<Pool<MySql>>>,
-    session_store: Extension<MySqlSessionStore>,
+    repository: Extension<SharedRepository>,
+    session_store: Extension<AppSessionStore>,
     cookie_jar: CookieJar,
 ) -> impl IntoResponse {
     // リクエストされた名前が存在するか調べる
-    match User::find_by_name(&payload.name, &arc_pool).await {
+    match repository.find_by_name(&payload.name).await {
         Ok(user) => match user {
-            // ユーザー名が存在するならログイン処理
-            Some(user) => {
-                let mut session = Session::new();
-                let expire_seconds = 86400;
-                session.expire_in(std::time::Duration::from_secs(expire_seconds));
-                session.insert("user_id", user.id).unwrap();
-                // MySQLにセッション保存を試みる
-                match session_store.store_session(session).await {
-                    Ok(cookie_value) => Ok((
-                        StatusCode::CREATED,
-                        // 成功したらSet-Cookieレスポンスヘッダを通じてクッキーを更新
-                        cookie_jar.add(
-                            Cookie::build(AXUM_SESSION_COOKIE_KEY, cookie_value.unwrap())
-                                // HTTPS(TLS)非対応なのでfalseとした
-                                .secure(false)
-                                .http_only(true)
-                                .same_site(cookie::SameSite::Lax)
-                                .max_age(cookie::time::Duration::new(expire_seconds as i64, 0))
-                                .finish(),
-                        ),
-                    )),
-                    Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+            // ユーザー名が存在し、パスワードが一致するならログイン処理
+            Some(user) if password_matches(&user, payload.password.as_deref()) => {
+                // OIDCで作成したユーザはパスワードを持たないが、名前だけでのログインは認めない
+                if user.password_hash.is_none() {
+                    match repository.has_oidc_identity(user.id.unwrap()).await {
+                        Ok(false) => {}
+                        Ok(true) => {
+                            return Err(ApiError::new(
+                                StatusCode::BAD_REQUEST,
+                                ErrorCode::PasswordLoginDisabled,
+                            ))
+                        }
+                        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE.into()),
+                    }
+                }
+                // 凍結中のユーザはログインさせない、パスワード確認後に判定して凍結の有無を漏らさない
+                if user.is_suspended {
+                    return Err(ApiError::new(
+                        StatusCode::FORBIDDEN,
+                        ErrorCode::AccountSuspended,
+                    ));
                 }
+                let cookie_jar =
+                    start_login_session(&session_store, cookie_jar, user.id.unwrap()).await?;
+                Ok((StatusCode::CREATED, cookie_jar))
             }
-            // ユーザー名が存在しない場合
-            None => Err(StatusCode::BAD_REQUEST),
+            // ユーザー名が存在しない、もしくはパスワードが一致しない場合
+            _ => Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidCredentials,
+            )),
         },
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE.into()),
+    }
+}
+
+// ログインしたユーザのセッションをセッションストアに保存し、セッションクッキーを追加する
+pub(crate) async fn start_login_session(
+    session_store: &AppSessionStore,
+    cookie_jar: CookieJar,
+    user_id: u64,
+) -> Result<CookieJar, StatusCode> {
+    let mut session = Session::new();
+    let expire_seconds = 86400;
+    session.expire_in(std::time::Duration::from_secs(expire_seconds));
+    session.insert("user_id", user_id).unwrap();
+    // セッションストアにセッション保存を試みる
+    match session_store.store_session(session).await {
+        // 成功したらSet-Cookieレスポンスヘッダを通じてクッキーを更新
+        Ok(cookie_value) => Ok(cookie_jar.add(
+            Cookie::build(AXUM_SESSION_COOKIE_KEY, cookie_value.unwrap())
+                // HTTPS(TLS)非対応なのでfalseとした
+                .secure(false)
+                .http_only(true)
+                .same_site(cookie::SameSite::Lax)
+                .max_age(cookie::time::Duration::new(expire_seconds as i64, 0))
+                .finish(),
+        )),
         Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
     }
 }
 
-#[derive(serde::Deserialize)]
+// パスワードを設定したユーザは一致を確認し、パスワード導入前のユーザは名前だけでログインできる
+// ただしモデレーター以上のユーザは名前を知っていれば乗っ取れてしまうので、パスワードなしでは認めない
+pub(crate) fn password_matches(user: &User, password: Option<&str>) -> bool {
+    match (&user.password_hash, password) {
+        (Some(password_hash), Some(password)) => verify_password(password, password_hash),
+        (Some(_), None) => false,
+        (None, _) => !user.role.can_moderate(),
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct CreateUserTweetParams {
     pub content: String,
+    // POST /api/mediaで取得した添付メディアのID、最大4件
+    #[serde(default)]
+    pub media_ids: Vec<u64>,
+    // 返信先のツイートID
+    #[serde(default)]
+    pub in_reply_to_id: Option<u64>,
+    // 指定するとこの日時にバックグラウンドジョブが公開する、過去の日時なら即時に投稿する
+    #[serde(default)]
+    pub scheduled_at: Option<DateTime<Utc>>,
 }
 
 // ツイート作成API
+#[utoipa::path(
+    post,
+    path = "/api/user_tweets",
+    request_body = CreateUserTweetParams,
+    responses(
+        (status = 201, description = "ツイート作成成功"),
+        (status = 202, description = "予約投稿の登録成功"),
+        (status = 400, description = "添付メディアもしくは返信先の指定が不正"),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
 pub(crate) async fn create_user_tweet(
     Json(payload): Json<CreateUserTweetParams>,
-    arc_pool: Extension<Arc<Pool<MySql>>>,
+    repository: Extension<SharedRepository>,
+    Extension(timeline_mode): Extension<TimelineMode>,
+    Extension(link_previews): Extension<LinkPreviews>,
     session: CurrentSession,
 ) -> impl IntoResponse {
     // セッションからuser_idを取得する
     match session.0.get::<u64>("user_id") {
         Some(user_id) => {
+            validate_media_ids(&repository, user_id, &payload.media_ids).await?;
+            // 返信先の投稿者に通知するので、返信先が存在することを確かめておく
+            let in_reply_to = match payload.in_reply_to_id {
+                Some(id) => match find_visible_user_tweet(&repository, user_id, id).await {
+                    Ok(Some(tweet)) => Some(tweet),
+                    Ok(None) => return Err(StatusCode::BAD_REQUEST),
+                    Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+                },
+                None => None,
+            };
+            if let Some(scheduled_at) = payload.scheduled_at.filter(|at| *at > Utc::now()) {
+                // 添付メディアは公開時に改めて確保するので、それまでに他のツイートに添付されると公開に失敗する
+                let job_payload = ScheduledTweetPayload {
+                    user_id,
+                    content: payload.content,
+                    media_ids: payload.media_ids,
+                    in_reply_to_id: payload.in_reply_to_id,
+                };
+                let job = Job::new(
+                    JobKind::PublishScheduledTweet,
+                    serde_json::to_string(&job_payload).unwrap(),
+                    scheduled_at.naive_utc(),
+                );
+                return match repository.enqueue_job(&job).await {
+                    Ok(_) => Ok(StatusCode::ACCEPTED),
+                    Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+                };
+            }
             let tweet = UserTweet {
                 id: None,
                 user_id,
                 content: payload.content,
+                in_reply_to_id: payload.in_reply_to_id,
+            };
+            let result = if payload.media_ids.is_empty() {
+                repository.insert_user_tweet(&tweet).await
+            } else {
+                repository
+                    .insert_user_tweet_with_media(&tweet, &payload.media_ids)
+                    .await
             };
-            match tweet.insert(&arc_pool).await {
-                Ok(_) => Ok(StatusCode::CREATED),
+            match result {
+                Ok(tweet_id) => {
+                    user_tweet_published(
+                        &repository,
+                        timeline_mode,
+                        link_previews.0,
+                        tweet_id,
+                        &tweet,
+                        in_reply_to,
+                    )
+                    .await;
+                    Ok(StatusCode::CREATED)
+                }
                 Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
             }
         }
@@ -116,30 +300,266 @@ pub(crate) async fn create_user_tweet(
     }
 }
 
-#[derive(serde::Deserialize)]
+// リンクプレビューを取得するかどうか、ServerConfig::link_previewsをハンドラに渡す
+#[derive(Clone, Copy)]
+pub(crate) struct LinkPreviews(pub bool);
+
+// 保存したツイートをフォロワーに配り、返信先とメンションされたユーザに通知する
+// 予約投稿の公開時にも使う
+pub(crate) async fn user_tweet_published(
+    repository: &SharedRepository,
+    timeline_mode: TimelineMode,
+    unfurl_links: bool,
+    tweet_id: u64,
+    tweet: &UserTweet,
+    in_reply_to: Option<UserTweet>,
+) {
+    let user_id = tweet.user_id;
+    fan_out(
+        repository,
+        timeline_mode,
+        user_id,
+        FanOut::UserTweet { tweet_id },
+    )
+    .await;
+    let reply_author_id = in_reply_to.map(|reply_to| (reply_to.user_id, reply_to.id.unwrap()));
+    if let Some((author_id, reply_to_id)) = reply_author_id {
+        notifications::notify(
+            repository,
+            author_id,
+            NotificationKind::Reply,
+            user_id,
+            reply_to_id,
+        )
+        .await;
+    }
+    notifications::notify_mentions(
+        repository,
+        user_id,
+        tweet_id,
+        &tweet.content,
+        reply_author_id.map(|(author_id, _)| author_id),
+    )
+    .await;
+    if unfurl_links {
+        enqueue_unfurl_links(repository, tweet_id, &tweet.content).await;
+    }
+    emit_webhook(
+        repository,
+        WebhookEvent::TweetPosted,
+        serde_json::json!({
+            "id": tweet_id,
+            "user_id": user_id,
+            "content": tweet.content,
+            "in_reply_to_id": tweet.in_reply_to_id,
+        }),
+    )
+    .await;
+}
+
+// ユーザ登録後の処理、パスワードでもOIDCでも同じ
+pub(crate) async fn user_created(repository: &SharedRepository, user_id: u64, name: &str) {
+    emit_webhook(
+        repository,
+        WebhookEvent::UserCreated,
+        serde_json::json!({ "id": user_id, "name": name }),
+    )
+    .await;
+}
+
+// 本文中のURLのリンクプレビューの取得をジョブに登録する
+// 取得は外部サイトへのアクセスになるので、投稿のレスポンスを待たせない
+async fn enqueue_unfurl_links(repository: &SharedRepository, tweet_id: u64, content: &str) {
+    let mut urls: Vec<String> = Vec::new();
+    for entity in extract_entities(content) {
+        if entity.kind == EntityKind::Url && !urls.iter().any(|url| url == entity.value()) {
+            urls.push(entity.value().to_string());
+        }
+    }
+    urls.truncate(MAX_UNFURL_URLS);
+    if urls.is_empty() {
+        return;
+    }
+    let payload = UnfurlLinksPayload { urls };
+    let job = Job::new(
+        JobKind::UnfurlLinks,
+        serde_json::to_string(&payload).unwrap(),
+        Utc::now().naive_utc(),
+    );
+    if let Err(e) = repository.enqueue_job(&job).await {
+        eprintln!(
+            "failed to enqueue unfurl_links for user_tweet {}: {}",
+            tweet_id, e
+        );
+    }
+}
+
+// 添付メディアは自分がアップロードした未添付のものを最大4件まで、重複なく指定できる
+async fn validate_media_ids(
+    repository: &SharedRepository,
+    user_id: u64,
+    media_ids: &[u64],
+) -> Result<(), StatusCode> {
+    let unique_ids = media_ids.iter().collect::<std::collections::HashSet<_>>();
+    if media_ids.len() > MAX_MEDIA_PER_TWEET || unique_ids.len() != media_ids.len() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    for media_id in media_ids {
+        match repository.find_unattached_media(user_id, *media_id).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(StatusCode::BAD_REQUEST),
+            Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+        }
+    }
+    Ok(())
+}
+
+// ホームタイムラインに配るツイート
+#[derive(Debug, Clone, Copy)]
+enum FanOut {
+    // 投稿されたツイートを投稿者のフォロワー全員に配る
+    UserTweet { tweet_id: u64 },
+    // フォローしたユーザの過去のツイートをフォローした人に配る
+    FolloweeTweets { follower_id: u64 },
+}
+
+// ホームタイムラインにツイートを配る
+// ハイブリッド方式ではフォロワー数が閾値を超える投稿者は配らず、読み出し時に結合する
+// 元の操作は保存済みなので、失敗してもログに残すだけにする、配れなかった分はbackfill_timelineで復旧できる
+async fn fan_out(
+    repository: &SharedRepository,
+    timeline_mode: TimelineMode,
+    author_id: u64,
+    target: FanOut,
+) {
+    if !timeline_mode.uses_home_timeline() {
+        return;
+    }
+    let result = async {
+        if let Some(threshold) = timeline_mode.fan_out_threshold() {
+            if repository.count_followers(author_id).await? > threshold {
+                return Ok(0);
+            }
+        }
+        match target {
+            FanOut::UserTweet { tweet_id } => {
+                repository.fan_out_user_tweet(tweet_id, author_id).await
+            }
+            FanOut::FolloweeTweets { follower_id } => {
+                repository
+                    .fan_out_followee_tweets(follower_id, author_id)
+                    .await
+            }
+        }
+    }
+    .await;
+    if let Err(e) = result {
+        eprintln!(
+            "failed to fan out {:?} of user {}: {}",
+            target, author_id, e
+        );
+    }
+}
+
+// いいねAPI
+#[utoipa::path(
+    post,
+    path = "/api/user_tweets/{id}/likes",
+    params(("id" = u64, Path, description = "ツイートID")),
+    responses(
+        (status = 201, description = "いいね成功"),
+        (status = 200, description = "既にいいね済み"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "ツイートが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn create_like(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let tweet = match find_visible_user_tweet(&repository, user_id, id).await {
+        Ok(Some(tweet)) => tweet,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+    match repository.insert_like(user_id, id).await {
+        Ok(true) => {
+            notifications::notify(
+                &repository,
+                tweet.user_id,
+                NotificationKind::Like,
+                user_id,
+                id,
+            )
+            .await;
+            Ok(StatusCode::CREATED)
+        }
+        Ok(false) => Ok(StatusCode::OK),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
 pub struct CreateFollowRelationParams {
     pub name: String,
 }
 
 // フォローAPI
+#[utoipa::path(
+    post,
+    path = "/api/follow_relations",
+    request_body = CreateFollowRelationParams,
+    responses(
+        (status = 200, description = "非公開アカウントを既にフォローしている"),
+        (status = 201, description = "フォロー成功"),
+        (status = 202, description = "非公開アカウントへのフォロー申請を受け付けた"),
+        (status = 400, description = "フォロー対象のユーザ名が存在しない"),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
 pub(crate) async fn create_follow_relation(
     Json(payload): Json<CreateFollowRelationParams>,
-    arc_pool: Extension<Arc<Pool<MySql>>>,
+    repository: Extension<SharedRepository>,
+    Extension(timeline_mode): Extension<TimelineMode>,
+    Extension(recommendation_cache): Extension<RecommendationCache>,
     session: CurrentSession,
 ) -> impl IntoResponse {
     match session.0.get::<u64>("user_id") {
         Some(user_id) => {
-            let result = User::find_by_name(&payload.name, &arc_pool).await;
+            let result = repository.find_by_name(&payload.name).await;
             match result {
                 Ok(followee) => match followee {
                     Some(followee) => {
+                        // 非公開アカウントは本人が承認するまでフォロー関係を作らない
+                        if followee.is_protected && followee.id != Some(user_id) {
+                            return request_follow(&repository, user_id, followee.id.unwrap())
+                                .await;
+                        }
                         let follow_relation = FollowRelation {
                             id: None,
                             followee_id: followee.id.unwrap(),
                             follower_id: user_id,
                         };
-                        match follow_relation.insert(&arc_pool).await {
-                            Ok(_) => Ok(StatusCode::CREATED),
+                        match repository.insert_follow_relation(&follow_relation).await {
+                            Ok(_) => {
+                                follow_relation_created(
+                                    &repository,
+                                    timeline_mode,
+                                    &recommendation_cache,
+                                    &follow_relation,
+                                )
+                                .await;
+                                Ok(StatusCode::CREATED)
+                            }
                             Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
                         }
                     }
@@ -152,12 +572,170 @@ pub(crate) async fn create_follow_relation(
     }
 }
 
+// 非公開アカウントへのフォロー申請を登録する、フォロー済みなら何もしない
+async fn request_follow(
+    repository: &SharedRepository,
+    requester_id: u64,
+    target_id: u64,
+) -> Result<StatusCode, StatusCode> {
+    match repository.is_following(requester_id, target_id).await {
+        Ok(true) => return Ok(StatusCode::OK),
+        Ok(false) => {}
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+    let request = FollowRequest {
+        id: None,
+        requester_id,
+        target_id,
+    };
+    match repository.insert_follow_request(&request).await {
+        Ok(_) => Ok(StatusCode::ACCEPTED),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// フォロー関係を作成した後の処理、フォロー申請の承認時にも使う
+pub(crate) async fn follow_relation_created(
+    repository: &SharedRepository,
+    timeline_mode: TimelineMode,
+    recommendation_cache: &RecommendationCache,
+    follow_relation: &FollowRelation,
+) {
+    // フォローしたユーザはおすすめから外れるので計算し直させる
+    recommendation_cache.invalidate(follow_relation.follower_id);
+    // フォロー前のツイートもホームタイムラインに載せる
+    fan_out(
+        repository,
+        timeline_mode,
+        follow_relation.followee_id,
+        FanOut::FolloweeTweets {
+            follower_id: follow_relation.follower_id,
+        },
+    )
+    .await;
+    notifications::notify(
+        repository,
+        follow_relation.followee_id,
+        NotificationKind::Follow,
+        follow_relation.follower_id,
+        follow_relation.followee_id,
+    )
+    .await;
+    emit_webhook(
+        repository,
+        WebhookEvent::FollowCreated,
+        serde_json::json!({
+            "follower_id": follow_relation.follower_id,
+            "followee_id": follow_relation.followee_id,
+        }),
+    )
+    .await;
+}
+
+// 閲覧者に見せてよいツイートを取得する
+// 非公開アカウントのツイートは本人とフォロワーにだけ見せ、それ以外には存在しないものとして扱う
+// モデレーターが非表示にしたツイートも本人以外には存在しないものとして扱う
+pub(crate) async fn find_visible_user_tweet(
+    repository: &SharedRepository,
+    viewer_id: u64,
+    tweet_id: u64,
+) -> Result<Option<UserTweet>, sqlx::Error> {
+    let tweet = match repository.find_user_tweet(tweet_id).await? {
+        Some(tweet) => tweet,
+        None => return Ok(None),
+    };
+    if tweet.user_id == viewer_id {
+        return Ok(Some(tweet));
+    }
+    if repository.is_user_tweet_hidden(tweet_id).await? {
+        return Ok(None);
+    }
+    let is_protected = repository
+        .find_by_id(tweet.user_id)
+        .await?
+        .is_some_and(|author| author.is_protected);
+    if is_protected && !repository.is_following(viewer_id, tweet.user_id).await? {
+        return Ok(None);
+    }
+    Ok(Some(tweet))
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct CreateBlockParams {
+    pub name: String,
+}
+
+// ブロックAPI
+// ブロックしたユーザはおすすめユーザから除外され、ダイレクトメッセージも送り合えなくなる
+#[utoipa::path(
+    post,
+    path = "/api/blocks",
+    request_body = CreateBlockParams,
+    responses(
+        (status = 201, description = "ブロック成功"),
+        (status = 200, description = "既にブロック済み"),
+        (status = 400, description = "ブロック対象のユーザ名が存在しない、もしくは自分自身"),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn create_block(
+    Json(payload): Json<CreateBlockParams>,
+    repository: Extension<SharedRepository>,
+    Extension(recommendation_cache): Extension<RecommendationCache>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let blocked_id = match repository.find_by_name(&payload.name).await {
+        Ok(Some(user)) => user.id.unwrap(),
+        Ok(None) => return Err(StatusCode::BAD_REQUEST),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+    if blocked_id == user_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let block = Block {
+        id: None,
+        blocker_id: user_id,
+        blocked_id,
+    };
+    match repository.insert_block(&block).await {
+        Ok(inserted) => {
+            // ブロックは双方のおすすめから外れるので両方計算し直させる
+            recommendation_cache.invalidate(user_id);
+            recommendation_cache.invalidate(blocked_id);
+            Ok(if inserted {
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            })
+        }
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// タイムライン取得API
+#[utoipa::path(
+    get,
+    path = "/api/pages/timeline",
+    responses(
+        (status = 200, description = "タイムライン取得成功", body = [TimelineItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
 pub(crate) async fn get_timeline(
-    arc_pool: Extension<Arc<Pool<MySql>>>,
+    repository: Extension<SharedRepository>,
+    Extension(timeline_mode): Extension<TimelineMode>,
     session: CurrentSession,
 ) -> impl IntoResponse {
     match session.0.get::<u64>("user_id") {
-        Some(user_id) => match timeline(user_id, &arc_pool).await {
+        Some(user_id) => match timeline_for(&repository, timeline_mode, user_id).await {
             Ok(tweets) => Ok(axum::Json(tweets)),
             Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
         },
@@ -165,27 +743,255 @@ pub(crate) async fn get_timeline(
     }
 }
 
-pub async fn run_server(
-    arc_pool: Arc<Pool<MySql>>,
-    session_store: MySqlSessionStore,
-) -> anyhow::Result<()> {
-    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 8888));
-    let app = Router::new()
+// OpenAPIドキュメント取得API
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    responses((status = 200, description = "このAPIのOpenAPIドキュメント"))
+)]
+pub(crate) async fn get_openapi() -> impl IntoResponse {
+    axum::Json(openapi())
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct HealthResponse {
+    // "ok"か"unavailable"
+    pub database: String,
+}
+
+// ヘルスチェックAPI
+// サーバはDBが落ちていても起動するので、ロードバランサはこれでDBに接続できるかを確かめる
+#[utoipa::path(
+    get,
+    path = "/api/health",
+    responses(
+        (status = 200, description = "DBに接続できる", body = HealthResponse),
+        (status = 503, description = "DBに接続できない", body = HealthResponse),
+    )
+)]
+pub(crate) async fn get_health(repository: Extension<SharedRepository>) -> impl IntoResponse {
+    let (status, database) = match repository.ping().await {
+        Ok(()) => (StatusCode::OK, "ok"),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+    };
+    let body = HealthResponse {
+        database: database.to_string(),
+    };
+    (status, axum::Json(body))
+}
+
+// 設定に応じてタイムラインの読み出し方を切り替える
+async fn timeline_for(
+    repository: &SharedRepository,
+    timeline_mode: TimelineMode,
+    user_id: u64,
+) -> Result<Vec<TimelineItem>, sqlx::Error> {
+    let mut items = if timeline_mode.uses_home_timeline() {
+        repository
+            .home_timeline(user_id, timeline_mode.fan_out_threshold())
+            .await?
+    } else {
+        repository.timeline(user_id).await?
+    };
+    attach_link_previews(repository, &mut items).await?;
+    Ok(items)
+}
+
+// 本文中のURLのうち、プレビューを取得済みのものを付ける
+async fn attach_link_previews(
+    repository: &SharedRepository,
+    items: &mut [TimelineItem],
+) -> Result<(), sqlx::Error> {
+    let mut urls: Vec<String> = Vec::new();
+    for entity in items.iter().flat_map(|item| &item.entities) {
+        if entity.kind == EntityKind::Url && !urls.iter().any(|url| url == entity.value()) {
+            urls.push(entity.value().to_string());
+        }
+    }
+    let previews = repository.link_previews(&urls).await?;
+    for item in items {
+        for entity in &item.entities {
+            let preview = previews
+                .iter()
+                .find(|preview| entity.kind == EntityKind::Url && preview.url == entity.value());
+            if let Some(preview) = preview {
+                if !item.link_previews.contains(preview) {
+                    item.link_previews.push(preview.clone());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// APIのルーティングを組み立てる
+// テストからもサーバを起動せずに呼び出せるようにrun_serverから分離している
+pub fn app(
+    repository: SharedRepository,
+    session_store: AppSessionStore,
+    config: ServerConfig,
+) -> Router {
+    let router = Router::new()
         .route("/api/users", post(create_user))
+        .route("/api/users/me", delete(accounts::delete_current_user))
+        .route("/api/users/me/export", get(accounts::export_current_user))
+        .route(
+            "/api/users/me/protected",
+            post(accounts::set_current_user_protected),
+        )
         .route("/api/sessions", post(create_session))
+        .route("/api/oidc/login", get(oidc::start_oidc_login))
+        .route("/api/oidc/callback", get(oidc::finish_oidc_login))
+        .route(
+            "/api/tokens",
+            get(api_tokens::get_api_tokens).post(api_tokens::create_api_token),
+        )
+        .route("/api/tokens/:id", delete(api_tokens::delete_api_token))
         .route("/api/user_tweets", post(create_user_tweet))
+        .route("/api/user_tweets/:id/likes", post(create_like))
+        .route("/api/user_tweets/:id/reports", post(admin::create_report))
         .route("/api/follow_relations", post(create_follow_relation))
+        .route(
+            "/api/follow_requests",
+            get(follow_requests::get_follow_requests),
+        )
+        .route(
+            "/api/follow_requests/:id/approve",
+            post(follow_requests::approve_follow_request),
+        )
+        .route(
+            "/api/follow_requests/:id/reject",
+            post(follow_requests::reject_follow_request),
+        )
+        .route("/api/blocks", post(create_block))
+        .route("/api/lists", get(lists::get_lists).post(lists::create_list))
+        .route(
+            "/api/lists/:id",
+            put(lists::update_list).delete(lists::delete_list),
+        )
+        .route(
+            "/api/lists/:id/members",
+            get(lists::get_list_members).post(lists::add_list_member),
+        )
+        .route(
+            "/api/lists/:id/members/:name",
+            delete(lists::delete_list_member),
+        )
+        .route("/api/lists/:id/timeline", get(lists::get_list_timeline))
+        .route(
+            "/api/recommendations/users",
+            get(recommendations::get_recommended_users),
+        )
+        .route("/api/trends", get(trends::get_trends))
         .route("/api/pages/timeline", get(get_timeline))
-        .layer(Extension(arc_pool))
-        .layer(Extension(session_store));
+        .route("/api/openapi.json", get(get_openapi))
+        .route("/api/health", get(get_health))
+        .route("/api/media", post(media::create_media))
+        .route("/api/media/:id", get(media::get_media))
+        .route("/api/media/:id/thumbnail", get(media::get_media_thumbnail))
+        .route("/api/notifications", get(notifications::get_notifications))
+        .route(
+            "/api/notifications/read",
+            post(notifications::mark_notifications_read),
+        )
+        .route(
+            "/api/conversations",
+            get(direct_messages::get_conversations).post(direct_messages::create_conversation),
+        )
+        .route(
+            "/api/conversations/:id/messages",
+            get(direct_messages::get_direct_messages).post(direct_messages::create_direct_message),
+        )
+        .route("/api/admin/reports", get(admin::get_reports))
+        .route(
+            "/api/admin/reports/:id/resolve",
+            post(admin::resolve_report),
+        )
+        .route(
+            "/api/admin/user_tweets/:id/hide",
+            post(admin::hide_user_tweet),
+        )
+        .route("/api/admin/users/:id/suspend", post(admin::suspend_user))
+        .route("/api/admin/users/:id/role", post(admin::change_user_role))
+        .route("/api/admin/audit_logs", get(admin::get_audit_logs))
+        .route(
+            "/api/admin/webhooks",
+            get(webhooks::get_webhooks).post(webhooks::create_webhook),
+        )
+        .route("/api/admin/webhooks/:id", delete(webhooks::delete_webhook))
+        .route(
+            "/api/admin/webhooks/:id/deliveries",
+            get(webhooks::get_webhook_deliveries),
+        )
+        .route(
+            "/api/admin/webhook_dead_letters",
+            get(webhooks::get_webhook_dead_letters),
+        );
+    // メディア本体の保存先、現状はローカルファイルシステムのみ
+    let blob_store: SharedBlobStore = Arc::new(LocalFsBlobStore::new(config.media_dir));
+    let recommendation_cache = RecommendationCache::new(config.recommendation_cache_ttl);
+    let trend_cache = TrendCache::new(config.trend_cache_ttl);
+    let sticky_primary =
+        StickyPrimary::new(repository.primary_only(), config.sticky_primary_window);
+    // エラーメッセージの翻訳、ファイルは埋め込んでいるので失敗するのは翻訳のJSONが壊れている場合だけ
+    let messages: SharedMessages =
+        Arc::new(Messages::load().expect("failed to load translation catalogs"));
+    // OIDCログインは設定されている場合のみ有効にする
+    let oidc_client: Option<SharedOidcClient> = config
+        .oidc
+        .map(|oidc_config| Arc::new(OidcClient::new(oidc_config)));
+    // 静的ファイルのディレクトリが指定されていればAPI以外のパスでフロントエンドを配信する
+    let router = match config.static_dir {
+        Some(static_dir) => router.fallback(
+            (move |request: Request<Body>| serve_static(static_dir.clone(), request))
+                .into_service(),
+        ),
+        None => router,
+    };
+    router
+        // Extensionを参照するので、Extensionより内側に置く
+        .layer(axum::middleware::from_fn(idempotency::idempotency))
+        // リポジトリを差し替えるので、リポジトリを参照する他のミドルウェアより外側に置く
+        .layer(axum::middleware::from_fn(sticky_primary::sticky_primary))
+        // 他のミドルウェアが返したエラーにも本文を付けるため、ミドルウェアの中で最も外側に置く
+        .layer(axum::middleware::from_fn(errors::localize_errors))
+        .layer(Extension(messages))
+        .layer(Extension(repository))
+        .layer(Extension(session_store))
+        .layer(Extension(config.timeline_mode))
+        .layer(Extension(blob_store))
+        .layer(Extension(recommendation_cache))
+        .layer(Extension(trend_cache))
+        .layer(Extension(oidc_client))
+        .layer(Extension(LinkPreviews(config.link_previews)))
+        .layer(Extension(sticky_primary))
+}
+
+pub async fn run_server(
+    repository: SharedRepository,
+    session_store: AppSessionStore,
+    config: ServerConfig,
+) -> anyhow::Result<()> {
+    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 8888));
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        .serve(app(repository, session_store, config).into_make_service())
         .await?;
     Ok(())
 }
 
-pub struct CurrentSession(Session);
-const AXUM_SESSION_COOKIE_KEY: &str = "axum_session";
+// ログインの方法
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Authentication {
+    // ログインAPIが発行したセッションクッキー
+    Cookie,
+    // Authorization: Bearerヘッダの個人アクセストークン
+    ApiToken,
+}
+
+// セッションとログイン中のユーザ
+// APIトークンの場合はハンドラがuser_idを読めるように、ストアに保存しないセッションを作る
+pub struct CurrentSession(Session, User, Authentication);
+pub(crate) const AXUM_SESSION_COOKIE_KEY: &str = "axum_session";
 // https://github.com/tokio-rs/axum/blob/main/examples/sessions/src/main.rsを改変
 // axumのカスタムextractorを定義
 // クッキーに格納されたセッションキーからセッションデータを復元する
@@ -196,8 +1002,49 @@ where
 {
     type Rejection = StatusCode;
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        // MySQLセッションストアを参照する
-        let Extension(store) = Extension::<MySqlSessionStore>::from_request(req)
+        // Idempotency-Keyの処理で引いたセッションがあればそれを使う
+        if let Some(session) = req.extensions_mut().remove::<CurrentSession>() {
+            return Ok(session);
+        }
+        let Extension(repository) = Extension::<SharedRepository>::from_request(req)
+            .await
+            .unwrap();
+        // Authorizationヘッダがあればクッキーより優先する
+        if let Some(token) = bearer_token(req) {
+            let api_token = match repository
+                .find_api_token_by_hash(&hash_api_token(&token))
+                .await
+            {
+                Ok(Some(api_token)) => api_token,
+                Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+                Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+            };
+            // 読み取り専用のトークンではGETとHEAD以外のリクエストを受け付けない
+            let required_scope = if matches!(*req.method(), Method::GET | Method::HEAD) {
+                ApiTokenScope::Read
+            } else {
+                ApiTokenScope::Write
+            };
+            if !api_token.allows(required_scope) {
+                return Err(StatusCode::FORBIDDEN);
+            }
+            let token_id = api_token.id.unwrap();
+            if let Err(e) = repository
+                .touch_api_token(token_id, Utc::now().naive_utc())
+                .await
+            {
+                eprintln!(
+                    "failed to update last_used_at of api token {}: {}",
+                    token_id, e
+                );
+            }
+            let user = find_active_user(&repository, api_token.user_id).await?;
+            let mut session = Session::new();
+            session.insert("user_id", api_token.user_id).unwrap();
+            return Ok(CurrentSession(session, user, Authentication::ApiToken));
+        }
+        // セッションストアを参照する
+        let Extension(store) = Extension::<AppSessionStore>::from_request(req)
             .await
             .unwrap();
         // ブラウザから送信されたクッキーを参照する
@@ -210,15 +1057,77 @@ where
             .to_string();
         // セッションキーからセッションデータを復元する
         let session_data = store.load_session(session_id).await;
-        match session_data {
+        let session_data = match session_data {
             Ok(session_data) => match session_data {
                 // セッションデータが存在＝セッションデータを返す
-                Some(session_data) => Ok(CurrentSession(session_data)),
+                Some(session_data) => session_data,
                 // セッションデータが存在しない＝ログインできていない
-                None => Err(StatusCode::UNAUTHORIZED),
+                None => return Err(StatusCode::UNAUTHORIZED),
             },
             // RDBとの接続が切れている可能性がある、500を返す
-            Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+            Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+        };
+        let user_id = session_data
+            .get::<u64>("user_id")
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let user = find_active_user(&repository, user_id).await?;
+        Ok(CurrentSession(session_data, user, Authentication::Cookie))
+    }
+}
+
+// Authorization: Bearerヘッダのトークンを取り出す
+fn bearer_token<B>(req: &RequestParts<B>) -> Option<String> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    value
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+// 削除済みのユーザと凍結中のユーザのセッションやトークンは無効にする
+// セッションストアはユーザ単位でセッションを列挙できないので、読み出し時に確かめる
+async fn find_active_user(repository: &SharedRepository, user_id: u64) -> Result<User, StatusCode> {
+    match repository.find_by_id(user_id).await {
+        Ok(Some(user)) if user.is_suspended => Err(StatusCode::FORBIDDEN),
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(StatusCode::UNAUTHORIZED),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// モデレーターもしくは管理者としてログインしている場合のみ通すextractor
+pub struct ModeratorSession(CurrentSession);
+
+#[axum::async_trait]
+impl<B> FromRequest<B> for ModeratorSession
+where
+    B: Send,
+{
+    type Rejection = StatusCode;
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let session = CurrentSession::from_request(req).await?;
+        if session.1.role.can_moderate() {
+            Ok(ModeratorSession(session))
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+// 管理者としてログインしている場合のみ通すextractor
+pub struct AdminSession(CurrentSession);
+
+#[axum::async_trait]
+impl<B> FromRequest<B> for AdminSession
+where
+    B: Send,
+{
+    type Rejection = StatusCode;
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let session = CurrentSession::from_request(req).await?;
+        if session.1.role == Role::Admin {
+            Ok(AdminSession(session))
+        } else {
+            Err(StatusCode::FORBIDDEN)
         }
     }
 }
@@ -0,0 +1,193 @@
+// アカウント削除とデータエクスポートAPI
+use super::{Authentication, CurrentSession, AXUM_SESSION_COOKIE_KEY};
+use crate::media::SharedBlobStore;
+use crate::models::{ExportRecord, ExportSection};
+use crate::password::verify_password;
+use crate::repositories::{AppSessionStore, SharedRepository};
+use async_session::SessionStore as _;
+use axum::{
+    body::{Bytes, StreamBody},
+    extract::{Extension, Json},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+use futures::{stream, StreamExt as _};
+
+// エクスポートで1回のクエリで読む行数
+const EXPORT_PAGE_SIZE: u64 = 500;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct DeleteUserParams {
+    // 再確認のためのパスワード
+    pub password: String,
+}
+
+// アカウント削除API
+#[utoipa::path(
+    delete,
+    path = "/api/users/me",
+    request_body = DeleteUserParams,
+    responses(
+        (status = 204, description = "削除成功、セッションクッキーも削除する"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "パスワードが一致しない、パスワードのないユーザ、APIトークンで呼び出した"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn delete_current_user(
+    Json(payload): Json<DeleteUserParams>,
+    repository: Extension<SharedRepository>,
+    session_store: Extension<AppSessionStore>,
+    blob_store: Extension<SharedBlobStore>,
+    cookie_jar: CookieJar,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    // 取り消せない操作なので、トークンを渡したボットには削除させない
+    if session.2 != Authentication::Cookie {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let user = &session.1;
+    let user_id = user.id.unwrap();
+    // 名前は誰でも知り得るので、パスワードのないユーザは本人と確認できず削除できない
+    let confirmed = user
+        .password_hash
+        .as_deref()
+        .is_some_and(|password_hash| verify_password(&payload.password, password_hash));
+    if !confirmed {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    // メディアの行はユーザと一緒に消えるので、本体を消すために先にキーを控えておく
+    let media = repository
+        .find_media_by_user_id(user_id)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    // ツイートやフォロー関係などはON DELETE CASCADEで一緒に削除される
+    if repository.delete_user(user_id).await.is_err() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    for key in media
+        .iter()
+        .flat_map(|media| [&media.blob_key, &media.thumbnail_key])
+    {
+        if let Err(e) = blob_store.delete(key).await {
+            eprintln!("failed to delete blob {}: {}", key, e);
+        }
+    }
+    // 他の端末のセッションはCurrentSessionがユーザの不在を検出して無効にする
+    if let Err(e) = session_store.destroy_session(session.0).await {
+        eprintln!(
+            "failed to destroy session of deleted user {}: {}",
+            user_id, e
+        );
+    }
+    Ok((
+        StatusCode::NO_CONTENT,
+        cookie_jar.remove(Cookie::named(AXUM_SESSION_COOKIE_KEY)),
+    ))
+}
+
+// データエクスポートAPI
+// プロフィール、ツイート、フォロー関係、いいねを1行1件のNDJSONファイルとしてダウンロードさせる
+// 全件をメモリに載せないよう、区分ごとにIDをカーソルにして少しずつ読みながら書き出す
+#[utoipa::path(
+    get,
+    path = "/api/users/me/export",
+    responses(
+        (status = 200, description = "エクスポート成功、1行目がユーザで以降にツイート、フォロー、フォロワー、いいねが続く", body = ExportRecord, content_type = "application/x-ndjson"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "APIトークンで呼び出した"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn export_current_user(
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    // 読み取り権限のトークンで全データを持ち出させない
+    if session.2 != Authentication::Cookie {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let user_id = session.1.id.unwrap();
+    let user = ExportRecord::User {
+        id: user_id,
+        name: session.1.name.clone(),
+    };
+    let repository = repository.0;
+    let records = stream::try_unfold((0, 0), move |(section, after_id)| {
+        let repository = repository.clone();
+        async move {
+            let mut cursor = (section, after_id);
+            while let Some(&kind) = ExportSection::ALL.get(cursor.0) {
+                let records = repository
+                    .export_records(user_id, kind, cursor.1, EXPORT_PAGE_SIZE)
+                    .await?;
+                let next = match records.last() {
+                    Some((last_id, _)) if records.len() as u64 == EXPORT_PAGE_SIZE => {
+                        (cursor.0, *last_id)
+                    }
+                    _ => (cursor.0 + 1, 0),
+                };
+                if !records.is_empty() {
+                    let lines = records.iter().flat_map(|(_, record)| export_line(record));
+                    return Ok(Some((Bytes::from(lines.collect::<Vec<u8>>()), next)));
+                }
+                cursor = next;
+            }
+            Ok::<_, sqlx::Error>(None)
+        }
+    });
+    let body = stream::once(async move { Ok(Bytes::from(export_line(&user))) }).chain(records);
+    let disposition = format!("attachment; filename=\"ruitter-export-{}.ndjson\"", user_id);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        StreamBody::new(body),
+    ))
+}
+
+fn export_line(record: &ExportRecord) -> Vec<u8> {
+    let mut line = serde_json::to_vec(record).unwrap();
+    line.push(b'\n');
+    line
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct SetProtectedParams {
+    // trueで非公開アカウントにする、falseを送ると公開に戻す
+    pub protected: bool,
+}
+
+// 非公開アカウント設定API
+// 非公開にしても既存のフォロワーはそのまま残り、新しいフォローは申請になる
+#[utoipa::path(
+    post,
+    path = "/api/users/me/protected",
+    request_body = SetProtectedParams,
+    responses(
+        (status = 204, description = "変更成功"),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn set_current_user_protected(
+    Json(payload): Json<SetProtectedParams>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    match repository
+        .set_user_protected(user_id, payload.protected)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
@@ -0,0 +1,266 @@
+// 通報とモデレーションAPI
+// 通報は全ユーザ、通報の確認とツイートの非表示はモデレーター以上、凍結とロールの変更は管理者のみ
+use super::{find_visible_user_tweet, AdminSession, CurrentSession, ModeratorSession};
+use crate::models::{Report, Role};
+use crate::repositories::SharedRepository;
+use axum::{
+    extract::{Extension, Json, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+// 通報理由の最大文字数
+pub const MAX_REPORT_REASON_LENGTH: usize = 255;
+// 一覧APIで返す件数
+const REPORT_LIMIT: u64 = 100;
+const AUDIT_LOG_LIMIT: u64 = 100;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct CreateReportParams {
+    pub reason: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct HideUserTweetParams {
+    // falseを送ると非表示を解除する
+    pub hidden: bool,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct SuspendUserParams {
+    // falseを送ると凍結を解除する
+    pub suspended: bool,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ChangeUserRoleParams {
+    pub role: Role,
+}
+
+// ツイート通報API
+#[utoipa::path(
+    post,
+    path = "/api/user_tweets/{id}/reports",
+    params(("id" = u64, Path, description = "ツイートID")),
+    request_body = CreateReportParams,
+    responses(
+        (status = 201, description = "通報成功"),
+        (status = 200, description = "既に通報済み"),
+        (status = 400, description = "通報理由が空もしくは長すぎる"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "ツイートが存在しない、もしくは閲覧できない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn create_report(
+    Path(id): Path<u64>,
+    Json(payload): Json<CreateReportParams>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let length = payload.reason.chars().count();
+    if length == 0 || length > MAX_REPORT_REASON_LENGTH {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let reporter_id = session.1.id.unwrap();
+    // 見えないツイートは存在しないものとして扱い、通報の結果から存在を推測させない
+    match find_visible_user_tweet(&repository, reporter_id, id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+    let report = Report {
+        id: None,
+        reporter_id,
+        user_tweet_id: id,
+        reason: payload.reason,
+        is_resolved: false,
+    };
+    match repository.insert_report(&report).await {
+        Ok(true) => Ok(StatusCode::CREATED),
+        Ok(false) => Ok(StatusCode::OK),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// 未対応の通報一覧API
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports",
+    responses(
+        (status = 200, description = "古い順の未対応の通報", body = [ReportItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "モデレーターではない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_reports(
+    repository: Extension<SharedRepository>,
+    _session: ModeratorSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    match repository.unresolved_reports(REPORT_LIMIT).await {
+        Ok(reports) => Ok(axum::Json(reports)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// 通報を対応済みにするAPI
+#[utoipa::path(
+    post,
+    path = "/api/admin/reports/{id}/resolve",
+    params(("id" = u64, Path, description = "通報ID")),
+    responses(
+        (status = 204, description = "対応済みにした"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "モデレーターではない"),
+        (status = 404, description = "通報が存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn resolve_report(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    session: ModeratorSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    no_content_or_not_found(repository.resolve_report(actor_id(&session.0), id).await)
+}
+
+// ツイート非表示API、非表示のツイートはタイムラインに出なくなる
+#[utoipa::path(
+    post,
+    path = "/api/admin/user_tweets/{id}/hide",
+    params(("id" = u64, Path, description = "ツイートID")),
+    request_body = HideUserTweetParams,
+    responses(
+        (status = 204, description = "変更成功"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "モデレーターではない"),
+        (status = 404, description = "ツイートが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn hide_user_tweet(
+    Path(id): Path<u64>,
+    Json(payload): Json<HideUserTweetParams>,
+    repository: Extension<SharedRepository>,
+    session: ModeratorSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    no_content_or_not_found(
+        repository
+            .set_user_tweet_hidden(actor_id(&session.0), id, payload.hidden)
+            .await,
+    )
+}
+
+// アカウント凍結API、凍結中のユーザはログインできず、既存のセッションも使えなくなる
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{id}/suspend",
+    params(("id" = u64, Path, description = "ユーザID")),
+    request_body = SuspendUserParams,
+    responses(
+        (status = 204, description = "変更成功"),
+        (status = 400, description = "自分自身は凍結できない"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "管理者ではない"),
+        (status = 404, description = "ユーザが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn suspend_user(
+    Path(id): Path<u64>,
+    Json(payload): Json<SuspendUserParams>,
+    repository: Extension<SharedRepository>,
+    session: AdminSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let actor_id = actor_id(&session.0);
+    if id == actor_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    no_content_or_not_found(
+        repository
+            .set_user_suspended(actor_id, id, payload.suspended)
+            .await,
+    )
+}
+
+// ロール変更API
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{id}/role",
+    params(("id" = u64, Path, description = "ユーザID")),
+    request_body = ChangeUserRoleParams,
+    responses(
+        (status = 204, description = "変更成功"),
+        (status = 400, description = "自分自身のロールは変更できない、パスワードのないユーザをモデレーター以上にしようとした"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "管理者ではない"),
+        (status = 404, description = "ユーザが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn change_user_role(
+    Path(id): Path<u64>,
+    Json(payload): Json<ChangeUserRoleParams>,
+    repository: Extension<SharedRepository>,
+    session: AdminSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let actor_id = actor_id(&session.0);
+    // 最後の管理者が自分の権限を外して誰も管理できなくなるのを防ぐ
+    if id == actor_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let user = repository
+        .find_by_id(id)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !user.can_hold_role(payload.role) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    no_content_or_not_found(
+        repository
+            .set_user_role(Some(actor_id), id, payload.role)
+            .await,
+    )
+}
+
+// 監査ログ一覧API
+#[utoipa::path(
+    get,
+    path = "/api/admin/audit_logs",
+    responses(
+        (status = 200, description = "新しい順の監査ログ", body = [ModerationLogItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "管理者ではない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_audit_logs(
+    repository: Extension<SharedRepository>,
+    _session: AdminSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    match repository.moderation_logs(AUDIT_LOG_LIMIT).await {
+        Ok(logs) => Ok(axum::Json(logs)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+fn actor_id(session: &CurrentSession) -> u64 {
+    session.1.id.unwrap()
+}
+
+fn no_content_or_not_found(result: Result<bool, sqlx::Error>) -> Result<StatusCode, StatusCode> {
+    match result {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
@@ -0,0 +1,134 @@
+// ボットやCLI向けの個人アクセストークンAPI
+// トークンからトークンを発行して権限を広げられないよう、管理はクッキーでログインしている場合のみ受け付ける
+use super::{Authentication, CurrentSession};
+use crate::api_token::{generate_api_token, hash_api_token};
+use crate::models::{ApiToken, ApiTokenScope};
+use crate::repositories::SharedRepository;
+use axum::{
+    extract::{Extension, Json, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+// トークン名の最大文字数
+const MAX_API_TOKEN_NAME_LENGTH: usize = 255;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct CreateApiTokenParams {
+    pub name: String,
+    // 省略するとreadのみ
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<ApiTokenScope>,
+}
+
+fn default_scopes() -> Vec<ApiTokenScope> {
+    vec![ApiTokenScope::Read]
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct CreateApiTokenResponse {
+    pub id: u64,
+    // トークン本体、保存しないのでこのレスポンスでしか受け取れない
+    pub token: String,
+}
+
+// トークン発行API
+#[utoipa::path(
+    post,
+    path = "/api/tokens",
+    request_body = CreateApiTokenParams,
+    responses(
+        (status = 201, description = "発行成功", body = CreateApiTokenResponse),
+        (status = 400, description = "名前が空もしくは長すぎる、権限が空"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "APIトークンで呼び出した"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn create_api_token(
+    Json(payload): Json<CreateApiTokenParams>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = cookie_session_user_id(&session)?;
+    let length = payload.name.chars().count();
+    if length == 0 || length > MAX_API_TOKEN_NAME_LENGTH || payload.scopes.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut scopes = payload.scopes;
+    scopes.sort_by_key(|scope| scope.as_str());
+    scopes.dedup();
+    let token = generate_api_token();
+    let api_token = ApiToken {
+        id: None,
+        user_id,
+        name: payload.name,
+        token_hash: hash_api_token(&token),
+        scopes,
+    };
+    match repository.insert_api_token(&api_token).await {
+        Ok(id) => Ok((
+            StatusCode::CREATED,
+            axum::Json(CreateApiTokenResponse { id, token }),
+        )),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// トークン一覧API、トークン本体は返さない
+#[utoipa::path(
+    get,
+    path = "/api/tokens",
+    responses(
+        (status = 200, description = "作成順のトークン", body = [ApiTokenItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "APIトークンで呼び出した"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_api_tokens(
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = cookie_session_user_id(&session)?;
+    match repository.api_tokens(user_id).await {
+        Ok(tokens) => Ok(axum::Json(tokens)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// トークン失効API
+#[utoipa::path(
+    delete,
+    path = "/api/tokens/{id}",
+    params(("id" = u64, Path, description = "トークンID")),
+    responses(
+        (status = 204, description = "失効成功"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "APIトークンで呼び出した"),
+        (status = 404, description = "自分のトークンではない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn delete_api_token(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = cookie_session_user_id(&session)?;
+    match repository.delete_api_token(user_id, id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+fn cookie_session_user_id(session: &CurrentSession) -> Result<u64, StatusCode> {
+    if session.2 != Authentication::Cookie {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(session.1.id.unwrap())
+}
@@ -0,0 +1,227 @@
+// 相互フォローのユーザ同士の1対1のダイレクトメッセージAPI
+use super::CurrentSession;
+use crate::models::{Conversation, DirectMessage};
+use crate::repositories::SharedRepository;
+use axum::{
+    extract::{Extension, Json, Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+// 本文の最大文字数
+pub const MAX_DIRECT_MESSAGE_LENGTH: usize = 1000;
+// メッセージ取得APIのデフォルトと最大の件数
+const DEFAULT_MESSAGE_LIMIT: u64 = 50;
+const MAX_MESSAGE_LIMIT: u64 = 100;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct CreateConversationParams {
+    // 会話相手のユーザ名
+    pub name: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct CreateConversationResponse {
+    pub id: u64,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+pub struct DirectMessagesQuery {
+    // このIDより古いメッセージを返す
+    pub before_id: Option<u64>,
+    // 取得件数、最大100
+    pub limit: Option<u64>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct CreateDirectMessageParams {
+    pub content: String,
+}
+
+// 会話一覧API
+#[utoipa::path(
+    get,
+    path = "/api/conversations",
+    responses(
+        (status = 200, description = "会話一覧取得成功", body = [ConversationItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_conversations(
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    match repository.conversations(user_id).await {
+        Ok(conversations) => Ok(axum::Json(conversations)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// 会話開始API、既に会話があればそのIDを返す
+#[utoipa::path(
+    post,
+    path = "/api/conversations",
+    request_body = CreateConversationParams,
+    responses(
+        (status = 201, description = "会話開始成功", body = CreateConversationResponse),
+        (status = 400, description = "相手のユーザ名が存在しない、もしくは自分自身"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "相互フォローではない、もしくはどちらかがブロックしている"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn create_conversation(
+    Json(payload): Json<CreateConversationParams>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let partner_id = match repository.find_by_name(&payload.name).await {
+        Ok(Some(user)) => user.id.unwrap(),
+        Ok(None) => return Err(StatusCode::BAD_REQUEST),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+    if partner_id == user_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    ensure_can_message(&repository, user_id, partner_id).await?;
+    match repository
+        .find_or_create_conversation(&Conversation::between(user_id, partner_id))
+        .await
+    {
+        Ok(conversation) => Ok((
+            StatusCode::CREATED,
+            axum::Json(CreateConversationResponse {
+                id: conversation.id.unwrap(),
+            }),
+        )),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// メッセージ取得API
+// 取得すると相手から届いたメッセージを既読にする
+#[utoipa::path(
+    get,
+    path = "/api/conversations/{id}/messages",
+    params(("id" = u64, Path, description = "会話ID"), DirectMessagesQuery),
+    responses(
+        (status = 200, description = "新しい順のメッセージ", body = [DirectMessageItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "会話が存在しない、もしくは参加していない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_direct_messages(
+    Path(id): Path<u64>,
+    Query(query): Query<DirectMessagesQuery>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    find_own_conversation(&repository, id, user_id).await?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_MESSAGE_LIMIT)
+        .clamp(1, MAX_MESSAGE_LIMIT);
+    let messages = repository
+        .direct_messages(id, query.before_id, limit)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    // 既読にできなくてもメッセージは返す
+    if let Err(e) = repository.mark_direct_messages_read(id, user_id).await {
+        eprintln!("failed to mark direct messages read: {}", e);
+    }
+    Ok(axum::Json(messages))
+}
+
+// メッセージ送信API
+#[utoipa::path(
+    post,
+    path = "/api/conversations/{id}/messages",
+    params(("id" = u64, Path, description = "会話ID")),
+    request_body = CreateDirectMessageParams,
+    responses(
+        (status = 201, description = "送信成功"),
+        (status = 400, description = "本文が空もしくは長すぎる"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "相互フォローでなくなった、もしくはどちらかがブロックしている"),
+        (status = 404, description = "会話が存在しない、もしくは参加していない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn create_direct_message(
+    Path(id): Path<u64>,
+    Json(payload): Json<CreateDirectMessageParams>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let length = payload.content.chars().count();
+    if length == 0 || length > MAX_DIRECT_MESSAGE_LENGTH {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let conversation = find_own_conversation(&repository, id, user_id).await?;
+    // 会話を始めた後にフォローやブロックの関係が変わっていれば送れない
+    ensure_can_message(&repository, user_id, conversation.partner_id(user_id)).await?;
+    let message = DirectMessage {
+        id: None,
+        conversation_id: id,
+        sender_id: user_id,
+        content: payload.content,
+        is_read: false,
+    };
+    match repository.insert_direct_message(&message).await {
+        Ok(_) => Ok(StatusCode::CREATED),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// メッセージを送れるのは相互フォローで、どちらもブロックしていないユーザ同士だけ
+async fn ensure_can_message(
+    repository: &SharedRepository,
+    user_id: u64,
+    partner_id: u64,
+) -> Result<(), StatusCode> {
+    match repository.are_mutual_followers(user_id, partner_id).await {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::FORBIDDEN),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+    match repository.is_blocked_between(user_id, partner_id).await {
+        Ok(false) => Ok(()),
+        Ok(true) => Err(StatusCode::FORBIDDEN),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// 自分が参加している会話を返す、他人の会話は存在しないものとして扱う
+async fn find_own_conversation(
+    repository: &SharedRepository,
+    id: u64,
+    user_id: u64,
+) -> Result<Conversation, StatusCode> {
+    match repository.find_conversation(id).await {
+        Ok(Some(conversation)) if conversation.has_participant(user_id) => Ok(conversation),
+        Ok(_) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
@@ -0,0 +1,164 @@
+// APIのエラーレスポンス
+// ハンドラはステータスコードだけを返し、本文はlocalize_errorsがエラーコードとAccept-Languageの言語のメッセージで組み立てる
+use crate::i18n::{Locale, SharedMessages};
+use axum::{
+    body::{boxed, Body, Full},
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+// クライアントが分岐に使うエラーコード、追加はしても変更はしない
+// 翻訳はlocales/の全ての言語に用意する
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    utoipa::ToSchema,
+    strum::VariantArray,
+    strum::IntoStaticStr,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ErrorCode {
+    // ステータスコードごとの汎用のコード
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    UnprocessableEntity,
+    InternalError,
+    BadGateway,
+    ServiceUnavailable,
+    // ログイン
+    InvalidCredentials,
+    AccountSuspended,
+    PasswordLoginDisabled,
+    // Idempotency-Key
+    IdempotencyKeyInvalid,
+    IdempotencyKeyInProgress,
+    IdempotencyKeyReused,
+}
+impl ErrorCode {
+    // 一覧と文字列は列挙子から導出するので、コードを追加すれば自動で含まれる
+    pub const ALL: &'static [Self] = <Self as strum::VariantArray>::VARIANTS;
+
+    pub fn as_str(&self) -> &'static str {
+        self.into()
+    }
+
+    // ハンドラがコードを指定しなかった場合のコード
+    // 専用のコードのないステータスは4xxと5xxの汎用のコードにまとめる
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST => Self::BadRequest,
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => Self::MethodNotAllowed,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Self::UnsupportedMediaType,
+            StatusCode::UNPROCESSABLE_ENTITY => Self::UnprocessableEntity,
+            StatusCode::BAD_GATEWAY => Self::BadGateway,
+            StatusCode::SERVICE_UNAVAILABLE => Self::ServiceUnavailable,
+            status if status.is_client_error() => Self::BadRequest,
+            _ => Self::InternalError,
+        }
+    }
+}
+
+// エラーレスポンスの本文
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    // Accept-Languageの言語のメッセージ、表示用なので分岐には使わない
+    pub message: String,
+}
+
+// ステータスコードと汎用でないエラーコードを返すハンドラのエラー
+// StatusCodeから変換できるので、StatusCodeを返す関数も?で呼べる
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: ErrorCode,
+}
+impl ApiError {
+    pub fn new(status: StatusCode, code: ErrorCode) -> Self {
+        Self { status, code }
+    }
+}
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        Self::new(status, ErrorCode::from_status(status))
+    }
+}
+impl IntoResponse for ApiError {
+    // 本文はlocalize_errorsで付けるので、ここではコードをレスポンスに持たせるだけにする
+    fn into_response(self) -> Response {
+        let mut response = self.status.into_response();
+        response.extensions_mut().insert(self.code);
+        response
+    }
+}
+
+// /api/以下のエラーレスポンスにエラーコードと翻訳したメッセージの本文を付ける
+// 本文がJSONのエラーレスポンスはそのまま返す
+pub(crate) async fn localize_errors(req: Request<Body>, next: Next<Body>) -> Response {
+    if !req.uri().path().starts_with("/api/") {
+        return next.run(req).await;
+    }
+    let locale = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::from_accept_language)
+        .unwrap_or_default();
+    let messages = req.extensions().get::<SharedMessages>().unwrap().clone();
+    let response = next.run(req).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) || is_json(&response) {
+        return response;
+    }
+    let code = response
+        .extensions()
+        .get::<ErrorCode>()
+        .copied()
+        .unwrap_or_else(|| ErrorCode::from_status(status));
+    let body = ErrorResponse {
+        code,
+        message: messages.message(locale, code.as_str()).to_string(),
+    };
+    // Set-Cookieなど他のヘッダは残し、本文だけを差し替える
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    parts.headers.insert(
+        header::CONTENT_LANGUAGE,
+        HeaderValue::from_static(locale.as_str()),
+    );
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("accept-language"));
+    let body = serde_json::to_vec(&body).unwrap();
+    Response::from_parts(parts, boxed(Full::from(body)))
+}
+
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"))
+}
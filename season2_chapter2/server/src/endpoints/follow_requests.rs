@@ -0,0 +1,108 @@
+// 非公開アカウントへのフォロー申請の一覧、承認、拒否API
+// 申請はcreate_follow_relationで非公開アカウントをフォローしたときに作られる
+use super::{follow_relation_created, CurrentSession};
+use crate::config::TimelineMode;
+use crate::endpoints::recommendations::RecommendationCache;
+use crate::repositories::SharedRepository;
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+// 自分宛てのフォロー申請一覧API
+#[utoipa::path(
+    get,
+    path = "/api/follow_requests",
+    responses(
+        (status = 200, description = "未処理の申請を古い順に返す", body = [FollowRequestItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_follow_requests(
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    match repository.follow_requests(user_id).await {
+        Ok(requests) => Ok(axum::Json(requests)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// フォロー申請承認API、申請したユーザがフォロワーになる
+#[utoipa::path(
+    post,
+    path = "/api/follow_requests/{id}/approve",
+    params(("id" = u64, Path, description = "フォロー申請ID")),
+    responses(
+        (status = 204, description = "承認成功"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "自分宛ての申請が存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn approve_follow_request(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    Extension(timeline_mode): Extension<TimelineMode>,
+    Extension(recommendation_cache): Extension<RecommendationCache>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    match repository.approve_follow_request(user_id, id).await {
+        Ok(Some(follow_relation)) => {
+            // 既にフォロー済みだった場合は配布と通知を繰り返さない
+            if follow_relation.id.is_some() {
+                follow_relation_created(
+                    &repository,
+                    timeline_mode,
+                    &recommendation_cache,
+                    &follow_relation,
+                )
+                .await;
+            }
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// フォロー申請拒否API、申請したユーザには通知しない
+#[utoipa::path(
+    post,
+    path = "/api/follow_requests/{id}/reject",
+    params(("id" = u64, Path, description = "フォロー申請ID")),
+    responses(
+        (status = 204, description = "拒否成功"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "自分宛ての申請が存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn reject_follow_request(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    match repository.delete_follow_request(user_id, id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
@@ -0,0 +1,219 @@
+// Idempotency-Keyヘッダによる再送の検出
+// 不安定な回線のクライアントが同じリクエストを再送しても、最初のレスポンスを返して二重に処理しない
+use super::errors::{ApiError, ErrorCode};
+use super::media::MAX_UPLOAD_BYTES;
+use super::CurrentSession;
+use crate::models::IdempotentResponse;
+use crate::repositories::SharedRepository;
+use axum::{
+    body::{boxed, Body, Bytes, Full, HttpBody as _},
+    extract::{FromRequest, RequestParts},
+    http::{header, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{Duration, Utc};
+use sha2::{Digest as _, Sha256};
+
+pub(crate) const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+// 保存したレスポンスを返したことを示すヘッダ
+pub(crate) const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+const MAX_KEY_LENGTH: usize = 255;
+// レスポンスを保存しておく時間
+const KEY_TTL_HOURS: i64 = 24;
+// 処理中のキーの有効期限、処理中にサーバが落ちてもこの時間が過ぎれば再送を受け付ける
+const PROCESSING_TIMEOUT_SECONDS: i64 = 60;
+// ハッシュを計算するためにメモリに読むリクエストの上限、メディアのアップロードが最大
+const MAX_REQUEST_BYTES: u64 = MAX_UPLOAD_BYTES;
+// 保存するレスポンスの上限、これより大きいか大きさのわからないレスポンスは保存せずにそのまま返す
+const MAX_STORED_RESPONSE_BYTES: u64 = 64 * 1024;
+// 接続ごとのヘッダと、保存したボディから計算し直すヘッダは保存しない
+const UNSTORED_HEADERS: [header::HeaderName; 9] = [
+    header::CONNECTION,
+    header::CONTENT_LENGTH,
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+    header::HeaderName::from_static("keep-alive"),
+];
+
+// ログイン中のユーザのPOST、PUT、PATCH、DELETEにIdempotency-Keyヘッダがあれば、
+// 同じユーザの同じキーのリクエストには保存したレスポンスを返す
+// LocationやSet-Cookieなどのヘッダも保存して返すが、値がUTF-8でないヘッダは保存しない
+// 未ログインのリクエストはキーを無視してそのまま処理する
+pub(crate) async fn idempotency(
+    req: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, ApiError> {
+    if !matches!(
+        *req.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    ) {
+        return Ok(next.run(req).await);
+    }
+    let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+            .ok_or(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::IdempotencyKeyInvalid,
+            ))?
+            .to_string(),
+        None => return Ok(next.run(req).await),
+    };
+    let mut parts = RequestParts::new(req);
+    let session = CurrentSession::from_request(&mut parts).await;
+    let mut req = parts.try_into_request().unwrap();
+    let user_id = match session {
+        Ok(session) => {
+            let user_id = session.1.id.unwrap();
+            // ハンドラがセッションとユーザを引き直さないように渡しておく
+            req.extensions_mut().insert(session);
+            user_id
+        }
+        Err(_) => return Ok(next.run(req).await),
+    };
+    let repository = req.extensions().get::<SharedRepository>().unwrap().clone();
+
+    let (parts, body) = req.into_parts();
+    let body = read_limited(body, MAX_REQUEST_BYTES)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .ok_or(StatusCode::PAYLOAD_TOO_LARGE)?;
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map_or(parts.uri.path(), |path_and_query| path_and_query.as_str());
+    let request_hash = request_hash(&parts.method, path_and_query, &body);
+    let now = Utc::now().naive_utc();
+    let reserved = repository
+        .reserve_idempotency_key(
+            user_id,
+            &key,
+            &request_hash,
+            now,
+            now + Duration::seconds(PROCESSING_TIMEOUT_SECONDS),
+        )
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    if !reserved {
+        let existing = repository
+            .find_idempotency_key(user_id, &key)
+            .await
+            .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?
+            // 登録から参照までの間に期限切れで削除された
+            .ok_or(StatusCode::CONFLICT)?;
+        // 同じキーを別のリクエストに使い回している
+        if existing.request_hash != request_hash {
+            return Err(ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::IdempotencyKeyReused,
+            ));
+        }
+        // 最初のリクエストがまだ処理中
+        let stored = existing.response.ok_or(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::IdempotencyKeyInProgress,
+        ))?;
+        return Ok(replay(stored));
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let (parts, body) = response.into_parts();
+    // ファイルのダウンロードのような大きいレスポンスは保存しない、再送は処理し直す
+    let storable = body
+        .size_hint()
+        .upper()
+        .is_some_and(|upper| upper <= MAX_STORED_RESPONSE_BYTES);
+    if !storable {
+        forget_key(&repository, user_id, &key).await;
+        return Ok(Response::from_parts(parts, body));
+    }
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => {
+            forget_key(&repository, user_id, &key).await;
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+        }
+    };
+    // DBに接続できないなどのサーバ側の失敗は保存せず、再送で処理し直せるようにする
+    if parts.status.is_server_error() {
+        forget_key(&repository, user_id, &key).await;
+    } else {
+        let stored = IdempotentResponse {
+            status_code: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .filter(|(name, _)| !UNSTORED_HEADERS.contains(name))
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: body.to_vec(),
+        };
+        let expires_at = Utc::now().naive_utc() + Duration::hours(KEY_TTL_HOURS);
+        if let Err(e) = repository
+            .complete_idempotency_key(user_id, &key, &stored, expires_at)
+            .await
+        {
+            eprintln!("failed to store idempotent response: {}", e);
+        }
+    }
+    Ok(Response::from_parts(parts, boxed(Full::from(body))))
+}
+
+// 上限までボディを読む、Content-Lengthか読んだ量が上限を超えたらNoneを返す
+async fn read_limited(mut body: Body, limit: u64) -> Result<Option<Bytes>, hyper::Error> {
+    if body.size_hint().lower() > limit {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if (bytes.len() + chunk.len()) as u64 > limit {
+            return Ok(None);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(Some(Bytes::from(bytes)))
+}
+
+// メソッド、クエリ文字列を含むパス、ボディが同じなら同じリクエストとみなす
+fn request_hash(method: &Method, path_and_query: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b" ");
+    hasher.update(path_and_query);
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+fn replay(stored: IdempotentResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status_code).unwrap_or(StatusCode::OK);
+    let mut response = (status, stored.body).into_response();
+    let headers = response.headers_mut();
+    headers.remove(header::CONTENT_TYPE);
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (
+            header::HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+async fn forget_key(repository: &SharedRepository, user_id: u64, key: &str) {
+    if let Err(e) = repository.delete_idempotency_key(user_id, key).await {
+        eprintln!("failed to delete idempotency key: {}", e);
+    }
+}
@@ -0,0 +1,317 @@
+// 選んだユーザのツイートだけを読むリストのAPI
+// リストは作成したユーザだけが参照、編集でき、他のユーザのリストは存在しないものとして扱う
+use super::{attach_link_previews, CurrentSession};
+use crate::models::{ListMember, UserList};
+use crate::repositories::SharedRepository;
+use axum::{
+    extract::{Extension, Json, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+// リスト名の最大文字数
+pub const MAX_LIST_NAME_LENGTH: usize = 64;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ListParams {
+    pub name: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct CreateListResponse {
+    pub id: u64,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct AddListMemberParams {
+    // 追加するユーザ名
+    pub name: String,
+}
+
+// 前後の空白を除いたリスト名、空か長すぎる場合は400
+fn validate_list_name(name: &str) -> Result<String, StatusCode> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_LIST_NAME_LENGTH {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(name.to_string())
+}
+
+// ログイン中のユーザが作成したリストを返す、他のユーザのリストは404
+async fn find_own_list(
+    repository: &SharedRepository,
+    user_id: u64,
+    list_id: u64,
+) -> Result<UserList, StatusCode> {
+    match repository.find_list(list_id).await {
+        Ok(Some(list)) if list.owner_id == user_id => Ok(list),
+        Ok(_) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// 自分のリスト一覧API
+#[utoipa::path(
+    get,
+    path = "/api/lists",
+    responses(
+        (status = 200, description = "作成した順のリスト", body = [UserListItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_lists(
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    match repository.lists(user_id).await {
+        Ok(lists) => Ok(axum::Json(lists)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// リスト作成API
+#[utoipa::path(
+    post,
+    path = "/api/lists",
+    request_body = ListParams,
+    responses(
+        (status = 201, description = "作成成功", body = CreateListResponse),
+        (status = 400, description = "リスト名が空、もしくは長すぎる"),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn create_list(
+    Json(payload): Json<ListParams>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let list = UserList {
+        id: None,
+        owner_id: user_id,
+        name: validate_list_name(&payload.name)?,
+    };
+    match repository.insert_list(&list).await {
+        Ok(id) => Ok((StatusCode::CREATED, axum::Json(CreateListResponse { id }))),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// リスト名変更API
+#[utoipa::path(
+    put,
+    path = "/api/lists/{id}",
+    params(("id" = u64, Path, description = "リストID")),
+    request_body = ListParams,
+    responses(
+        (status = 204, description = "変更成功"),
+        (status = 400, description = "リスト名が空、もしくは長すぎる"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "自分のリストが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn update_list(
+    Path(id): Path<u64>,
+    Json(payload): Json<ListParams>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let name = validate_list_name(&payload.name)?;
+    find_own_list(&repository, user_id, id).await?;
+    match repository.rename_list(id, &name).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// リスト削除API、メンバーも一緒に削除する
+#[utoipa::path(
+    delete,
+    path = "/api/lists/{id}",
+    params(("id" = u64, Path, description = "リストID")),
+    responses(
+        (status = 204, description = "削除成功"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "自分のリストが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn delete_list(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    find_own_list(&repository, user_id, id).await?;
+    match repository.delete_list(id).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// リストのメンバー一覧API
+#[utoipa::path(
+    get,
+    path = "/api/lists/{id}/members",
+    params(("id" = u64, Path, description = "リストID")),
+    responses(
+        (status = 200, description = "追加した順のメンバー", body = [ListMemberItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "自分のリストが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_list_members(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    find_own_list(&repository, user_id, id).await?;
+    match repository.list_members(id).await {
+        Ok(members) => Ok(axum::Json(members)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// リストへのメンバー追加API
+// フォローしていないユーザも追加できるが、非公開アカウントのツイートはフォローするまで表示しない
+#[utoipa::path(
+    post,
+    path = "/api/lists/{id}/members",
+    params(("id" = u64, Path, description = "リストID")),
+    request_body = AddListMemberParams,
+    responses(
+        (status = 201, description = "追加成功"),
+        (status = 200, description = "追加済み"),
+        (status = 400, description = "ユーザ名が存在しない"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "自分のリストが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn add_list_member(
+    Path(id): Path<u64>,
+    Json(payload): Json<AddListMemberParams>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    find_own_list(&repository, user_id, id).await?;
+    let member_id = match repository.find_by_name(&payload.name).await {
+        Ok(Some(user)) => user.id.unwrap(),
+        Ok(None) => return Err(StatusCode::BAD_REQUEST),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+    let member = ListMember {
+        id: None,
+        list_id: id,
+        user_id: member_id,
+    };
+    match repository.insert_list_member(&member).await {
+        Ok(true) => Ok(StatusCode::CREATED),
+        Ok(false) => Ok(StatusCode::OK),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// リストからのメンバー削除API
+#[utoipa::path(
+    delete,
+    path = "/api/lists/{id}/members/{name}",
+    params(
+        ("id" = u64, Path, description = "リストID"),
+        ("name" = String, Path, description = "削除するユーザ名"),
+    ),
+    responses(
+        (status = 204, description = "削除成功"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "自分のリストが存在しない、もしくはメンバーではない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn delete_list_member(
+    Path((id, name)): Path<(u64, String)>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    find_own_list(&repository, user_id, id).await?;
+    let member_id = match repository.find_by_name(&name).await {
+        Ok(Some(user)) => user.id.unwrap(),
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+    match repository.delete_list_member(id, member_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// リストのタイムラインAPI
+// ホームタイムラインと同じ形式で、フォロイーの代わりにリストのメンバーのツイートを返す
+#[utoipa::path(
+    get,
+    path = "/api/lists/{id}/timeline",
+    params(("id" = u64, Path, description = "リストID")),
+    responses(
+        (status = 200, description = "新しい順のツイート", body = [TimelineItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "自分のリストが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_list_timeline(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    find_own_list(&repository, user_id, id).await?;
+    let mut items = repository
+        .list_timeline(id, user_id)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    attach_link_previews(&repository, &mut items)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(axum::Json(items))
+}
@@ -0,0 +1,196 @@
+// メディアのアップロードと配信API
+use super::{find_visible_user_tweet, CurrentSession};
+use crate::media::{
+    generate_blob_key, generate_thumbnail, MediaError, SharedBlobStore, MAX_MEDIA_BYTES,
+    THUMBNAIL_CONTENT_TYPE,
+};
+use crate::models::{Media, TimelineMedia};
+use crate::repositories::SharedRepository;
+use axum::{
+    extract::{ContentLengthLimit, Extension, Multipart, Path},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+
+// multipartの境界文字列などの分だけメディアの上限より少し大きくする
+pub(crate) const MAX_UPLOAD_BYTES: u64 = MAX_MEDIA_BYTES as u64 + 64 * 1024;
+// メディアは内容が変わらないので長期キャッシュさせる
+// 見られるユーザが限られるので共有キャッシュには置かせない
+const MEDIA_CACHE_CONTROL: &str = "private, max-age=31536000, immutable";
+
+// アップロードフォームのスキーマ(OpenAPIドキュメント用)
+#[allow(dead_code)]
+#[derive(utoipa::ToSchema)]
+pub struct MediaUploadForm {
+    // 画像ファイル(JPEG, PNG, GIF, WebP)
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+}
+
+// メディアアップロードAPI
+#[utoipa::path(
+    post,
+    path = "/api/media",
+    request_body(content = MediaUploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "アップロード成功", body = TimelineMedia),
+        (status = 400, description = "fileフィールドがない、もしくは画像として読めない"),
+        (status = 401, description = "未ログイン"),
+        (status = 413, description = "ファイルもしくは画像の縦横が大きすぎる"),
+        (status = 415, description = "対応していないMIMEタイプ"),
+        (status = 503, description = "DBもしくは保存先に接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn create_media(
+    ContentLengthLimit(mut multipart): ContentLengthLimit<Multipart, MAX_UPLOAD_BYTES>,
+    repository: Extension<SharedRepository>,
+    blob_store: Extension<SharedBlobStore>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    // fileフィールドを探す
+    let (content_type, bytes) = loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("file") => {
+                let content_type = field.content_type().unwrap_or("").to_string();
+                let bytes = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                break (content_type, bytes);
+            }
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(_) => return Err(StatusCode::BAD_REQUEST),
+        }
+    };
+    // 画像のデコードでランタイムのワーカースレッドを塞がない
+    let thumbnail = tokio::task::spawn_blocking({
+        let (content_type, bytes) = (content_type.clone(), bytes.clone());
+        move || generate_thumbnail(&content_type, &bytes)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| match e {
+        MediaError::UnsupportedContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        MediaError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        MediaError::Malformed => StatusCode::BAD_REQUEST,
+    })?;
+    let media = Media {
+        id: None,
+        user_id,
+        content_type,
+        byte_size: bytes.len() as u64,
+        blob_key: generate_blob_key(user_id, ""),
+        thumbnail_key: generate_blob_key(user_id, ".thumbnail.png"),
+    };
+    // 本体とサムネイルを保存してからメタデータを登録する
+    blob_store
+        .put(&media.blob_key, &bytes)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    blob_store
+        .put(&media.thumbnail_key, &thumbnail)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    match repository.insert_media(&media).await {
+        Ok(id) => Ok((StatusCode::CREATED, axum::Json(TimelineMedia::new(id)))),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// メディア取得API
+#[utoipa::path(
+    get,
+    path = "/api/media/{id}",
+    params(("id" = u64, Path, description = "メディアID")),
+    responses(
+        (status = 200, description = "メディア本体"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "メディアが存在しない、もしくは添付したツイートを見られない"),
+        (status = 503, description = "DBもしくは保存先に接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_media(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    blob_store: Extension<SharedBlobStore>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let media = find_visible_media(id, &repository, session.1.id.unwrap()).await?;
+    serve_blob(&blob_store, &media.blob_key, media.content_type).await
+}
+
+// サムネイル取得API
+#[utoipa::path(
+    get,
+    path = "/api/media/{id}/thumbnail",
+    params(("id" = u64, Path, description = "メディアID")),
+    responses(
+        (status = 200, description = "サムネイル(PNG)"),
+        (status = 401, description = "未ログイン"),
+        (status = 404, description = "メディアが存在しない、もしくは添付したツイートを見られない"),
+        (status = 503, description = "DBもしくは保存先に接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_media_thumbnail(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    blob_store: Extension<SharedBlobStore>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let media = find_visible_media(id, &repository, session.1.id.unwrap()).await?;
+    serve_blob(
+        &blob_store,
+        &media.thumbnail_key,
+        THUMBNAIL_CONTENT_TYPE.to_string(),
+    )
+    .await
+}
+
+// アップロードした本人以外には、添付したツイートを見られる場合だけメディアを返す
+// 添付前のメディアと非表示のツイートのメディアは本人しか見られない
+async fn find_visible_media(
+    id: u64,
+    repository: &SharedRepository,
+    viewer_id: u64,
+) -> Result<Media, StatusCode> {
+    let media = match repository.find_media(id).await {
+        Ok(Some(media)) => media,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+    if media.user_id == viewer_id {
+        return Ok(media);
+    }
+    let tweet_id = match repository.find_media_user_tweet_id(id).await {
+        Ok(Some(tweet_id)) => tweet_id,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+    match find_visible_user_tweet(repository, viewer_id, tweet_id).await {
+        Ok(Some(_)) => Ok(media),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+async fn serve_blob(
+    blob_store: &SharedBlobStore,
+    key: &str,
+    content_type: String,
+) -> Result<impl IntoResponse, StatusCode> {
+    match blob_store.get(key).await {
+        Ok(Some(bytes)) => Ok((
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, MEDIA_CACHE_CONTROL.to_string()),
+            ],
+            bytes,
+        )),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
@@ -0,0 +1,199 @@
+// フォロー、返信、いいね、メンションの通知API
+use super::CurrentSession;
+use crate::models::{Notification, NotificationEvent, NotificationKind};
+use crate::render::{extract_entities, EntityKind};
+use crate::repositories::SharedRepository;
+use axum::{
+    extract::{Extension, Json},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+// 通知一覧で集約の対象にする直近の通知の件数
+const NOTIFICATION_LIMIT: u64 = 200;
+// 1つのツイートで通知するメンションの上限
+const MAX_MENTIONS: usize = 10;
+
+// 同じ種類・同じ対象の通知をまとめたもの
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct NotificationGroup {
+    pub kind: NotificationKind,
+    pub target_id: u64,
+    // きっかけになったユーザの名前、新しい順で重複なし
+    pub actor_names: Vec<String>,
+    pub count: u64,
+    pub unread: bool,
+    // グループ内で最も新しい通知のID、既読APIのup_to_idに使う
+    pub latest_id: u64,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct NotificationsResponse {
+    pub unread_count: u64,
+    pub groups: Vec<NotificationGroup>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct MarkNotificationsReadParams {
+    // 指定するとこのID以下の通知だけを既読にする、省略すると全て既読にする
+    #[serde(default)]
+    pub up_to_id: Option<u64>,
+}
+
+// 通知一覧API
+#[utoipa::path(
+    get,
+    path = "/api/notifications",
+    responses(
+        (status = 200, description = "通知取得成功", body = NotificationsResponse),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_notifications(
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let events = repository
+        .notifications(user_id, NOTIFICATION_LIMIT)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    let unread_count = repository
+        .count_unread_notifications(user_id)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(axum::Json(NotificationsResponse {
+        unread_count,
+        groups: group_notifications(events),
+    }))
+}
+
+// 既読API
+#[utoipa::path(
+    post,
+    path = "/api/notifications/read",
+    request_body = MarkNotificationsReadParams,
+    responses(
+        (status = 204, description = "既読にした"),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn mark_notifications_read(
+    Json(payload): Json<MarkNotificationsReadParams>,
+    repository: Extension<SharedRepository>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    match repository
+        .mark_notifications_read(user_id, payload.up_to_id)
+        .await
+    {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// 新しい順に並んだ通知を種類と対象ごとにまとめる、グループは最新の通知の順に並ぶ
+fn group_notifications(events: Vec<NotificationEvent>) -> Vec<NotificationGroup> {
+    let mut groups: Vec<NotificationGroup> = Vec::new();
+    for event in events {
+        match groups
+            .iter_mut()
+            .find(|group| group.kind == event.kind && group.target_id == event.target_id)
+        {
+            Some(group) => {
+                if !group.actor_names.contains(&event.actor_name) {
+                    group.actor_names.push(event.actor_name);
+                }
+                group.count += 1;
+                group.unread |= !event.is_read;
+            }
+            None => groups.push(NotificationGroup {
+                kind: event.kind,
+                target_id: event.target_id,
+                actor_names: vec![event.actor_name],
+                count: 1,
+                unread: !event.is_read,
+                latest_id: event.id,
+            }),
+        }
+    }
+    groups
+}
+
+// 通知を書き込む、自分自身の操作は通知しない
+// 通知はおまけなので失敗しても元の操作は成功させ、ログだけ残す
+pub(crate) async fn notify(
+    repository: &SharedRepository,
+    user_id: u64,
+    kind: NotificationKind,
+    actor_id: u64,
+    target_id: u64,
+) {
+    if user_id == actor_id {
+        return;
+    }
+    let notification = Notification {
+        id: None,
+        user_id,
+        kind,
+        actor_id,
+        target_id,
+        is_read: false,
+    };
+    if let Err(e) = repository.insert_notification(&notification).await {
+        eprintln!("failed to notify {:?} to user {}: {}", kind, user_id, e);
+    }
+}
+
+// ツイート本文の@ユーザ名を列挙する、重複は除く
+pub fn mentioned_names(content: &str) -> Vec<String> {
+    // タイムラインでリンクにするメンションと同じ規則で抽出する
+    let mut names: Vec<String> = Vec::new();
+    for entity in extract_entities(content) {
+        if entity.kind == EntityKind::Mention && !names.iter().any(|name| name == entity.value()) {
+            names.push(entity.value().to_string());
+        }
+    }
+    names
+}
+
+// ツイート本文でメンションされたユーザに通知する
+// excluded_user_idは返信通知を送った相手など、重ねて通知しないユーザ
+pub(crate) async fn notify_mentions(
+    repository: &SharedRepository,
+    actor_id: u64,
+    tweet_id: u64,
+    content: &str,
+    excluded_user_id: Option<u64>,
+) {
+    for name in mentioned_names(content).into_iter().take(MAX_MENTIONS) {
+        match repository.find_by_name(&name).await {
+            Ok(Some(user)) => {
+                let user_id = user.id.unwrap();
+                if Some(user_id) != excluded_user_id {
+                    notify(
+                        repository,
+                        user_id,
+                        NotificationKind::Mention,
+                        actor_id,
+                        tweet_id,
+                    )
+                    .await;
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("failed to find mentioned user {}: {}", name, e),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+// OIDCプロバイダ(IdP)によるログインAPI
+// /api/oidc/loginでIdPへリダイレクトし、IdPから戻った/api/oidc/callbackでログインセッションを発行する
+use super::{start_login_session, user_created};
+use crate::models::{Role, User};
+use crate::oidc::{
+    AuthorizationRequest, IdTokenClaims, OidcError, SharedOidcClient, AUTHORIZATION_EXPIRE_SECONDS,
+};
+use crate::repositories::{AppSessionStore, SharedRepository};
+use async_session::{Session, SessionStore as _};
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+use rand::Rng as _;
+
+// 認可リクエストの状態を保存したセッションのクッキー、ログインのセッションとは別に持つ
+const OIDC_COOKIE_KEY: &str = "ruitter_oidc";
+const OIDC_COOKIE_PATH: &str = "/api/oidc";
+// ユーザ名が既に使われていた場合に接尾辞を変えて作成を試みる回数
+const MAX_USER_NAME_ATTEMPTS: usize = 5;
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+pub struct OidcCallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    // ユーザが同意しなかった場合などにIdPが付ける
+    pub error: Option<String>,
+}
+
+// OIDCログイン開始API
+#[utoipa::path(
+    get,
+    path = "/api/oidc/login",
+    responses(
+        (status = 303, description = "IdPの認可エンドポイントへリダイレクト"),
+        (status = 404, description = "OIDCログインが設定されていない"),
+        (status = 503, description = "DBに接続できない"),
+    )
+)]
+pub(crate) async fn start_oidc_login(
+    Extension(oidc): Extension<Option<SharedOidcClient>>,
+    session_store: Extension<AppSessionStore>,
+    cookie_jar: CookieJar,
+) -> Result<impl IntoResponse, StatusCode> {
+    let oidc = oidc.ok_or(StatusCode::NOT_FOUND)?;
+    let request = AuthorizationRequest::new();
+    // 複数のサーバで動かしてもコールバックを受けられるよう、セッションストアに保存する
+    let mut session = Session::new();
+    session.expire_in(std::time::Duration::from_secs(AUTHORIZATION_EXPIRE_SECONDS));
+    session.insert("state", &request.state).unwrap();
+    session.insert("nonce", &request.nonce).unwrap();
+    session
+        .insert("code_verifier", &request.code_verifier)
+        .unwrap();
+    let cookie_value = session_store
+        .store_session(session)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?
+        .unwrap();
+    let cookie = Cookie::build(OIDC_COOKIE_KEY, cookie_value)
+        .path(OIDC_COOKIE_PATH)
+        // HTTPS(TLS)非対応なのでfalseとした
+        .secure(false)
+        .http_only(true)
+        // IdPからのリダイレクトはトップレベルのGETなのでLaxでも送られる
+        .same_site(cookie::SameSite::Lax)
+        .max_age(cookie::time::Duration::new(
+            AUTHORIZATION_EXPIRE_SECONDS as i64,
+            0,
+        ))
+        .finish();
+    Ok((
+        cookie_jar.add(cookie),
+        Redirect::to(&oidc.authorization_url(&request)),
+    ))
+}
+
+// OIDCログインのコールバックAPI
+// 初回ログイン時はIdPのユーザに紐付けたユーザを作成する
+#[utoipa::path(
+    get,
+    path = "/api/oidc/callback",
+    params(OidcCallbackParams),
+    responses(
+        (status = 303, description = "ログインしてトップページへリダイレクト"),
+        (status = 400, description = "認可リクエストが見つからない、もしくはstateが一致しない"),
+        (status = 401, description = "IdPで認証されなかった、もしくはIDトークンが正しくない"),
+        (status = 403, description = "アカウントが凍結されている"),
+        (status = 404, description = "OIDCログインが設定されていない"),
+        (status = 502, description = "IdPに接続できない"),
+        (status = 503, description = "DBに接続できない"),
+    )
+)]
+pub(crate) async fn finish_oidc_login(
+    Query(params): Query<OidcCallbackParams>,
+    Extension(oidc): Extension<Option<SharedOidcClient>>,
+    repository: Extension<SharedRepository>,
+    session_store: Extension<AppSessionStore>,
+    cookie_jar: CookieJar,
+) -> Result<impl IntoResponse, StatusCode> {
+    let oidc = oidc.ok_or(StatusCode::NOT_FOUND)?;
+    let cookie_value = cookie_jar
+        .get(OIDC_COOKIE_KEY)
+        .ok_or(StatusCode::BAD_REQUEST)?
+        .value()
+        .to_string();
+    let cookie_jar = cookie_jar.remove(
+        Cookie::build(OIDC_COOKIE_KEY, "")
+            .path(OIDC_COOKIE_PATH)
+            .finish(),
+    );
+    let session = session_store
+        .load_session(cookie_value)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let request = AuthorizationRequest {
+        state: session.get("state").ok_or(StatusCode::BAD_REQUEST)?,
+        nonce: session.get("nonce").ok_or(StatusCode::BAD_REQUEST)?,
+        code_verifier: session
+            .get("code_verifier")
+            .ok_or(StatusCode::BAD_REQUEST)?,
+    };
+    // 認可コードの使い回しを防ぐため、結果にかかわらず認可リクエストは1回で破棄する
+    if session_store.destroy_session(session).await.is_err() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    if params.state.as_deref() != Some(request.state.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if params.error.is_some() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let code = params.code.ok_or(StatusCode::BAD_REQUEST)?;
+    let claims = oidc
+        .exchange_code(&code, &request)
+        .await
+        .map_err(|e| match e {
+            OidcError::Provider => StatusCode::BAD_GATEWAY,
+            OidcError::InvalidIdToken => StatusCode::UNAUTHORIZED,
+        })?;
+    let user = find_or_create_user(&repository, oidc.issuer(), &claims)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    if user.is_suspended {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let cookie_jar = start_login_session(&session_store, cookie_jar, user.id.unwrap()).await?;
+    Ok((cookie_jar, Redirect::to("/")))
+}
+
+async fn find_or_create_user(
+    repository: &SharedRepository,
+    issuer: &str,
+    claims: &IdTokenClaims,
+) -> Result<User, sqlx::Error> {
+    let mut name = default_user_name(claims);
+    let mut attempts = 0;
+    loop {
+        if let Some(user) = repository
+            .find_user_by_oidc_identity(issuer, &claims.sub)
+            .await?
+        {
+            return Ok(user);
+        }
+        // パスワードは持たせず、名前だけでのログインはcreate_sessionで拒否する
+        let user = User {
+            id: None,
+            name,
+            password_hash: None,
+            role: Role::User,
+            is_suspended: false,
+            is_protected: false,
+        };
+        match repository
+            .insert_user_with_oidc_identity(&user, issuer, &claims.sub)
+            .await
+        {
+            Ok(id) => {
+                user_created(repository, id, &user.name).await;
+                return Ok(User {
+                    id: Some(id),
+                    ..user
+                });
+            }
+            // 名前の重複か、同じユーザの同時ログインによる紐付けの重複
+            // 後者の場合は次のループで作成済みのユーザが見つかる
+            Err(sqlx::Error::Database(_)) if attempts < MAX_USER_NAME_ATTEMPTS => {
+                attempts += 1;
+                name = format!(
+                    "{}_{:04x}",
+                    default_user_name(claims),
+                    rand::thread_rng().gen::<u16>()
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// IdPのユーザ名、なければメールアドレスの@より前をユーザ名にする
+fn default_user_name(claims: &IdTokenClaims) -> String {
+    claims
+        .preferred_username
+        .as_deref()
+        .or_else(|| claims.email.as_deref()?.split('@').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("user")
+        .chars()
+        .take(64)
+        .collect()
+}
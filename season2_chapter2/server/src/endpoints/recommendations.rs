@@ -0,0 +1,86 @@
+// おすすめユーザ(who to follow)API
+use super::CurrentSession;
+use crate::models::RecommendedUser;
+use crate::repositories::SharedRepository;
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 返すおすすめユーザの件数
+const RECOMMENDATION_LIMIT: u64 = 20;
+
+// ユーザIDごとに計算した時刻と結果を持つ
+type CacheEntries = HashMap<u64, (Instant, Vec<RecommendedUser>)>;
+
+// ユーザごとのおすすめユーザのキャッシュ
+// 友達の友達の集計はフォロー数に応じて重くなるので、一定時間は結果を使い回す
+// 自分がフォローやブロックをした場合は結果が変わるので、そのユーザの分をすぐに捨てる
+#[derive(Clone)]
+pub struct RecommendationCache {
+    ttl: Duration,
+    entries: Arc<Mutex<CacheEntries>>,
+}
+
+impl RecommendationCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn get(&self, user_id: u64) -> Option<Vec<RecommendedUser>> {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(&user_id) {
+            Some((cached_at, users)) if cached_at.elapsed() < self.ttl => Some(users.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&self, user_id: u64, users: Vec<RecommendedUser>) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        // 期限切れのエントリが溜まり続けないよう、書き込みのついでに掃除する
+        entries.retain(|_, (cached_at, _)| cached_at.elapsed() < self.ttl);
+        entries.insert(user_id, (Instant::now(), users));
+    }
+
+    pub fn invalidate(&self, user_id: u64) {
+        self.entries.lock().unwrap().remove(&user_id);
+    }
+}
+
+// おすすめユーザ取得API
+// フォロイーのフォロイーを、共通のフォロイー数と直近の投稿数によるスコア順に返す
+#[utoipa::path(
+    get,
+    path = "/api/recommendations/users",
+    responses(
+        (status = 200, description = "スコア順のおすすめユーザ", body = [RecommendedUser]),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_recommended_users(
+    repository: Extension<SharedRepository>,
+    Extension(cache): Extension<RecommendationCache>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    if let Some(users) = cache.get(user_id) {
+        return Ok(axum::Json(users));
+    }
+    let users = repository
+        .recommended_users(user_id, RECOMMENDATION_LIMIT)
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    cache.insert(user_id, users.clone());
+    Ok(axum::Json(users))
+}
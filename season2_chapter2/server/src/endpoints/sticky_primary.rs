@@ -0,0 +1,98 @@
+// レプリカを使う場合に、書き込んだ直後のセッションの読み出しをプライマリに送る
+// レプリカへの反映が遅れても、自分の投稿やフォローがタイムラインにすぐ現れるようにする
+use super::AXUM_SESSION_COOKIE_KEY;
+use crate::repositories::SharedRepository;
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// セッションごとに、プライマリから読む期限を持つ
+// サーバのプロセスごとに持つので、複数台で動かす場合はロードバランサでセッションを同じサーバに振り分ける
+#[derive(Clone)]
+pub struct StickyPrimary {
+    // レプリカを使わないリポジトリ、レプリカを使っていなければNoneで何もしない
+    primary: Option<SharedRepository>,
+    window: Duration,
+    entries: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl StickyPrimary {
+    pub fn new(primary: Option<SharedRepository>, window: Duration) -> Self {
+        Self {
+            primary,
+            window,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn is_active(&self, key: &str) -> bool {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .is_some_and(|until| *until > Instant::now())
+    }
+
+    fn mark(&self, key: String) {
+        if self.window.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        // 期限切れのエントリが溜まり続けないよう、書き込みのついでに掃除する
+        entries.retain(|_, until| *until > now);
+        entries.insert(key, now + self.window);
+    }
+}
+
+// セッションのクッキーかAPIトークンでリクエストしたセッションを見分ける
+fn session_key(headers: &HeaderMap) -> Option<String> {
+    // CurrentSessionと同じく、Authorizationヘッダがあればクッキーより優先する
+    if let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(format!("token:{}", token.trim()));
+    }
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == AXUM_SESSION_COOKIE_KEY).then(|| format!("cookie:{}", value))
+        })
+}
+
+// 書き込みに成功したセッションの以降のリクエストは、一定時間リポジトリをプライマリだけのものに差し替える
+pub(crate) async fn sticky_primary(mut req: Request<Body>, next: Next<Body>) -> Response {
+    let sticky = req.extensions().get::<StickyPrimary>().unwrap().clone();
+    let primary = match &sticky.primary {
+        Some(primary) => primary.clone(),
+        None => return next.run(req).await,
+    };
+    let key = session_key(req.headers());
+    if let Some(key) = &key {
+        if sticky.is_active(key) {
+            req.extensions_mut().insert(primary);
+        }
+    }
+    let writes = matches!(
+        *req.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    );
+    let response = next.run(req).await;
+    if let Some(key) = key {
+        if writes && response.status().is_success() {
+            sticky.mark(key);
+        }
+    }
+    response
+}
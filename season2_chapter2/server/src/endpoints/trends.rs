@@ -0,0 +1,96 @@
+// ハッシュタグのトレンドAPI
+use super::CurrentSession;
+use crate::models::Trend;
+use crate::repositories::SharedRepository;
+use crate::trends::{trends, TrendWindow};
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+pub struct TrendsQuery {
+    // トレンドを求める期間、"1h"(デフォルト)か"24h"
+    pub window: Option<String>,
+}
+
+// 期間ごとに計算した時刻と結果を持つ
+type CacheEntries = HashMap<TrendWindow, (Instant, Vec<Trend>)>;
+
+// 期間ごとのトレンドのキャッシュ
+// トレンドはユーザによらないので、全員で同じ結果を使い回す
+#[derive(Clone)]
+pub struct TrendCache {
+    ttl: Duration,
+    entries: Arc<Mutex<CacheEntries>>,
+}
+
+impl TrendCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn get(&self, window: TrendWindow) -> Option<Vec<Trend>> {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(&window) {
+            Some((cached_at, trends)) if cached_at.elapsed() < self.ttl => Some(trends.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&self, window: TrendWindow, trends: Vec<Trend>) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(window, (Instant::now(), trends));
+    }
+}
+
+// トレンド取得API
+// 直近の期間に使用回数が普段より伸びているハッシュタグを、伸びの大きい順に返す
+#[utoipa::path(
+    get,
+    path = "/api/trends",
+    params(TrendsQuery),
+    responses(
+        (status = 200, description = "伸びの大きい順のハッシュタグ", body = [Trend]),
+        (status = 400, description = "期間の指定が不正"),
+        (status = 401, description = "未ログイン"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_trends(
+    Query(query): Query<TrendsQuery>,
+    repository: Extension<SharedRepository>,
+    Extension(cache): Extension<TrendCache>,
+    session: CurrentSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    session
+        .0
+        .get::<u64>("user_id")
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let window = match query.window {
+        Some(window) => TrendWindow::parse(&window).ok_or(StatusCode::BAD_REQUEST)?,
+        None => TrendWindow::default(),
+    };
+    if let Some(trends) = cache.get(window) {
+        return Ok(axum::Json(trends));
+    }
+    let trends = trends(&repository, window, Utc::now().timestamp())
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    cache.insert(window, trends.clone());
+    Ok(axum::Json(trends))
+}
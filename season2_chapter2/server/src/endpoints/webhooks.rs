@@ -0,0 +1,176 @@
+// Webhookの管理API、登録と配信ログの確認は管理者のみ
+use super::AdminSession;
+use crate::models::{Webhook, WebhookEvent};
+use crate::repositories::SharedRepository;
+use crate::webhooks::generate_webhook_secret;
+use axum::{
+    extract::{Extension, Json, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+// URLの最大文字数
+const MAX_WEBHOOK_URL_LENGTH: usize = 2048;
+// 一覧APIで返す件数
+const DELIVERY_LIMIT: u64 = 100;
+const DEAD_LETTER_LIMIT: u64 = 100;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct CreateWebhookParams {
+    // http(s)のURL
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct CreateWebhookResponse {
+    pub id: u64,
+    // 署名の鍵、このレスポンスでしか受け取れない
+    pub secret: String,
+}
+
+// Webhook登録API
+#[utoipa::path(
+    post,
+    path = "/api/admin/webhooks",
+    request_body = CreateWebhookParams,
+    responses(
+        (status = 201, description = "登録成功", body = CreateWebhookResponse),
+        (status = 400, description = "URLが不正、イベントが空"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "管理者ではない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn create_webhook(
+    Json(payload): Json<CreateWebhookParams>,
+    repository: Extension<SharedRepository>,
+    session: AdminSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    if payload.url.len() > MAX_WEBHOOK_URL_LENGTH || payload.events.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let url = reqwest::Url::parse(&payload.url).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !matches!(url.scheme(), "http" | "https") || !url.has_host() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut events = payload.events;
+    events.sort_by_key(|event| event.as_str());
+    events.dedup();
+    let secret = generate_webhook_secret();
+    let webhook = Webhook {
+        id: None,
+        url: payload.url,
+        secret: secret.clone(),
+        events,
+        created_by: session.0 .1.id,
+    };
+    match repository.insert_webhook(&webhook).await {
+        Ok(id) => Ok((
+            StatusCode::CREATED,
+            axum::Json(CreateWebhookResponse { id, secret }),
+        )),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// Webhook一覧API、鍵は返さない
+#[utoipa::path(
+    get,
+    path = "/api/admin/webhooks",
+    responses(
+        (status = 200, description = "登録順のWebhook", body = [WebhookItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "管理者ではない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_webhooks(
+    repository: Extension<SharedRepository>,
+    _session: AdminSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    match repository.webhooks().await {
+        Ok(webhooks) => Ok(axum::Json(webhooks)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// Webhook削除API、未配信のイベントも送らなくなる
+#[utoipa::path(
+    delete,
+    path = "/api/admin/webhooks/{id}",
+    params(("id" = u64, Path, description = "WebhookID")),
+    responses(
+        (status = 204, description = "削除成功"),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "管理者ではない"),
+        (status = 404, description = "Webhookが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn delete_webhook(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    _session: AdminSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    match repository.delete_webhook(id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// 配信ログAPI
+#[utoipa::path(
+    get,
+    path = "/api/admin/webhooks/{id}/deliveries",
+    params(("id" = u64, Path, description = "WebhookID")),
+    responses(
+        (status = 200, description = "新しい順の配信ログ", body = [WebhookDeliveryItem]),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "管理者ではない"),
+        (status = 404, description = "Webhookが存在しない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_webhook_deliveries(
+    Path(id): Path<u64>,
+    repository: Extension<SharedRepository>,
+    _session: AdminSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    match repository.find_webhook(id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+    match repository.webhook_deliveries(id, DELIVERY_LIMIT).await {
+        Ok(deliveries) => Ok(axum::Json(deliveries)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+// デッドレター一覧API、Webhookを削除しても残る
+#[utoipa::path(
+    get,
+    path = "/api/admin/webhook_dead_letters",
+    responses(
+        (status = 200, description = "新しい順の配信できなかったイベント", body = [WebhookDeadLetter]),
+        (status = 401, description = "未ログイン"),
+        (status = 403, description = "管理者ではない"),
+        (status = 503, description = "DBに接続できない"),
+    ),
+    security(("session_cookie" = []))
+)]
+pub(crate) async fn get_webhook_dead_letters(
+    repository: Extension<SharedRepository>,
+    _session: AdminSession,
+) -> Result<impl IntoResponse, StatusCode> {
+    match repository.webhook_dead_letters(DEAD_LETTER_LIMIT).await {
+        Ok(dead_letters) => Ok(axum::Json(dead_letters)),
+        Err(_) => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
@@ -0,0 +1,104 @@
+// APIのエラーメッセージの翻訳
+// 翻訳はlocales/の言語ごとのJSONに「エラーコード → メッセージ」で持ち、コンパイル時にバイナリに埋め込む
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// 対応する言語、Accept-Languageで指定されなければ日本語
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Locale {
+    #[default]
+    Ja,
+    En,
+}
+impl Locale {
+    pub const ALL: [Self; 2] = [Self::Ja, Self::En];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ja => "ja",
+            Self::En => "en",
+        }
+    }
+
+    // "en-US"のような地域付きの指定も言語の部分で判定する
+    pub fn parse(value: &str) -> Option<Self> {
+        let language = value.split('-').next().unwrap_or_default();
+        match language.trim().to_ascii_lowercase().as_str() {
+            "ja" => Some(Self::Ja),
+            "en" => Some(Self::En),
+            _ => None,
+        }
+    }
+
+    // Accept-Languageヘッダのうち、対応している言語で最もqの大きいものを選ぶ
+    // qが同じなら先に書かれたものを優先する
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut best: Option<(Self, f32)> = None;
+        for range in header.split(',') {
+            let mut params = range.split(';');
+            let locale = match Self::parse(params.next().unwrap_or_default()) {
+                Some(locale) => locale,
+                None => continue,
+            };
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((locale, quality));
+            }
+        }
+        best.map(|(locale, _)| locale).unwrap_or_default()
+    }
+
+    fn catalog_source(&self) -> &'static str {
+        match self {
+            Self::Ja => include_str!("../locales/ja.json"),
+            Self::En => include_str!("../locales/en.json"),
+        }
+    }
+}
+
+// 言語ごとの翻訳
+pub struct Messages {
+    catalogs: HashMap<Locale, HashMap<String, String>>,
+}
+
+pub type SharedMessages = Arc<Messages>;
+
+impl Messages {
+    pub fn load() -> anyhow::Result<Self> {
+        let mut catalogs = HashMap::new();
+        for locale in Locale::ALL {
+            let catalog: HashMap<String, String> = serde_json::from_str(locale.catalog_source())
+                .map_err(|e| anyhow::anyhow!("invalid {} catalog: {}", locale.as_str(), e))?;
+            catalogs.insert(locale, catalog);
+        }
+        Ok(Self { catalogs })
+    }
+
+    pub fn get(&self, locale: Locale, code: &str) -> Option<&str> {
+        self.catalogs
+            .get(&locale)
+            .and_then(|catalog| catalog.get(code))
+            .map(|message| message.as_str())
+    }
+
+    // 翻訳がなければ日本語、それもなければコードをそのまま返す
+    pub fn message<'a>(&'a self, locale: Locale, code: &'a str) -> &'a str {
+        self.get(locale, code)
+            .or_else(|| self.get(Locale::default(), code))
+            .unwrap_or(code)
+    }
+
+    // 翻訳のあるコードの一覧、テストで過不足を調べるのに使う
+    pub fn codes(&self, locale: Locale) -> Vec<&str> {
+        let mut codes: Vec<&str> = self
+            .catalogs
+            .get(&locale)
+            .map(|catalog| catalog.keys().map(|code| code.as_str()).collect())
+            .unwrap_or_default();
+        codes.sort_unstable();
+        codes
+    }
+}
@@ -1,5 +1,6 @@
 // src/init_db.rs
-use ruitter::models::{create_pool, create_tokio_runtime, setup_tables, DB_STRING_PRODUCTION};
+use ruitter::models::{create_tokio_runtime, database_url};
+use ruitter::repositories::connect;
 
 fn main() -> anyhow::Result<()> {
     let tokio_rt = create_tokio_runtime();
@@ -7,13 +8,11 @@ fn main() -> anyhow::Result<()> {
 }
 
 async fn run() -> anyhow::Result<()> {
-    // 本番DBにセッションテーブルを作成
-    let session_store = async_sqlx_session::MySqlSessionStore::new(DB_STRING_PRODUCTION).await?;
+    // 本番DB(もしくは環境変数で指定したDB)に接続する
+    let (repository, session_store) = connect(&database_url()).await?;
+    // セッションテーブルを作成
     session_store.migrate().await?;
-
-    // 本番DBに接続するクライアントプールを作成
-    let pool = create_pool(DB_STRING_PRODUCTION).await?;
-    // 本番DBにその他テーブルを作成
-    setup_tables(&pool).await;
+    // その他テーブルを作成
+    repository.setup_tables().await?;
     Ok(())
 }
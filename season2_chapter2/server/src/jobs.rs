@@ -0,0 +1,285 @@
+// RDBのjobsテーブルを使うバックグラウンドジョブの実行
+// 複数のサーバで同時に動かしても、1つのジョブは1つのワーカーだけが取得する
+use crate::config::TimelineMode;
+use crate::endpoints::user_tweet_published;
+use crate::models::{
+    DeliverWebhookPayload, Job, JobKind, ScheduledTweetPayload, UnfurlLinksPayload, UserTweet,
+};
+use crate::repositories::{AppSessionStore, SharedRepository};
+use crate::trends::{aggregate_hashtags, RETENTION_SECONDS};
+use crate::unfurl::{parse_open_graph, SharedHttpFetcher};
+use crate::webhooks::WebhookClient;
+use chrono::{Duration, NaiveDateTime, Utc};
+
+// 実行予定のジョブがないときにテーブルを見に行く間隔
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+// 1回に取得するジョブの件数
+const CLAIM_LIMIT: u64 = 10;
+// 取得したジョブがこの時間を過ぎても終わらなければ、ワーカーが落ちたとみなして他のワーカーが再実行する
+const LEASE_SECONDS: i64 = 300;
+// この回数失敗したジョブは再実行を諦める
+const MAX_ATTEMPTS: u32 = 5;
+
+// 定期ジョブの実行間隔
+const CLEANUP_SESSIONS_INTERVAL_SECONDS: i64 = 60 * 60;
+const AGGREGATE_TRENDS_INTERVAL_SECONDS: i64 = 5 * 60;
+
+pub struct JobRunner {
+    repository: SharedRepository,
+    session_store: AppSessionStore,
+    timeline_mode: TimelineMode,
+    // 指定した場合のみツイート中のURLのリンクプレビューを取得する
+    link_fetcher: Option<SharedHttpFetcher>,
+    webhook_client: WebhookClient,
+}
+
+impl JobRunner {
+    pub fn new(
+        repository: SharedRepository,
+        session_store: AppSessionStore,
+        timeline_mode: TimelineMode,
+    ) -> Self {
+        Self {
+            repository,
+            session_store,
+            timeline_mode,
+            link_fetcher: None,
+            webhook_client: WebhookClient::new(),
+        }
+    }
+
+    pub fn with_link_fetcher(mut self, link_fetcher: SharedHttpFetcher) -> Self {
+        self.link_fetcher = Some(link_fetcher);
+        self
+    }
+
+    // サーバと並行してジョブを実行し続ける
+    pub async fn run(self) {
+        let now = Utc::now().naive_utc();
+        for kind in [JobKind::CleanupSessions, JobKind::AggregateTrends] {
+            if let Err(e) = self.schedule_recurring_job(kind, now).await {
+                eprintln!("failed to schedule {} job: {}", kind.as_str(), e);
+            }
+        }
+        loop {
+            match self.run_due_jobs(Utc::now().naive_utc()).await {
+                // 取得できた場合は続けて次のジョブを取りに行く
+                Ok(count) if count > 0 => continue,
+                Ok(_) => {}
+                Err(e) => eprintln!("failed to claim jobs: {}", e),
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    // nowの時点で実行予定のジョブを取得して実行し、取得した件数を返す
+    pub async fn run_due_jobs(&self, now: NaiveDateTime) -> Result<usize, sqlx::Error> {
+        let locked_until = now + Duration::seconds(LEASE_SECONDS);
+        let jobs = self
+            .repository
+            .claim_due_jobs(now, locked_until, CLAIM_LIMIT)
+            .await?;
+        for job in &jobs {
+            let id = job.id.unwrap();
+            let outcome = self.run_job(job).await;
+            // 定期ジョブは失敗しても次回の分を登録し、再試行を諦めても定期実行が途切れないようにする
+            // 停止していた間の分はまとめて1回とし、nowより後の最初の実行時刻に登録する
+            if is_recurring(job.kind) {
+                let after = job.run_at.max(now);
+                if let Err(e) = self.schedule_recurring_job(job.kind, after).await {
+                    eprintln!("failed to schedule next {} job: {}", job.kind.as_str(), e);
+                }
+            }
+            // 結果を書き込めなくても、残りのジョブは実行する
+            // 書き込めなかったジョブはlocked_untilを過ぎてから再実行される
+            let result = match outcome {
+                Ok(()) => self.repository.complete_job(job).await,
+                Err(e) if job.attempts >= MAX_ATTEMPTS => {
+                    eprintln!("job {} failed permanently: {}", id, e);
+                    self.repository.fail_job(job, &e.to_string()).await
+                }
+                Err(e) => {
+                    eprintln!("job {} failed, will retry: {}", id, e);
+                    // 失敗するたびに再実行までの間隔を延ばす
+                    let backoff = Duration::seconds(30 * 2i64.pow(job.attempts));
+                    self.repository
+                        .retry_job(job, now + backoff, &e.to_string())
+                        .await
+                }
+            };
+            match result {
+                Ok(true) => {}
+                Ok(false) => eprintln!("job {} was claimed by another worker", id),
+                Err(e) => eprintln!("failed to record result of job {}: {}", id, e),
+            }
+        }
+        Ok(jobs.len())
+    }
+
+    async fn run_job(&self, job: &Job) -> anyhow::Result<()> {
+        match job.kind {
+            JobKind::PublishScheduledTweet => self.publish_scheduled_tweet(job).await,
+            JobKind::UnfurlLinks => self.unfurl_links(job).await,
+            JobKind::DeliverWebhook => self.deliver_webhook(job).await,
+            JobKind::CleanupSessions => {
+                self.session_store.cleanup().await?;
+                self.repository
+                    .delete_expired_idempotency_keys(Utc::now().naive_utc())
+                    .await?;
+                Ok(())
+            }
+            // 配信手段ができるまで登録しない、以前に登録されたジョブは何もせずに終える
+            JobKind::NotificationDigest => Ok(()),
+            JobKind::AggregateTrends => {
+                aggregate_hashtags(&self.repository).await?;
+                self.repository
+                    .delete_hashtag_buckets(job.run_at.timestamp() - RETENTION_SECONDS)
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn publish_scheduled_tweet(&self, job: &Job) -> anyhow::Result<()> {
+        let payload: ScheduledTweetPayload = serde_json::from_str(&job.payload)?;
+        // 予約後に投稿者が削除か凍結されていれば、公開せずにジョブを終える
+        match self.repository.find_by_id(payload.user_id).await? {
+            Some(user) if !user.is_suspended => {}
+            _ => return Ok(()),
+        }
+        // 予約後に返信先が削除されていれば、通常の返信と同じく返信先なしとして公開する
+        let in_reply_to = match payload.in_reply_to_id {
+            Some(id) => self.repository.find_user_tweet(id).await?,
+            None => None,
+        };
+        let tweet = UserTweet {
+            id: None,
+            user_id: payload.user_id,
+            content: payload.content,
+            in_reply_to_id: in_reply_to.as_ref().and_then(|tweet| tweet.id),
+        };
+        let published = self
+            .repository
+            .publish_scheduled_tweet(job, &tweet, &payload.media_ids)
+            .await?;
+        // 他のワーカーが公開済みなら配布と通知もそちらが行う
+        if let Some(tweet_id) = published {
+            user_tweet_published(
+                &self.repository,
+                self.timeline_mode,
+                self.link_fetcher.is_some(),
+                tweet_id,
+                &tweet,
+                in_reply_to,
+            )
+            .await;
+        }
+        Ok(())
+    }
+
+    // 取得済みのURLは取得し直さない、取得に失敗したURLがあればジョブごと再実行する
+    async fn unfurl_links(&self, job: &Job) -> anyhow::Result<()> {
+        let link_fetcher = match &self.link_fetcher {
+            Some(link_fetcher) => link_fetcher,
+            // 取得を無効にしたサーバに残っていたジョブは何もせずに終える
+            None => return Ok(()),
+        };
+        let payload: UnfurlLinksPayload = serde_json::from_str(&job.payload)?;
+        let fetched = self.repository.link_previews(&payload.urls).await?;
+        let mut result = Ok(());
+        for url in &payload.urls {
+            if fetched.iter().any(|preview| &preview.url == url) {
+                continue;
+            }
+            match link_fetcher.fetch_html(url).await {
+                Ok(html) => {
+                    let preview = parse_open_graph(url, &html);
+                    self.repository.upsert_link_preview(&preview).await?;
+                }
+                Err(e) => result = Err(e.context(format!("failed to fetch {}", url))),
+            }
+        }
+        result
+    }
+
+    // 失敗した送信も配信ログに残し、最後の再試行でも失敗すればデッドレターに移す
+    async fn deliver_webhook(&self, job: &Job) -> anyhow::Result<()> {
+        let payload: DeliverWebhookPayload = serde_json::from_str(&job.payload)?;
+        // 配信済みか、Webhookごと削除されていれば何もしない
+        let delivery = match self
+            .repository
+            .find_pending_webhook_delivery(payload.delivery_id)
+            .await?
+        {
+            Some(delivery) => delivery,
+            None => return Ok(()),
+        };
+        let webhook = match self.repository.find_webhook(delivery.webhook_id).await? {
+            Some(webhook) => webhook,
+            None => return Ok(()),
+        };
+        let result = self
+            .webhook_client
+            .deliver(
+                &webhook.url,
+                &webhook.secret,
+                payload.delivery_id,
+                delivery.event,
+                &delivery.payload,
+            )
+            .await;
+        self.repository
+            .record_webhook_attempt(payload.delivery_id, result.status, result.error.as_deref())
+            .await?;
+        match result.error {
+            None => Ok(()),
+            Some(error) => {
+                if job.attempts >= MAX_ATTEMPTS {
+                    self.repository
+                        .dead_letter_webhook_delivery(payload.delivery_id)
+                        .await?;
+                }
+                Err(anyhow::anyhow!(
+                    "failed to deliver {} to webhook {}: {}",
+                    delivery.event.as_str(),
+                    delivery.webhook_id,
+                    error
+                ))
+            }
+        }
+    }
+
+    // afterより後の最初の実行時刻に定期ジョブを登録する
+    // 実行時刻をキーにするので、複数のサーバが登録しても1件になる
+    async fn schedule_recurring_job(
+        &self,
+        kind: JobKind,
+        after: NaiveDateTime,
+    ) -> Result<bool, sqlx::Error> {
+        let interval = recurring_interval(kind).expect("not a recurring job");
+        let timestamp = (after.timestamp() / interval + 1) * interval;
+        let mut job = Job::new(
+            kind,
+            "{}".to_string(),
+            NaiveDateTime::from_timestamp(timestamp, 0),
+        );
+        job.unique_key = Some(format!("{}:{}", kind.as_str(), timestamp));
+        self.repository.enqueue_job(&job).await
+    }
+}
+
+// 定期ジョブの実行間隔、定期ジョブでなければNone
+fn recurring_interval(kind: JobKind) -> Option<i64> {
+    match kind {
+        JobKind::CleanupSessions => Some(CLEANUP_SESSIONS_INTERVAL_SECONDS),
+        JobKind::AggregateTrends => Some(AGGREGATE_TRENDS_INTERVAL_SECONDS),
+        JobKind::PublishScheduledTweet
+        | JobKind::NotificationDigest
+        | JobKind::UnfurlLinks
+        | JobKind::DeliverWebhook => None,
+    }
+}
+
+fn is_recurring(kind: JobKind) -> bool {
+    recurring_interval(kind).is_some()
+}
@@ -1,2 +1,18 @@
+pub mod api_token;
+pub mod config;
 pub mod endpoints;
+pub mod i18n;
+pub mod jobs;
+pub mod loadgen;
+pub mod media;
 pub mod models;
+pub mod oidc;
+pub mod openapi;
+pub mod password;
+pub mod render;
+pub mod repositories;
+pub mod seed;
+pub mod static_files;
+pub mod trends;
+pub mod unfurl;
+pub mod webhooks;
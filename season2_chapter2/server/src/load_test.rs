@@ -0,0 +1,254 @@
+// src/load_test.rs
+// 起動中のruitter APIサーバに、ログインした仮想ユーザから一定のRPSでリクエストを送り、レイテンシとエラー率を表示する
+// 仮想ユーザはseed_dbで投入したユーザ(<prefix>0, <prefix>1, ...)を使う
+// 使い方: cargo run --release --bin load_test -- --url http://localhost:8888 --users 100 --rps 200 --duration 30
+use anyhow::{anyhow, bail};
+use rand::rngs::StdRng;
+use rand::{Rng as _, SeedableRng as _};
+use ruitter::loadgen::{summarize, Action, ActionMix, Sample};
+use ruitter::models::create_tokio_runtime;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+
+const USAGE: &str = "usage: load_test [--url URL] [--users N] [--targets N] [--prefix NAME] [--rps R] [--duration SECONDS] [--mix timeline=80,post=15,follow=5] [--concurrency C] [--seed S]";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// ログインを同時に送る数
+const LOGIN_CONCURRENCY: usize = 32;
+
+struct LoadTestConfig {
+    url: String,
+    // ログインする仮想ユーザ数
+    users: usize,
+    // フォロー先に選ぶユーザ数、seed_dbで投入したユーザ数を指定する
+    targets: usize,
+    prefix: String,
+    rps: f64,
+    duration: Duration,
+    mix: ActionMix,
+    // 同時に処理中にできるリクエスト数、超えた分は送らずに数える
+    concurrency: usize,
+    seed: u64,
+}
+
+fn main() -> anyhow::Result<()> {
+    let tokio_rt = create_tokio_runtime();
+    tokio_rt.block_on(run())
+}
+
+fn parse_args() -> anyhow::Result<LoadTestConfig> {
+    let mut config = LoadTestConfig {
+        url: "http://localhost:8888".to_string(),
+        users: 100,
+        targets: 0,
+        prefix: "seed".to_string(),
+        rps: 50.0,
+        duration: Duration::from_secs(30),
+        mix: ActionMix::default(),
+        concurrency: 256,
+        seed: 0,
+    };
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    for pair in args.chunks(2) {
+        let (flag, value) = match pair {
+            [flag, value] => (flag.as_str(), value),
+            _ => bail!(USAGE),
+        };
+        let invalid = || anyhow!("invalid {}: {}", flag, value);
+        match flag {
+            "--url" => config.url = value.trim_end_matches('/').to_string(),
+            "--users" => config.users = value.parse().map_err(|_| invalid())?,
+            "--targets" => config.targets = value.parse().map_err(|_| invalid())?,
+            "--prefix" => config.prefix = value.clone(),
+            "--rps" => config.rps = value.parse().map_err(|_| invalid())?,
+            "--duration" => {
+                config.duration = Duration::from_secs(value.parse().map_err(|_| invalid())?)
+            }
+            "--mix" => config.mix = ActionMix::parse(value).map_err(|e| anyhow!(e))?,
+            "--concurrency" => config.concurrency = value.parse().map_err(|_| invalid())?,
+            "--seed" => config.seed = value.parse().map_err(|_| invalid())?,
+            _ => bail!(USAGE),
+        }
+    }
+    if config.users == 0 || config.concurrency == 0 || config.rps <= 0.0 {
+        bail!("--users, --concurrency and --rps must be positive");
+    }
+    // 未指定なら仮想ユーザ同士でフォローする
+    config.targets = config.targets.max(config.users);
+    Ok(config)
+}
+
+async fn run() -> anyhow::Result<()> {
+    let config = parse_args()?;
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+
+    let started = Instant::now();
+    let mut cookies = Vec::with_capacity(config.users);
+    for chunk in (0..config.users)
+        .collect::<Vec<_>>()
+        .chunks(LOGIN_CONCURRENCY)
+    {
+        let logins = chunk
+            .iter()
+            .map(|i| log_in(&client, &config.url, format!("{}{}", config.prefix, i)));
+        for cookie in futures::future::join_all(logins).await {
+            cookies.push(cookie?);
+        }
+    }
+    println!(
+        "logged in {} users in {:?}",
+        cookies.len(),
+        started.elapsed()
+    );
+
+    let cookies = Arc::new(cookies);
+    // この計測中に送ったフォローを覚えておき、同じ相手へのフォローを繰り返さない
+    let followed = Arc::new(Mutex::new(HashSet::new()));
+    let semaphore = Arc::new(Semaphore::new(config.concurrency));
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / config.rps));
+    let mut handles = Vec::new();
+    let mut dropped = 0;
+    let started = Instant::now();
+    while started.elapsed() < config.duration {
+        interval.tick().await;
+        // 処理が追いつかない場合に送信を待つと実際のRPSが下がるので、送らずに数える
+        let permit = match semaphore.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                dropped += 1;
+                continue;
+            }
+        };
+        let user = rng.gen_range(0..config.users);
+        let mut action = config.mix.pick(&mut rng);
+        let mut target = None;
+        if action == Action::Follow {
+            target = pick_follow_target(&mut rng, &followed, user, config.targets);
+            // フォローできる相手が残っていなければタイムラインを読む
+            if target.is_none() {
+                action = Action::Timeline;
+            }
+        }
+        let request = build_request(&client, &config, &cookies[user], action, target, &mut rng);
+        let sender = sender.clone();
+        handles.push(tokio::spawn(async move {
+            let sent = Instant::now();
+            let status = request
+                .send()
+                .await
+                .ok()
+                .map(|response| response.status().as_u16());
+            let _ = sender.send(Sample {
+                action,
+                latency: sent.elapsed(),
+                status,
+            });
+            drop(permit);
+        }));
+    }
+    let elapsed = started.elapsed();
+    drop(sender);
+    futures::future::join_all(handles).await;
+    let mut samples = Vec::new();
+    while let Some(sample) = receiver.recv().await {
+        samples.push(sample);
+    }
+
+    println!(
+        "sent {} requests in {:?} ({:.1} rps, target {:.1} rps), dropped {}",
+        samples.len(),
+        elapsed,
+        samples.len() as f64 / elapsed.as_secs_f64(),
+        config.rps,
+        dropped
+    );
+    println!(
+        "{:<10} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}  statuses",
+        "action", "count", "errors", "p50", "p90", "p99", "max"
+    );
+    for summary in summarize(&samples) {
+        let statuses: Vec<String> = summary
+            .statuses
+            .iter()
+            .map(|(status, count)| format!("{}:{}", status, count))
+            .collect();
+        println!(
+            "{:<10} {:>8} {:>7.2}% {:>10.2?} {:>10.2?} {:>10.2?} {:>10.2?}  {}",
+            summary.action.as_str(),
+            summary.count,
+            summary.error_rate() * 100.0,
+            summary.p50,
+            summary.p90,
+            summary.p99,
+            summary.max,
+            statuses.join(" ")
+        );
+    }
+    Ok(())
+}
+
+// パスワードなしでログインし、Cookieヘッダの値を返す
+async fn log_in(client: &reqwest::Client, url: &str, name: String) -> anyhow::Result<String> {
+    let response = client
+        .post(format!("{}/api/sessions", url))
+        .json(&serde_json::json!({ "name": name }))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        bail!("failed to log in as {}: {}", name, response.status());
+    }
+    let set_cookie = response
+        .headers()
+        .get(reqwest::header::SET_COOKIE)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| anyhow!("no session cookie for {}", name))?;
+    Ok(set_cookie.split(';').next().unwrap().to_string())
+}
+
+// 自分自身とこの計測中にフォロー済みの相手を除いて選ぶ、見つからなければNone
+fn pick_follow_target(
+    rng: &mut StdRng,
+    followed: &Mutex<HashSet<(usize, usize)>>,
+    user: usize,
+    targets: usize,
+) -> Option<usize> {
+    let mut followed = followed.lock().unwrap();
+    for _ in 0..8 {
+        let target = rng.gen_range(0..targets);
+        if target != user && followed.insert((user, target)) {
+            return Some(target);
+        }
+    }
+    None
+}
+
+fn build_request(
+    client: &reqwest::Client,
+    config: &LoadTestConfig,
+    cookie: &str,
+    action: Action,
+    target: Option<usize>,
+    rng: &mut StdRng,
+) -> reqwest::RequestBuilder {
+    let request = match action {
+        Action::Timeline => client.get(format!("{}/api/pages/timeline", config.url)),
+        Action::Post => {
+            client
+                .post(format!("{}/api/user_tweets", config.url))
+                .json(&serde_json::json!({
+                    "content": format!("load test tweet {}", rng.gen::<u32>()),
+                }))
+        }
+        Action::Follow => client
+            .post(format!("{}/api/follow_relations", config.url))
+            .json(&serde_json::json!({
+                "name": format!("{}{}", config.prefix, target.unwrap()),
+            })),
+    };
+    request.header(reqwest::header::COOKIE, cookie)
+}
@@ -0,0 +1,176 @@
+// 負荷試験(load_test)のリクエストの配分と結果の集計
+// HTTPの送信はバイナリ側で行い、ここではサーバに接続しない部分だけを持つ
+use rand::Rng;
+use std::time::Duration;
+
+// 仮想ユーザが送るリクエストの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    // GET /api/pages/timeline
+    Timeline,
+    // POST /api/user_tweets
+    Post,
+    // POST /api/follow_relations
+    Follow,
+}
+
+impl Action {
+    pub const ALL: [Action; 3] = [Action::Timeline, Action::Post, Action::Follow];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Timeline => "timeline",
+            Self::Post => "post",
+            Self::Follow => "follow",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == value)
+    }
+}
+
+// リクエストの種類ごとの重み
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionMix {
+    weights: Vec<(Action, u32)>,
+}
+
+impl Default for ActionMix {
+    fn default() -> Self {
+        Self {
+            weights: vec![
+                (Action::Timeline, 80),
+                (Action::Post, 15),
+                (Action::Follow, 5),
+            ],
+        }
+    }
+}
+
+impl ActionMix {
+    // "timeline=80,post=15,follow=5"の形式を読み込む、省略した種類は送らない
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut weights = Vec::new();
+        for pair in value.split(',') {
+            let (name, weight) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("invalid mix entry: {}", pair))?;
+            let action =
+                Action::parse(name.trim()).ok_or_else(|| format!("unknown action: {}", name))?;
+            let weight = weight
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("invalid weight: {}", weight))?;
+            if weights.iter().any(|(existing, _)| *existing == action) {
+                return Err(format!("duplicate action: {}", name));
+            }
+            weights.push((action, weight));
+        }
+        if weights.iter().all(|(_, weight)| *weight == 0) {
+            return Err("mix needs at least one positive weight".to_string());
+        }
+        Ok(Self { weights })
+    }
+
+    // 重みに比例した確率で種類を選ぶ
+    pub fn pick(&self, rng: &mut impl Rng) -> Action {
+        let total: u32 = self.weights.iter().map(|(_, weight)| weight).sum();
+        let mut point = rng.gen_range(0..total);
+        for (action, weight) in &self.weights {
+            if point < *weight {
+                return *action;
+            }
+            point -= weight;
+        }
+        unreachable!("point is less than total weight")
+    }
+}
+
+// 1リクエストの結果
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub action: Action,
+    pub latency: Duration,
+    // レスポンスのステータスコード、接続エラーやタイムアウトはNone
+    pub status: Option<u16>,
+}
+
+impl Sample {
+    pub fn is_error(&self) -> bool {
+        !matches!(self.status, Some(200..=299))
+    }
+}
+
+// 種類ごとの集計結果
+#[derive(Debug, PartialEq)]
+pub struct ActionSummary {
+    pub action: Action,
+    pub count: usize,
+    pub errors: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    // (ステータスコード, 件数)、接続エラーは0として数える
+    pub statuses: Vec<(u16, usize)>,
+}
+
+impl ActionSummary {
+    pub fn error_rate(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.count as f64
+        }
+    }
+}
+
+// リクエストの種類ごとに集計する、送らなかった種類は含めない
+pub fn summarize(samples: &[Sample]) -> Vec<ActionSummary> {
+    Action::ALL
+        .into_iter()
+        .filter_map(|action| {
+            let samples: Vec<&Sample> = samples
+                .iter()
+                .filter(|sample| sample.action == action)
+                .collect();
+            if samples.is_empty() {
+                return None;
+            }
+            let mut latencies: Vec<Duration> =
+                samples.iter().map(|sample| sample.latency).collect();
+            latencies.sort_unstable();
+            let mut statuses: Vec<(u16, usize)> = Vec::new();
+            for sample in &samples {
+                let status = sample.status.unwrap_or(0);
+                match statuses.iter_mut().find(|(code, _)| *code == status) {
+                    Some((_, count)) => *count += 1,
+                    None => statuses.push((status, 1)),
+                }
+            }
+            statuses.sort_unstable();
+            Some(ActionSummary {
+                action,
+                count: samples.len(),
+                errors: samples.iter().filter(|sample| sample.is_error()).count(),
+                p50: percentile(&latencies, 50.0),
+                p90: percentile(&latencies, 90.0),
+                p99: percentile(&latencies, 99.0),
+                max: *latencies.last().unwrap(),
+                statuses,
+            })
+        })
+        .collect()
+}
+
+// 昇順に並べた値のパーセンタイル(nearest-rank法)、空ならゼロ
+pub fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((percent / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
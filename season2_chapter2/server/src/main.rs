@@ -1,6 +1,10 @@
 // src/main.rs
+use ruitter::config::{DatabaseConfig, ServerConfig};
 use ruitter::endpoints::run_server;
-use ruitter::models::{create_pool, create_tokio_runtime, DB_STRING_PRODUCTION};
+use ruitter::jobs::JobRunner;
+use ruitter::models::{create_tokio_runtime, database_url, replica_database_url};
+use ruitter::repositories::connect_with;
+use ruitter::unfurl::ReqwestFetcher;
 use std::sync::Arc;
 
 fn main() -> anyhow::Result<()> {
@@ -10,8 +14,30 @@ fn main() -> anyhow::Result<()> {
 }
 
 async fn run() -> anyhow::Result<()> {
-    let arc_pool = Arc::new(create_pool(DB_STRING_PRODUCTION).await?);
-    let session_store = async_sqlx_session::MySqlSessionStore::new(DB_STRING_PRODUCTION).await?;
+    // 接続文字列のスキームに応じてMySQLかSQLiteのリポジトリを生成する
+    // レプリカが指定されていればタイムラインなどの参照系の一部のクエリをレプリカに送る
+    // DBが落ちていても起動できるよう、接続は最初のクエリまで遅らせる
+    let database_config = DatabaseConfig {
+        lazy_connect: true,
+        ..DatabaseConfig::from_env()?
+    };
+    let (repository, session_store) = connect_with(
+        &database_url(),
+        replica_database_url().as_deref(),
+        &database_config,
+    )
+    .await?;
+    let config = ServerConfig::from_env()?;
+    // 予約投稿の公開などのバックグラウンドジョブをAPIサーバと同じプロセスで実行する
+    let mut job_runner = JobRunner::new(
+        repository.clone(),
+        session_store.clone(),
+        config.timeline_mode,
+    );
+    if config.link_previews {
+        job_runner = job_runner.with_link_fetcher(Arc::new(ReqwestFetcher::new()));
+    }
+    tokio::spawn(job_runner.run());
     // APIサーバの起動
-    run_server(arc_pool, session_store).await
+    run_server(repository, session_store, config).await
 }
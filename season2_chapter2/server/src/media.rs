@@ -0,0 +1,125 @@
+// ツイートに添付するメディアの検証と保存
+use image::{ImageError, ImageFormat, ImageReader, Limits};
+use rand::Rng as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// 1ツイートに添付できるメディア数
+pub const MAX_MEDIA_PER_TWEET: usize = 4;
+// アップロードできるメディアの最大バイト数
+pub const MAX_MEDIA_BYTES: usize = 5 * 1024 * 1024;
+// デコードする画像の縦横の最大ピクセル数とデコード時のメモリの上限
+// 小さなファイルに巨大な画像を詰めたもの(decompression bomb)を展開させない
+const MAX_IMAGE_DIMENSION: u32 = 8192;
+const MAX_DECODE_ALLOC_BYTES: u64 = 256 * 1024 * 1024;
+// サムネイルの長辺のピクセル数
+const THUMBNAIL_SIZE: u32 = 320;
+pub const THUMBNAIL_CONTENT_TYPE: &str = "image/png";
+
+// メディア本体の保存先
+// まずはローカルファイルシステムのみだが、オブジェクトストレージなどに差し替えられるようにする
+#[axum::async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()>;
+    // キーが存在しなければNoneを返す
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    // キーが存在しなくてもエラーにしない
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+pub type SharedBlobStore = Arc<dyn BlobStore>;
+
+// 指定ディレクトリ以下にキーをファイル名として保存する
+pub struct LocalFsBlobStore {
+    root: PathBuf,
+}
+
+impl LocalFsBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[axum::async_trait]
+impl BlobStore for LocalFsBlobStore {
+    async fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, bytes).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.root.join(key)).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.root.join(key)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// 受け付けるメディアのMIMEタイプと画像形式
+fn accepted_format(content_type: &str) -> Option<ImageFormat> {
+    match content_type {
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/png" => Some(ImageFormat::Png),
+        "image/gif" => Some(ImageFormat::Gif),
+        "image/webp" => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MediaError {
+    UnsupportedContentType,
+    // ファイルもしくは画像の縦横が大きすぎる
+    TooLarge,
+    // 申告されたMIMEタイプと中身が一致しない、もしくは画像として読めない
+    Malformed,
+}
+
+// アップロードされたメディアを検証し、サムネイル(PNG)を生成する
+// デコードと縮小はCPUを使うので、非同期のコンテキストからはspawn_blockingで呼ぶ
+pub fn generate_thumbnail(content_type: &str, bytes: &[u8]) -> Result<Vec<u8>, MediaError> {
+    let format = accepted_format(content_type).ok_or(MediaError::UnsupportedContentType)?;
+    if bytes.len() > MAX_MEDIA_BYTES {
+        return Err(MediaError::TooLarge);
+    }
+    // 拡張子やヘッダではなく中身のマジックナンバーで形式を確認する
+    if image::guess_format(bytes).ok() != Some(format) {
+        return Err(MediaError::Malformed);
+    }
+    // 縦横はヘッダから読んで、本体を展開する前に確かめられる
+    let mut reader = ImageReader::with_format(std::io::Cursor::new(bytes), format);
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC_BYTES);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|e| match e {
+        ImageError::Limits(_) => MediaError::TooLarge,
+        _ => MediaError::Malformed,
+    })?;
+    let mut thumbnail = std::io::Cursor::new(Vec::new());
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut thumbnail, ImageFormat::Png)
+        .map_err(|_| MediaError::Malformed)?;
+    Ok(thumbnail.into_inner())
+}
+
+// 推測されにくいBlobStoreのキーを生成する
+pub fn generate_blob_key(user_id: u64, suffix: &str) -> String {
+    let random: u128 = rand::thread_rng().gen();
+    format!("{}/{:032x}{}", user_id, random, suffix)
+}
@@ -0,0 +1,251 @@
+// 社内のOIDCプロバイダ(IdP)によるログイン
+// 認可コードフローにPKCEを組み合わせ、IDトークンの署名はIdPが公開するJWKSで検証する
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use rand::RngCore as _;
+use sha2::{Digest as _, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// 認可リクエストを開始してからコールバックされるまでの猶予
+pub const AUTHORIZATION_EXPIRE_SECONDS: u64 = 600;
+
+// IDトークンの署名に受け付けるアルゴリズム、共通鍵方式(HS256など)は受け付けない
+const ACCEPTED_ALGORITHMS: [Algorithm; 8] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+];
+
+// JWKSの読み込み元
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JwksSource {
+    Url(String),
+    // IdPに到達できない環境やテスト向けに、ファイルに保存したJWKSも使える
+    File(PathBuf),
+}
+
+impl JwksSource {
+    pub fn parse(value: &str) -> Self {
+        if value.starts_with("http://") || value.starts_with("https://") {
+            Self::Url(value.to_string())
+        } else {
+            Self::File(PathBuf::from(value))
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    // IDトークンのissと一致しなければならない
+    pub issuer: String,
+    pub client_id: String,
+    // 公開クライアントとして登録した場合はNone
+    pub client_secret: Option<String>,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks: JwksSource,
+    // IdPに登録したコールバックURL(/api/oidc/callback)
+    pub redirect_uri: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OidcError {
+    // IdPに接続できない、もしくはIdPがエラーを返した
+    Provider,
+    // IDトークンの署名や内容が正しくない
+    InvalidIdToken,
+}
+
+// 認可リクエストごとに生成し、コールバックまでサーバ側で保持する値
+pub struct AuthorizationRequest {
+    // CSRF対策、コールバックのstateと一致しなければならない
+    pub state: String,
+    // IDトークンの使い回し対策、IDトークンのnonceと一致しなければならない
+    pub nonce: String,
+    // PKCE、トークンの交換時にIdPへ送る
+    pub code_verifier: String,
+}
+
+impl AuthorizationRequest {
+    pub fn new() -> Self {
+        Self {
+            state: random_string(),
+            nonce: random_string(),
+            code_verifier: random_string(),
+        }
+    }
+}
+
+impl Default for AuthorizationRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// IDトークンのうち使用するクレーム、iss、aud、expはデコード時に検証する
+#[derive(Debug, serde::Deserialize)]
+pub struct IdTokenClaims {
+    // IdP内でユーザを一意に識別するID
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+pub type SharedOidcClient = Arc<OidcClient>;
+
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    // 最初に使うときに読み込み、未知のkidが来たら鍵の更新とみなして読み直す
+    jwks: RwLock<Option<JwkSet>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.config.issuer
+    }
+
+    // ユーザをリダイレクトさせるIdPの認可エンドポイントのURL
+    pub fn authorization_url(&self, request: &AuthorizationRequest) -> String {
+        let challenge = code_challenge(&request.code_verifier);
+        let params = [
+            ("response_type", "code"),
+            ("client_id", &self.config.client_id),
+            ("redirect_uri", &self.config.redirect_uri),
+            ("scope", "openid profile email"),
+            ("state", &request.state),
+            ("nonce", &request.nonce),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+        ];
+        // 設定の読み込み時にURLとして検証している
+        reqwest::Url::parse_with_params(&self.config.authorization_endpoint, &params)
+            .expect("invalid authorization endpoint")
+            .to_string()
+    }
+
+    // 認可コードをIDトークンと交換し、検証したクレームを返す
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        request: &AuthorizationRequest,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_uri),
+            ("client_id", &self.config.client_id),
+            ("code_verifier", &request.code_verifier),
+        ];
+        if let Some(client_secret) = &self.config.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+        let response = self
+            .http
+            .post(&self.config.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                eprintln!("failed to exchange oidc authorization code: {}", e);
+                OidcError::Provider
+            })?;
+        let token: TokenResponse = response.json().await.map_err(|e| {
+            eprintln!("invalid oidc token response: {}", e);
+            OidcError::Provider
+        })?;
+        self.verify_id_token(&token.id_token, &request.nonce).await
+    }
+
+    pub async fn verify_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let header = decode_header(id_token).map_err(|_| OidcError::InvalidIdToken)?;
+        if !ACCEPTED_ALGORITHMS.contains(&header.alg) {
+            return Err(OidcError::InvalidIdToken);
+        }
+        let kid = header.kid.ok_or(OidcError::InvalidIdToken)?;
+        let jwk = self.find_jwk(&kid).await?;
+        let key = DecodingKey::from_jwk(&jwk).map_err(|_| OidcError::InvalidIdToken)?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| {
+                eprintln!("invalid oidc id token: {}", e);
+                OidcError::InvalidIdToken
+            })?
+            .claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::InvalidIdToken);
+        }
+        Ok(claims)
+    }
+
+    async fn find_jwk(&self, kid: &str) -> Result<Jwk, OidcError> {
+        if let Some(jwks) = self.jwks.read().await.as_ref() {
+            if let Some(jwk) = jwks.find(kid) {
+                return Ok(jwk.clone());
+            }
+        }
+        let jwks = self.load_jwks().await?;
+        let jwk = jwks.find(kid).cloned();
+        *self.jwks.write().await = Some(jwks);
+        jwk.ok_or(OidcError::InvalidIdToken)
+    }
+
+    async fn load_jwks(&self) -> Result<JwkSet, OidcError> {
+        let jwks = match &self.config.jwks {
+            JwksSource::Url(url) => match self.http.get(url).send().await {
+                Ok(response) => response.json().await.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+            JwksSource::File(path) => tokio::fs::read(path)
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string())),
+        };
+        jwks.map_err(|e| {
+            eprintln!("failed to load oidc jwks: {}", e);
+            OidcError::Provider
+        })
+    }
+}
+
+// PKCEのcode_challenge(S256)
+pub fn code_challenge(code_verifier: &str) -> String {
+    base64::encode_config(
+        Sha256::digest(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+fn random_string() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
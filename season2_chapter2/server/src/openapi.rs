@@ -0,0 +1,218 @@
+// エンドポイントの型から生成するOpenAPI 3ドキュメント
+// フロントエンドはこのドキュメントを参照し、APIのパスを独自に持たないようにする
+// ルーティングとの乖離はtests/openapi.rsで検出する
+use crate::endpoints::accounts::{DeleteUserParams, SetProtectedParams};
+use crate::endpoints::admin::{
+    ChangeUserRoleParams, CreateReportParams, HideUserTweetParams, SuspendUserParams,
+};
+use crate::endpoints::api_tokens::{CreateApiTokenParams, CreateApiTokenResponse};
+use crate::endpoints::direct_messages::{
+    CreateConversationParams, CreateConversationResponse, CreateDirectMessageParams,
+};
+use crate::endpoints::errors::{ErrorCode, ErrorResponse};
+use crate::endpoints::lists::{AddListMemberParams, CreateListResponse, ListParams};
+use crate::endpoints::media::MediaUploadForm;
+use crate::endpoints::notifications::{
+    MarkNotificationsReadParams, NotificationGroup, NotificationsResponse,
+};
+use crate::endpoints::webhooks::{CreateWebhookParams, CreateWebhookResponse};
+use crate::endpoints::{
+    CreateBlockParams, CreateFollowRelationParams, CreateSessionParams, CreateUserParams,
+    CreateUserTweetParams, HealthResponse,
+};
+use crate::models::{
+    ApiTokenItem, ApiTokenScope, ConversationItem, DirectMessageItem, ExportRecord,
+    FollowRequestItem, LinkPreview, ListMemberItem, ModerationAction, ModerationLogItem,
+    NotificationKind, RecommendedUser, ReportItem, Role, TimelineItem, TimelineMedia, Trend,
+    UserListItem, WebhookDeadLetter, WebhookDeliveryItem, WebhookDeliveryStatus, WebhookEvent,
+    WebhookItem,
+};
+use crate::render::{Entity, EntityKind};
+use utoipa::openapi::path::{Parameter, ParameterBuilder, ParameterIn, PathItemType};
+use utoipa::openapi::schema::{ObjectBuilder, SchemaType};
+use utoipa::openapi::security::{
+    ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme,
+};
+use utoipa::{Modify, OpenApi};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ruitter API"),
+    paths(
+        crate::endpoints::create_user,
+        crate::endpoints::create_session,
+        crate::endpoints::oidc::start_oidc_login,
+        crate::endpoints::oidc::finish_oidc_login,
+        crate::endpoints::accounts::delete_current_user,
+        crate::endpoints::accounts::export_current_user,
+        crate::endpoints::accounts::set_current_user_protected,
+        crate::endpoints::create_user_tweet,
+        crate::endpoints::create_like,
+        crate::endpoints::create_follow_relation,
+        crate::endpoints::follow_requests::get_follow_requests,
+        crate::endpoints::follow_requests::approve_follow_request,
+        crate::endpoints::follow_requests::reject_follow_request,
+        crate::endpoints::create_block,
+        crate::endpoints::lists::get_lists,
+        crate::endpoints::lists::create_list,
+        crate::endpoints::lists::update_list,
+        crate::endpoints::lists::delete_list,
+        crate::endpoints::lists::get_list_members,
+        crate::endpoints::lists::add_list_member,
+        crate::endpoints::lists::delete_list_member,
+        crate::endpoints::lists::get_list_timeline,
+        crate::endpoints::recommendations::get_recommended_users,
+        crate::endpoints::trends::get_trends,
+        crate::endpoints::get_timeline,
+        crate::endpoints::get_openapi,
+        crate::endpoints::get_health,
+        crate::endpoints::media::create_media,
+        crate::endpoints::media::get_media,
+        crate::endpoints::media::get_media_thumbnail,
+        crate::endpoints::notifications::get_notifications,
+        crate::endpoints::notifications::mark_notifications_read,
+        crate::endpoints::direct_messages::get_conversations,
+        crate::endpoints::direct_messages::create_conversation,
+        crate::endpoints::direct_messages::get_direct_messages,
+        crate::endpoints::direct_messages::create_direct_message,
+        crate::endpoints::admin::create_report,
+        crate::endpoints::admin::get_reports,
+        crate::endpoints::admin::resolve_report,
+        crate::endpoints::admin::hide_user_tweet,
+        crate::endpoints::admin::suspend_user,
+        crate::endpoints::admin::change_user_role,
+        crate::endpoints::admin::get_audit_logs,
+        crate::endpoints::webhooks::get_webhooks,
+        crate::endpoints::webhooks::create_webhook,
+        crate::endpoints::webhooks::delete_webhook,
+        crate::endpoints::webhooks::get_webhook_deliveries,
+        crate::endpoints::webhooks::get_webhook_dead_letters,
+        crate::endpoints::api_tokens::get_api_tokens,
+        crate::endpoints::api_tokens::create_api_token,
+        crate::endpoints::api_tokens::delete_api_token,
+    ),
+    components(schemas(
+        CreateUserParams,
+        CreateSessionParams,
+        CreateUserTweetParams,
+        CreateFollowRelationParams,
+        CreateBlockParams,
+        HealthResponse,
+        ErrorCode,
+        ErrorResponse,
+        RecommendedUser,
+        Trend,
+        TimelineItem,
+        TimelineMedia,
+        Entity,
+        EntityKind,
+        LinkPreview,
+        MediaUploadForm,
+        NotificationKind,
+        NotificationGroup,
+        NotificationsResponse,
+        MarkNotificationsReadParams,
+        ConversationItem,
+        DirectMessageItem,
+        CreateConversationParams,
+        CreateConversationResponse,
+        CreateDirectMessageParams,
+        DeleteUserParams,
+        SetProtectedParams,
+        FollowRequestItem,
+        ListParams,
+        CreateListResponse,
+        AddListMemberParams,
+        UserListItem,
+        ListMemberItem,
+        ExportRecord,
+        CreateReportParams,
+        HideUserTweetParams,
+        SuspendUserParams,
+        ChangeUserRoleParams,
+        Role,
+        ReportItem,
+        ModerationAction,
+        ModerationLogItem,
+        ApiTokenScope,
+        ApiTokenItem,
+        CreateApiTokenParams,
+        CreateApiTokenResponse,
+        WebhookEvent,
+        WebhookItem,
+        CreateWebhookParams,
+        CreateWebhookResponse,
+        WebhookDeliveryStatus,
+        WebhookDeliveryItem,
+        WebhookDeadLetter,
+    )),
+    modifiers(&SessionCookie)
+)]
+pub struct ApiDoc;
+
+// ログインAPIが発行するセッションクッキーとAPIトークンを認証方式として登録する
+// トークンの管理API以外はどちらでも呼び出せる
+struct SessionCookie;
+
+impl Modify for SessionCookie {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session_cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(
+                crate::endpoints::AXUM_SESSION_COOKIE_KEY,
+            ))),
+        );
+        components.add_security_scheme(
+            "bearer_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        let session_cookie = SecurityRequirement::new("session_cookie", Vec::<String>::new());
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            for (method, operation) in item.operations.iter_mut() {
+                let security = match operation.security.as_mut() {
+                    Some(security) if security.contains(&session_cookie) => security,
+                    _ => continue,
+                };
+                if !path.starts_with("/api/tokens") {
+                    security.push(SecurityRequirement::new(
+                        "bearer_token",
+                        Vec::<String>::new(),
+                    ));
+                }
+                // ログインが必要な更新系のAPIはIdempotency-Keyヘッダを受け付ける
+                if matches!(
+                    method,
+                    PathItemType::Post
+                        | PathItemType::Put
+                        | PathItemType::Patch
+                        | PathItemType::Delete
+                ) {
+                    operation
+                        .parameters
+                        .get_or_insert_with(Vec::new)
+                        .push(idempotency_key_parameter());
+                }
+            }
+        }
+    }
+}
+
+fn idempotency_key_parameter() -> Parameter {
+    ParameterBuilder::new()
+        .name("Idempotency-Key")
+        .parameter_in(ParameterIn::Header)
+        .description(Some(
+            "同じキーで再送されたリクエストは処理せず、最初のレスポンスを返す(24時間有効)",
+        ))
+        .schema(Some(
+            ObjectBuilder::new()
+                .schema_type(SchemaType::String)
+                .max_length(Some(255)),
+        ))
+        .build()
+}
+
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
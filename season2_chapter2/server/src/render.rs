@@ -0,0 +1,195 @@
+// ツイート本文の表示用の加工
+// 本文は入力のまま保存し、読み出し時にURL、メンション、ハッシュタグを抽出してHTMLを組み立てる
+
+// メンションとハッシュタグのリンク先、フロントエンドのページのパス
+pub const MENTION_PATH_PREFIX: &str = "/users/";
+pub const HASHTAG_PATH_PREFIX: &str = "/hashtags/";
+
+// URLの末尾に付いていても文の区切りとみなしてURLに含めない文字
+const URL_TRAILING_PUNCTUATION: &[char] = &['.', ',', ':', ';', '!', '?', '\'', ')'];
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Url,
+    Mention,
+    Hashtag,
+}
+
+// 本文中のリンクにする部分
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct Entity {
+    pub kind: EntityKind,
+    // 本文中の位置、文字(Unicodeのコードポイント)単位の[start, end)
+    pub start: usize,
+    pub end: usize,
+    // 本文中の文字列、"https://..."、"@alice"、"#rust"など
+    pub text: String,
+    pub href: String,
+}
+
+impl Entity {
+    // メンションのユーザ名やハッシュタグのタグ名、URLはURLそのもの
+    pub fn value(&self) -> &str {
+        match self.kind {
+            EntityKind::Url => &self.text,
+            // 先頭の@と#(全角を含む)を除く
+            EntityKind::Mention | EntityKind::Hashtag => {
+                let mut chars = self.text.chars();
+                chars.next();
+                chars.as_str()
+            }
+        }
+    }
+}
+
+// 本文からURL、メンション、ハッシュタグを先頭から順に抽出する
+// URL中の@や#はメンションやハッシュタグとみなさない
+pub fn extract_entities(content: &str) -> Vec<Entity> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut entities = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        // メールアドレスのような英数字直後の@や#は対象にしない
+        let preceded_by_word = i > 0 && is_word_char(chars[i - 1]);
+        let entity = match chars[i] {
+            _ if preceded_by_word => None,
+            '@' => word_entity(&chars, i, EntityKind::Mention),
+            '#' | '＃' => word_entity(&chars, i, EntityKind::Hashtag)
+                // 数字だけのタグは番号として使われることが多いのでハッシュタグにしない
+                .filter(|entity| !entity.value().chars().all(|c| c.is_ascii_digit())),
+            'h' => url_entity(&chars, i),
+            _ => None,
+        };
+        match entity {
+            Some(entity) => {
+                i = entity.end;
+                entities.push(entity);
+            }
+            None => i += 1,
+        }
+    }
+    entities
+}
+
+// 本文中のハッシュタグのタグ名を重複なく列挙する、大文字と小文字は区別しない
+pub fn hashtags(content: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for entity in extract_entities(content) {
+        if entity.kind == EntityKind::Hashtag {
+            let tag = entity.value().to_lowercase();
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+// 本文をHTMLエスケープし、抽出した部分をリンクにしたHTMLを返す
+pub fn render_html(content: &str, entities: &[Entity]) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut html = String::with_capacity(content.len());
+    let mut position = 0;
+    for entity in entities {
+        html.push_str(&escape_html(
+            &chars[position..entity.start].iter().collect::<String>(),
+        ));
+        // 外部サイトへのリンクは新しいタブで開き、開いたページから元のページを操作させない
+        let attributes = match entity.kind {
+            EntityKind::Url => r#" target="_blank" rel="nofollow noopener noreferrer""#,
+            EntityKind::Mention | EntityKind::Hashtag => "",
+        };
+        html.push_str(&format!(
+            r#"<a href="{}"{}>{}</a>"#,
+            escape_html(&entity.href),
+            attributes,
+            escape_html(&entity.text)
+        ));
+        position = entity.end;
+    }
+    html.push_str(&escape_html(&chars[position..].iter().collect::<String>()));
+    html
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// 記号の直後から英数字とアンダースコアが続く範囲をメンションかハッシュタグにする
+fn word_entity(chars: &[char], start: usize, kind: EntityKind) -> Option<Entity> {
+    let length = chars[start + 1..]
+        .iter()
+        .take_while(|c| is_word_char(**c))
+        .count();
+    if length == 0 {
+        return None;
+    }
+    let end = start + 1 + length;
+    let text: String = chars[start..end].iter().collect();
+    let value: String = chars[start + 1..end].iter().collect();
+    let href = match kind {
+        EntityKind::Mention => format!("{}{}", MENTION_PATH_PREFIX, value),
+        _ => format!("{}{}", HASHTAG_PATH_PREFIX, value.to_lowercase()),
+    };
+    Some(Entity {
+        kind,
+        start,
+        end,
+        text,
+        href,
+    })
+}
+
+// http://かhttps://で始まり、空白やASCII以外の文字の手前までをURLにする
+fn url_entity(chars: &[char], start: usize) -> Option<Entity> {
+    let rest = &chars[start..];
+    let scheme_length = ["https://", "http://"]
+        .iter()
+        .find(|scheme| rest.iter().copied().take(scheme.len()).eq(scheme.chars()))?
+        .len();
+    let mut end = start
+        + rest
+            .iter()
+            .take_while(|c| c.is_ascii_graphic() && !matches!(c, '<' | '>' | '"'))
+            .count();
+    while end > start + scheme_length && URL_TRAILING_PUNCTUATION.contains(&chars[end - 1]) {
+        // "(https://example.com/a_(b))"のようにURL中で対応している括弧は残す
+        if chars[end - 1] == ')' {
+            let opened = chars[start..end - 1].iter().filter(|c| **c == '(').count();
+            let closed = chars[start..end - 1].iter().filter(|c| **c == ')').count();
+            if opened > closed {
+                break;
+            }
+        }
+        end -= 1;
+    }
+    if end == start + scheme_length {
+        return None;
+    }
+    let text: String = chars[start..end].iter().collect();
+    Some(Entity {
+        kind: EntityKind::Url,
+        start,
+        end,
+        href: text.clone(),
+        text,
+    })
+}
@@ -0,0 +1,758 @@
+// データアクセス層のトレイト定義
+// エンドポイントはこれらのトレイトにのみ依存し、MySQLとSQLiteの実装を差し替えられるようにする
+use crate::config::DatabaseConfig;
+use crate::models::{
+    create_pool, ApiToken, ApiTokenItem, Block, Conversation, ConversationItem, DirectMessage,
+    DirectMessageItem, ExportRecord, ExportSection, FollowRelation, FollowRequest,
+    FollowRequestItem, HashtagBucket, IdempotencyKey, IdempotentResponse, Job, LinkPreview,
+    ListMember, ListMemberItem, Media, ModerationLogItem, Notification, NotificationEvent,
+    RecommendedUser, Report, ReportItem, Role, TimelineItem, TrendTweet, User, UserList,
+    UserListItem, UserTweet, Webhook, WebhookDeadLetter, WebhookDelivery, WebhookDeliveryItem,
+    WebhookDeliveryStatus, WebhookEvent, WebhookItem,
+};
+use async_session::{MemoryStore, Session, SessionStore};
+use async_sqlx_session::{MySqlSessionStore, SqliteSessionStore};
+use chrono::NaiveDateTime;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod mysql;
+pub mod sqlite;
+
+pub use self::mysql::MySqlRepository;
+pub use self::sqlite::SqliteRepository;
+
+#[axum::async_trait]
+pub trait UserRepository {
+    // 指定ユーザ名からUser構造体を取得
+    async fn find_by_name(&self, name: &str) -> Result<Option<User>, sqlx::Error>;
+    async fn find_by_id(&self, id: u64) -> Result<Option<User>, sqlx::Error>;
+    // UserデータをRDBに永続化し、採番されたIDを返す
+    async fn insert_user(&self, user: &User) -> Result<u64, sqlx::Error>;
+    // 非公開アカウントにするかどうかを変更する
+    async fn set_user_protected(&self, user_id: u64, protected: bool) -> Result<(), sqlx::Error>;
+}
+
+#[axum::async_trait]
+pub trait UserTweetRepository {
+    // ツイートをRDBに永続化し、採番されたIDを返す
+    async fn insert_user_tweet(&self, tweet: &UserTweet) -> Result<u64, sqlx::Error>;
+    async fn find_user_tweet(&self, id: u64) -> Result<Option<UserTweet>, sqlx::Error>;
+    // モデレーターが非表示にしたツイートかどうかを返す、存在しなければfalse
+    async fn is_user_tweet_hidden(&self, id: u64) -> Result<bool, sqlx::Error>;
+}
+
+#[axum::async_trait]
+pub trait FollowRelationRepository {
+    // フォロー関係をRDBに永続化し、採番されたIDを返す
+    async fn insert_follow_relation(
+        &self,
+        follow_relation: &FollowRelation,
+    ) -> Result<u64, sqlx::Error>;
+    // 指定ユーザがフォローしている関係を列挙
+    async fn find_by_follower_id(
+        &self,
+        follower_id: u64,
+    ) -> Result<Vec<FollowRelation>, sqlx::Error>;
+    // 2人のユーザが互いにフォローしているかどうか
+    // follower_idのユーザがfollowee_idのユーザをフォローしているかどうか
+    async fn is_following(&self, follower_id: u64, followee_id: u64) -> Result<bool, sqlx::Error>;
+    async fn are_mutual_followers(
+        &self,
+        user_id: u64,
+        other_user_id: u64,
+    ) -> Result<bool, sqlx::Error>;
+}
+
+#[axum::async_trait]
+pub trait TimelineRepository {
+    // タイムラインデータを返す
+    // 本当はページネーションなどが必要
+    async fn timeline(&self, follower_id: u64) -> Result<Vec<TimelineItem>, sqlx::Error>;
+    // リストのメンバーのツイートを新しい順に返す
+    // 非公開アカウントのツイートは閲覧者がフォローしている場合だけ含める
+    async fn list_timeline(
+        &self,
+        list_id: u64,
+        viewer_id: u64,
+    ) -> Result<Vec<TimelineItem>, sqlx::Error>;
+}
+
+// 書き込み時に配るホームタイムライン(fan-out-on-write)
+// fan_out_thresholdを指定するとフォロワー数がそれを超える投稿者は配らず、読み出し時に結合する
+#[axum::async_trait]
+pub trait HomeTimelineRepository {
+    // フォロワー数を返す
+    async fn count_followers(&self, followee_id: u64) -> Result<u64, sqlx::Error>;
+    // ツイートを投稿者のフォロワー全員のホームタイムラインに書き込み、書き込んだ行数を返す
+    async fn fan_out_user_tweet(&self, tweet_id: u64, author_id: u64) -> Result<u64, sqlx::Error>;
+    // フォロー開始時にフォロイーの既存ツイートをフォロワーのホームタイムラインに書き込む
+    async fn fan_out_followee_tweets(
+        &self,
+        follower_id: u64,
+        followee_id: u64,
+    ) -> Result<u64, sqlx::Error>;
+    // ホームタイムラインを読み出す、自分自身の投稿も含める
+    async fn home_timeline(
+        &self,
+        user_id: u64,
+        fan_out_threshold: Option<u64>,
+    ) -> Result<Vec<TimelineItem>, sqlx::Error>;
+    // 既存のツイートとフォロー関係からホームタイムラインを埋め直し、書き込んだ行数を返す
+    async fn backfill_home_timelines(
+        &self,
+        fan_out_threshold: Option<u64>,
+    ) -> Result<u64, sqlx::Error>;
+}
+
+// ツイートに添付するメディアのメタデータ
+// 本体はBlobStoreに保存する
+#[axum::async_trait]
+pub trait MediaRepository {
+    // メディアのメタデータを永続化し、採番されたIDを返す
+    async fn insert_media(&self, media: &Media) -> Result<u64, sqlx::Error>;
+    async fn find_media(&self, id: u64) -> Result<Option<Media>, sqlx::Error>;
+    // 指定ユーザがアップロードしたメディアを列挙
+    async fn find_media_by_user_id(&self, user_id: u64) -> Result<Vec<Media>, sqlx::Error>;
+    // 指定ユーザがアップロードし、まだどのツイートにも添付されていないメディアを返す
+    async fn find_unattached_media(
+        &self,
+        user_id: u64,
+        media_id: u64,
+    ) -> Result<Option<Media>, sqlx::Error>;
+    // メディアを添付した、非表示になっていないツイートのIDを返す
+    async fn find_media_user_tweet_id(&self, media_id: u64) -> Result<Option<u64>, sqlx::Error>;
+    // ツイートと添付メディアの紐付けを1トランザクションで永続化し、ツイートIDを返す
+    async fn insert_user_tweet_with_media(
+        &self,
+        tweet: &UserTweet,
+        media_ids: &[u64],
+    ) -> Result<u64, sqlx::Error>;
+}
+
+#[axum::async_trait]
+pub trait LikeRepository {
+    // いいねを永続化する、既にいいね済みならfalseを返す
+    async fn insert_like(&self, user_id: u64, user_tweet_id: u64) -> Result<bool, sqlx::Error>;
+}
+
+#[axum::async_trait]
+pub trait NotificationRepository {
+    // 通知を永続化し、採番されたIDを返す
+    async fn insert_notification(&self, notification: &Notification) -> Result<u64, sqlx::Error>;
+    // 指定ユーザへの通知を新しい順に最大limit件返す
+    async fn notifications(
+        &self,
+        user_id: u64,
+        limit: u64,
+    ) -> Result<Vec<NotificationEvent>, sqlx::Error>;
+    async fn count_unread_notifications(&self, user_id: u64) -> Result<u64, sqlx::Error>;
+    // 指定ユーザへの通知を既読にし、更新した行数を返す
+    // up_to_idを指定するとそのID以下の通知だけを既読にする
+    async fn mark_notifications_read(
+        &self,
+        user_id: u64,
+        up_to_id: Option<u64>,
+    ) -> Result<u64, sqlx::Error>;
+}
+
+// 1対1のダイレクトメッセージ
+#[axum::async_trait]
+pub trait DirectMessageRepository {
+    // 2人の会話を返す、まだなければ作成する
+    async fn find_or_create_conversation(
+        &self,
+        conversation: &Conversation,
+    ) -> Result<Conversation, sqlx::Error>;
+    async fn find_conversation(&self, id: u64) -> Result<Option<Conversation>, sqlx::Error>;
+    // 指定ユーザが参加している会話を最新メッセージの新しい順に返す
+    async fn conversations(&self, user_id: u64) -> Result<Vec<ConversationItem>, sqlx::Error>;
+    // メッセージを永続化し、採番されたIDを返す
+    async fn insert_direct_message(&self, message: &DirectMessage) -> Result<u64, sqlx::Error>;
+    // 会話内のメッセージを新しい順に最大limit件返す
+    // before_idを指定するとそれより古いメッセージを返す(ページネーション)
+    async fn direct_messages(
+        &self,
+        conversation_id: u64,
+        before_id: Option<u64>,
+        limit: u64,
+    ) -> Result<Vec<DirectMessageItem>, sqlx::Error>;
+    // 指定ユーザ宛てのメッセージを既読にし、更新した行数を返す
+    async fn mark_direct_messages_read(
+        &self,
+        conversation_id: u64,
+        reader_id: u64,
+    ) -> Result<u64, sqlx::Error>;
+}
+
+// アカウントの削除とデータエクスポート
+#[axum::async_trait]
+pub trait AccountRepository {
+    // ユーザを削除する、関連するデータは外部キーのON DELETE CASCADEで削除される
+    // 削除したらtrueを返す
+    async fn delete_user(&self, id: u64) -> Result<bool, sqlx::Error>;
+    // エクスポートする区分の行のうち、IDがafter_idより大きいものをlimit件まで返す
+    // 各行は次のページのafter_idにするIDと組にする
+    async fn export_records(
+        &self,
+        user_id: u64,
+        section: ExportSection,
+        after_id: u64,
+        limit: u64,
+    ) -> Result<Vec<(u64, ExportRecord)>, sqlx::Error>;
+}
+
+// 通報とモデレーション操作
+// モデレーション操作は監査ログの書き込みと同じトランザクションで行う
+#[axum::async_trait]
+pub trait ModerationRepository {
+    // 通報を記録する、同じユーザが同じツイートを通報済みならfalseを返す
+    async fn insert_report(&self, report: &Report) -> Result<bool, sqlx::Error>;
+    // 未対応の通報を古い順に返す
+    async fn unresolved_reports(&self, limit: u64) -> Result<Vec<ReportItem>, sqlx::Error>;
+    // 以下の操作は対象が存在しなければ何もせずfalseを返す
+    async fn resolve_report(&self, actor_id: u64, report_id: u64) -> Result<bool, sqlx::Error>;
+    async fn set_user_tweet_hidden(
+        &self,
+        actor_id: u64,
+        user_tweet_id: u64,
+        hidden: bool,
+    ) -> Result<bool, sqlx::Error>;
+    async fn set_user_suspended(
+        &self,
+        actor_id: u64,
+        user_id: u64,
+        suspended: bool,
+    ) -> Result<bool, sqlx::Error>;
+    // actor_idがNoneならコマンドラインからの操作として記録する
+    async fn set_user_role(
+        &self,
+        actor_id: Option<u64>,
+        user_id: u64,
+        role: Role,
+    ) -> Result<bool, sqlx::Error>;
+    // 監査ログを新しい順に返す
+    async fn moderation_logs(&self, limit: u64) -> Result<Vec<ModerationLogItem>, sqlx::Error>;
+}
+
+#[axum::async_trait]
+pub trait BlockRepository {
+    // ブロック関係を記録する、ブロック済みならfalseを返す
+    async fn insert_block(&self, block: &Block) -> Result<bool, sqlx::Error>;
+    // 2人のどちらかが相手をブロックしているかを返す
+    async fn is_blocked_between(
+        &self,
+        user_id: u64,
+        other_user_id: u64,
+    ) -> Result<bool, sqlx::Error>;
+}
+
+// おすすめユーザのスコア
+// 共通のフォロイー数 * RECOMMENDATION_MUTUAL_WEIGHT + 直近の投稿数(RECOMMENDATION_RECENT_TWEET_CAPまで)
+pub const RECOMMENDATION_MUTUAL_WEIGHT: u64 = 10;
+pub const RECOMMENDATION_RECENT_TWEET_CAP: u64 = 10;
+// 直近の投稿として数える日数
+pub const RECOMMENDATION_RECENT_DAYS: u64 = 7;
+
+#[axum::async_trait]
+pub trait RecommendationRepository {
+    // フォロイーのフォロイーのうち、フォロー済み、ブロック関係にある、凍結中のユーザを除いてスコア順に返す
+    async fn recommended_users(
+        &self,
+        user_id: u64,
+        limit: u64,
+    ) -> Result<Vec<RecommendedUser>, sqlx::Error>;
+}
+
+// バックグラウンドジョブのキュー
+// 日時は全てUTCで、DBの時計ではなく呼び出し側の時計を使う
+#[axum::async_trait]
+pub trait JobRepository {
+    // ジョブを登録する、unique_keyが重複していれば登録せずfalseを返す
+    async fn enqueue_job(&self, job: &Job) -> Result<bool, sqlx::Error>;
+    // 実行予定日時を過ぎたジョブと、locked_untilを過ぎても終わっていないジョブを最大limit件取得して実行中にする
+    // 同じジョブを複数のワーカーが同時に取得することはない
+    async fn claim_due_jobs(
+        &self,
+        now: NaiveDateTime,
+        locked_until: NaiveDateTime,
+        limit: u64,
+    ) -> Result<Vec<Job>, sqlx::Error>;
+    // 以下の取得したジョブの更新は、locked_untilを過ぎて他のワーカーに取得し直されていれば何もしない
+    // 更新した場合はtrueを返す
+    async fn complete_job(&self, job: &Job) -> Result<bool, sqlx::Error>;
+    // 失敗したジョブをrun_atに再実行する
+    async fn retry_job(
+        &self,
+        job: &Job,
+        run_at: NaiveDateTime,
+        error: &str,
+    ) -> Result<bool, sqlx::Error>;
+    // 再実行を諦める
+    async fn fail_job(&self, job: &Job, error: &str) -> Result<bool, sqlx::Error>;
+    // 予約投稿のジョブを完了にするのと同じトランザクションでツイートを書き込み、採番されたIDを返す
+    // ジョブが既に完了しているか他のワーカーに取得し直されていれば何もせずNoneを返すので、
+    // 同じ予約投稿が二重に公開されることはない
+    async fn publish_scheduled_tweet(
+        &self,
+        job: &Job,
+        tweet: &UserTweet,
+        media_ids: &[u64],
+    ) -> Result<Option<u64>, sqlx::Error>;
+}
+
+// 個人アクセストークン
+#[axum::async_trait]
+pub trait ApiTokenRepository {
+    // トークンをRDBに永続化し、採番されたIDを返す
+    async fn insert_api_token(&self, token: &ApiToken) -> Result<u64, sqlx::Error>;
+    // 指定ユーザのトークンを作成順に返す
+    async fn api_tokens(&self, user_id: u64) -> Result<Vec<ApiTokenItem>, sqlx::Error>;
+    async fn find_api_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<ApiToken>, sqlx::Error>;
+    async fn touch_api_token(&self, id: u64, used_at: NaiveDateTime) -> Result<(), sqlx::Error>;
+    // 指定ユーザのトークンを削除する、削除したらtrueを返す
+    async fn delete_api_token(&self, user_id: u64, id: u64) -> Result<bool, sqlx::Error>;
+}
+
+// OIDCプロバイダのユーザとの紐付け
+#[axum::async_trait]
+pub trait OidcIdentityRepository {
+    async fn find_user_by_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<Option<User>, sqlx::Error>;
+    // ユーザと紐付けを同じトランザクションで作成し、採番されたユーザIDを返す
+    async fn insert_user_with_oidc_identity(
+        &self,
+        user: &User,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<u64, sqlx::Error>;
+    // OIDCで作成したユーザかどうか
+    async fn has_oidc_identity(&self, user_id: u64) -> Result<bool, sqlx::Error>;
+}
+
+// 非公開アカウントへのフォロー申請
+#[axum::async_trait]
+pub trait FollowRequestRepository {
+    // 申請済みなら登録せずfalseを返す
+    async fn insert_follow_request(&self, request: &FollowRequest) -> Result<bool, sqlx::Error>;
+    // target_id宛ての未処理の申請を古い順に返す
+    async fn follow_requests(&self, target_id: u64) -> Result<Vec<FollowRequestItem>, sqlx::Error>;
+    // target_id宛ての申請を削除してフォロー関係を作成する、申請がなければNoneを返す
+    async fn approve_follow_request(
+        &self,
+        target_id: u64,
+        request_id: u64,
+    ) -> Result<Option<FollowRelation>, sqlx::Error>;
+    // target_id宛ての申請を削除する、申請がなければfalseを返す
+    async fn delete_follow_request(
+        &self,
+        target_id: u64,
+        request_id: u64,
+    ) -> Result<bool, sqlx::Error>;
+}
+
+// ユーザが作成するリスト
+// 所有者の確認はエンドポイントでfind_listの結果を見て行う
+#[axum::async_trait]
+pub trait ListRepository {
+    async fn insert_list(&self, list: &UserList) -> Result<u64, sqlx::Error>;
+    async fn find_list(&self, id: u64) -> Result<Option<UserList>, sqlx::Error>;
+    // owner_idのユーザが作成したリストを作成順に返す
+    async fn lists(&self, owner_id: u64) -> Result<Vec<UserListItem>, sqlx::Error>;
+    async fn rename_list(&self, id: u64, name: &str) -> Result<(), sqlx::Error>;
+    // メンバーも一緒に削除される
+    async fn delete_list(&self, id: u64) -> Result<(), sqlx::Error>;
+    // 追加済みなら追加せずfalseを返す
+    async fn insert_list_member(&self, member: &ListMember) -> Result<bool, sqlx::Error>;
+    // メンバーでなければfalseを返す
+    async fn delete_list_member(&self, list_id: u64, user_id: u64) -> Result<bool, sqlx::Error>;
+    // 追加した順に返す
+    async fn list_members(&self, list_id: u64) -> Result<Vec<ListMemberItem>, sqlx::Error>;
+}
+
+// 負荷試験用のデータ投入(seed_db)で使う複数行のINSERT
+// 1回に渡す行数はプレースホルダの上限を超えないように呼び出し側で分割する
+#[axum::async_trait]
+pub trait BulkInsertRepository {
+    // パスワードなしのユーザを作成し、namesと同じ順でIDを返す
+    async fn bulk_insert_users(&self, names: &[String]) -> Result<Vec<u64>, sqlx::Error>;
+    // 既に存在するフォロー関係は無視し、追加した件数を返す
+    async fn bulk_insert_follow_relations(
+        &self,
+        follow_relations: &[FollowRelation],
+    ) -> Result<u64, sqlx::Error>;
+    // 投稿日時を指定してツイートを作成する
+    async fn bulk_insert_user_tweets(
+        &self,
+        tweets: &[(UserTweet, NaiveDateTime)],
+    ) -> Result<(), sqlx::Error>;
+}
+
+// URLのリンクプレビュー
+#[axum::async_trait]
+pub trait LinkPreviewRepository {
+    // 同じURLのプレビューがあれば取得し直した内容で上書きする
+    async fn upsert_link_preview(&self, preview: &LinkPreview) -> Result<(), sqlx::Error>;
+    // 指定したURLのうち取得済みのプレビューを返す
+    async fn link_previews(&self, urls: &[String]) -> Result<Vec<LinkPreview>, sqlx::Error>;
+}
+
+// ハッシュタグのトレンドの集計
+#[axum::async_trait]
+pub trait TrendRepository {
+    // ハッシュタグを集計済みの最後のツイートID
+    async fn hashtag_cursor(&self) -> Result<u64, sqlx::Error>;
+    // after_idより後の、非表示でないツイートをID順に最大limit件返す
+    // 非公開アカウントと凍結されたユーザのツイートは公開のトレンドに含めない
+    async fn trend_tweets(&self, after_id: u64, limit: u64)
+        -> Result<Vec<TrendTweet>, sqlx::Error>;
+    // バケットに使用回数を加え、集計済みの位置をfromからtoに進める
+    // 他のワーカーが先に位置を進めていれば何もせずfalseを返す、同じツイートを二重に数えない
+    async fn add_hashtag_counts(
+        &self,
+        from_id: u64,
+        to_id: u64,
+        buckets: &[HashtagBucket],
+    ) -> Result<bool, sqlx::Error>;
+    // 開始時刻がsince以上until未満のバケットをタグごとに合計する
+    async fn hashtag_counts(
+        &self,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(String, u64)>, sqlx::Error>;
+    // 開始時刻がbeforeより前のバケットを削除し、削除した行数を返す
+    async fn delete_hashtag_buckets(&self, before: i64) -> Result<u64, sqlx::Error>;
+}
+
+// Webhookの登録と配信
+#[axum::async_trait]
+pub trait WebhookRepository {
+    // Webhookを永続化し、採番されたIDを返す
+    async fn insert_webhook(&self, webhook: &Webhook) -> Result<u64, sqlx::Error>;
+    // Webhookを登録順に返す
+    async fn webhooks(&self) -> Result<Vec<WebhookItem>, sqlx::Error>;
+    async fn find_webhook(&self, id: u64) -> Result<Option<Webhook>, sqlx::Error>;
+    // 指定したイベントを購読しているWebhookを返す
+    async fn subscribed_webhooks(&self, event: WebhookEvent) -> Result<Vec<Webhook>, sqlx::Error>;
+    // Webhookと配信ログを削除する、削除したらtrueを返す
+    async fn delete_webhook(&self, id: u64) -> Result<bool, sqlx::Error>;
+    // 配信を未配信として永続化し、採番されたIDを返す
+    async fn insert_webhook_delivery(&self, delivery: &WebhookDelivery)
+        -> Result<u64, sqlx::Error>;
+    // 未配信の配信を返す、配信済みやデッドレターに移したものはNone
+    async fn find_pending_webhook_delivery(
+        &self,
+        id: u64,
+    ) -> Result<Option<WebhookDelivery>, sqlx::Error>;
+    // 送信の結果を記録する、errorがNoneなら配信済みにする
+    async fn record_webhook_attempt(
+        &self,
+        id: u64,
+        response_status: Option<u16>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+    // 未配信の配信をデッドレターに移す
+    async fn dead_letter_webhook_delivery(&self, id: u64) -> Result<bool, sqlx::Error>;
+    // 指定したWebhookの配信ログを新しい順に最大limit件返す
+    async fn webhook_deliveries(
+        &self,
+        webhook_id: u64,
+        limit: u64,
+    ) -> Result<Vec<WebhookDeliveryItem>, sqlx::Error>;
+    // デッドレターを新しい順に最大limit件返す
+    async fn webhook_dead_letters(&self, limit: u64)
+        -> Result<Vec<WebhookDeadLetter>, sqlx::Error>;
+}
+
+// Idempotency-Keyヘッダによる再送の検出
+#[axum::async_trait]
+pub trait IdempotencyKeyRepository {
+    // キーを処理中として登録する、有効期限内の同じキーがあれば登録せずfalseを返す
+    async fn reserve_idempotency_key(
+        &self,
+        user_id: u64,
+        key: &str,
+        request_hash: &str,
+        now: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> Result<bool, sqlx::Error>;
+    async fn find_idempotency_key(
+        &self,
+        user_id: u64,
+        key: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error>;
+    // 処理結果のレスポンスを保存し、有効期限を延ばす
+    async fn complete_idempotency_key(
+        &self,
+        user_id: u64,
+        key: &str,
+        response: &IdempotentResponse,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), sqlx::Error>;
+    async fn delete_idempotency_key(&self, user_id: u64, key: &str) -> Result<(), sqlx::Error>;
+    // 有効期限切れのキーを削除し、削除した件数を返す
+    async fn delete_expired_idempotency_keys(&self, now: NaiveDateTime)
+        -> Result<u64, sqlx::Error>;
+}
+
+// エンドポイントが必要とするリポジトリをまとめたトレイト
+#[axum::async_trait]
+pub trait Repository:
+    UserRepository
+    + UserTweetRepository
+    + FollowRelationRepository
+    + TimelineRepository
+    + HomeTimelineRepository
+    + MediaRepository
+    + LikeRepository
+    + NotificationRepository
+    + DirectMessageRepository
+    + AccountRepository
+    + ModerationRepository
+    + BlockRepository
+    + RecommendationRepository
+    + JobRepository
+    + ApiTokenRepository
+    + OidcIdentityRepository
+    + LinkPreviewRepository
+    + IdempotencyKeyRepository
+    + FollowRequestRepository
+    + ListRepository
+    + BulkInsertRepository
+    + TrendRepository
+    + WebhookRepository
+    + Send
+    + Sync
+{
+    // テーブルを生成する
+    async fn setup_tables(&self) -> Result<(), sqlx::Error>;
+    // DBに接続できるかを確かめる
+    async fn ping(&self) -> Result<(), sqlx::Error>;
+    // すべてのクエリをプライマリに送るリポジトリ、レプリカを使っていなければNone
+    // 書き込んだ直後のユーザの読み出しがレプリカの遅延で古くならないようにする
+    fn primary_only(&self) -> Option<SharedRepository>;
+}
+
+// 接続の失敗で参照系のクエリが失敗した場合の再試行の設定
+// 何度実行しても結果が変わらない読み出しにだけ使い、書き込みは再試行しない
+// 書き込みのトランザクションの中の読み出しはトランザクションごと失敗させ、疎通確認のpingはそのまま失敗を返す
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadRetry {
+    // 最初の実行に加えて再試行する回数
+    pub retries: u32,
+    // 1回目の再試行までの待ち時間、以降は再試行ごとに倍にする
+    pub backoff: Duration,
+}
+
+impl Default for ReadRetry {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl ReadRetry {
+    pub async fn run<T, F, Fut>(&self, mut query: F) -> Result<T, sqlx::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match query().await {
+                Err(e) if attempt < self.retries && is_connection_error(&e) => {
+                    let backoff = self.backoff * 2u32.pow(attempt);
+                    eprintln!("retrying read in {:?}: {}", backoff, e);
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// DBの再起動などで接続できなかったエラーかどうか、クエリ自体の誤りや制約違反は含めない
+pub fn is_connection_error(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::WorkerCrashed => true,
+        // SQLSTATEのクラス08は接続の例外
+        sqlx::Error::Database(e) => e.code().is_some_and(|code| code.starts_with("08")),
+        _ => false,
+    }
+}
+
+// RDBにJSONで保存したIdempotency-Keyのレスポンスヘッダを読み出す
+pub(crate) fn parse_response_headers(
+    value: Option<&str>,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    match value {
+        Some(value) => serde_json::from_str(value).map_err(|e| sqlx::Error::Decode(e.into())),
+        None => Ok(vec![]),
+    }
+}
+
+// RDBに文字列で保存したWebhookのイベントと配信の状態を読み出す
+pub(crate) fn parse_webhook_event(value: &str) -> Result<WebhookEvent, sqlx::Error> {
+    WebhookEvent::parse(value)
+        .ok_or_else(|| sqlx::Error::Decode(format!("unknown event: {}", value).into()))
+}
+
+pub(crate) fn parse_webhook_events(value: &str) -> Result<Vec<WebhookEvent>, sqlx::Error> {
+    WebhookEvent::split(value)
+        .ok_or_else(|| sqlx::Error::Decode(format!("unknown events: {}", value).into()))
+}
+
+pub(crate) fn parse_webhook_delivery_status(
+    value: &str,
+) -> Result<WebhookDeliveryStatus, sqlx::Error> {
+    WebhookDeliveryStatus::parse(value)
+        .ok_or_else(|| sqlx::Error::Decode(format!("unknown status: {}", value).into()))
+}
+
+// ジョブ取得時に行に書き込むトークン、取得した行を見分けるためにワーカーと取得ごとに変える
+pub(crate) fn claim_token() -> String {
+    use rand::Rng as _;
+    format!("{:016x}", rand::rngs::OsRng.gen::<u64>())
+}
+
+// link_previewsテーブルの検索キー
+pub(crate) fn url_hash(url: &str) -> String {
+    use sha2::{Digest as _, Sha256};
+    hex::encode(Sha256::digest(url.as_bytes()))
+}
+
+// ハンドラ間で共有するリポジトリ
+pub type SharedRepository = Arc<dyn Repository>;
+
+// セッションの保存先
+// async_session::SessionStoreはCloneを要求するのでトレイトオブジェクトにできず、列挙型で切り替える
+#[derive(Clone, Debug)]
+pub enum AppSessionStore {
+    MySql(MySqlSessionStore),
+    Sqlite(SqliteSessionStore),
+    // SQLiteのインメモリDBはコネクションごとに別DBになるので、セッションもメモリに置く
+    Memory(MemoryStore),
+}
+
+impl AppSessionStore {
+    // セッションテーブルを生成する
+    pub async fn migrate(&self) -> anyhow::Result<()> {
+        match self {
+            Self::MySql(store) => store.migrate().await?,
+            Self::Sqlite(store) => store.migrate().await?,
+            Self::Memory(_) => {}
+        }
+        Ok(())
+    }
+}
+
+impl AppSessionStore {
+    // 期限切れのセッションを削除する
+    pub async fn cleanup(&self) -> anyhow::Result<()> {
+        match self {
+            Self::MySql(store) => store.cleanup().await?,
+            Self::Sqlite(store) => store.cleanup().await?,
+            Self::Memory(store) => store.cleanup().await?,
+        }
+        Ok(())
+    }
+}
+
+#[axum::async_trait]
+impl SessionStore for AppSessionStore {
+    async fn load_session(&self, cookie_value: String) -> async_session::Result<Option<Session>> {
+        match self {
+            Self::MySql(store) => store.load_session(cookie_value).await,
+            Self::Sqlite(store) => store.load_session(cookie_value).await,
+            Self::Memory(store) => store.load_session(cookie_value).await,
+        }
+    }
+
+    async fn store_session(&self, session: Session) -> async_session::Result<Option<String>> {
+        match self {
+            Self::MySql(store) => store.store_session(session).await,
+            Self::Sqlite(store) => store.store_session(session).await,
+            Self::Memory(store) => store.store_session(session).await,
+        }
+    }
+
+    async fn destroy_session(&self, session: Session) -> async_session::Result {
+        match self {
+            Self::MySql(store) => store.destroy_session(session).await,
+            Self::Sqlite(store) => store.destroy_session(session).await,
+            Self::Memory(store) => store.destroy_session(session).await,
+        }
+    }
+
+    async fn clear_store(&self) -> async_session::Result {
+        match self {
+            Self::MySql(store) => store.clear_store().await,
+            Self::Sqlite(store) => store.clear_store().await,
+            Self::Memory(store) => store.clear_store().await,
+        }
+    }
+}
+
+// 接続文字列のスキームからMySQLかSQLiteかを判定してリポジトリとセッションストアを生成する
+pub async fn connect(url: &str) -> anyhow::Result<(SharedRepository, AppSessionStore)> {
+    connect_with(url, None, &DatabaseConfig::default()).await
+}
+
+// 読み取り専用のレプリカを指定した場合は、参照系の一部のクエリをレプリカに送る
+// セッションはログイン直後に読み出すのでプライマリにだけ保存する
+pub async fn connect_with(
+    url: &str,
+    replica_url: Option<&str>,
+    config: &DatabaseConfig,
+) -> anyhow::Result<(SharedRepository, AppSessionStore)> {
+    if url.starts_with("sqlite:") {
+        let mut repository = SqliteRepository::connect_with(url, config).await?;
+        if let Some(replica_url) = replica_url {
+            repository = repository
+                .with_replica(SqliteRepository::connect_replica(replica_url, config).await?);
+        }
+        let session_store = if SqliteRepository::is_memory(url) {
+            AppSessionStore::Memory(MemoryStore::new())
+        } else {
+            AppSessionStore::Sqlite(SqliteSessionStore::from_client(session_pool(url, config)?))
+        };
+        Ok((Arc::new(repository), session_store))
+    } else {
+        let mut repository = MySqlRepository::connect_with(url, config).await?;
+        if let Some(replica_url) = replica_url {
+            repository = repository.with_replica(create_pool(replica_url, config).await?);
+        }
+        let session_store =
+            AppSessionStore::MySql(MySqlSessionStore::from_client(session_pool(url, config)?));
+        Ok((Arc::new(repository), session_store))
+    }
+}
+
+// セッションストアのプール、リポジトリと同じく接続の設定に従う
+// セッションストアはsqlxの別のバージョンを使うので、リポジトリのプールを共有できない
+fn session_pool<DB: sqlx05::Database>(
+    url: &str,
+    config: &DatabaseConfig,
+) -> Result<sqlx05::Pool<DB>, sqlx05::Error> {
+    let options = sqlx05::pool::PoolOptions::<DB>::new()
+        .max_connections(config.max_connections)
+        .connect_timeout(config.acquire_timeout)
+        .idle_timeout(config.idle_timeout)
+        .max_lifetime(config.max_lifetime)
+        .test_before_acquire(true);
+    // セッションの読み書きで初めて接続するので、起動時にDBが落ちていても失敗しない
+    options.connect_lazy(url)
+}
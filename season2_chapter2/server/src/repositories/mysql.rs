@@ -0,0 +1,3033 @@
+// MySQLによるリポジトリ実装
+use super::{
+    claim_token, parse_response_headers, parse_webhook_delivery_status, parse_webhook_event,
+    parse_webhook_events, url_hash, AccountRepository, ApiTokenRepository, BlockRepository,
+    BulkInsertRepository, DirectMessageRepository, FollowRelationRepository,
+    FollowRequestRepository, HomeTimelineRepository, IdempotencyKeyRepository, JobRepository,
+    LikeRepository, LinkPreviewRepository, ListRepository, MediaRepository, ModerationRepository,
+    NotificationRepository, OidcIdentityRepository, ReadRetry, RecommendationRepository,
+    Repository, SharedRepository, TimelineRepository, TrendRepository, UserRepository,
+    UserTweetRepository, WebhookRepository, RECOMMENDATION_MUTUAL_WEIGHT,
+    RECOMMENDATION_RECENT_DAYS, RECOMMENDATION_RECENT_TWEET_CAP,
+};
+use crate::config::DatabaseConfig;
+use crate::models::{
+    create_pool, timeline_item, ApiToken, ApiTokenItem, ApiTokenScope, Block, Conversation,
+    ConversationItem, DirectMessage, DirectMessageItem, ExportRecord, ExportSection,
+    FollowRelation, FollowRequest, FollowRequestItem, FollowerCount, HashtagBucket, HashtagCursor,
+    HomeTimelineEntry, IdempotencyKey, IdempotentResponse, Job, JobKind, Like, LinkPreview,
+    ListMember, ListMemberItem, Media, ModerationAction, ModerationLog, ModerationLogItem,
+    Notification, NotificationEvent, NotificationKind, OidcIdentity, RecommendedUser, Report,
+    ReportItem, Role, TimelineItem, TrendTweet, User, UserList, UserListItem, UserTweet,
+    UserTweetMedia, Webhook, WebhookDeadLetter, WebhookDelivery, WebhookDeliveryItem, WebhookEvent,
+    WebhookItem,
+};
+use chrono::NaiveDateTime;
+use sqlx::{mysql::MySqlQueryResult, Executor as _, MySql, Pool};
+use std::sync::Arc;
+
+// タイムラインの共通SELECT句、添付メディアIDは並び順にカンマ区切りで受け取る
+const TIMELINE_SELECT: &str = r#"
+  SELECT user_tweets.id as id, users.name as name, user_tweets.content as content,
+  (
+    SELECT GROUP_CONCAT(CAST(media_id AS CHAR) ORDER BY position)
+    FROM user_tweet_media WHERE user_tweet_media.user_tweet_id = user_tweets.id
+  ) as media_ids
+  FROM user_tweets
+  INNER JOIN users
+  ON user_tweets.user_id = users.id
+"#;
+
+#[derive(sqlx::FromRow)]
+struct TimelineRow {
+    id: u64,
+    name: String,
+    content: String,
+    media_ids: Option<String>,
+}
+impl TimelineRow {
+    fn into_item(self) -> TimelineItem {
+        timeline_item(self.id, self.name, self.content, self.media_ids.as_deref())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UserRow {
+    id: u64,
+    name: String,
+    password_hash: Option<String>,
+    role: String,
+    is_suspended: bool,
+    is_protected: bool,
+}
+impl UserRow {
+    fn into_user(self) -> Result<User, sqlx::Error> {
+        let role = Role::parse(&self.role)
+            .ok_or_else(|| sqlx::Error::Decode(format!("unknown role: {}", self.role).into()))?;
+        Ok(User {
+            id: Some(self.id),
+            name: self.name,
+            password_hash: self.password_hash,
+            role,
+            is_suspended: self.is_suspended,
+            is_protected: self.is_protected,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ModerationLogRow {
+    id: u64,
+    actor_name: Option<String>,
+    action: String,
+    target_id: u64,
+    detail: Option<String>,
+    created_at: String,
+}
+impl ModerationLogRow {
+    fn into_item(self) -> Result<ModerationLogItem, sqlx::Error> {
+        let action = ModerationAction::parse(&self.action).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown action: {}", self.action).into())
+        })?;
+        Ok(ModerationLogItem {
+            id: self.id,
+            actor_name: self.actor_name,
+            action,
+            target_id: self.target_id,
+            detail: self.detail,
+            created_at: self.created_at,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RecommendedUserRow {
+    id: u64,
+    name: String,
+    mutual_count: i64,
+    recent_tweet_count: i64,
+}
+impl RecommendedUserRow {
+    fn into_item(self) -> RecommendedUser {
+        RecommendedUser {
+            id: self.id,
+            name: self.name,
+            mutual_count: self.mutual_count as u64,
+            recent_tweet_count: self.recent_tweet_count as u64,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct JobRow {
+    id: u64,
+    kind: String,
+    payload: String,
+    unique_key: Option<String>,
+    run_at: NaiveDateTime,
+    attempts: u32,
+    locked_by: Option<String>,
+}
+impl JobRow {
+    fn into_job(self) -> Result<Job, sqlx::Error> {
+        let kind = JobKind::parse(&self.kind)
+            .ok_or_else(|| sqlx::Error::Decode(format!("unknown kind: {}", self.kind).into()))?;
+        Ok(Job {
+            id: Some(self.id),
+            kind,
+            payload: self.payload,
+            unique_key: self.unique_key,
+            run_at: self.run_at,
+            attempts: self.attempts,
+            locked_by: self.locked_by,
+        })
+    }
+}
+
+fn parse_scopes(value: &str) -> Result<Vec<ApiTokenScope>, sqlx::Error> {
+    ApiTokenScope::split(value)
+        .ok_or_else(|| sqlx::Error::Decode(format!("unknown scopes: {}", value).into()))
+}
+
+#[derive(sqlx::FromRow)]
+struct ApiTokenRow {
+    id: u64,
+    user_id: u64,
+    name: String,
+    token_hash: String,
+    scopes: String,
+}
+impl ApiTokenRow {
+    fn into_token(self) -> Result<ApiToken, sqlx::Error> {
+        Ok(ApiToken {
+            id: Some(self.id),
+            user_id: self.user_id,
+            name: self.name,
+            token_hash: self.token_hash,
+            scopes: parse_scopes(&self.scopes)?,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct IdempotencyKeyRow {
+    request_hash: String,
+    status_code: Option<u16>,
+    response_headers: Option<String>,
+    response_body: Option<Vec<u8>>,
+}
+impl IdempotencyKeyRow {
+    fn into_idempotency_key(self) -> Result<IdempotencyKey, sqlx::Error> {
+        let response = match self.status_code {
+            Some(status_code) => Some(IdempotentResponse {
+                status_code,
+                headers: parse_response_headers(self.response_headers.as_deref())?,
+                body: self.response_body.unwrap_or_default(),
+            }),
+            None => None,
+        };
+        Ok(IdempotencyKey {
+            request_hash: self.request_hash,
+            response,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct FollowRequestItemRow {
+    id: u64,
+    name: String,
+}
+impl FollowRequestItemRow {
+    fn into_item(self) -> FollowRequestItem {
+        FollowRequestItem {
+            id: self.id,
+            name: self.name,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UserListItemRow {
+    id: u64,
+    name: String,
+    member_count: i64,
+}
+impl UserListItemRow {
+    fn into_item(self) -> UserListItem {
+        UserListItem {
+            id: self.id,
+            name: self.name,
+            member_count: self.member_count as u64,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ListMemberItemRow {
+    id: u64,
+    name: String,
+}
+impl ListMemberItemRow {
+    fn into_item(self) -> ListMemberItem {
+        ListMemberItem {
+            id: self.id,
+            name: self.name,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ApiTokenItemRow {
+    id: u64,
+    name: String,
+    scopes: String,
+    created_at: String,
+    last_used_at: Option<String>,
+}
+impl ApiTokenItemRow {
+    fn into_item(self) -> Result<ApiTokenItem, sqlx::Error> {
+        Ok(ApiTokenItem {
+            id: self.id,
+            name: self.name,
+            scopes: parse_scopes(&self.scopes)?,
+            created_at: self.created_at,
+            last_used_at: self.last_used_at,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct WebhookRow {
+    id: u64,
+    url: String,
+    secret: String,
+    events: String,
+    created_by: Option<u64>,
+}
+impl WebhookRow {
+    fn into_webhook(self) -> Result<Webhook, sqlx::Error> {
+        Ok(Webhook {
+            id: Some(self.id),
+            url: self.url,
+            secret: self.secret,
+            events: parse_webhook_events(&self.events)?,
+            created_by: self.created_by,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct WebhookDeliveryItemRow {
+    id: u64,
+    event: String,
+    status: String,
+    attempts: u32,
+    response_status: Option<u16>,
+    last_error: Option<String>,
+    created_at: String,
+    delivered_at: Option<String>,
+}
+impl WebhookDeliveryItemRow {
+    fn into_item(self) -> Result<WebhookDeliveryItem, sqlx::Error> {
+        Ok(WebhookDeliveryItem {
+            id: self.id,
+            event: parse_webhook_event(&self.event)?,
+            status: parse_webhook_delivery_status(&self.status)?,
+            attempts: self.attempts,
+            response_status: self.response_status,
+            last_error: self.last_error,
+            created_at: self.created_at,
+            delivered_at: self.delivered_at,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct WebhookDeadLetterRow {
+    id: u64,
+    delivery_id: u64,
+    webhook_id: u64,
+    url: String,
+    event: String,
+    payload: String,
+    last_error: Option<String>,
+    created_at: String,
+}
+impl WebhookDeadLetterRow {
+    fn into_dead_letter(self) -> Result<WebhookDeadLetter, sqlx::Error> {
+        Ok(WebhookDeadLetter {
+            id: self.id,
+            delivery_id: self.delivery_id,
+            webhook_id: self.webhook_id,
+            url: self.url,
+            event: parse_webhook_event(&self.event)?,
+            payload: self.payload,
+            last_error: self.last_error,
+            created_at: self.created_at,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct NotificationRow {
+    id: u64,
+    kind: String,
+    actor_name: String,
+    target_id: u64,
+    is_read: bool,
+}
+impl NotificationRow {
+    fn into_event(self) -> Result<NotificationEvent, sqlx::Error> {
+        let kind = NotificationKind::parse(&self.kind)
+            .ok_or_else(|| sqlx::Error::Decode(format!("unknown kind: {}", self.kind).into()))?;
+        Ok(NotificationEvent {
+            id: self.id,
+            kind,
+            actor_name: self.actor_name,
+            target_id: self.target_id,
+            is_read: self.is_read,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ConversationRow {
+    id: u64,
+    partner_name: String,
+    last_message: Option<String>,
+    unread_count: i64,
+}
+impl ConversationRow {
+    fn into_item(self) -> ConversationItem {
+        ConversationItem {
+            id: self.id,
+            partner_name: self.partner_name,
+            last_message: self.last_message,
+            unread_count: self.unread_count as u64,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct DirectMessageRow {
+    id: u64,
+    sender_name: String,
+    content: String,
+    is_read: bool,
+}
+impl DirectMessageRow {
+    fn into_item(self) -> DirectMessageItem {
+        DirectMessageItem {
+            id: self.id,
+            sender_name: self.sender_name,
+            content: self.content,
+            is_read: self.is_read,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MySqlRepository {
+    pool: Pool<MySql>,
+    // 読み取り専用のレプリカ、タイムラインなどの参照系の一部のクエリだけをこちらに送る
+    replica: Option<Pool<MySql>>,
+    // 接続の失敗で参照系のクエリが失敗した場合の再試行
+    read_retry: ReadRetry,
+}
+
+impl MySqlRepository {
+    pub fn new(pool: Pool<MySql>) -> Self {
+        Self {
+            pool,
+            replica: None,
+            read_retry: ReadRetry::default(),
+        }
+    }
+
+    pub fn with_read_retry(self, read_retry: ReadRetry) -> Self {
+        Self { read_retry, ..self }
+    }
+
+    pub fn with_replica(self, replica: Pool<MySql>) -> Self {
+        Self {
+            replica: Some(replica),
+            ..self
+        }
+    }
+
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        Self::connect_with(url, &DatabaseConfig::default()).await
+    }
+
+    pub async fn connect_with(url: &str, config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        Ok(Self::new(create_pool(url, config).await?).with_read_retry(config.read_retry))
+    }
+
+    // レプリカに送ってよい参照系のクエリの接続先、レプリカがなければプライマリ
+    // 書き込み直後に読み直すクエリには使わない
+    fn reader(&self) -> &Pool<MySql> {
+        self.replica.as_ref().unwrap_or(&self.pool)
+    }
+
+    pub fn pool(&self) -> &Pool<MySql> {
+        &self.pool
+    }
+}
+
+#[axum::async_trait]
+impl UserRepository for MySqlRepository {
+    async fn find_by_name(&self, name: &str) -> Result<Option<User>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE name = ?;"#, User::TABLE_NAME);
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, UserRow>(&sql)
+                    .bind(name)
+                    .fetch_optional(self.reader())
+            })
+            .await?
+            .map(UserRow::into_user)
+            .transpose()
+    }
+
+    async fn find_by_id(&self, id: u64) -> Result<Option<User>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE id = ?;"#, User::TABLE_NAME);
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, UserRow>(&sql)
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+            })
+            .await?
+            .map(UserRow::into_user)
+            .transpose()
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (name, password_hash) VALUES (?, ?);"#,
+            User::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(&user.name)
+            .bind(&user.password_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn set_user_protected(&self, user_id: u64, protected: bool) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"UPDATE {} SET is_protected = ? WHERE id = ?;"#,
+            User::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(protected)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[axum::async_trait]
+impl UserTweetRepository for MySqlRepository {
+    async fn insert_user_tweet(&self, tweet: &UserTweet) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, content, in_reply_to_id) VALUES (?, ?, ?);"#,
+            UserTweet::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(tweet.user_id)
+            .bind(&tweet.content)
+            .bind(tweet.in_reply_to_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn find_user_tweet(&self, id: u64) -> Result<Option<UserTweet>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE id = ?;"#, UserTweet::TABLE_NAME);
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, UserTweet>(&sql)
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+            })
+            .await
+    }
+
+    async fn is_user_tweet_hidden(&self, id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT COUNT(*) FROM {} WHERE id = ? AND is_hidden = TRUE;"#,
+            UserTweet::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| sqlx::query_scalar(&sql).bind(id).fetch_one(&self.pool))
+            .await?;
+        Ok(count > 0)
+    }
+}
+
+#[axum::async_trait]
+impl FollowRelationRepository for MySqlRepository {
+    async fn insert_follow_relation(
+        &self,
+        follow_relation: &FollowRelation,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"INSERT INTO {} (followee_id, follower_id) VALUES (?, ?);"#,
+            FollowRelation::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(follow_relation.followee_id)
+            .bind(follow_relation.follower_id)
+            .execute(&mut tx)
+            .await?;
+        increment_follower_count(&mut tx, follow_relation.followee_id).await?;
+        tx.commit().await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn find_by_follower_id(
+        &self,
+        follower_id: u64,
+    ) -> Result<Vec<FollowRelation>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE follower_id = ?;"#,
+            FollowRelation::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, FollowRelation>(&sql)
+                    .bind(follower_id)
+                    .fetch_all(&self.pool)
+            })
+            .await
+    }
+
+    async fn is_following(&self, follower_id: u64, followee_id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT COUNT(*) FROM {} WHERE follower_id = ? AND followee_id = ?;"#,
+            FollowRelation::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(follower_id)
+                    .bind(followee_id)
+                    .fetch_one(&self.pool)
+            })
+            .await?;
+        Ok(count > 0)
+    }
+
+    async fn are_mutual_followers(
+        &self,
+        user_id: u64,
+        other_user_id: u64,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT COUNT(*) FROM {}
+              WHERE (follower_id = ? AND followee_id = ?)
+              OR (follower_id = ? AND followee_id = ?);
+            "#,
+            FollowRelation::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(user_id)
+                    .bind(other_user_id)
+                    .bind(other_user_id)
+                    .bind(user_id)
+                    .fetch_one(&self.pool)
+            })
+            .await?;
+        Ok(count == 2)
+    }
+}
+
+#[axum::async_trait]
+impl TimelineRepository for MySqlRepository {
+    async fn timeline(&self, follower_id: u64) -> Result<Vec<TimelineItem>, sqlx::Error> {
+        // フォロイーのIDをアプリ側で列挙してIN句に展開すると、フォロイー数に比例して
+        // プレースホルダとクエリ文字列が膨らむので、follow_relationsへのサブクエリで絞り込む
+        // タイムラインには自分自身の投稿も含め、モデレーターが非表示にした投稿は除く
+        let sql = format!(
+            r#"
+              {select}
+              WHERE user_tweets.is_hidden = FALSE AND (
+                user_tweets.user_id = ?
+                OR user_tweets.user_id IN (
+                  SELECT followee_id FROM {} WHERE follower_id = ?
+                )
+              )
+              ORDER BY user_tweets.id DESC;
+            "#,
+            FollowRelation::TABLE_NAME,
+            select = TIMELINE_SELECT,
+        );
+        let rows = self
+            .read_retry
+            .run(|| {
+                sqlx::query_as::<_, TimelineRow>(&sql)
+                    .bind(follower_id)
+                    .bind(follower_id)
+                    .fetch_all(self.reader())
+            })
+            .await?;
+        Ok(rows.into_iter().map(TimelineRow::into_item).collect())
+    }
+
+    async fn list_timeline(
+        &self,
+        list_id: u64,
+        viewer_id: u64,
+    ) -> Result<Vec<TimelineItem>, sqlx::Error> {
+        // timelineのフォロイーの代わりにリストのメンバーで絞り込む
+        let sql = format!(
+            r#"
+              {select}
+              WHERE user_tweets.is_hidden = FALSE
+              AND user_tweets.user_id IN (
+                SELECT user_id FROM {} WHERE list_id = ?
+              )
+              AND (
+                users.is_protected = FALSE
+                OR users.id = ?
+                OR users.id IN (SELECT followee_id FROM {} WHERE follower_id = ?)
+              )
+              ORDER BY user_tweets.id DESC;
+            "#,
+            ListMember::TABLE_NAME,
+            FollowRelation::TABLE_NAME,
+            select = TIMELINE_SELECT,
+        );
+        let rows = self
+            .read_retry
+            .run(|| {
+                sqlx::query_as::<_, TimelineRow>(&sql)
+                    .bind(list_id)
+                    .bind(viewer_id)
+                    .bind(viewer_id)
+                    .fetch_all(self.reader())
+            })
+            .await?;
+        Ok(rows.into_iter().map(TimelineRow::into_item).collect())
+    }
+}
+
+#[axum::async_trait]
+impl HomeTimelineRepository for MySqlRepository {
+    async fn count_followers(&self, followee_id: u64) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT follower_count FROM {} WHERE user_id = ?;"#,
+            FollowerCount::TABLE_NAME
+        );
+        let count: Option<u64> = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(followee_id)
+                    .fetch_optional(&self.pool)
+            })
+            .await?;
+        Ok(count.unwrap_or(0))
+    }
+
+    async fn fan_out_user_tweet(&self, tweet_id: u64, author_id: u64) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT IGNORE INTO {} (user_id, tweet_id)
+              SELECT follower_id, ? FROM {} WHERE followee_id = ?;
+            "#,
+            HomeTimelineEntry::TABLE_NAME,
+            FollowRelation::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(tweet_id)
+            .bind(author_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn fan_out_followee_tweets(
+        &self,
+        follower_id: u64,
+        followee_id: u64,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT IGNORE INTO {} (user_id, tweet_id)
+              SELECT ?, id FROM {} WHERE user_id = ?;
+            "#,
+            HomeTimelineEntry::TABLE_NAME,
+            UserTweet::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(follower_id)
+            .bind(followee_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn home_timeline(
+        &self,
+        user_id: u64,
+        fan_out_threshold: Option<u64>,
+    ) -> Result<Vec<TimelineItem>, sqlx::Error> {
+        // 閾値を超える投稿者のツイートは配られていないので、読み出し時にフォロー関係から結合する
+        let fan_out_on_read = match fan_out_threshold {
+            Some(_) => {
+                r#"
+                  OR user_tweets.user_id IN (
+                    SELECT follow_relations.followee_id FROM follow_relations
+                    INNER JOIN follower_counts
+                    ON follower_counts.user_id = follow_relations.followee_id
+                    WHERE follow_relations.follower_id = ?
+                    AND follower_counts.follower_count > ?
+                  )
+                "#
+            }
+            None => "",
+        };
+        let sql = format!(
+            r#"
+              {select}
+              WHERE user_tweets.is_hidden = FALSE AND (
+                user_tweets.user_id = ?
+                OR user_tweets.id IN (SELECT tweet_id FROM home_timeline_entries WHERE user_id = ?)
+                {}
+              )
+              ORDER BY user_tweets.id DESC;
+            "#,
+            fan_out_on_read,
+            select = TIMELINE_SELECT,
+        );
+        let rows = self
+            .read_retry
+            .run(|| {
+                let mut query = sqlx::query_as::<_, TimelineRow>(&sql)
+                    .bind(user_id)
+                    .bind(user_id);
+                if let Some(threshold) = fan_out_threshold {
+                    query = query.bind(user_id).bind(threshold);
+                }
+                query.fetch_all(self.reader())
+            })
+            .await?;
+        Ok(rows.into_iter().map(TimelineRow::into_item).collect())
+    }
+
+    async fn backfill_home_timelines(
+        &self,
+        fan_out_threshold: Option<u64>,
+    ) -> Result<u64, sqlx::Error> {
+        let threshold_condition = match fan_out_threshold {
+            Some(_) => {
+                r#"
+                  WHERE COALESCE((
+                    SELECT follower_count FROM follower_counts
+                    WHERE follower_counts.user_id = user_tweets.user_id
+                  ), 0) <= ?
+                "#
+            }
+            None => "",
+        };
+        let sql = format!(
+            r#"
+              INSERT IGNORE INTO home_timeline_entries (user_id, tweet_id)
+              SELECT follow_relations.follower_id, user_tweets.id
+              FROM user_tweets
+              INNER JOIN follow_relations
+              ON follow_relations.followee_id = user_tweets.user_id
+              {};
+            "#,
+            threshold_condition
+        );
+        let mut query = sqlx::query(&sql);
+        if let Some(threshold) = fan_out_threshold {
+            query = query.bind(threshold);
+        }
+        Ok(query.execute(&self.pool).await?.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl MediaRepository for MySqlRepository {
+    async fn insert_media(&self, media: &Media) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT INTO {} (user_id, content_type, byte_size, blob_key, thumbnail_key)
+              VALUES (?, ?, ?, ?, ?);
+            "#,
+            Media::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(media.user_id)
+            .bind(&media.content_type)
+            .bind(media.byte_size)
+            .bind(&media.blob_key)
+            .bind(&media.thumbnail_key)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn find_media(&self, id: u64) -> Result<Option<Media>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE id = ?;"#, Media::TABLE_NAME);
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, Media>(&sql)
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+            })
+            .await
+    }
+
+    async fn find_media_by_user_id(&self, user_id: u64) -> Result<Vec<Media>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE user_id = ?;"#, Media::TABLE_NAME);
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, Media>(&sql)
+                    .bind(user_id)
+                    .fetch_all(&self.pool)
+            })
+            .await
+    }
+
+    async fn find_unattached_media(
+        &self,
+        user_id: u64,
+        media_id: u64,
+    ) -> Result<Option<Media>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT * FROM {} WHERE id = ? AND user_id = ?
+              AND NOT EXISTS (SELECT 1 FROM {} WHERE media_id = media.id);
+            "#,
+            Media::TABLE_NAME,
+            UserTweetMedia::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, Media>(&sql)
+                    .bind(media_id)
+                    .bind(user_id)
+                    .fetch_optional(&self.pool)
+            })
+            .await
+    }
+
+    async fn find_media_user_tweet_id(&self, media_id: u64) -> Result<Option<u64>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT {media}.user_tweet_id FROM {media}
+              INNER JOIN {tweets} ON {tweets}.id = {media}.user_tweet_id
+              WHERE {media}.media_id = ? AND {tweets}.is_hidden = FALSE;
+            "#,
+            media = UserTweetMedia::TABLE_NAME,
+            tweets = UserTweet::TABLE_NAME,
+        );
+        let id: Option<u64> = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(media_id)
+                    .fetch_optional(&self.pool)
+            })
+            .await?;
+        Ok(id)
+    }
+
+    async fn insert_user_tweet_with_media(
+        &self,
+        tweet: &UserTweet,
+        media_ids: &[u64],
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, content, in_reply_to_id) VALUES (?, ?, ?);"#,
+            UserTweet::TABLE_NAME
+        );
+        let tweet_id = sqlx::query(&sql)
+            .bind(tweet.user_id)
+            .bind(&tweet.content)
+            .bind(tweet.in_reply_to_id)
+            .execute(&mut tx)
+            .await?
+            .last_insert_id();
+        let sql = format!(
+            r#"INSERT INTO {} (user_tweet_id, media_id, position) VALUES (?, ?, ?);"#,
+            UserTweetMedia::TABLE_NAME
+        );
+        for (position, media_id) in media_ids.iter().enumerate() {
+            sqlx::query(&sql)
+                .bind(tweet_id)
+                .bind(*media_id)
+                .bind(position as u32)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(tweet_id)
+    }
+}
+
+#[axum::async_trait]
+impl LikeRepository for MySqlRepository {
+    async fn insert_like(&self, user_id: u64, user_tweet_id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT IGNORE INTO {} (user_id, user_tweet_id) VALUES (?, ?);"#,
+            Like::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(user_tweet_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[axum::async_trait]
+impl NotificationRepository for MySqlRepository {
+    async fn insert_notification(&self, notification: &Notification) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT INTO {} (user_id, kind, actor_id, target_id, is_read)
+              VALUES (?, ?, ?, ?, ?);
+            "#,
+            Notification::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(notification.user_id)
+            .bind(notification.kind.as_str())
+            .bind(notification.actor_id)
+            .bind(notification.target_id)
+            .bind(notification.is_read)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn notifications(
+        &self,
+        user_id: u64,
+        limit: u64,
+    ) -> Result<Vec<NotificationEvent>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT notifications.id as id, notifications.kind as kind,
+              users.name as actor_name, notifications.target_id as target_id,
+              notifications.is_read as is_read
+              FROM {}
+              INNER JOIN users
+              ON notifications.actor_id = users.id
+              WHERE notifications.user_id = ?
+              ORDER BY notifications.id DESC
+              LIMIT ?;
+            "#,
+            Notification::TABLE_NAME
+        );
+        let rows = self
+            .read_retry
+            .run(|| {
+                sqlx::query_as::<_, NotificationRow>(&sql)
+                    .bind(user_id)
+                    .bind(limit)
+                    .fetch_all(&self.pool)
+            })
+            .await?;
+        rows.into_iter().map(NotificationRow::into_event).collect()
+    }
+
+    async fn count_unread_notifications(&self, user_id: u64) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT COUNT(*) FROM {} WHERE user_id = ? AND is_read = FALSE;"#,
+            Notification::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| sqlx::query_scalar(&sql).bind(user_id).fetch_one(&self.pool))
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn mark_notifications_read(
+        &self,
+        user_id: u64,
+        up_to_id: Option<u64>,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET is_read = TRUE
+              WHERE user_id = ? AND is_read = FALSE AND id <= ?;
+            "#,
+            Notification::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(up_to_id.unwrap_or(u64::MAX))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl DirectMessageRepository for MySqlRepository {
+    async fn find_or_create_conversation(
+        &self,
+        conversation: &Conversation,
+    ) -> Result<Conversation, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT IGNORE INTO {} (user1_id, user2_id) VALUES (?, ?);"#,
+            Conversation::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(conversation.user1_id)
+            .bind(conversation.user2_id)
+            .execute(&self.pool)
+            .await?;
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE user1_id = ? AND user2_id = ?;"#,
+            Conversation::TABLE_NAME
+        );
+        sqlx::query_as::<_, Conversation>(&sql)
+            .bind(conversation.user1_id)
+            .bind(conversation.user2_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    async fn find_conversation(&self, id: u64) -> Result<Option<Conversation>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE id = ?;"#,
+            Conversation::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, Conversation>(&sql)
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+            })
+            .await
+    }
+
+    async fn conversations(&self, user_id: u64) -> Result<Vec<ConversationItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT conversations.id as id, users.name as partner_name,
+              (
+                SELECT content FROM direct_messages
+                WHERE direct_messages.conversation_id = conversations.id
+                ORDER BY direct_messages.id DESC LIMIT 1
+              ) as last_message,
+              (
+                SELECT COUNT(*) FROM direct_messages
+                WHERE direct_messages.conversation_id = conversations.id
+                AND direct_messages.sender_id <> ? AND direct_messages.is_read = FALSE
+              ) as unread_count,
+              (
+                SELECT MAX(direct_messages.id) FROM direct_messages
+                WHERE direct_messages.conversation_id = conversations.id
+              ) as last_message_id
+              FROM {}
+              INNER JOIN users
+              ON users.id = CASE
+                WHEN conversations.user1_id = ? THEN conversations.user2_id
+                ELSE conversations.user1_id
+              END
+              WHERE conversations.user1_id = ? OR conversations.user2_id = ?
+              ORDER BY last_message_id DESC, conversations.id DESC;
+            "#,
+            Conversation::TABLE_NAME
+        );
+        let rows = self
+            .read_retry
+            .run(|| {
+                sqlx::query_as::<_, ConversationRow>(&sql)
+                    .bind(user_id)
+                    .bind(user_id)
+                    .bind(user_id)
+                    .bind(user_id)
+                    .fetch_all(&self.pool)
+            })
+            .await?;
+        Ok(rows.into_iter().map(ConversationRow::into_item).collect())
+    }
+
+    async fn insert_direct_message(&self, message: &DirectMessage) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT INTO {} (conversation_id, sender_id, content, is_read)
+              VALUES (?, ?, ?, ?);
+            "#,
+            DirectMessage::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(message.conversation_id)
+            .bind(message.sender_id)
+            .bind(&message.content)
+            .bind(message.is_read)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn direct_messages(
+        &self,
+        conversation_id: u64,
+        before_id: Option<u64>,
+        limit: u64,
+    ) -> Result<Vec<DirectMessageItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT direct_messages.id as id, users.name as sender_name,
+              direct_messages.content as content, direct_messages.is_read as is_read
+              FROM {}
+              INNER JOIN users
+              ON direct_messages.sender_id = users.id
+              WHERE direct_messages.conversation_id = ? AND direct_messages.id < ?
+              ORDER BY direct_messages.id DESC
+              LIMIT ?;
+            "#,
+            DirectMessage::TABLE_NAME
+        );
+        let rows = self
+            .read_retry
+            .run(|| {
+                sqlx::query_as::<_, DirectMessageRow>(&sql)
+                    .bind(conversation_id)
+                    .bind(before_id.unwrap_or(u64::MAX))
+                    .bind(limit)
+                    .fetch_all(&self.pool)
+            })
+            .await?;
+        Ok(rows.into_iter().map(DirectMessageRow::into_item).collect())
+    }
+
+    async fn mark_direct_messages_read(
+        &self,
+        conversation_id: u64,
+        reader_id: u64,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET is_read = TRUE
+              WHERE conversation_id = ? AND sender_id <> ? AND is_read = FALSE;
+            "#,
+            DirectMessage::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(conversation_id)
+            .bind(reader_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl AccountRepository for MySqlRepository {
+    async fn delete_user(&self, id: u64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // フォロー関係はON DELETE CASCADEで消えるので、先にフォローしていたユーザのフォロワー数を減らす
+        let sql = format!(
+            r#"
+              UPDATE {} SET follower_count = GREATEST(follower_count, 1) - 1
+              WHERE user_id IN (SELECT followee_id FROM {} WHERE follower_id = ?);
+            "#,
+            FollowerCount::TABLE_NAME,
+            FollowRelation::TABLE_NAME
+        );
+        sqlx::query(&sql).bind(id).execute(&mut tx).await?;
+        let sql = format!(r#"DELETE FROM {} WHERE id = ?;"#, User::TABLE_NAME);
+        let result = sqlx::query(&sql).bind(id).execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn export_records(
+        &self,
+        user_id: u64,
+        section: ExportSection,
+        after_id: u64,
+        limit: u64,
+    ) -> Result<Vec<(u64, ExportRecord)>, sqlx::Error> {
+        match section {
+            ExportSection::Tweets => {
+                let sql = format!(
+                    r#"SELECT id, content, in_reply_to_id FROM {} WHERE user_id = ? AND id > ? ORDER BY id LIMIT ?;"#,
+                    UserTweet::TABLE_NAME
+                );
+                let rows: Vec<(u64, String, Option<u64>)> = self
+                    .read_retry
+                    .run(|| {
+                        sqlx::query_as(&sql)
+                            .bind(user_id)
+                            .bind(after_id)
+                            .bind(limit)
+                            .fetch_all(&self.pool)
+                    })
+                    .await?;
+                Ok(rows
+                    .into_iter()
+                    .map(|(id, content, in_reply_to_id)| {
+                        let record = ExportRecord::Tweet {
+                            id,
+                            content,
+                            in_reply_to_id,
+                        };
+                        (id, record)
+                    })
+                    .collect())
+            }
+            ExportSection::Following | ExportSection::Followers => {
+                // フォロー関係の一方の列が指定ユーザである行について、もう一方のユーザ名を返す
+                let (user_column, other_column) = match section {
+                    ExportSection::Following => ("follower_id", "followee_id"),
+                    _ => ("followee_id", "follower_id"),
+                };
+                let sql = format!(
+                    r#"
+                      SELECT {relations}.id, users.name FROM {relations}
+                      INNER JOIN users ON users.id = {relations}.{other}
+                      WHERE {relations}.{user} = ? AND {relations}.id > ?
+                      ORDER BY {relations}.id LIMIT ?;
+                    "#,
+                    relations = FollowRelation::TABLE_NAME,
+                    user = user_column,
+                    other = other_column,
+                );
+                let rows: Vec<(u64, String)> = self
+                    .read_retry
+                    .run(|| {
+                        sqlx::query_as(&sql)
+                            .bind(user_id)
+                            .bind(after_id)
+                            .bind(limit)
+                            .fetch_all(&self.pool)
+                    })
+                    .await?;
+                Ok(rows
+                    .into_iter()
+                    .map(|(id, name)| {
+                        let record = match section {
+                            ExportSection::Following => ExportRecord::Following { name },
+                            _ => ExportRecord::Follower { name },
+                        };
+                        (id, record)
+                    })
+                    .collect())
+            }
+            ExportSection::Likes => {
+                let sql = format!(
+                    r#"SELECT id, user_tweet_id FROM {} WHERE user_id = ? AND id > ? ORDER BY id LIMIT ?;"#,
+                    Like::TABLE_NAME
+                );
+                let rows: Vec<(u64, u64)> = self
+                    .read_retry
+                    .run(|| {
+                        sqlx::query_as(&sql)
+                            .bind(user_id)
+                            .bind(after_id)
+                            .bind(limit)
+                            .fetch_all(&self.pool)
+                    })
+                    .await?;
+                Ok(rows
+                    .into_iter()
+                    .map(|(id, user_tweet_id)| {
+                        let record = ExportRecord::Like { user_tweet_id };
+                        (id, record)
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+#[axum::async_trait]
+impl ModerationRepository for MySqlRepository {
+    async fn insert_report(&self, report: &Report) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT IGNORE INTO {} (reporter_id, user_tweet_id, reason) VALUES (?, ?, ?);"#,
+            Report::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(report.reporter_id)
+            .bind(report.user_tweet_id)
+            .bind(&report.reason)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn unresolved_reports(&self, limit: u64) -> Result<Vec<ReportItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT reports.id as id, reports.user_tweet_id as user_tweet_id,
+              user_tweets.content as content, authors.name as author_name,
+              reporters.name as reporter_name, reports.reason as reason,
+              user_tweets.is_hidden as is_hidden
+              FROM {} as reports
+              INNER JOIN user_tweets ON user_tweets.id = reports.user_tweet_id
+              INNER JOIN users as authors ON authors.id = user_tweets.user_id
+              INNER JOIN users as reporters ON reporters.id = reports.reporter_id
+              WHERE reports.is_resolved = FALSE
+              ORDER BY reports.id
+              LIMIT ?;
+            "#,
+            Report::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, ReportItem>(&sql)
+                    .bind(limit)
+                    .fetch_all(&self.pool)
+            })
+            .await
+    }
+
+    async fn resolve_report(&self, actor_id: u64, report_id: u64) -> Result<bool, sqlx::Error> {
+        self.update_with_moderation_log(
+            Report::TABLE_NAME,
+            "is_resolved = TRUE",
+            &ModerationLog {
+                id: None,
+                actor_id: Some(actor_id),
+                action: ModerationAction::ResolveReport,
+                target_id: report_id,
+                detail: None,
+            },
+        )
+        .await
+    }
+
+    async fn set_user_tweet_hidden(
+        &self,
+        actor_id: u64,
+        user_tweet_id: u64,
+        hidden: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let (assignment, action) = if hidden {
+            ("is_hidden = TRUE", ModerationAction::HideTweet)
+        } else {
+            ("is_hidden = FALSE", ModerationAction::UnhideTweet)
+        };
+        self.update_with_moderation_log(
+            UserTweet::TABLE_NAME,
+            assignment,
+            &ModerationLog {
+                id: None,
+                actor_id: Some(actor_id),
+                action,
+                target_id: user_tweet_id,
+                detail: None,
+            },
+        )
+        .await
+    }
+
+    async fn set_user_suspended(
+        &self,
+        actor_id: u64,
+        user_id: u64,
+        suspended: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let (assignment, action) = if suspended {
+            ("is_suspended = TRUE", ModerationAction::SuspendUser)
+        } else {
+            ("is_suspended = FALSE", ModerationAction::UnsuspendUser)
+        };
+        self.update_with_moderation_log(
+            User::TABLE_NAME,
+            assignment,
+            &ModerationLog {
+                id: None,
+                actor_id: Some(actor_id),
+                action,
+                target_id: user_id,
+                detail: None,
+            },
+        )
+        .await
+    }
+
+    async fn set_user_role(
+        &self,
+        actor_id: Option<u64>,
+        user_id: u64,
+        role: Role,
+    ) -> Result<bool, sqlx::Error> {
+        // ロール名は固定の文字列なのでSQLに埋め込んでよい
+        self.update_with_moderation_log(
+            User::TABLE_NAME,
+            &format!("role = '{}'", role.as_str()),
+            &ModerationLog {
+                id: None,
+                actor_id,
+                action: ModerationAction::ChangeRole,
+                target_id: user_id,
+                detail: Some(role.as_str().to_string()),
+            },
+        )
+        .await
+    }
+
+    async fn moderation_logs(&self, limit: u64) -> Result<Vec<ModerationLogItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT logs.id as id, users.name as actor_name, logs.action as action,
+              logs.target_id as target_id, logs.detail as detail,
+              DATE_FORMAT(logs.created_at, '%Y-%m-%d %H:%i:%s') as created_at
+              FROM {} as logs
+              LEFT OUTER JOIN users ON users.id = logs.actor_id
+              ORDER BY logs.id DESC
+              LIMIT ?;
+            "#,
+            ModerationLog::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, ModerationLogRow>(&sql)
+                    .bind(limit)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .into_iter()
+            .map(ModerationLogRow::into_item)
+            .collect()
+    }
+}
+
+impl MySqlRepository {
+    // 対象の行を更新し、同じトランザクションで監査ログを書き込む
+    // 対象の行が存在しなければロールバックしてfalseを返す
+    async fn update_with_moderation_log(
+        &self,
+        table_name: &str,
+        assignment: &str,
+        log: &ModerationLog,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // MySQLのUPDATEの影響行数は値が変わった行だけを数えるので、存在確認は行ロックで行う
+        let sql = format!(r#"SELECT id FROM {} WHERE id = ? FOR UPDATE;"#, table_name);
+        let exists = sqlx::query(&sql)
+            .bind(log.target_id)
+            .fetch_optional(&mut tx)
+            .await?
+            .is_some();
+        if !exists {
+            return Ok(false);
+        }
+        let sql = format!(r#"UPDATE {} SET {} WHERE id = ?;"#, table_name, assignment);
+        sqlx::query(&sql)
+            .bind(log.target_id)
+            .execute(&mut tx)
+            .await?;
+        let sql = format!(
+            r#"INSERT INTO {} (actor_id, action, target_id, detail) VALUES (?, ?, ?, ?);"#,
+            ModerationLog::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(log.actor_id)
+            .bind(log.action.as_str())
+            .bind(log.target_id)
+            .bind(&log.detail)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+// フォローされたユーザのフォロワー数を増やす、フォロー関係の書き込みと同じトランザクションで呼ぶ
+async fn increment_follower_count(
+    tx: &mut sqlx::Transaction<'_, MySql>,
+    followee_id: u64,
+) -> Result<(), sqlx::Error> {
+    let sql = format!(
+        r#"
+          INSERT INTO {} (user_id, follower_count) VALUES (?, 1)
+          ON DUPLICATE KEY UPDATE follower_count = follower_count + 1;
+        "#,
+        FollowerCount::TABLE_NAME
+    );
+    sqlx::query(&sql)
+        .bind(followee_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+// ベースラインのDDLで作ったテーブルに後から追加した列(テーブル名、列名、ADD COLUMNに続く定義)
+// CREATE TABLE IF NOT EXISTSは既存のテーブルを変更しないので、setup_tablesで足りない列だけを追加する
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("users", "password_hash", "password_hash VARCHAR(255) NULL"),
+    ("users", "role", "role VARCHAR(16) NOT NULL DEFAULT 'user'"),
+    (
+        "users",
+        "is_suspended",
+        "is_suspended BOOLEAN NOT NULL DEFAULT FALSE",
+    ),
+    (
+        "users",
+        "is_protected",
+        "is_protected BOOLEAN NOT NULL DEFAULT FALSE",
+    ),
+    (
+        "user_tweets",
+        "in_reply_to_id",
+        "in_reply_to_id BIGINT UNSIGNED NULL, \
+         ADD FOREIGN KEY (in_reply_to_id) REFERENCES user_tweets(id) ON DELETE SET NULL",
+    ),
+    (
+        "user_tweets",
+        "is_hidden",
+        "is_hidden BOOLEAN NOT NULL DEFAULT FALSE",
+    ),
+    // 既存のツイートの投稿日時は追加した時刻になる
+    (
+        "user_tweets",
+        "created_at",
+        "created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP",
+    ),
+];
+
+impl MySqlRepository {
+    // 列を使うインデックスを作る前に呼ぶ、テーブルがなければCREATE TABLEで全ての列が作られるので何もしない
+    async fn add_missing_columns(&self) -> Result<(), sqlx::Error> {
+        for (table, column, definition) in ADDED_COLUMNS {
+            let (columns, found): (i64, Option<i64>) = sqlx::query_as(
+                r#"
+                  SELECT COUNT(*), CAST(SUM(column_name = ?) AS SIGNED)
+                  FROM information_schema.columns
+                  WHERE table_schema = DATABASE() AND table_name = ?;
+                "#,
+            )
+            .bind(column)
+            .bind(table)
+            .fetch_one(&self.pool)
+            .await?;
+            if columns == 0 || found.unwrap_or(0) > 0 {
+                continue;
+            }
+            let sql = format!("ALTER TABLE {} ADD COLUMN {};", table, definition);
+            self.pool.execute(sql.as_str()).await?;
+        }
+        Ok(())
+    }
+}
+
+// MySQLではINDEXにIF NOT EXISTSを宣言できないのでエラーハンドリングする
+// 作成済みのINDEXのエラーだけを無視し、接続の失敗などそれ以外のエラーは呼び出し元に返す
+pub fn ignore_duplicate_key(
+    result: Result<MySqlQueryResult, sqlx::Error>,
+) -> Result<(), sqlx::Error> {
+    if let Err(e) = result {
+        let is_duplicate_index_error = e
+            .as_database_error()
+            .is_some_and(|e| e.message().starts_with("Duplicate key name"));
+        if !is_duplicate_index_error {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[axum::async_trait]
+impl BlockRepository for MySqlRepository {
+    async fn insert_block(&self, block: &Block) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT IGNORE INTO {} (blocker_id, blocked_id) VALUES (?, ?);"#,
+            Block::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(block.blocker_id)
+            .bind(block.blocked_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn is_blocked_between(
+        &self,
+        user_id: u64,
+        other_user_id: u64,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT COUNT(*) FROM {}
+              WHERE (blocker_id = ? AND blocked_id = ?) OR (blocker_id = ? AND blocked_id = ?);
+            "#,
+            Block::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(user_id)
+                    .bind(other_user_id)
+                    .bind(other_user_id)
+                    .bind(user_id)
+                    .fetch_one(&self.pool)
+            })
+            .await?;
+        Ok(count > 0)
+    }
+}
+
+#[axum::async_trait]
+impl RecommendationRepository for MySqlRepository {
+    async fn recommended_users(
+        &self,
+        user_id: u64,
+        limit: u64,
+    ) -> Result<Vec<RecommendedUser>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT id, name, mutual_count, recent_tweet_count FROM (
+                SELECT candidates.id as id, users.name as name,
+                candidates.mutual_count as mutual_count,
+                (
+                  SELECT COUNT(*) FROM user_tweets
+                  WHERE user_tweets.user_id = candidates.id
+                  AND user_tweets.created_at >= NOW() - INTERVAL {days} DAY
+                ) as recent_tweet_count
+                FROM (
+                  SELECT theirs.followee_id as id, COUNT(*) as mutual_count
+                  FROM {relations} as mine
+                  INNER JOIN {relations} as theirs ON theirs.follower_id = mine.followee_id
+                  WHERE mine.follower_id = ? AND theirs.followee_id <> ?
+                  GROUP BY theirs.followee_id
+                ) as candidates
+                INNER JOIN users ON users.id = candidates.id
+                WHERE users.is_suspended = FALSE
+                AND NOT EXISTS (
+                  SELECT 1 FROM {relations}
+                  WHERE follower_id = ? AND followee_id = candidates.id
+                )
+                AND NOT EXISTS (
+                  SELECT 1 FROM {blocks}
+                  WHERE (blocker_id = ? AND blocked_id = candidates.id)
+                  OR (blocker_id = candidates.id AND blocked_id = ?)
+                )
+              ) as ranked
+              ORDER BY mutual_count * {weight} + LEAST(recent_tweet_count, {cap}) DESC, id
+              LIMIT ?;
+            "#,
+            relations = FollowRelation::TABLE_NAME,
+            blocks = Block::TABLE_NAME,
+            weight = RECOMMENDATION_MUTUAL_WEIGHT,
+            cap = RECOMMENDATION_RECENT_TWEET_CAP,
+            days = RECOMMENDATION_RECENT_DAYS,
+        );
+        Ok(self
+            .read_retry
+            .run(|| {
+                sqlx::query_as::<_, RecommendedUserRow>(&sql)
+                    .bind(user_id)
+                    .bind(user_id)
+                    .bind(user_id)
+                    .bind(user_id)
+                    .bind(user_id)
+                    .bind(limit)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .into_iter()
+            .map(RecommendedUserRow::into_item)
+            .collect())
+    }
+}
+
+#[axum::async_trait]
+impl JobRepository for MySqlRepository {
+    async fn enqueue_job(&self, job: &Job) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT IGNORE INTO {} (kind, payload, unique_key, run_at) VALUES (?, ?, ?, ?);"#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(job.kind.as_str())
+            .bind(&job.payload)
+            .bind(&job.unique_key)
+            .bind(job.run_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn claim_due_jobs(
+        &self,
+        now: NaiveDateTime,
+        locked_until: NaiveDateTime,
+        limit: u64,
+    ) -> Result<Vec<Job>, sqlx::Error> {
+        // 他のワーカーがロック中の行は読み飛ばし、取得した行はコミットまでロックしたまま実行中にする
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"
+              SELECT id FROM {}
+              WHERE (status = 'pending' AND run_at <= ?)
+              OR (status = 'running' AND locked_until <= ?)
+              ORDER BY run_at, id
+              LIMIT ?
+              FOR UPDATE SKIP LOCKED;
+            "#,
+            Job::TABLE_NAME
+        );
+        let ids: Vec<u64> = sqlx::query_scalar(&sql)
+            .bind(now)
+            .bind(now)
+            .bind(limit)
+            .fetch_all(&mut tx)
+            .await?;
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let token = claim_token();
+        let sql = format!(
+            r#"
+              UPDATE {} SET status = 'running', locked_by = ?, locked_until = ?,
+              attempts = attempts + 1
+              WHERE id IN ({});
+            "#,
+            Job::TABLE_NAME,
+            vec!["?"; ids.len()].join(",")
+        );
+        let mut query = sqlx::query(&sql).bind(&token).bind(locked_until);
+        for id in &ids {
+            query = query.bind(id);
+        }
+        query.execute(&mut tx).await?;
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE locked_by = ? ORDER BY run_at, id;"#,
+            Job::TABLE_NAME
+        );
+        let jobs = sqlx::query_as::<_, JobRow>(&sql)
+            .bind(&token)
+            .fetch_all(&mut tx)
+            .await?;
+        tx.commit().await?;
+        jobs.into_iter().map(JobRow::into_job).collect()
+    }
+
+    async fn complete_job(&self, job: &Job) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"UPDATE {} SET status = 'done', locked_until = NULL WHERE id = ? AND locked_by = ?;"#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(job.id.unwrap())
+            .bind(&job.locked_by)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn retry_job(
+        &self,
+        job: &Job,
+        run_at: NaiveDateTime,
+        error: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET status = 'pending', run_at = ?, locked_until = NULL, last_error = ?
+              WHERE id = ? AND locked_by = ?;
+            "#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(run_at)
+            .bind(error)
+            .bind(job.id.unwrap())
+            .bind(&job.locked_by)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn fail_job(&self, job: &Job, error: &str) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET status = 'failed', locked_until = NULL, last_error = ?
+              WHERE id = ? AND locked_by = ?;
+            "#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(error)
+            .bind(job.id.unwrap())
+            .bind(&job.locked_by)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn publish_scheduled_tweet(
+        &self,
+        job: &Job,
+        tweet: &UserTweet,
+        media_ids: &[u64],
+    ) -> Result<Option<u64>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // 先にジョブの行を更新してロックを取る、既に完了していれば他のワーカーが公開済み
+        let sql = format!(
+            r#"
+              UPDATE {} SET status = 'done', locked_until = NULL
+              WHERE id = ? AND status = 'running' AND locked_by = ?;
+            "#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(job.id)
+            .bind(&job.locked_by)
+            .execute(&mut tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, content, in_reply_to_id) VALUES (?, ?, ?);"#,
+            UserTweet::TABLE_NAME
+        );
+        let tweet_id = sqlx::query(&sql)
+            .bind(tweet.user_id)
+            .bind(&tweet.content)
+            .bind(tweet.in_reply_to_id)
+            .execute(&mut tx)
+            .await?
+            .last_insert_id();
+        let sql = format!(
+            r#"INSERT INTO {} (user_tweet_id, media_id, position) VALUES (?, ?, ?);"#,
+            UserTweetMedia::TABLE_NAME
+        );
+        for (position, media_id) in media_ids.iter().enumerate() {
+            sqlx::query(&sql)
+                .bind(tweet_id)
+                .bind(*media_id)
+                .bind(position as u64)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(Some(tweet_id))
+    }
+}
+
+#[axum::async_trait]
+impl ApiTokenRepository for MySqlRepository {
+    async fn insert_api_token(&self, token: &ApiToken) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, name, token_hash, scopes) VALUES (?, ?, ?, ?);"#,
+            ApiToken::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(token.user_id)
+            .bind(&token.name)
+            .bind(&token.token_hash)
+            .bind(ApiTokenScope::join(&token.scopes))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn api_tokens(&self, user_id: u64) -> Result<Vec<ApiTokenItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT id, name, scopes,
+              DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s') as created_at,
+              DATE_FORMAT(last_used_at, '%Y-%m-%d %H:%i:%s') as last_used_at
+              FROM {} WHERE user_id = ? ORDER BY id;
+            "#,
+            ApiToken::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, ApiTokenItemRow>(&sql)
+                    .bind(user_id)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .into_iter()
+            .map(ApiTokenItemRow::into_item)
+            .collect()
+    }
+
+    async fn find_api_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<ApiToken>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE token_hash = ?;"#,
+            ApiToken::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, ApiTokenRow>(&sql)
+                    .bind(token_hash)
+                    .fetch_optional(&self.pool)
+            })
+            .await?
+            .map(ApiTokenRow::into_token)
+            .transpose()
+    }
+
+    async fn touch_api_token(&self, id: u64, used_at: NaiveDateTime) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"UPDATE {} SET last_used_at = ? WHERE id = ?;"#,
+            ApiToken::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(used_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_api_token(&self, user_id: u64, id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE id = ? AND user_id = ?;"#,
+            ApiToken::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[axum::async_trait]
+impl OidcIdentityRepository for MySqlRepository {
+    async fn find_user_by_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT u.* FROM {} u
+              INNER JOIN {} i ON i.user_id = u.id
+              WHERE i.issuer = ? AND i.subject = ?;
+            "#,
+            User::TABLE_NAME,
+            OidcIdentity::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, UserRow>(&sql)
+                    .bind(issuer)
+                    .bind(subject)
+                    .fetch_optional(&self.pool)
+            })
+            .await?
+            .map(UserRow::into_user)
+            .transpose()
+    }
+
+    async fn insert_user_with_oidc_identity(
+        &self,
+        user: &User,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"INSERT INTO {} (name, password_hash) VALUES (?, ?);"#,
+            User::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(&user.name)
+            .bind(&user.password_hash)
+            .execute(&mut tx)
+            .await?;
+        let user_id = result.last_insert_id();
+        let sql = format!(
+            r#"INSERT INTO {} (issuer, subject, user_id) VALUES (?, ?, ?);"#,
+            OidcIdentity::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(issuer)
+            .bind(subject)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(user_id)
+    }
+
+    async fn has_oidc_identity(&self, user_id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT COUNT(*) FROM {} WHERE user_id = ?;"#,
+            OidcIdentity::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| sqlx::query_scalar(&sql).bind(user_id).fetch_one(&self.pool))
+            .await?;
+        Ok(count > 0)
+    }
+}
+
+#[axum::async_trait]
+impl LinkPreviewRepository for MySqlRepository {
+    async fn upsert_link_preview(&self, preview: &LinkPreview) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT INTO {} (url_hash, url, title, description, image_url)
+              VALUES (?, ?, ?, ?, ?)
+              ON DUPLICATE KEY UPDATE title = VALUES(title), description = VALUES(description),
+              image_url = VALUES(image_url), fetched_at = CURRENT_TIMESTAMP;
+            "#,
+            LinkPreview::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(url_hash(&preview.url))
+            .bind(&preview.url)
+            .bind(&preview.title)
+            .bind(&preview.description)
+            .bind(&preview.image_url)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn link_previews(&self, urls: &[String]) -> Result<Vec<LinkPreview>, sqlx::Error> {
+        if urls.is_empty() {
+            return Ok(vec![]);
+        }
+        let sql = format!(
+            r#"SELECT url, title, description, image_url FROM {} WHERE url_hash IN ({});"#,
+            LinkPreview::TABLE_NAME,
+            vec!["?"; urls.len()].join(",")
+        );
+        self.read_retry
+            .run(|| {
+                let mut query = sqlx::query_as::<_, LinkPreview>(&sql);
+                for url in urls {
+                    query = query.bind(url_hash(url));
+                }
+                query.fetch_all(&self.pool)
+            })
+            .await
+    }
+}
+
+#[axum::async_trait]
+impl IdempotencyKeyRepository for MySqlRepository {
+    async fn reserve_idempotency_key(
+        &self,
+        user_id: u64,
+        key: &str,
+        request_hash: &str,
+        now: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> Result<bool, sqlx::Error> {
+        // 有効期限切れのキーは新しいリクエストで置き換える
+        let sql = format!(
+            r#"DELETE FROM {} WHERE user_id = ? AND idempotency_key = ? AND expires_at <= ?;"#,
+            IdempotencyKey::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(user_id)
+            .bind(key)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        let sql = format!(
+            r#"
+              INSERT IGNORE INTO {} (user_id, idempotency_key, request_hash, expires_at)
+              VALUES (?, ?, ?, ?);
+            "#,
+            IdempotencyKey::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(key)
+            .bind(request_hash)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn find_idempotency_key(
+        &self,
+        user_id: u64,
+        key: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT request_hash, status_code, response_headers, response_body FROM {}
+              WHERE user_id = ? AND idempotency_key = ?;
+            "#,
+            IdempotencyKey::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, IdempotencyKeyRow>(&sql)
+                    .bind(user_id)
+                    .bind(key)
+                    .fetch_optional(&self.pool)
+            })
+            .await?
+            .map(IdempotencyKeyRow::into_idempotency_key)
+            .transpose()
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        user_id: u64,
+        key: &str,
+        response: &IdempotentResponse,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET status_code = ?, response_headers = ?, response_body = ?, expires_at = ?
+              WHERE user_id = ? AND idempotency_key = ?;
+            "#,
+            IdempotencyKey::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(response.status_code)
+            .bind(serde_json::to_string(&response.headers).unwrap())
+            .bind(&response.body)
+            .bind(expires_at)
+            .bind(user_id)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_idempotency_key(&self, user_id: u64, key: &str) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE user_id = ? AND idempotency_key = ?;"#,
+            IdempotencyKey::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(user_id)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_expired_idempotency_keys(
+        &self,
+        now: NaiveDateTime,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE expires_at <= ?;"#,
+            IdempotencyKey::TABLE_NAME
+        );
+        let result = sqlx::query(&sql).bind(now).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl FollowRequestRepository for MySqlRepository {
+    async fn insert_follow_request(&self, request: &FollowRequest) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT IGNORE INTO {} (requester_id, target_id) VALUES (?, ?);"#,
+            FollowRequest::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(request.requester_id)
+            .bind(request.target_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn follow_requests(&self, target_id: u64) -> Result<Vec<FollowRequestItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT follow_requests.id, users.name FROM {} AS follow_requests
+              INNER JOIN {} AS users ON users.id = follow_requests.requester_id
+              WHERE follow_requests.target_id = ?
+              ORDER BY follow_requests.id;
+            "#,
+            FollowRequest::TABLE_NAME,
+            User::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, FollowRequestItemRow>(&sql)
+                    .bind(target_id)
+                    .fetch_all(&self.pool)
+            })
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(FollowRequestItemRow::into_item)
+                    .collect()
+            })
+    }
+
+    async fn approve_follow_request(
+        &self,
+        target_id: u64,
+        request_id: u64,
+    ) -> Result<Option<FollowRelation>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"SELECT requester_id FROM {} WHERE id = ? AND target_id = ?;"#,
+            FollowRequest::TABLE_NAME
+        );
+        let requester_id: Option<u64> = sqlx::query_scalar(&sql)
+            .bind(request_id)
+            .bind(target_id)
+            .fetch_optional(&mut tx)
+            .await?;
+        let requester_id = match requester_id {
+            Some(requester_id) => requester_id,
+            None => return Ok(None),
+        };
+        let sql = format!(r#"DELETE FROM {} WHERE id = ?;"#, FollowRequest::TABLE_NAME);
+        sqlx::query(&sql).bind(request_id).execute(&mut tx).await?;
+        // 申請中に別の経路でフォロー済みになっていても申請は処理済みにする
+        let sql = format!(
+            r#"INSERT IGNORE INTO {} (followee_id, follower_id) VALUES (?, ?);"#,
+            FollowRelation::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(target_id)
+            .bind(requester_id)
+            .execute(&mut tx)
+            .await?;
+        if result.rows_affected() > 0 {
+            increment_follower_count(&mut tx, target_id).await?;
+        }
+        tx.commit().await?;
+        Ok(Some(FollowRelation {
+            id: (result.rows_affected() > 0).then(|| result.last_insert_id()),
+            followee_id: target_id,
+            follower_id: requester_id,
+        }))
+    }
+
+    async fn delete_follow_request(
+        &self,
+        target_id: u64,
+        request_id: u64,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE id = ? AND target_id = ?;"#,
+            FollowRequest::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(request_id)
+            .bind(target_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[axum::async_trait]
+impl ListRepository for MySqlRepository {
+    async fn insert_list(&self, list: &UserList) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (owner_id, name) VALUES (?, ?);"#,
+            UserList::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(list.owner_id)
+            .bind(&list.name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn find_list(&self, id: u64) -> Result<Option<UserList>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT id, owner_id, name FROM {} WHERE id = ?;"#,
+            UserList::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, UserList>(&sql)
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+            })
+            .await
+    }
+
+    async fn lists(&self, owner_id: u64) -> Result<Vec<UserListItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT lists.id, lists.name, (
+                SELECT COUNT(*) FROM {} AS list_members WHERE list_members.list_id = lists.id
+              ) AS member_count
+              FROM {} AS lists
+              WHERE lists.owner_id = ?
+              ORDER BY lists.id;
+            "#,
+            ListMember::TABLE_NAME,
+            UserList::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, UserListItemRow>(&sql)
+                    .bind(owner_id)
+                    .fetch_all(self.reader())
+            })
+            .await
+            .map(|rows| rows.into_iter().map(UserListItemRow::into_item).collect())
+    }
+
+    async fn rename_list(&self, id: u64, name: &str) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"UPDATE {} SET name = ? WHERE id = ?;"#,
+            UserList::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(name)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_list(&self, id: u64) -> Result<(), sqlx::Error> {
+        let sql = format!(r#"DELETE FROM {} WHERE id = ?;"#, UserList::TABLE_NAME);
+        sqlx::query(&sql).bind(id).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn insert_list_member(&self, member: &ListMember) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT IGNORE INTO {} (list_id, user_id) VALUES (?, ?);"#,
+            ListMember::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(member.list_id)
+            .bind(member.user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_list_member(&self, list_id: u64, user_id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE list_id = ? AND user_id = ?;"#,
+            ListMember::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(list_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_members(&self, list_id: u64) -> Result<Vec<ListMemberItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT users.id, users.name FROM {} AS list_members
+              INNER JOIN {} AS users ON users.id = list_members.user_id
+              WHERE list_members.list_id = ?
+              ORDER BY list_members.id;
+            "#,
+            ListMember::TABLE_NAME,
+            User::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, ListMemberItemRow>(&sql)
+                    .bind(list_id)
+                    .fetch_all(self.reader())
+            })
+            .await
+            .map(|rows| rows.into_iter().map(ListMemberItemRow::into_item).collect())
+    }
+}
+
+#[axum::async_trait]
+impl BulkInsertRepository for MySqlRepository {
+    async fn bulk_insert_users(&self, names: &[String]) -> Result<Vec<u64>, sqlx::Error> {
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"INSERT INTO {} (name) VALUES {};"#,
+            User::TABLE_NAME,
+            vec!["(?)"; names.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for name in names {
+            query = query.bind(name);
+        }
+        query.execute(&mut tx).await?;
+        // 複数行INSERTで採番されたIDが連番になるとは限らないので、名前で引き直す
+        let sql = format!(
+            r#"SELECT id, name FROM {} WHERE name IN ({});"#,
+            User::TABLE_NAME,
+            vec!["?"; names.len()].join(",")
+        );
+        let mut query = sqlx::query_as(&sql);
+        for name in names {
+            query = query.bind(name);
+        }
+        let rows: Vec<(u64, String)> = query.fetch_all(&mut tx).await?;
+        tx.commit().await?;
+        let ids: std::collections::HashMap<String, u64> =
+            rows.into_iter().map(|(id, name)| (name, id)).collect();
+        Ok(names.iter().map(|name| ids[name]).collect())
+    }
+
+    async fn bulk_insert_follow_relations(
+        &self,
+        follow_relations: &[FollowRelation],
+    ) -> Result<u64, sqlx::Error> {
+        if follow_relations.is_empty() {
+            return Ok(0);
+        }
+        let sql = format!(
+            r#"INSERT IGNORE INTO {} (followee_id, follower_id) VALUES {};"#,
+            FollowRelation::TABLE_NAME,
+            vec!["(?, ?)"; follow_relations.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for follow_relation in follow_relations {
+            query = query
+                .bind(follow_relation.followee_id)
+                .bind(follow_relation.follower_id);
+        }
+        let mut tx = self.pool.begin().await?;
+        let inserted = query.execute(&mut tx).await?.rows_affected();
+        // INSERT IGNOREでどの行が増えたかわからないので、フォロワー数は数え直す
+        let mut followee_ids: Vec<u64> = follow_relations
+            .iter()
+            .map(|follow_relation| follow_relation.followee_id)
+            .collect();
+        followee_ids.sort_unstable();
+        followee_ids.dedup();
+        let sql = format!(
+            r#"
+              REPLACE INTO {} (user_id, follower_count)
+              SELECT followee_id, COUNT(*) FROM {} WHERE followee_id IN ({}) GROUP BY followee_id;
+            "#,
+            FollowerCount::TABLE_NAME,
+            FollowRelation::TABLE_NAME,
+            vec!["?"; followee_ids.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for followee_id in followee_ids {
+            query = query.bind(followee_id);
+        }
+        query.execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn bulk_insert_user_tweets(
+        &self,
+        tweets: &[(UserTweet, NaiveDateTime)],
+    ) -> Result<(), sqlx::Error> {
+        if tweets.is_empty() {
+            return Ok(());
+        }
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, content, in_reply_to_id, created_at) VALUES {};"#,
+            UserTweet::TABLE_NAME,
+            vec!["(?, ?, ?, ?)"; tweets.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for (tweet, created_at) in tweets {
+            query = query
+                .bind(tweet.user_id)
+                .bind(&tweet.content)
+                .bind(tweet.in_reply_to_id)
+                .bind(created_at);
+        }
+        query.execute(&self.pool).await?;
+        Ok(())
+    }
+}
+
+#[axum::async_trait]
+impl TrendRepository for MySqlRepository {
+    async fn hashtag_cursor(&self) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT last_tweet_id FROM {} WHERE id = 1;"#,
+            HashtagCursor::TABLE_NAME
+        );
+        let (last_tweet_id,): (u64,) = self
+            .read_retry
+            .run(|| sqlx::query_as(&sql).fetch_one(&self.pool))
+            .await?;
+        Ok(last_tweet_id)
+    }
+
+    async fn trend_tweets(
+        &self,
+        after_id: u64,
+        limit: u64,
+    ) -> Result<Vec<TrendTweet>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT {tweets}.id, {tweets}.content,
+              CAST(UNIX_TIMESTAMP({tweets}.created_at) AS SIGNED) as posted_at
+              FROM {tweets} INNER JOIN {users} ON {users}.id = {tweets}.user_id
+              WHERE {tweets}.id > ? AND {tweets}.is_hidden = FALSE
+              AND {users}.is_protected = FALSE AND {users}.is_suspended = FALSE
+              ORDER BY {tweets}.id LIMIT ?;
+            "#,
+            tweets = UserTweet::TABLE_NAME,
+            users = User::TABLE_NAME,
+        );
+        let rows: Vec<(u64, Option<String>, i64)> = self
+            .read_retry
+            .run(|| {
+                sqlx::query_as(&sql)
+                    .bind(after_id)
+                    .bind(limit)
+                    .fetch_all(&self.pool)
+            })
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, content, posted_at)| TrendTweet {
+                id,
+                content: content.unwrap_or_default(),
+                posted_at,
+            })
+            .collect())
+    }
+
+    async fn add_hashtag_counts(
+        &self,
+        from_id: u64,
+        to_id: u64,
+        buckets: &[HashtagBucket],
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"UPDATE {} SET last_tweet_id = ? WHERE id = 1 AND last_tweet_id = ?;"#,
+            HashtagCursor::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(to_id)
+            .bind(from_id)
+            .execute(&mut tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        if !buckets.is_empty() {
+            let sql = format!(
+                r#"
+                  INSERT INTO {} (tag, bucket_start, count) VALUES {}
+                  ON DUPLICATE KEY UPDATE count = count + VALUES(count);
+                "#,
+                HashtagBucket::TABLE_NAME,
+                vec!["(?, ?, ?)"; buckets.len()].join(", ")
+            );
+            let mut query = sqlx::query(&sql);
+            for bucket in buckets {
+                query = query
+                    .bind(&bucket.tag)
+                    .bind(bucket.bucket_start)
+                    .bind(bucket.count);
+            }
+            query.execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn hashtag_counts(
+        &self,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(String, u64)>, sqlx::Error> {
+        // SUMはDECIMALを返すので整数に変換する
+        let sql = format!(
+            r#"
+              SELECT tag, CAST(SUM(count) AS UNSIGNED) as count FROM {}
+              WHERE bucket_start >= ? AND bucket_start < ?
+              GROUP BY tag;
+            "#,
+            HashtagBucket::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as(&sql)
+                    .bind(since)
+                    .bind(until)
+                    .fetch_all(self.reader())
+            })
+            .await
+    }
+
+    async fn delete_hashtag_buckets(&self, before: i64) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE bucket_start < ?;"#,
+            HashtagBucket::TABLE_NAME
+        );
+        let result = sqlx::query(&sql).bind(before).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl WebhookRepository for MySqlRepository {
+    async fn insert_webhook(&self, webhook: &Webhook) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (url, secret, events, created_by) VALUES (?, ?, ?, ?);"#,
+            Webhook::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(&webhook.url)
+            .bind(&webhook.secret)
+            .bind(WebhookEvent::join(&webhook.events))
+            .bind(webhook.created_by)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn webhooks(&self) -> Result<Vec<WebhookItem>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT id, url, events, DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s') as created_at FROM {} ORDER BY id;"#,
+            Webhook::TABLE_NAME
+        );
+        let rows: Vec<(u64, String, String, String)> = self
+            .read_retry
+            .run(|| sqlx::query_as(&sql).fetch_all(&self.pool))
+            .await?;
+        rows.into_iter()
+            .map(|(id, url, events, created_at)| {
+                Ok(WebhookItem {
+                    id,
+                    url,
+                    events: parse_webhook_events(&events)?,
+                    created_at,
+                })
+            })
+            .collect()
+    }
+
+    async fn find_webhook(&self, id: u64) -> Result<Option<Webhook>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE id = ?;"#, Webhook::TABLE_NAME);
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, WebhookRow>(&sql)
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+            })
+            .await?
+            .map(WebhookRow::into_webhook)
+            .transpose()
+    }
+
+    async fn subscribed_webhooks(&self, event: WebhookEvent) -> Result<Vec<Webhook>, sqlx::Error> {
+        // Webhookの数は少ないので、すべて読み出してから絞り込む
+        let sql = format!(r#"SELECT * FROM {} ORDER BY id;"#, Webhook::TABLE_NAME);
+        let webhooks = self
+            .read_retry
+            .run(|| sqlx::query_as::<_, WebhookRow>(&sql).fetch_all(&self.pool))
+            .await?
+            .into_iter()
+            .map(WebhookRow::into_webhook)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(webhooks
+            .into_iter()
+            .filter(|webhook| webhook.events.contains(&event))
+            .collect())
+    }
+
+    async fn delete_webhook(&self, id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(r#"DELETE FROM {} WHERE id = ?;"#, Webhook::TABLE_NAME);
+        let result = sqlx::query(&sql).bind(id).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn insert_webhook_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (webhook_id, event, payload) VALUES (?, ?, ?);"#,
+            WebhookDelivery::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(delivery.webhook_id)
+            .bind(delivery.event.as_str())
+            .bind(&delivery.payload)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_id())
+    }
+
+    async fn find_pending_webhook_delivery(
+        &self,
+        id: u64,
+    ) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT id, webhook_id, event, payload FROM {} WHERE id = ? AND status = 'pending';"#,
+            WebhookDelivery::TABLE_NAME
+        );
+        let row: Option<(u64, u64, String, String)> = self
+            .read_retry
+            .run(|| sqlx::query_as(&sql).bind(id).fetch_optional(&self.pool))
+            .await?;
+        row.map(|(id, webhook_id, event, payload)| {
+            Ok(WebhookDelivery {
+                id: Some(id),
+                webhook_id,
+                event: parse_webhook_event(&event)?,
+                payload,
+            })
+        })
+        .transpose()
+    }
+
+    async fn record_webhook_attempt(
+        &self,
+        id: u64,
+        response_status: Option<u16>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let set_status = match error {
+            None => "status = 'delivered', delivered_at = CURRENT_TIMESTAMP,",
+            Some(_) => "",
+        };
+        let sql = format!(
+            r#"
+              UPDATE {} SET {} attempts = attempts + 1, response_status = ?, last_error = ?
+              WHERE id = ?;
+            "#,
+            WebhookDelivery::TABLE_NAME,
+            set_status
+        );
+        sqlx::query(&sql)
+            .bind(response_status)
+            .bind(error)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn dead_letter_webhook_delivery(&self, id: u64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"UPDATE {} SET status = 'dead_lettered' WHERE id = ? AND status = 'pending';"#,
+            WebhookDelivery::TABLE_NAME
+        );
+        let result = sqlx::query(&sql).bind(id).execute(&mut tx).await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        let sql = format!(
+            r#"
+              INSERT INTO {} (delivery_id, webhook_id, url, event, payload, last_error)
+              SELECT deliveries.id, deliveries.webhook_id, webhooks.url, deliveries.event,
+              deliveries.payload, deliveries.last_error
+              FROM {} AS deliveries
+              INNER JOIN {} AS webhooks ON webhooks.id = deliveries.webhook_id
+              WHERE deliveries.id = ?;
+            "#,
+            WebhookDeadLetter::TABLE_NAME,
+            WebhookDelivery::TABLE_NAME,
+            Webhook::TABLE_NAME
+        );
+        sqlx::query(&sql).bind(id).execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn webhook_deliveries(
+        &self,
+        webhook_id: u64,
+        limit: u64,
+    ) -> Result<Vec<WebhookDeliveryItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT id, event, status, attempts, response_status, last_error,
+              DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s') as created_at,
+              DATE_FORMAT(delivered_at, '%Y-%m-%d %H:%i:%s') as delivered_at
+              FROM {} WHERE webhook_id = ? ORDER BY id DESC LIMIT ?;
+            "#,
+            WebhookDelivery::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, WebhookDeliveryItemRow>(&sql)
+                    .bind(webhook_id)
+                    .bind(limit)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .into_iter()
+            .map(WebhookDeliveryItemRow::into_item)
+            .collect()
+    }
+
+    async fn webhook_dead_letters(
+        &self,
+        limit: u64,
+    ) -> Result<Vec<WebhookDeadLetter>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT id, delivery_id, webhook_id, url, event, payload, last_error,
+              DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s') as created_at
+              FROM {} ORDER BY id DESC LIMIT ?;
+            "#,
+            WebhookDeadLetter::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query_as::<_, WebhookDeadLetterRow>(&sql)
+                    .bind(limit)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .into_iter()
+            .map(WebhookDeadLetterRow::into_dead_letter)
+            .collect()
+    }
+}
+
+#[axum::async_trait]
+impl Repository for MySqlRepository {
+    // structに対するループはマクロなどを使うことを実現できるが省略
+    fn primary_only(&self) -> Option<SharedRepository> {
+        self.replica.as_ref()?;
+        Some(Arc::new(Self {
+            replica: None,
+            ..self.clone()
+        }))
+    }
+
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1;").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn setup_tables(&self) -> Result<(), sqlx::Error> {
+        self.add_missing_columns().await?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/users_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/user_tweets_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/follow_relations_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/follow_requests_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/follower_counts_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!(
+                    "../../sql/ddl/home_timeline_entries_create.sql"
+                ))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/media_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/user_tweet_media_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/likes_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/notifications_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/conversations_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/direct_messages_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/reports_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/moderation_logs_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/blocks_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/jobs_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/api_tokens_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/oidc_identities_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/link_previews_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/idempotency_keys_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/lists_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/list_members_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/hashtag_buckets_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/hashtag_cursor_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/webhooks_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!("../../sql/ddl/webhook_deliveries_create.sql"))
+                .await,
+        )?;
+        ignore_duplicate_key(
+            self.pool
+                .execute(include_str!(
+                    "../../sql/ddl/webhook_dead_letters_create.sql"
+                ))
+                .await,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,2944 @@
+// SQLiteによるリポジトリ実装
+// MySQLコンテナを用意できないローカル環境やCIでサーバとテストを動かすために使う
+use super::{
+    claim_token, parse_response_headers, parse_webhook_delivery_status, parse_webhook_event,
+    parse_webhook_events, url_hash, AccountRepository, ApiTokenRepository, BlockRepository,
+    BulkInsertRepository, DirectMessageRepository, FollowRelationRepository,
+    FollowRequestRepository, HomeTimelineRepository, IdempotencyKeyRepository, JobRepository,
+    LikeRepository, LinkPreviewRepository, ListRepository, MediaRepository, ModerationRepository,
+    NotificationRepository, OidcIdentityRepository, ReadRetry, RecommendationRepository,
+    Repository, SharedRepository, TimelineRepository, TrendRepository, UserRepository,
+    UserTweetRepository, WebhookRepository, RECOMMENDATION_MUTUAL_WEIGHT,
+    RECOMMENDATION_RECENT_DAYS, RECOMMENDATION_RECENT_TWEET_CAP,
+};
+use crate::config::DatabaseConfig;
+use crate::models::{
+    timeline_item, ApiToken, ApiTokenItem, ApiTokenScope, Block, Conversation, ConversationItem,
+    DirectMessage, DirectMessageItem, ExportRecord, ExportSection, FollowRelation, FollowRequest,
+    FollowRequestItem, FollowerCount, HashtagBucket, HashtagCursor, HomeTimelineEntry,
+    IdempotencyKey, IdempotentResponse, Job, JobKind, Like, LinkPreview, ListMember,
+    ListMemberItem, Media, ModerationAction, ModerationLog, ModerationLogItem, Notification,
+    NotificationEvent, NotificationKind, OidcIdentity, RecommendedUser, Report, ReportItem, Role,
+    TimelineItem, TrendTweet, User, UserList, UserListItem, UserTweet, UserTweetMedia, Webhook,
+    WebhookDeadLetter, WebhookDelivery, WebhookDeliveryItem, WebhookEvent, WebhookItem,
+};
+use chrono::NaiveDateTime;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow},
+    Executor as _, Pool, Row as _, Sqlite,
+};
+use std::str::FromStr as _;
+use std::sync::Arc;
+
+// タイムラインの共通SELECT句、添付メディアIDは並び順にカンマ区切りで受け取る
+// SQLiteのgroup_concatはORDER BYを取れないので並べ替えたサブクエリを集約する
+const TIMELINE_SELECT: &str = r#"
+  SELECT user_tweets.id as id, users.name as name, user_tweets.content as content,
+  (
+    SELECT group_concat(media_id) FROM (
+      SELECT media_id FROM user_tweet_media
+      WHERE user_tweet_media.user_tweet_id = user_tweets.id
+      ORDER BY position
+    )
+  ) as media_ids
+  FROM user_tweets
+  INNER JOIN users
+  ON user_tweets.user_id = users.id
+"#;
+
+#[derive(Clone)]
+pub struct SqliteRepository {
+    pool: Pool<Sqlite>,
+    // 読み取り専用のレプリカ、タイムラインなどの参照系の一部のクエリだけをこちらに送る
+    replica: Option<Pool<Sqlite>>,
+    // 接続の失敗で参照系のクエリが失敗した場合の再試行
+    read_retry: ReadRetry,
+}
+
+impl SqliteRepository {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self {
+            pool,
+            replica: None,
+            read_retry: ReadRetry::default(),
+        }
+    }
+
+    pub fn with_read_retry(self, read_retry: ReadRetry) -> Self {
+        Self { read_retry, ..self }
+    }
+
+    pub fn with_replica(self, replica: Pool<Sqlite>) -> Self {
+        Self {
+            replica: Some(replica),
+            ..self
+        }
+    }
+
+    // インメモリDBかどうか
+    pub fn is_memory(url: &str) -> bool {
+        url.contains(":memory:") || url.contains("mode=memory")
+    }
+
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        Self::connect_with(url, &DatabaseConfig::default()).await
+    }
+
+    pub async fn connect_with(url: &str, config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?
+            .create_if_missing(true)
+            .foreign_keys(true);
+        let pool = if Self::is_memory(url) {
+            // インメモリDBはコネクションを閉じると消えるので、単一コネクションを保持し続ける
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect_with(options)
+                .await?
+        } else {
+            Self::create_pool(options, config).await?
+        };
+        Ok(Self::new(pool).with_read_retry(config.read_retry))
+    }
+
+    // レプリカのファイルは別プロセスが同期する前提で、読み取り専用で開く
+    pub async fn connect_replica(
+        replica_url: &str,
+        config: &DatabaseConfig,
+    ) -> Result<Pool<Sqlite>, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(replica_url)?.read_only(true);
+        Self::create_pool(options, config).await
+    }
+
+    // 列を使うインデックスを作る前に呼ぶ、テーブルがなければCREATE TABLEで全ての列が作られるので何もしない
+    async fn add_missing_columns(&self) -> Result<(), sqlx::Error> {
+        for (table, column, definition, after) in ADDED_COLUMNS {
+            let (columns, found): (i64, Option<i64>) =
+                sqlx::query_as(r#"SELECT COUNT(*), SUM(name = ?) FROM pragma_table_info(?);"#)
+                    .bind(column)
+                    .bind(table)
+                    .fetch_one(&self.pool)
+                    .await?;
+            if columns == 0 || found.unwrap_or(0) > 0 {
+                continue;
+            }
+            let sql = format!("ALTER TABLE {} ADD COLUMN {};", table, definition);
+            self.pool.execute(sql.as_str()).await?;
+            if let Some(after) = after {
+                self.pool.execute(*after).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn create_pool(
+        options: SqliteConnectOptions,
+        config: &DatabaseConfig,
+    ) -> Result<Pool<Sqlite>, sqlx::Error> {
+        if config.lazy_connect {
+            Ok(config.pool_options().connect_lazy_with(options))
+        } else {
+            config.pool_options().connect_with(options).await
+        }
+    }
+
+    // レプリカに送ってよい参照系のクエリの接続先、レプリカがなければプライマリ
+    // 書き込み直後に読み直すクエリには使わない
+    fn reader(&self) -> &Pool<Sqlite> {
+        self.replica.as_ref().unwrap_or(&self.pool)
+    }
+
+    pub fn pool(&self) -> &Pool<Sqlite> {
+        &self.pool
+    }
+}
+
+// SQLiteの整数はi64なのでIDなどのu64に変換する
+pub(crate) fn get_u64(row: &SqliteRow, column: &str) -> Result<u64, sqlx::Error> {
+    Ok(row.try_get::<i64, _>(column)? as u64)
+}
+
+fn user_from_row(row: &SqliteRow) -> Result<User, sqlx::Error> {
+    Ok(User {
+        id: Some(get_u64(row, "id")?),
+        name: row.try_get("name")?,
+        password_hash: row.try_get("password_hash")?,
+        role: role_from_row(row)?,
+        is_suspended: row.try_get("is_suspended")?,
+        is_protected: row.try_get("is_protected")?,
+    })
+}
+
+fn role_from_row(row: &SqliteRow) -> Result<Role, sqlx::Error> {
+    let role: String = row.try_get("role")?;
+    Role::parse(&role).ok_or_else(|| sqlx::Error::Decode(format!("unknown role: {}", role).into()))
+}
+
+fn parse_scopes(value: &str) -> Result<Vec<ApiTokenScope>, sqlx::Error> {
+    ApiTokenScope::split(value)
+        .ok_or_else(|| sqlx::Error::Decode(format!("unknown scopes: {}", value).into()))
+}
+
+// ベースラインのスキーマで作ったテーブルに後から追加した列(テーブル名、列名、ADD COLUMNに続く定義、追加後に実行するSQL)
+// CREATE TABLE IF NOT EXISTSは既存のテーブルを変更しないので、setup_tablesで足りない列だけを追加する
+const ADDED_COLUMNS: &[(&str, &str, &str, Option<&str>)] = &[
+    (
+        "users",
+        "password_hash",
+        "password_hash VARCHAR(255) NULL",
+        None,
+    ),
+    (
+        "users",
+        "role",
+        "role VARCHAR(16) NOT NULL DEFAULT 'user'",
+        None,
+    ),
+    (
+        "users",
+        "is_suspended",
+        "is_suspended BOOLEAN NOT NULL DEFAULT FALSE",
+        None,
+    ),
+    (
+        "users",
+        "is_protected",
+        "is_protected BOOLEAN NOT NULL DEFAULT FALSE",
+        None,
+    ),
+    (
+        "user_tweets",
+        "in_reply_to_id",
+        "in_reply_to_id INTEGER NULL REFERENCES user_tweets(id) ON DELETE SET NULL",
+        None,
+    ),
+    (
+        "user_tweets",
+        "is_hidden",
+        "is_hidden BOOLEAN NOT NULL DEFAULT FALSE",
+        None,
+    ),
+    // SQLiteはCURRENT_TIMESTAMPを既定値にした列を追加できないので、NULLで追加してトリガーで埋める
+    // 既存のツイートの投稿日時は追加した時刻になる
+    (
+        "user_tweets",
+        "created_at",
+        "created_at TIMESTAMP NULL",
+        Some(
+            r#"
+              UPDATE user_tweets SET created_at = CURRENT_TIMESTAMP;
+              CREATE TRIGGER IF NOT EXISTS user_tweets__created_at
+              AFTER INSERT ON user_tweets WHEN NEW.created_at IS NULL
+              BEGIN
+                UPDATE user_tweets SET created_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+              END;
+            "#,
+        ),
+    ),
+];
+
+fn webhook_from_row(row: &SqliteRow) -> Result<Webhook, sqlx::Error> {
+    let events: String = row.try_get("events")?;
+    Ok(Webhook {
+        id: Some(get_u64(row, "id")?),
+        url: row.try_get("url")?,
+        secret: row.try_get("secret")?,
+        events: parse_webhook_events(&events)?,
+        created_by: row
+            .try_get::<Option<i64>, _>("created_by")?
+            .map(|id| id as u64),
+    })
+}
+
+fn job_from_row(row: &SqliteRow) -> Result<Job, sqlx::Error> {
+    let kind: String = row.try_get("kind")?;
+    Ok(Job {
+        id: Some(get_u64(row, "id")?),
+        kind: JobKind::parse(&kind)
+            .ok_or_else(|| sqlx::Error::Decode(format!("unknown kind: {}", kind).into()))?,
+        payload: row.try_get("payload")?,
+        unique_key: row.try_get("unique_key")?,
+        run_at: row.try_get("run_at")?,
+        attempts: row.try_get::<i64, _>("attempts")? as u32,
+        locked_by: row.try_get("locked_by")?,
+    })
+}
+
+// フォローされたユーザのフォロワー数を増やす、フォロー関係の書き込みと同じトランザクションで呼ぶ
+async fn increment_follower_count(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    followee_id: u64,
+) -> Result<(), sqlx::Error> {
+    let sql = format!(
+        r#"
+          INSERT INTO {} (user_id, follower_count) VALUES (?, 1)
+          ON CONFLICT (user_id) DO UPDATE SET follower_count = follower_count + 1;
+        "#,
+        FollowerCount::TABLE_NAME
+    );
+    sqlx::query(&sql)
+        .bind(followee_id as i64)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+fn user_tweet_from_row(row: &SqliteRow) -> Result<UserTweet, sqlx::Error> {
+    Ok(UserTweet {
+        id: Some(get_u64(row, "id")?),
+        user_id: get_u64(row, "user_id")?,
+        content: row.try_get("content")?,
+        in_reply_to_id: row
+            .try_get::<Option<i64>, _>("in_reply_to_id")?
+            .map(|id| id as u64),
+    })
+}
+
+fn follow_relation_from_row(row: &SqliteRow) -> Result<FollowRelation, sqlx::Error> {
+    Ok(FollowRelation {
+        id: Some(get_u64(row, "id")?),
+        followee_id: get_u64(row, "followee_id")?,
+        follower_id: get_u64(row, "follower_id")?,
+    })
+}
+
+fn media_from_row(row: &SqliteRow) -> Result<Media, sqlx::Error> {
+    Ok(Media {
+        id: Some(get_u64(row, "id")?),
+        user_id: get_u64(row, "user_id")?,
+        content_type: row.try_get("content_type")?,
+        byte_size: get_u64(row, "byte_size")?,
+        blob_key: row.try_get("blob_key")?,
+        thumbnail_key: row.try_get("thumbnail_key")?,
+    })
+}
+
+fn notification_event_from_row(row: &SqliteRow) -> Result<NotificationEvent, sqlx::Error> {
+    let kind: String = row.try_get("kind")?;
+    Ok(NotificationEvent {
+        id: get_u64(row, "id")?,
+        kind: NotificationKind::parse(&kind)
+            .ok_or_else(|| sqlx::Error::Decode(format!("unknown kind: {}", kind).into()))?,
+        actor_name: row.try_get("actor_name")?,
+        target_id: get_u64(row, "target_id")?,
+        is_read: row.try_get("is_read")?,
+    })
+}
+
+fn conversation_from_row(row: &SqliteRow) -> Result<Conversation, sqlx::Error> {
+    Ok(Conversation {
+        id: Some(get_u64(row, "id")?),
+        user1_id: get_u64(row, "user1_id")?,
+        user2_id: get_u64(row, "user2_id")?,
+    })
+}
+
+fn conversation_item_from_row(row: &SqliteRow) -> Result<ConversationItem, sqlx::Error> {
+    Ok(ConversationItem {
+        id: get_u64(row, "id")?,
+        partner_name: row.try_get("partner_name")?,
+        last_message: row.try_get("last_message")?,
+        unread_count: get_u64(row, "unread_count")?,
+    })
+}
+
+fn direct_message_item_from_row(row: &SqliteRow) -> Result<DirectMessageItem, sqlx::Error> {
+    Ok(DirectMessageItem {
+        id: get_u64(row, "id")?,
+        sender_name: row.try_get("sender_name")?,
+        content: row.try_get("content")?,
+        is_read: row.try_get("is_read")?,
+    })
+}
+
+fn timeline_item_from_row(row: &SqliteRow) -> Result<TimelineItem, sqlx::Error> {
+    Ok(timeline_item(
+        get_u64(row, "id")?,
+        row.try_get("name")?,
+        row.try_get("content")?,
+        row.try_get::<Option<String>, _>("media_ids")?.as_deref(),
+    ))
+}
+
+#[axum::async_trait]
+impl UserRepository for SqliteRepository {
+    async fn find_by_name(&self, name: &str) -> Result<Option<User>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE name = ?;"#, User::TABLE_NAME);
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(name).fetch_optional(self.reader()))
+            .await?
+            .map(|row| user_from_row(&row))
+            .transpose()
+    }
+
+    async fn find_by_id(&self, id: u64) -> Result<Option<User>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE id = ?;"#, User::TABLE_NAME);
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(id as i64).fetch_optional(&self.pool))
+            .await?
+            .map(|row| user_from_row(&row))
+            .transpose()
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (name, password_hash) VALUES (?, ?);"#,
+            User::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(&user.name)
+            .bind(&user.password_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn set_user_protected(&self, user_id: u64, protected: bool) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"UPDATE {} SET is_protected = ? WHERE id = ?;"#,
+            User::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(protected)
+            .bind(user_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[axum::async_trait]
+impl UserTweetRepository for SqliteRepository {
+    async fn insert_user_tweet(&self, tweet: &UserTweet) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, content, in_reply_to_id) VALUES (?, ?, ?);"#,
+            UserTweet::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(tweet.user_id as i64)
+            .bind(&tweet.content)
+            .bind(tweet.in_reply_to_id.map(|id| id as i64))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn find_user_tweet(&self, id: u64) -> Result<Option<UserTweet>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE id = ?;"#, UserTweet::TABLE_NAME);
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(id as i64).fetch_optional(&self.pool))
+            .await?
+            .map(|row| user_tweet_from_row(&row))
+            .transpose()
+    }
+
+    async fn is_user_tweet_hidden(&self, id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT COUNT(*) FROM {} WHERE id = ? AND is_hidden = TRUE;"#,
+            UserTweet::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(id as i64)
+                    .fetch_one(&self.pool)
+            })
+            .await?;
+        Ok(count > 0)
+    }
+}
+
+#[axum::async_trait]
+impl FollowRelationRepository for SqliteRepository {
+    async fn insert_follow_relation(
+        &self,
+        follow_relation: &FollowRelation,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"INSERT INTO {} (followee_id, follower_id) VALUES (?, ?);"#,
+            FollowRelation::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(follow_relation.followee_id as i64)
+            .bind(follow_relation.follower_id as i64)
+            .execute(&mut tx)
+            .await?;
+        increment_follower_count(&mut tx, follow_relation.followee_id).await?;
+        tx.commit().await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn find_by_follower_id(
+        &self,
+        follower_id: u64,
+    ) -> Result<Vec<FollowRelation>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE follower_id = ?;"#,
+            FollowRelation::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(follower_id as i64)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .iter()
+            .map(follow_relation_from_row)
+            .collect()
+    }
+
+    async fn is_following(&self, follower_id: u64, followee_id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT COUNT(*) FROM {} WHERE follower_id = ? AND followee_id = ?;"#,
+            FollowRelation::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(follower_id as i64)
+                    .bind(followee_id as i64)
+                    .fetch_one(&self.pool)
+            })
+            .await?;
+        Ok(count > 0)
+    }
+
+    async fn are_mutual_followers(
+        &self,
+        user_id: u64,
+        other_user_id: u64,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT COUNT(*) FROM {}
+              WHERE (follower_id = ? AND followee_id = ?)
+              OR (follower_id = ? AND followee_id = ?);
+            "#,
+            FollowRelation::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(user_id as i64)
+                    .bind(other_user_id as i64)
+                    .bind(other_user_id as i64)
+                    .bind(user_id as i64)
+                    .fetch_one(&self.pool)
+            })
+            .await?;
+        Ok(count == 2)
+    }
+}
+
+#[axum::async_trait]
+impl TimelineRepository for SqliteRepository {
+    async fn timeline(&self, follower_id: u64) -> Result<Vec<TimelineItem>, sqlx::Error> {
+        // フォロイーのIDをアプリ側で列挙してIN句に展開すると、フォロイー数に比例して
+        // プレースホルダとクエリ文字列が膨らむので、follow_relationsへのサブクエリで絞り込む
+        // タイムラインには自分自身の投稿も含め、モデレーターが非表示にした投稿は除く
+        let sql = format!(
+            r#"
+              {select}
+              WHERE user_tweets.is_hidden = FALSE AND (
+                user_tweets.user_id = ?
+                OR user_tweets.user_id IN (
+                  SELECT followee_id FROM {} WHERE follower_id = ?
+                )
+              )
+              ORDER BY user_tweets.id DESC;
+            "#,
+            FollowRelation::TABLE_NAME,
+            select = TIMELINE_SELECT,
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(follower_id as i64)
+                    .bind(follower_id as i64)
+                    .fetch_all(self.reader())
+            })
+            .await?
+            .iter()
+            .map(timeline_item_from_row)
+            .collect()
+    }
+
+    async fn list_timeline(
+        &self,
+        list_id: u64,
+        viewer_id: u64,
+    ) -> Result<Vec<TimelineItem>, sqlx::Error> {
+        // timelineのフォロイーの代わりにリストのメンバーで絞り込む
+        let sql = format!(
+            r#"
+              {select}
+              WHERE user_tweets.is_hidden = FALSE
+              AND user_tweets.user_id IN (
+                SELECT user_id FROM {} WHERE list_id = ?
+              )
+              AND (
+                users.is_protected = FALSE
+                OR users.id = ?
+                OR users.id IN (SELECT followee_id FROM {} WHERE follower_id = ?)
+              )
+              ORDER BY user_tweets.id DESC;
+            "#,
+            ListMember::TABLE_NAME,
+            FollowRelation::TABLE_NAME,
+            select = TIMELINE_SELECT,
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(list_id as i64)
+                    .bind(viewer_id as i64)
+                    .bind(viewer_id as i64)
+                    .fetch_all(self.reader())
+            })
+            .await?
+            .iter()
+            .map(timeline_item_from_row)
+            .collect()
+    }
+}
+
+#[axum::async_trait]
+impl HomeTimelineRepository for SqliteRepository {
+    async fn count_followers(&self, followee_id: u64) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT follower_count FROM {} WHERE user_id = ?;"#,
+            FollowerCount::TABLE_NAME
+        );
+        let count: Option<i64> = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(followee_id as i64)
+                    .fetch_optional(&self.pool)
+            })
+            .await?;
+        Ok(count.unwrap_or(0) as u64)
+    }
+
+    async fn fan_out_user_tweet(&self, tweet_id: u64, author_id: u64) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT OR IGNORE INTO {} (user_id, tweet_id)
+              SELECT follower_id, ? FROM {} WHERE followee_id = ?;
+            "#,
+            HomeTimelineEntry::TABLE_NAME,
+            FollowRelation::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(tweet_id as i64)
+            .bind(author_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn fan_out_followee_tweets(
+        &self,
+        follower_id: u64,
+        followee_id: u64,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT OR IGNORE INTO {} (user_id, tweet_id)
+              SELECT ?, id FROM {} WHERE user_id = ?;
+            "#,
+            HomeTimelineEntry::TABLE_NAME,
+            UserTweet::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(follower_id as i64)
+            .bind(followee_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn home_timeline(
+        &self,
+        user_id: u64,
+        fan_out_threshold: Option<u64>,
+    ) -> Result<Vec<TimelineItem>, sqlx::Error> {
+        // 閾値を超える投稿者のツイートは配られていないので、読み出し時にフォロー関係から結合する
+        let fan_out_on_read = match fan_out_threshold {
+            Some(_) => {
+                r#"
+                  OR user_tweets.user_id IN (
+                    SELECT follow_relations.followee_id FROM follow_relations
+                    INNER JOIN follower_counts
+                    ON follower_counts.user_id = follow_relations.followee_id
+                    WHERE follow_relations.follower_id = ?
+                    AND follower_counts.follower_count > ?
+                  )
+                "#
+            }
+            None => "",
+        };
+        let sql = format!(
+            r#"
+              {select}
+              WHERE user_tweets.is_hidden = FALSE AND (
+                user_tweets.user_id = ?
+                OR user_tweets.id IN (SELECT tweet_id FROM home_timeline_entries WHERE user_id = ?)
+                {}
+              )
+              ORDER BY user_tweets.id DESC;
+            "#,
+            fan_out_on_read,
+            select = TIMELINE_SELECT,
+        );
+        self.read_retry
+            .run(|| {
+                let mut query = sqlx::query(&sql).bind(user_id as i64).bind(user_id as i64);
+                if let Some(threshold) = fan_out_threshold {
+                    query = query
+                        .bind(user_id as i64)
+                        .bind(threshold.min(i64::MAX as u64) as i64);
+                }
+                query.fetch_all(self.reader())
+            })
+            .await?
+            .iter()
+            .map(timeline_item_from_row)
+            .collect()
+    }
+
+    async fn backfill_home_timelines(
+        &self,
+        fan_out_threshold: Option<u64>,
+    ) -> Result<u64, sqlx::Error> {
+        let threshold_condition = match fan_out_threshold {
+            Some(_) => {
+                r#"
+                  WHERE COALESCE((
+                    SELECT follower_count FROM follower_counts
+                    WHERE follower_counts.user_id = user_tweets.user_id
+                  ), 0) <= ?
+                "#
+            }
+            None => "",
+        };
+        let sql = format!(
+            r#"
+              INSERT OR IGNORE INTO home_timeline_entries (user_id, tweet_id)
+              SELECT follow_relations.follower_id, user_tweets.id
+              FROM user_tweets
+              INNER JOIN follow_relations
+              ON follow_relations.followee_id = user_tweets.user_id
+              {};
+            "#,
+            threshold_condition
+        );
+        let mut query = sqlx::query(&sql);
+        if let Some(threshold) = fan_out_threshold {
+            query = query.bind(threshold.min(i64::MAX as u64) as i64);
+        }
+        Ok(query.execute(&self.pool).await?.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl MediaRepository for SqliteRepository {
+    async fn insert_media(&self, media: &Media) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT INTO {} (user_id, content_type, byte_size, blob_key, thumbnail_key)
+              VALUES (?, ?, ?, ?, ?);
+            "#,
+            Media::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(media.user_id as i64)
+            .bind(&media.content_type)
+            .bind(media.byte_size as i64)
+            .bind(&media.blob_key)
+            .bind(&media.thumbnail_key)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn find_media(&self, id: u64) -> Result<Option<Media>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE id = ?;"#, Media::TABLE_NAME);
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(id as i64).fetch_optional(&self.pool))
+            .await?
+            .map(|row| media_from_row(&row))
+            .transpose()
+    }
+
+    async fn find_media_by_user_id(&self, user_id: u64) -> Result<Vec<Media>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE user_id = ?;"#, Media::TABLE_NAME);
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(user_id as i64).fetch_all(&self.pool))
+            .await?
+            .iter()
+            .map(media_from_row)
+            .collect()
+    }
+
+    async fn find_unattached_media(
+        &self,
+        user_id: u64,
+        media_id: u64,
+    ) -> Result<Option<Media>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT * FROM {} WHERE id = ? AND user_id = ?
+              AND NOT EXISTS (SELECT 1 FROM {} WHERE media_id = media.id);
+            "#,
+            Media::TABLE_NAME,
+            UserTweetMedia::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(media_id as i64)
+                    .bind(user_id as i64)
+                    .fetch_optional(&self.pool)
+            })
+            .await?
+            .map(|row| media_from_row(&row))
+            .transpose()
+    }
+
+    async fn find_media_user_tweet_id(&self, media_id: u64) -> Result<Option<u64>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT {media}.user_tweet_id FROM {media}
+              INNER JOIN {tweets} ON {tweets}.id = {media}.user_tweet_id
+              WHERE {media}.media_id = ? AND {tweets}.is_hidden = FALSE;
+            "#,
+            media = UserTweetMedia::TABLE_NAME,
+            tweets = UserTweet::TABLE_NAME,
+        );
+        let id: Option<i64> = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(media_id as i64)
+                    .fetch_optional(&self.pool)
+            })
+            .await?;
+        Ok(id.map(|id| id as u64))
+    }
+
+    async fn insert_user_tweet_with_media(
+        &self,
+        tweet: &UserTweet,
+        media_ids: &[u64],
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, content, in_reply_to_id) VALUES (?, ?, ?);"#,
+            UserTweet::TABLE_NAME
+        );
+        let tweet_id = sqlx::query(&sql)
+            .bind(tweet.user_id as i64)
+            .bind(&tweet.content)
+            .bind(tweet.in_reply_to_id.map(|id| id as i64))
+            .execute(&mut tx)
+            .await?
+            .last_insert_rowid() as u64;
+        let sql = format!(
+            r#"INSERT INTO {} (user_tweet_id, media_id, position) VALUES (?, ?, ?);"#,
+            UserTweetMedia::TABLE_NAME
+        );
+        for (position, media_id) in media_ids.iter().enumerate() {
+            sqlx::query(&sql)
+                .bind(tweet_id as i64)
+                .bind(*media_id as i64)
+                .bind(position as i64)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(tweet_id)
+    }
+}
+
+#[axum::async_trait]
+impl LikeRepository for SqliteRepository {
+    async fn insert_like(&self, user_id: u64, user_tweet_id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT OR IGNORE INTO {} (user_id, user_tweet_id) VALUES (?, ?);"#,
+            Like::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(user_id as i64)
+            .bind(user_tweet_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[axum::async_trait]
+impl NotificationRepository for SqliteRepository {
+    async fn insert_notification(&self, notification: &Notification) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT INTO {} (user_id, kind, actor_id, target_id, is_read)
+              VALUES (?, ?, ?, ?, ?);
+            "#,
+            Notification::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(notification.user_id as i64)
+            .bind(notification.kind.as_str())
+            .bind(notification.actor_id as i64)
+            .bind(notification.target_id as i64)
+            .bind(notification.is_read)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn notifications(
+        &self,
+        user_id: u64,
+        limit: u64,
+    ) -> Result<Vec<NotificationEvent>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT notifications.id as id, notifications.kind as kind,
+              users.name as actor_name, notifications.target_id as target_id,
+              notifications.is_read as is_read
+              FROM {}
+              INNER JOIN users
+              ON notifications.actor_id = users.id
+              WHERE notifications.user_id = ?
+              ORDER BY notifications.id DESC
+              LIMIT ?;
+            "#,
+            Notification::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(user_id as i64)
+                    .bind(limit.min(i64::MAX as u64) as i64)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .iter()
+            .map(notification_event_from_row)
+            .collect()
+    }
+
+    async fn count_unread_notifications(&self, user_id: u64) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT COUNT(*) FROM {} WHERE user_id = ? AND is_read = FALSE;"#,
+            Notification::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(user_id as i64)
+                    .fetch_one(&self.pool)
+            })
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn mark_notifications_read(
+        &self,
+        user_id: u64,
+        up_to_id: Option<u64>,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET is_read = TRUE
+              WHERE user_id = ? AND is_read = FALSE AND id <= ?;
+            "#,
+            Notification::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(user_id as i64)
+            .bind(up_to_id.unwrap_or(u64::MAX).min(i64::MAX as u64) as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl DirectMessageRepository for SqliteRepository {
+    async fn find_or_create_conversation(
+        &self,
+        conversation: &Conversation,
+    ) -> Result<Conversation, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT OR IGNORE INTO {} (user1_id, user2_id) VALUES (?, ?);"#,
+            Conversation::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(conversation.user1_id as i64)
+            .bind(conversation.user2_id as i64)
+            .execute(&self.pool)
+            .await?;
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE user1_id = ? AND user2_id = ?;"#,
+            Conversation::TABLE_NAME
+        );
+        let row = sqlx::query(&sql)
+            .bind(conversation.user1_id as i64)
+            .bind(conversation.user2_id as i64)
+            .fetch_one(&self.pool)
+            .await?;
+        conversation_from_row(&row)
+    }
+
+    async fn find_conversation(&self, id: u64) -> Result<Option<Conversation>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE id = ?;"#,
+            Conversation::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(id as i64).fetch_optional(&self.pool))
+            .await?
+            .map(|row| conversation_from_row(&row))
+            .transpose()
+    }
+
+    async fn conversations(&self, user_id: u64) -> Result<Vec<ConversationItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT conversations.id as id, users.name as partner_name,
+              (
+                SELECT content FROM direct_messages
+                WHERE direct_messages.conversation_id = conversations.id
+                ORDER BY direct_messages.id DESC LIMIT 1
+              ) as last_message,
+              (
+                SELECT COUNT(*) FROM direct_messages
+                WHERE direct_messages.conversation_id = conversations.id
+                AND direct_messages.sender_id <> ? AND direct_messages.is_read = FALSE
+              ) as unread_count,
+              (
+                SELECT MAX(direct_messages.id) FROM direct_messages
+                WHERE direct_messages.conversation_id = conversations.id
+              ) as last_message_id
+              FROM {}
+              INNER JOIN users
+              ON users.id = CASE
+                WHEN conversations.user1_id = ? THEN conversations.user2_id
+                ELSE conversations.user1_id
+              END
+              WHERE conversations.user1_id = ? OR conversations.user2_id = ?
+              ORDER BY last_message_id DESC, conversations.id DESC;
+            "#,
+            Conversation::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(user_id as i64)
+                    .bind(user_id as i64)
+                    .bind(user_id as i64)
+                    .bind(user_id as i64)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .iter()
+            .map(conversation_item_from_row)
+            .collect()
+    }
+
+    async fn insert_direct_message(&self, message: &DirectMessage) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT INTO {} (conversation_id, sender_id, content, is_read)
+              VALUES (?, ?, ?, ?);
+            "#,
+            DirectMessage::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(message.conversation_id as i64)
+            .bind(message.sender_id as i64)
+            .bind(&message.content)
+            .bind(message.is_read)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn direct_messages(
+        &self,
+        conversation_id: u64,
+        before_id: Option<u64>,
+        limit: u64,
+    ) -> Result<Vec<DirectMessageItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT direct_messages.id as id, users.name as sender_name,
+              direct_messages.content as content, direct_messages.is_read as is_read
+              FROM {}
+              INNER JOIN users
+              ON direct_messages.sender_id = users.id
+              WHERE direct_messages.conversation_id = ? AND direct_messages.id < ?
+              ORDER BY direct_messages.id DESC
+              LIMIT ?;
+            "#,
+            DirectMessage::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(conversation_id as i64)
+                    .bind(before_id.unwrap_or(u64::MAX).min(i64::MAX as u64) as i64)
+                    .bind(limit.min(i64::MAX as u64) as i64)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .iter()
+            .map(direct_message_item_from_row)
+            .collect()
+    }
+
+    async fn mark_direct_messages_read(
+        &self,
+        conversation_id: u64,
+        reader_id: u64,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET is_read = TRUE
+              WHERE conversation_id = ? AND sender_id <> ? AND is_read = FALSE;
+            "#,
+            DirectMessage::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(conversation_id as i64)
+            .bind(reader_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl AccountRepository for SqliteRepository {
+    async fn delete_user(&self, id: u64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // フォロー関係はON DELETE CASCADEで消えるので、先にフォローしていたユーザのフォロワー数を減らす
+        let sql = format!(
+            r#"
+              UPDATE {} SET follower_count = MAX(follower_count, 1) - 1
+              WHERE user_id IN (SELECT followee_id FROM {} WHERE follower_id = ?);
+            "#,
+            FollowerCount::TABLE_NAME,
+            FollowRelation::TABLE_NAME
+        );
+        sqlx::query(&sql).bind(id as i64).execute(&mut tx).await?;
+        let sql = format!(r#"DELETE FROM {} WHERE id = ?;"#, User::TABLE_NAME);
+        let result = sqlx::query(&sql).bind(id as i64).execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn export_records(
+        &self,
+        user_id: u64,
+        section: ExportSection,
+        after_id: u64,
+        limit: u64,
+    ) -> Result<Vec<(u64, ExportRecord)>, sqlx::Error> {
+        match section {
+            ExportSection::Tweets => {
+                let sql = format!(
+                    r#"SELECT id, content, in_reply_to_id FROM {} WHERE user_id = ? AND id > ? ORDER BY id LIMIT ?;"#,
+                    UserTweet::TABLE_NAME
+                );
+                let rows: Vec<(i64, String, Option<i64>)> = self
+                    .read_retry
+                    .run(|| {
+                        sqlx::query_as(&sql)
+                            .bind(user_id as i64)
+                            .bind(after_id as i64)
+                            .bind(limit as i64)
+                            .fetch_all(&self.pool)
+                    })
+                    .await?;
+                Ok(rows
+                    .into_iter()
+                    .map(|(id, content, in_reply_to_id)| {
+                        let record = ExportRecord::Tweet {
+                            id: id as u64,
+                            content,
+                            in_reply_to_id: in_reply_to_id.map(|id| id as u64),
+                        };
+                        (id as u64, record)
+                    })
+                    .collect())
+            }
+            ExportSection::Following | ExportSection::Followers => {
+                // フォロー関係の一方の列が指定ユーザである行について、もう一方のユーザ名を返す
+                let (user_column, other_column) = match section {
+                    ExportSection::Following => ("follower_id", "followee_id"),
+                    _ => ("followee_id", "follower_id"),
+                };
+                let sql = format!(
+                    r#"
+                      SELECT {relations}.id, users.name FROM {relations}
+                      INNER JOIN users ON users.id = {relations}.{other}
+                      WHERE {relations}.{user} = ? AND {relations}.id > ?
+                      ORDER BY {relations}.id LIMIT ?;
+                    "#,
+                    relations = FollowRelation::TABLE_NAME,
+                    user = user_column,
+                    other = other_column,
+                );
+                let rows: Vec<(i64, String)> = self
+                    .read_retry
+                    .run(|| {
+                        sqlx::query_as(&sql)
+                            .bind(user_id as i64)
+                            .bind(after_id as i64)
+                            .bind(limit as i64)
+                            .fetch_all(&self.pool)
+                    })
+                    .await?;
+                Ok(rows
+                    .into_iter()
+                    .map(|(id, name)| {
+                        let record = match section {
+                            ExportSection::Following => ExportRecord::Following { name },
+                            _ => ExportRecord::Follower { name },
+                        };
+                        (id as u64, record)
+                    })
+                    .collect())
+            }
+            ExportSection::Likes => {
+                let sql = format!(
+                    r#"SELECT id, user_tweet_id FROM {} WHERE user_id = ? AND id > ? ORDER BY id LIMIT ?;"#,
+                    Like::TABLE_NAME
+                );
+                let rows: Vec<(i64, i64)> = self
+                    .read_retry
+                    .run(|| {
+                        sqlx::query_as(&sql)
+                            .bind(user_id as i64)
+                            .bind(after_id as i64)
+                            .bind(limit as i64)
+                            .fetch_all(&self.pool)
+                    })
+                    .await?;
+                Ok(rows
+                    .into_iter()
+                    .map(|(id, user_tweet_id)| {
+                        let record = ExportRecord::Like {
+                            user_tweet_id: user_tweet_id as u64,
+                        };
+                        (id as u64, record)
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+#[axum::async_trait]
+impl ModerationRepository for SqliteRepository {
+    async fn insert_report(&self, report: &Report) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT OR IGNORE INTO {} (reporter_id, user_tweet_id, reason) VALUES (?, ?, ?);"#,
+            Report::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(report.reporter_id as i64)
+            .bind(report.user_tweet_id as i64)
+            .bind(&report.reason)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn unresolved_reports(&self, limit: u64) -> Result<Vec<ReportItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT reports.id as id, reports.user_tweet_id as user_tweet_id,
+              user_tweets.content as content, authors.name as author_name,
+              reporters.name as reporter_name, reports.reason as reason,
+              user_tweets.is_hidden as is_hidden
+              FROM {} as reports
+              INNER JOIN user_tweets ON user_tweets.id = reports.user_tweet_id
+              INNER JOIN users as authors ON authors.id = user_tweets.user_id
+              INNER JOIN users as reporters ON reporters.id = reports.reporter_id
+              WHERE reports.is_resolved = FALSE
+              ORDER BY reports.id
+              LIMIT ?;
+            "#,
+            Report::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(limit as i64).fetch_all(&self.pool))
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(ReportItem {
+                    id: get_u64(row, "id")?,
+                    user_tweet_id: get_u64(row, "user_tweet_id")?,
+                    content: row.try_get("content")?,
+                    author_name: row.try_get("author_name")?,
+                    reporter_name: row.try_get("reporter_name")?,
+                    reason: row.try_get("reason")?,
+                    is_hidden: row.try_get("is_hidden")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn resolve_report(&self, actor_id: u64, report_id: u64) -> Result<bool, sqlx::Error> {
+        self.update_with_moderation_log(
+            Report::TABLE_NAME,
+            "is_resolved = TRUE",
+            &ModerationLog {
+                id: None,
+                actor_id: Some(actor_id),
+                action: ModerationAction::ResolveReport,
+                target_id: report_id,
+                detail: None,
+            },
+        )
+        .await
+    }
+
+    async fn set_user_tweet_hidden(
+        &self,
+        actor_id: u64,
+        user_tweet_id: u64,
+        hidden: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let (assignment, action) = if hidden {
+            ("is_hidden = TRUE", ModerationAction::HideTweet)
+        } else {
+            ("is_hidden = FALSE", ModerationAction::UnhideTweet)
+        };
+        self.update_with_moderation_log(
+            UserTweet::TABLE_NAME,
+            assignment,
+            &ModerationLog {
+                id: None,
+                actor_id: Some(actor_id),
+                action,
+                target_id: user_tweet_id,
+                detail: None,
+            },
+        )
+        .await
+    }
+
+    async fn set_user_suspended(
+        &self,
+        actor_id: u64,
+        user_id: u64,
+        suspended: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let (assignment, action) = if suspended {
+            ("is_suspended = TRUE", ModerationAction::SuspendUser)
+        } else {
+            ("is_suspended = FALSE", ModerationAction::UnsuspendUser)
+        };
+        self.update_with_moderation_log(
+            User::TABLE_NAME,
+            assignment,
+            &ModerationLog {
+                id: None,
+                actor_id: Some(actor_id),
+                action,
+                target_id: user_id,
+                detail: None,
+            },
+        )
+        .await
+    }
+
+    async fn set_user_role(
+        &self,
+        actor_id: Option<u64>,
+        user_id: u64,
+        role: Role,
+    ) -> Result<bool, sqlx::Error> {
+        // ロール名は固定の文字列なのでSQLに埋め込んでよい
+        self.update_with_moderation_log(
+            User::TABLE_NAME,
+            &format!("role = '{}'", role.as_str()),
+            &ModerationLog {
+                id: None,
+                actor_id,
+                action: ModerationAction::ChangeRole,
+                target_id: user_id,
+                detail: Some(role.as_str().to_string()),
+            },
+        )
+        .await
+    }
+
+    async fn moderation_logs(&self, limit: u64) -> Result<Vec<ModerationLogItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT logs.id as id, users.name as actor_name, logs.action as action,
+              logs.target_id as target_id, logs.detail as detail,
+              strftime('%Y-%m-%d %H:%M:%S', logs.created_at) as created_at
+              FROM {} as logs
+              LEFT OUTER JOIN users ON users.id = logs.actor_id
+              ORDER BY logs.id DESC
+              LIMIT ?;
+            "#,
+            ModerationLog::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(limit as i64).fetch_all(&self.pool))
+            .await?
+            .iter()
+            .map(|row| {
+                let action: String = row.try_get("action")?;
+                Ok(ModerationLogItem {
+                    id: get_u64(row, "id")?,
+                    actor_name: row.try_get("actor_name")?,
+                    action: ModerationAction::parse(&action).ok_or_else(|| {
+                        sqlx::Error::Decode(format!("unknown action: {}", action).into())
+                    })?,
+                    target_id: get_u64(row, "target_id")?,
+                    detail: row.try_get("detail")?,
+                    created_at: row.try_get("created_at")?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl SqliteRepository {
+    // 対象の行を更新し、同じトランザクションで監査ログを書き込む
+    // 対象の行が存在しなければロールバックしてfalseを返す
+    async fn update_with_moderation_log(
+        &self,
+        table_name: &str,
+        assignment: &str,
+        log: &ModerationLog,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(r#"UPDATE {} SET {} WHERE id = ?;"#, table_name, assignment);
+        let result = sqlx::query(&sql)
+            .bind(log.target_id as i64)
+            .execute(&mut tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        let sql = format!(
+            r#"INSERT INTO {} (actor_id, action, target_id, detail) VALUES (?, ?, ?, ?);"#,
+            ModerationLog::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(log.actor_id.map(|id| id as i64))
+            .bind(log.action.as_str())
+            .bind(log.target_id as i64)
+            .bind(&log.detail)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+#[axum::async_trait]
+impl BlockRepository for SqliteRepository {
+    async fn insert_block(&self, block: &Block) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT OR IGNORE INTO {} (blocker_id, blocked_id) VALUES (?, ?);"#,
+            Block::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(block.blocker_id as i64)
+            .bind(block.blocked_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn is_blocked_between(
+        &self,
+        user_id: u64,
+        other_user_id: u64,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT COUNT(*) FROM {}
+              WHERE (blocker_id = ? AND blocked_id = ?) OR (blocker_id = ? AND blocked_id = ?);
+            "#,
+            Block::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(user_id as i64)
+                    .bind(other_user_id as i64)
+                    .bind(other_user_id as i64)
+                    .bind(user_id as i64)
+                    .fetch_one(&self.pool)
+            })
+            .await?;
+        Ok(count > 0)
+    }
+}
+
+#[axum::async_trait]
+impl RecommendationRepository for SqliteRepository {
+    async fn recommended_users(
+        &self,
+        user_id: u64,
+        limit: u64,
+    ) -> Result<Vec<RecommendedUser>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT id, name, mutual_count, recent_tweet_count FROM (
+                SELECT candidates.id as id, users.name as name,
+                candidates.mutual_count as mutual_count,
+                (
+                  SELECT COUNT(*) FROM user_tweets
+                  WHERE user_tweets.user_id = candidates.id
+                  AND user_tweets.created_at >= datetime('now', '-{days} days')
+                ) as recent_tweet_count
+                FROM (
+                  SELECT theirs.followee_id as id, COUNT(*) as mutual_count
+                  FROM {relations} as mine
+                  INNER JOIN {relations} as theirs ON theirs.follower_id = mine.followee_id
+                  WHERE mine.follower_id = ? AND theirs.followee_id <> ?
+                  GROUP BY theirs.followee_id
+                ) as candidates
+                INNER JOIN users ON users.id = candidates.id
+                WHERE users.is_suspended = FALSE
+                AND NOT EXISTS (
+                  SELECT 1 FROM {relations}
+                  WHERE follower_id = ? AND followee_id = candidates.id
+                )
+                AND NOT EXISTS (
+                  SELECT 1 FROM {blocks}
+                  WHERE (blocker_id = ? AND blocked_id = candidates.id)
+                  OR (blocker_id = candidates.id AND blocked_id = ?)
+                )
+              ) as ranked
+              ORDER BY mutual_count * {weight} + MIN(recent_tweet_count, {cap}) DESC, id
+              LIMIT ?;
+            "#,
+            relations = FollowRelation::TABLE_NAME,
+            blocks = Block::TABLE_NAME,
+            weight = RECOMMENDATION_MUTUAL_WEIGHT,
+            cap = RECOMMENDATION_RECENT_TWEET_CAP,
+            days = RECOMMENDATION_RECENT_DAYS,
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(user_id as i64)
+                    .bind(user_id as i64)
+                    .bind(user_id as i64)
+                    .bind(user_id as i64)
+                    .bind(user_id as i64)
+                    .bind(limit as i64)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(RecommendedUser {
+                    id: get_u64(row, "id")?,
+                    name: row.try_get("name")?,
+                    mutual_count: get_u64(row, "mutual_count")?,
+                    recent_tweet_count: get_u64(row, "recent_tweet_count")?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[axum::async_trait]
+impl JobRepository for SqliteRepository {
+    async fn enqueue_job(&self, job: &Job) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT OR IGNORE INTO {} (kind, payload, unique_key, run_at) VALUES (?, ?, ?, ?);"#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(job.kind.as_str())
+            .bind(&job.payload)
+            .bind(&job.unique_key)
+            .bind(job.run_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn claim_due_jobs(
+        &self,
+        now: NaiveDateTime,
+        locked_until: NaiveDateTime,
+        limit: u64,
+    ) -> Result<Vec<Job>, sqlx::Error> {
+        // SQLiteは書き込みが直列化されるので、1つのUPDATE文で取得と実行中への変更を行う
+        // 取得した行は毎回変わるトークンで見分ける
+        let token = claim_token();
+        let sql = format!(
+            r#"
+              UPDATE {jobs} SET status = 'running', locked_by = ?, locked_until = ?,
+              attempts = attempts + 1
+              WHERE id IN (
+                SELECT id FROM {jobs}
+                WHERE (status = 'pending' AND run_at <= ?)
+                OR (status = 'running' AND locked_until <= ?)
+                ORDER BY run_at, id
+                LIMIT ?
+              );
+            "#,
+            jobs = Job::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(&token)
+            .bind(locked_until)
+            .bind(now)
+            .bind(now)
+            .bind(limit as i64)
+            .execute(&self.pool)
+            .await?;
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE locked_by = ? AND status = 'running' ORDER BY run_at, id;"#,
+            Job::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(&token)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(job_from_row)
+            .collect()
+    }
+
+    async fn complete_job(&self, job: &Job) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"UPDATE {} SET status = 'done', locked_until = NULL WHERE id = ? AND locked_by = ?;"#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(job.id.unwrap() as i64)
+            .bind(&job.locked_by)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn retry_job(
+        &self,
+        job: &Job,
+        run_at: NaiveDateTime,
+        error: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET status = 'pending', run_at = ?, locked_until = NULL, last_error = ?
+              WHERE id = ? AND locked_by = ?;
+            "#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(run_at)
+            .bind(error)
+            .bind(job.id.unwrap() as i64)
+            .bind(&job.locked_by)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn fail_job(&self, job: &Job, error: &str) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET status = 'failed', locked_until = NULL, last_error = ?
+              WHERE id = ? AND locked_by = ?;
+            "#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(error)
+            .bind(job.id.unwrap() as i64)
+            .bind(&job.locked_by)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn publish_scheduled_tweet(
+        &self,
+        job: &Job,
+        tweet: &UserTweet,
+        media_ids: &[u64],
+    ) -> Result<Option<u64>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // 先にジョブの行を更新してロックを取る、既に完了していれば他のワーカーが公開済み
+        let sql = format!(
+            r#"
+              UPDATE {} SET status = 'done', locked_until = NULL
+              WHERE id = ? AND status = 'running' AND locked_by = ?;
+            "#,
+            Job::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(job.id.unwrap() as i64)
+            .bind(&job.locked_by)
+            .execute(&mut tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, content, in_reply_to_id) VALUES (?, ?, ?);"#,
+            UserTweet::TABLE_NAME
+        );
+        let tweet_id = sqlx::query(&sql)
+            .bind(tweet.user_id as i64)
+            .bind(&tweet.content)
+            .bind(tweet.in_reply_to_id.map(|id| id as i64))
+            .execute(&mut tx)
+            .await?
+            .last_insert_rowid() as u64;
+        let sql = format!(
+            r#"INSERT INTO {} (user_tweet_id, media_id, position) VALUES (?, ?, ?);"#,
+            UserTweetMedia::TABLE_NAME
+        );
+        for (position, media_id) in media_ids.iter().enumerate() {
+            sqlx::query(&sql)
+                .bind(tweet_id as i64)
+                .bind(*media_id as i64)
+                .bind(position as i64)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(Some(tweet_id))
+    }
+}
+
+#[axum::async_trait]
+impl ApiTokenRepository for SqliteRepository {
+    async fn insert_api_token(&self, token: &ApiToken) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, name, token_hash, scopes) VALUES (?, ?, ?, ?);"#,
+            ApiToken::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(token.user_id as i64)
+            .bind(&token.name)
+            .bind(&token.token_hash)
+            .bind(ApiTokenScope::join(&token.scopes))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn api_tokens(&self, user_id: u64) -> Result<Vec<ApiTokenItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT id, name, scopes,
+              strftime('%Y-%m-%d %H:%M:%S', created_at) as created_at,
+              strftime('%Y-%m-%d %H:%M:%S', last_used_at) as last_used_at
+              FROM {} WHERE user_id = ? ORDER BY id;
+            "#,
+            ApiToken::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(user_id as i64).fetch_all(&self.pool))
+            .await?
+            .iter()
+            .map(|row| {
+                let scopes: String = row.try_get("scopes")?;
+                Ok(ApiTokenItem {
+                    id: get_u64(row, "id")?,
+                    name: row.try_get("name")?,
+                    scopes: parse_scopes(&scopes)?,
+                    created_at: row.try_get("created_at")?,
+                    last_used_at: row.try_get("last_used_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn find_api_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<ApiToken>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT * FROM {} WHERE token_hash = ?;"#,
+            ApiToken::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(token_hash)
+                    .fetch_optional(&self.pool)
+            })
+            .await?
+            .map(|row| {
+                let scopes: String = row.try_get("scopes")?;
+                Ok(ApiToken {
+                    id: Some(get_u64(&row, "id")?),
+                    user_id: get_u64(&row, "user_id")?,
+                    name: row.try_get("name")?,
+                    token_hash: row.try_get("token_hash")?,
+                    scopes: parse_scopes(&scopes)?,
+                })
+            })
+            .transpose()
+    }
+
+    async fn touch_api_token(&self, id: u64, used_at: NaiveDateTime) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"UPDATE {} SET last_used_at = ? WHERE id = ?;"#,
+            ApiToken::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(used_at)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_api_token(&self, user_id: u64, id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE id = ? AND user_id = ?;"#,
+            ApiToken::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(id as i64)
+            .bind(user_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[axum::async_trait]
+impl OidcIdentityRepository for SqliteRepository {
+    async fn find_user_by_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT u.* FROM {} u
+              INNER JOIN {} i ON i.user_id = u.id
+              WHERE i.issuer = ? AND i.subject = ?;
+            "#,
+            User::TABLE_NAME,
+            OidcIdentity::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(issuer)
+                    .bind(subject)
+                    .fetch_optional(&self.pool)
+            })
+            .await?
+            .map(|row| user_from_row(&row))
+            .transpose()
+    }
+
+    async fn insert_user_with_oidc_identity(
+        &self,
+        user: &User,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"INSERT INTO {} (name, password_hash) VALUES (?, ?);"#,
+            User::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(&user.name)
+            .bind(&user.password_hash)
+            .execute(&mut tx)
+            .await?;
+        let user_id = result.last_insert_rowid() as u64;
+        let sql = format!(
+            r#"INSERT INTO {} (issuer, subject, user_id) VALUES (?, ?, ?);"#,
+            OidcIdentity::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(issuer)
+            .bind(subject)
+            .bind(user_id as i64)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(user_id)
+    }
+
+    async fn has_oidc_identity(&self, user_id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT COUNT(*) FROM {} WHERE user_id = ?;"#,
+            OidcIdentity::TABLE_NAME
+        );
+        let count: i64 = self
+            .read_retry
+            .run(|| {
+                sqlx::query_scalar(&sql)
+                    .bind(user_id as i64)
+                    .fetch_one(&self.pool)
+            })
+            .await?;
+        Ok(count > 0)
+    }
+}
+
+#[axum::async_trait]
+impl LinkPreviewRepository for SqliteRepository {
+    async fn upsert_link_preview(&self, preview: &LinkPreview) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"
+              INSERT INTO {} (url_hash, url, title, description, image_url)
+              VALUES (?, ?, ?, ?, ?)
+              ON CONFLICT (url_hash) DO UPDATE SET title = excluded.title,
+              description = excluded.description, image_url = excluded.image_url,
+              fetched_at = CURRENT_TIMESTAMP;
+            "#,
+            LinkPreview::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(url_hash(&preview.url))
+            .bind(&preview.url)
+            .bind(&preview.title)
+            .bind(&preview.description)
+            .bind(&preview.image_url)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn link_previews(&self, urls: &[String]) -> Result<Vec<LinkPreview>, sqlx::Error> {
+        if urls.is_empty() {
+            return Ok(vec![]);
+        }
+        let sql = format!(
+            r#"SELECT url, title, description, image_url FROM {} WHERE url_hash IN ({});"#,
+            LinkPreview::TABLE_NAME,
+            vec!["?"; urls.len()].join(",")
+        );
+        self.read_retry
+            .run(|| {
+                let mut query = sqlx::query_as::<_, LinkPreview>(&sql);
+                for url in urls {
+                    query = query.bind(url_hash(url));
+                }
+                query.fetch_all(&self.pool)
+            })
+            .await
+    }
+}
+
+#[axum::async_trait]
+impl IdempotencyKeyRepository for SqliteRepository {
+    async fn reserve_idempotency_key(
+        &self,
+        user_id: u64,
+        key: &str,
+        request_hash: &str,
+        now: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> Result<bool, sqlx::Error> {
+        // 有効期限切れのキーは新しいリクエストで置き換える
+        let sql = format!(
+            r#"DELETE FROM {} WHERE user_id = ? AND idempotency_key = ? AND expires_at <= ?;"#,
+            IdempotencyKey::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(user_id as i64)
+            .bind(key)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        let sql = format!(
+            r#"
+              INSERT OR IGNORE INTO {} (user_id, idempotency_key, request_hash, expires_at)
+              VALUES (?, ?, ?, ?);
+            "#,
+            IdempotencyKey::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(user_id as i64)
+            .bind(key)
+            .bind(request_hash)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn find_idempotency_key(
+        &self,
+        user_id: u64,
+        key: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT request_hash, status_code, response_headers, response_body FROM {}
+              WHERE user_id = ? AND idempotency_key = ?;
+            "#,
+            IdempotencyKey::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(user_id as i64)
+                    .bind(key)
+                    .fetch_optional(&self.pool)
+            })
+            .await?
+            .map(|row| {
+                let status_code: Option<i64> = row.try_get("status_code")?;
+                let response = match status_code {
+                    Some(status_code) => Some(IdempotentResponse {
+                        status_code: status_code as u16,
+                        headers: parse_response_headers(
+                            row.try_get::<Option<String>, _>("response_headers")?
+                                .as_deref(),
+                        )?,
+                        body: row
+                            .try_get::<Option<Vec<u8>>, _>("response_body")?
+                            .unwrap_or_default(),
+                    }),
+                    None => None,
+                };
+                Ok(IdempotencyKey {
+                    request_hash: row.try_get("request_hash")?,
+                    response,
+                })
+            })
+            .transpose()
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        user_id: u64,
+        key: &str,
+        response: &IdempotentResponse,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"
+              UPDATE {} SET status_code = ?, response_headers = ?, response_body = ?, expires_at = ?
+              WHERE user_id = ? AND idempotency_key = ?;
+            "#,
+            IdempotencyKey::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(response.status_code)
+            .bind(serde_json::to_string(&response.headers).unwrap())
+            .bind(&response.body)
+            .bind(expires_at)
+            .bind(user_id as i64)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_idempotency_key(&self, user_id: u64, key: &str) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE user_id = ? AND idempotency_key = ?;"#,
+            IdempotencyKey::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(user_id as i64)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_expired_idempotency_keys(
+        &self,
+        now: NaiveDateTime,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE expires_at <= ?;"#,
+            IdempotencyKey::TABLE_NAME
+        );
+        let result = sqlx::query(&sql).bind(now).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl FollowRequestRepository for SqliteRepository {
+    async fn insert_follow_request(&self, request: &FollowRequest) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT OR IGNORE INTO {} (requester_id, target_id) VALUES (?, ?);"#,
+            FollowRequest::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(request.requester_id as i64)
+            .bind(request.target_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn follow_requests(&self, target_id: u64) -> Result<Vec<FollowRequestItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT follow_requests.id, users.name FROM {} AS follow_requests
+              INNER JOIN {} AS users ON users.id = follow_requests.requester_id
+              WHERE follow_requests.target_id = ?
+              ORDER BY follow_requests.id;
+            "#,
+            FollowRequest::TABLE_NAME,
+            User::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(target_id as i64)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(FollowRequestItem {
+                    id: get_u64(row, "id")?,
+                    name: row.try_get("name")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn approve_follow_request(
+        &self,
+        target_id: u64,
+        request_id: u64,
+    ) -> Result<Option<FollowRelation>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"SELECT requester_id FROM {} WHERE id = ? AND target_id = ?;"#,
+            FollowRequest::TABLE_NAME
+        );
+        let requester_id: Option<i64> = sqlx::query_scalar(&sql)
+            .bind(request_id as i64)
+            .bind(target_id as i64)
+            .fetch_optional(&mut tx)
+            .await?;
+        let requester_id = match requester_id {
+            Some(requester_id) => requester_id as u64,
+            None => return Ok(None),
+        };
+        let sql = format!(r#"DELETE FROM {} WHERE id = ?;"#, FollowRequest::TABLE_NAME);
+        sqlx::query(&sql)
+            .bind(request_id as i64)
+            .execute(&mut tx)
+            .await?;
+        // 申請中に別の経路でフォロー済みになっていても申請は処理済みにする
+        let sql = format!(
+            r#"INSERT OR IGNORE INTO {} (followee_id, follower_id) VALUES (?, ?);"#,
+            FollowRelation::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(target_id as i64)
+            .bind(requester_id as i64)
+            .execute(&mut tx)
+            .await?;
+        if result.rows_affected() > 0 {
+            increment_follower_count(&mut tx, target_id).await?;
+        }
+        tx.commit().await?;
+        Ok(Some(FollowRelation {
+            id: (result.rows_affected() > 0).then(|| result.last_insert_rowid() as u64),
+            followee_id: target_id,
+            follower_id: requester_id,
+        }))
+    }
+
+    async fn delete_follow_request(
+        &self,
+        target_id: u64,
+        request_id: u64,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE id = ? AND target_id = ?;"#,
+            FollowRequest::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(request_id as i64)
+            .bind(target_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[axum::async_trait]
+impl ListRepository for SqliteRepository {
+    async fn insert_list(&self, list: &UserList) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (owner_id, name) VALUES (?, ?);"#,
+            UserList::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(list.owner_id as i64)
+            .bind(&list.name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn find_list(&self, id: u64) -> Result<Option<UserList>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT id, owner_id, name FROM {} WHERE id = ?;"#,
+            UserList::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(id as i64).fetch_optional(&self.pool))
+            .await?
+            .map(|row| {
+                Ok(UserList {
+                    id: Some(get_u64(&row, "id")?),
+                    owner_id: get_u64(&row, "owner_id")?,
+                    name: row.try_get("name")?,
+                })
+            })
+            .transpose()
+    }
+
+    async fn lists(&self, owner_id: u64) -> Result<Vec<UserListItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT lists.id, lists.name, (
+                SELECT COUNT(*) FROM {} AS list_members WHERE list_members.list_id = lists.id
+              ) AS member_count
+              FROM {} AS lists
+              WHERE lists.owner_id = ?
+              ORDER BY lists.id;
+            "#,
+            ListMember::TABLE_NAME,
+            UserList::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(owner_id as i64)
+                    .fetch_all(self.reader())
+            })
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(UserListItem {
+                    id: get_u64(row, "id")?,
+                    name: row.try_get("name")?,
+                    member_count: get_u64(row, "member_count")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn rename_list(&self, id: u64, name: &str) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"UPDATE {} SET name = ? WHERE id = ?;"#,
+            UserList::TABLE_NAME
+        );
+        sqlx::query(&sql)
+            .bind(name)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_list(&self, id: u64) -> Result<(), sqlx::Error> {
+        let sql = format!(r#"DELETE FROM {} WHERE id = ?;"#, UserList::TABLE_NAME);
+        sqlx::query(&sql)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_list_member(&self, member: &ListMember) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT OR IGNORE INTO {} (list_id, user_id) VALUES (?, ?);"#,
+            ListMember::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(member.list_id as i64)
+            .bind(member.user_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_list_member(&self, list_id: u64, user_id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE list_id = ? AND user_id = ?;"#,
+            ListMember::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(list_id as i64)
+            .bind(user_id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_members(&self, list_id: u64) -> Result<Vec<ListMemberItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT users.id, users.name FROM {} AS list_members
+              INNER JOIN {} AS users ON users.id = list_members.user_id
+              WHERE list_members.list_id = ?
+              ORDER BY list_members.id;
+            "#,
+            ListMember::TABLE_NAME,
+            User::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(list_id as i64)
+                    .fetch_all(self.reader())
+            })
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(ListMemberItem {
+                    id: get_u64(row, "id")?,
+                    name: row.try_get("name")?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[axum::async_trait]
+impl BulkInsertRepository for SqliteRepository {
+    async fn bulk_insert_users(&self, names: &[String]) -> Result<Vec<u64>, sqlx::Error> {
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"INSERT INTO {} (name) VALUES {};"#,
+            User::TABLE_NAME,
+            vec!["(?)"; names.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for name in names {
+            query = query.bind(name);
+        }
+        query.execute(&mut tx).await?;
+        // 複数行INSERTで採番されたIDが連番になるとは限らないので、名前で引き直す
+        let sql = format!(
+            r#"SELECT id, name FROM {} WHERE name IN ({});"#,
+            User::TABLE_NAME,
+            vec!["?"; names.len()].join(",")
+        );
+        let mut query = sqlx::query_as(&sql);
+        for name in names {
+            query = query.bind(name);
+        }
+        let rows: Vec<(i64, String)> = query.fetch_all(&mut tx).await?;
+        let rows = rows.into_iter().map(|(id, name)| (id as u64, name));
+        tx.commit().await?;
+        let ids: std::collections::HashMap<String, u64> =
+            rows.into_iter().map(|(id, name)| (name, id)).collect();
+        Ok(names.iter().map(|name| ids[name]).collect())
+    }
+
+    async fn bulk_insert_follow_relations(
+        &self,
+        follow_relations: &[FollowRelation],
+    ) -> Result<u64, sqlx::Error> {
+        if follow_relations.is_empty() {
+            return Ok(0);
+        }
+        let sql = format!(
+            r#"INSERT OR IGNORE INTO {} (followee_id, follower_id) VALUES {};"#,
+            FollowRelation::TABLE_NAME,
+            vec!["(?, ?)"; follow_relations.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for follow_relation in follow_relations {
+            query = query
+                .bind(follow_relation.followee_id as i64)
+                .bind(follow_relation.follower_id as i64);
+        }
+        let mut tx = self.pool.begin().await?;
+        let inserted = query.execute(&mut tx).await?.rows_affected();
+        // INSERT OR IGNOREでどの行が増えたかわからないので、フォロワー数は数え直す
+        let mut followee_ids: Vec<i64> = follow_relations
+            .iter()
+            .map(|follow_relation| follow_relation.followee_id as i64)
+            .collect();
+        followee_ids.sort_unstable();
+        followee_ids.dedup();
+        let sql = format!(
+            r#"
+              INSERT OR REPLACE INTO {} (user_id, follower_count)
+              SELECT followee_id, COUNT(*) FROM {} WHERE followee_id IN ({}) GROUP BY followee_id;
+            "#,
+            FollowerCount::TABLE_NAME,
+            FollowRelation::TABLE_NAME,
+            vec!["?"; followee_ids.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for followee_id in followee_ids {
+            query = query.bind(followee_id);
+        }
+        query.execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn bulk_insert_user_tweets(
+        &self,
+        tweets: &[(UserTweet, NaiveDateTime)],
+    ) -> Result<(), sqlx::Error> {
+        if tweets.is_empty() {
+            return Ok(());
+        }
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, content, in_reply_to_id, created_at) VALUES {};"#,
+            UserTweet::TABLE_NAME,
+            vec!["(?, ?, ?, ?)"; tweets.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for (tweet, created_at) in tweets {
+            query = query
+                .bind(tweet.user_id as i64)
+                .bind(&tweet.content)
+                .bind(tweet.in_reply_to_id.map(|id| id as i64))
+                .bind(created_at);
+        }
+        query.execute(&self.pool).await?;
+        Ok(())
+    }
+}
+
+#[axum::async_trait]
+impl TrendRepository for SqliteRepository {
+    async fn hashtag_cursor(&self) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT last_tweet_id FROM {} WHERE id = 1;"#,
+            HashtagCursor::TABLE_NAME
+        );
+        let row = self
+            .read_retry
+            .run(|| sqlx::query(&sql).fetch_one(&self.pool))
+            .await?;
+        get_u64(&row, "last_tweet_id")
+    }
+
+    async fn trend_tweets(
+        &self,
+        after_id: u64,
+        limit: u64,
+    ) -> Result<Vec<TrendTweet>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT {tweets}.id, {tweets}.content,
+              CAST(strftime('%s', {tweets}.created_at) AS INTEGER) as posted_at
+              FROM {tweets} INNER JOIN {users} ON {users}.id = {tweets}.user_id
+              WHERE {tweets}.id > ? AND {tweets}.is_hidden = FALSE
+              AND {users}.is_protected = FALSE AND {users}.is_suspended = FALSE
+              ORDER BY {tweets}.id LIMIT ?;
+            "#,
+            tweets = UserTweet::TABLE_NAME,
+            users = User::TABLE_NAME,
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(after_id as i64)
+                    .bind(limit as i64)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(TrendTweet {
+                    id: get_u64(row, "id")?,
+                    content: row
+                        .try_get::<Option<String>, _>("content")?
+                        .unwrap_or_default(),
+                    posted_at: row.try_get("posted_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn add_hashtag_counts(
+        &self,
+        from_id: u64,
+        to_id: u64,
+        buckets: &[HashtagBucket],
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"UPDATE {} SET last_tweet_id = ? WHERE id = 1 AND last_tweet_id = ?;"#,
+            HashtagCursor::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(to_id as i64)
+            .bind(from_id as i64)
+            .execute(&mut tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        if !buckets.is_empty() {
+            let sql = format!(
+                r#"
+                  INSERT INTO {} (tag, bucket_start, count) VALUES {}
+                  ON CONFLICT (tag, bucket_start) DO UPDATE SET count = count + excluded.count;
+                "#,
+                HashtagBucket::TABLE_NAME,
+                vec!["(?, ?, ?)"; buckets.len()].join(", ")
+            );
+            let mut query = sqlx::query(&sql);
+            for bucket in buckets {
+                query = query
+                    .bind(&bucket.tag)
+                    .bind(bucket.bucket_start)
+                    .bind(bucket.count as i64);
+            }
+            query.execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn hashtag_counts(
+        &self,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(String, u64)>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT tag, SUM(count) as count FROM {}
+              WHERE bucket_start >= ? AND bucket_start < ?
+              GROUP BY tag;
+            "#,
+            HashtagBucket::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(since)
+                    .bind(until)
+                    .fetch_all(self.reader())
+            })
+            .await?
+            .iter()
+            .map(|row| Ok((row.try_get("tag")?, get_u64(row, "count")?)))
+            .collect()
+    }
+
+    async fn delete_hashtag_buckets(&self, before: i64) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"DELETE FROM {} WHERE bucket_start < ?;"#,
+            HashtagBucket::TABLE_NAME
+        );
+        let result = sqlx::query(&sql).bind(before).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[axum::async_trait]
+impl WebhookRepository for SqliteRepository {
+    async fn insert_webhook(&self, webhook: &Webhook) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (url, secret, events, created_by) VALUES (?, ?, ?, ?);"#,
+            Webhook::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(&webhook.url)
+            .bind(&webhook.secret)
+            .bind(WebhookEvent::join(&webhook.events))
+            .bind(webhook.created_by.map(|id| id as i64))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn webhooks(&self) -> Result<Vec<WebhookItem>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT id, url, events, strftime('%Y-%m-%d %H:%M:%S', created_at) as created_at FROM {} ORDER BY id;"#,
+            Webhook::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| sqlx::query(&sql).fetch_all(&self.pool))
+            .await?
+            .iter()
+            .map(|row| {
+                let events: String = row.try_get("events")?;
+                Ok(WebhookItem {
+                    id: get_u64(row, "id")?,
+                    url: row.try_get("url")?,
+                    events: parse_webhook_events(&events)?,
+                    created_at: row.try_get("created_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn find_webhook(&self, id: u64) -> Result<Option<Webhook>, sqlx::Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE id = ?;"#, Webhook::TABLE_NAME);
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(id as i64).fetch_optional(&self.pool))
+            .await?
+            .map(|row| webhook_from_row(&row))
+            .transpose()
+    }
+
+    async fn subscribed_webhooks(&self, event: WebhookEvent) -> Result<Vec<Webhook>, sqlx::Error> {
+        // Webhookの数は少ないので、すべて読み出してから絞り込む
+        let sql = format!(r#"SELECT * FROM {} ORDER BY id;"#, Webhook::TABLE_NAME);
+        let webhooks = self
+            .read_retry
+            .run(|| sqlx::query(&sql).fetch_all(&self.pool))
+            .await?
+            .iter()
+            .map(webhook_from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(webhooks
+            .into_iter()
+            .filter(|webhook| webhook.events.contains(&event))
+            .collect())
+    }
+
+    async fn delete_webhook(&self, id: u64) -> Result<bool, sqlx::Error> {
+        let sql = format!(r#"DELETE FROM {} WHERE id = ?;"#, Webhook::TABLE_NAME);
+        let result = sqlx::query(&sql)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn insert_webhook_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+    ) -> Result<u64, sqlx::Error> {
+        let sql = format!(
+            r#"INSERT INTO {} (webhook_id, event, payload) VALUES (?, ?, ?);"#,
+            WebhookDelivery::TABLE_NAME
+        );
+        let result = sqlx::query(&sql)
+            .bind(delivery.webhook_id as i64)
+            .bind(delivery.event.as_str())
+            .bind(&delivery.payload)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid() as u64)
+    }
+
+    async fn find_pending_webhook_delivery(
+        &self,
+        id: u64,
+    ) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+        let sql = format!(
+            r#"SELECT id, webhook_id, event, payload FROM {} WHERE id = ? AND status = 'pending';"#,
+            WebhookDelivery::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(id as i64).fetch_optional(&self.pool))
+            .await?
+            .map(|row| {
+                let event: String = row.try_get("event")?;
+                Ok(WebhookDelivery {
+                    id: Some(get_u64(&row, "id")?),
+                    webhook_id: get_u64(&row, "webhook_id")?,
+                    event: parse_webhook_event(&event)?,
+                    payload: row.try_get("payload")?,
+                })
+            })
+            .transpose()
+    }
+
+    async fn record_webhook_attempt(
+        &self,
+        id: u64,
+        response_status: Option<u16>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let set_status = match error {
+            None => "status = 'delivered', delivered_at = CURRENT_TIMESTAMP,",
+            Some(_) => "",
+        };
+        let sql = format!(
+            r#"
+              UPDATE {} SET {} attempts = attempts + 1, response_status = ?, last_error = ?
+              WHERE id = ?;
+            "#,
+            WebhookDelivery::TABLE_NAME,
+            set_status
+        );
+        sqlx::query(&sql)
+            .bind(response_status)
+            .bind(error)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn dead_letter_webhook_delivery(&self, id: u64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!(
+            r#"UPDATE {} SET status = 'dead_lettered' WHERE id = ? AND status = 'pending';"#,
+            WebhookDelivery::TABLE_NAME
+        );
+        let result = sqlx::query(&sql).bind(id as i64).execute(&mut tx).await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        let sql = format!(
+            r#"
+              INSERT INTO {} (delivery_id, webhook_id, url, event, payload, last_error)
+              SELECT deliveries.id, deliveries.webhook_id, webhooks.url, deliveries.event,
+              deliveries.payload, deliveries.last_error
+              FROM {} AS deliveries
+              INNER JOIN {} AS webhooks ON webhooks.id = deliveries.webhook_id
+              WHERE deliveries.id = ?;
+            "#,
+            WebhookDeadLetter::TABLE_NAME,
+            WebhookDelivery::TABLE_NAME,
+            Webhook::TABLE_NAME
+        );
+        sqlx::query(&sql).bind(id as i64).execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn webhook_deliveries(
+        &self,
+        webhook_id: u64,
+        limit: u64,
+    ) -> Result<Vec<WebhookDeliveryItem>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT id, event, status, attempts, response_status, last_error,
+              strftime('%Y-%m-%d %H:%M:%S', created_at) as created_at,
+              strftime('%Y-%m-%d %H:%M:%S', delivered_at) as delivered_at
+              FROM {} WHERE webhook_id = ? ORDER BY id DESC LIMIT ?;
+            "#,
+            WebhookDelivery::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| {
+                sqlx::query(&sql)
+                    .bind(webhook_id as i64)
+                    .bind(limit as i64)
+                    .fetch_all(&self.pool)
+            })
+            .await?
+            .iter()
+            .map(|row| {
+                let event: String = row.try_get("event")?;
+                let status: String = row.try_get("status")?;
+                Ok(WebhookDeliveryItem {
+                    id: get_u64(row, "id")?,
+                    event: parse_webhook_event(&event)?,
+                    status: parse_webhook_delivery_status(&status)?,
+                    attempts: row.try_get::<i64, _>("attempts")? as u32,
+                    response_status: row
+                        .try_get::<Option<i64>, _>("response_status")?
+                        .map(|status| status as u16),
+                    last_error: row.try_get("last_error")?,
+                    created_at: row.try_get("created_at")?,
+                    delivered_at: row.try_get("delivered_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn webhook_dead_letters(
+        &self,
+        limit: u64,
+    ) -> Result<Vec<WebhookDeadLetter>, sqlx::Error> {
+        let sql = format!(
+            r#"
+              SELECT id, delivery_id, webhook_id, url, event, payload, last_error,
+              strftime('%Y-%m-%d %H:%M:%S', created_at) as created_at
+              FROM {} ORDER BY id DESC LIMIT ?;
+            "#,
+            WebhookDeadLetter::TABLE_NAME
+        );
+        self.read_retry
+            .run(|| sqlx::query(&sql).bind(limit as i64).fetch_all(&self.pool))
+            .await?
+            .iter()
+            .map(|row| {
+                let event: String = row.try_get("event")?;
+                Ok(WebhookDeadLetter {
+                    id: get_u64(row, "id")?,
+                    delivery_id: get_u64(row, "delivery_id")?,
+                    webhook_id: get_u64(row, "webhook_id")?,
+                    url: row.try_get("url")?,
+                    event: parse_webhook_event(&event)?,
+                    payload: row.try_get("payload")?,
+                    last_error: row.try_get("last_error")?,
+                    created_at: row.try_get("created_at")?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[axum::async_trait]
+impl Repository for SqliteRepository {
+    fn primary_only(&self) -> Option<SharedRepository> {
+        self.replica.as_ref()?;
+        Some(Arc::new(Self {
+            replica: None,
+            ..self.clone()
+        }))
+    }
+
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1;").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    // SQLiteはINDEXにもIF NOT EXISTSを宣言できるのでエラーをそのまま返す
+    async fn setup_tables(&self) -> Result<(), sqlx::Error> {
+        self.add_missing_columns().await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/users_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/user_tweets_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/follow_relations_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/follow_requests_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/follower_counts_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/home_timeline_entries_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/media_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/user_tweet_media_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/likes_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/notifications_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/conversations_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/direct_messages_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/reports_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/moderation_logs_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/blocks_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/jobs_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/api_tokens_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/oidc_identities_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/link_previews_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/idempotency_keys_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/lists_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/list_members_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/hashtag_buckets_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/hashtag_cursor_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!("../../sql/ddl/sqlite/webhooks_create.sql"))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/webhook_deliveries_create.sql"
+            ))
+            .await?;
+        self.pool
+            .execute(include_str!(
+                "../../sql/ddl/sqlite/webhook_dead_letters_create.sql"
+            ))
+            .await?;
+        Ok(())
+    }
+}
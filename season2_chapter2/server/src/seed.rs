@@ -0,0 +1,219 @@
+// タイムラインの性能検証用のダミーデータの生成と投入
+// 同じシードからは同じユーザ、フォロー関係、ツイートを生成する
+use crate::models::{FollowRelation, UserTweet};
+use crate::repositories::SharedRepository;
+use chrono::{Duration, NaiveDateTime};
+use rand::rngs::StdRng;
+use rand::{Rng as _, SeedableRng as _};
+use std::collections::HashSet;
+
+// ツイートの投稿日時を散らばらせる期間
+const TWEET_PERIOD_DAYS: i64 = 30;
+// ツイートに付けるハッシュタグの候補
+const HASHTAGS: &[&str] = &[
+    "rust",
+    "ruitter",
+    "axum",
+    "今日のごはん",
+    "週末",
+    "個人開発",
+];
+const JA_SUBJECTS: &[&str] = &[
+    "今日のランチ",
+    "新しいキーボード",
+    "朝の散歩",
+    "週末の勉強会",
+    "近所のカフェ",
+    "積読の山",
+    "リリース作業",
+];
+const JA_PREDICATES: &[&str] = &[
+    "が最高だった",
+    "がなかなか終わらない",
+    "のおかげで元気が出た",
+    "について考えている",
+    "がちょっと気になる",
+    "をみんなにおすすめしたい",
+];
+const EN_SUBJECTS: &[&str] = &[
+    "The new release",
+    "My morning coffee",
+    "This borrow checker error",
+    "Weekend hacking",
+    "The train ride home",
+    "Our standup",
+];
+const EN_PREDICATES: &[&str] = &[
+    "was surprisingly fun",
+    "took way longer than expected",
+    "made my day",
+    "needs more tests",
+    "is finally done",
+    "deserves its own blog post",
+];
+
+#[derive(Clone, Debug)]
+pub struct SeedConfig {
+    pub users: usize,
+    pub tweets: usize,
+    // 1ユーザあたりの平均フォロー数
+    pub follows_per_user: usize,
+    pub seed: u64,
+    // ユーザ名の接頭辞、既存のユーザと重複しないように変えられる
+    pub name_prefix: String,
+}
+
+impl Default for SeedConfig {
+    fn default() -> Self {
+        Self {
+            users: 1_000,
+            tweets: 10_000,
+            follows_per_user: 20,
+            seed: 0,
+            name_prefix: "seed".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SeedTweet {
+    // SeedData::user_namesの添字
+    pub author: usize,
+    pub content: String,
+    // 基準日時から遡る秒数
+    pub seconds_ago: i64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SeedData {
+    pub user_names: Vec<String>,
+    // (フォロワー, フォロイー)のuser_namesの添字
+    pub follows: Vec<(usize, usize)>,
+    // 古い順
+    pub tweets: Vec<SeedTweet>,
+}
+
+// フォロー関係は優先的選択(Barabási–Albertモデル)で作り、フォロワー数がべき分布になるようにする
+// 各ユーザは登録済みのユーザから、フォロワーの多いユーザほど選ばれやすい重みでフォロー先を選ぶ
+pub fn generate(config: &SeedConfig) -> SeedData {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let user_names: Vec<String> = (0..config.users)
+        .map(|i| format!("{}{}", config.name_prefix, i))
+        .collect();
+
+    // ユーザ1人につき1回と、フォロワー1人につき1回ずつ添字を入れる
+    // ここから一様に選ぶとフォロワー数+1に比例した確率で選ばれる
+    let mut weighted: Vec<usize> = Vec::with_capacity(config.users * (config.follows_per_user + 1));
+    let mut follows = Vec::with_capacity(config.users * config.follows_per_user);
+    for follower in 0..config.users {
+        // フォロー数は0から平均の2倍までばらつかせる
+        let count = rng.gen_range(0..=config.follows_per_user * 2).min(follower);
+        let mut followees = HashSet::new();
+        while followees.len() < count {
+            followees.insert(weighted[rng.gen_range(0..weighted.len())]);
+        }
+        let mut followees: Vec<usize> = followees.into_iter().collect();
+        // HashSetの順序は実行ごとに変わるので並べ替えて決定的にする
+        followees.sort_unstable();
+        for followee in followees {
+            follows.push((follower, followee));
+            weighted.push(followee);
+        }
+        weighted.push(follower);
+    }
+
+    // 投稿者もフォロワーの多いユーザほど多く選ばれるようにする
+    let mut tweets: Vec<SeedTweet> = (0..config.tweets)
+        .map(|_| SeedTweet {
+            author: weighted[rng.gen_range(0..weighted.len())],
+            content: tweet_content(&mut rng),
+            seconds_ago: rng.gen_range(0..TWEET_PERIOD_DAYS * 24 * 60 * 60),
+        })
+        .collect();
+    // タイムラインはIDの降順に並ぶので、古いツイートから投入してIDと投稿日時の順序を揃える
+    tweets.sort_by_key(|tweet| -tweet.seconds_ago);
+    SeedData {
+        user_names,
+        follows,
+        tweets,
+    }
+}
+
+// 日本語か英語の短文に、ときどきハッシュタグを付ける
+fn tweet_content(rng: &mut StdRng) -> String {
+    let mut content = if rng.gen_bool(0.5) {
+        format!(
+            "{}{}",
+            JA_SUBJECTS[rng.gen_range(0..JA_SUBJECTS.len())],
+            JA_PREDICATES[rng.gen_range(0..JA_PREDICATES.len())]
+        )
+    } else {
+        format!(
+            "{} {}.",
+            EN_SUBJECTS[rng.gen_range(0..EN_SUBJECTS.len())],
+            EN_PREDICATES[rng.gen_range(0..EN_PREDICATES.len())]
+        )
+    };
+    if rng.gen_bool(0.3) {
+        content.push_str(" #");
+        content.push_str(HASHTAGS[rng.gen_range(0..HASHTAGS.len())]);
+    }
+    content
+}
+
+// 生成したデータをbatch_size行ずつの複数行INSERTで投入する
+// progressには(対象のテーブル名, 投入済みの件数, 全件数)を渡す
+pub async fn insert(
+    repository: &SharedRepository,
+    data: &SeedData,
+    base_time: NaiveDateTime,
+    batch_size: usize,
+    mut progress: impl FnMut(&str, usize, usize),
+) -> anyhow::Result<()> {
+    let batch_size = batch_size.max(1);
+
+    let mut user_ids = Vec::with_capacity(data.user_names.len());
+    for names in data.user_names.chunks(batch_size) {
+        user_ids.extend(repository.bulk_insert_users(names).await?);
+        progress("users", user_ids.len(), data.user_names.len());
+    }
+
+    let mut inserted = 0;
+    for follows in data.follows.chunks(batch_size) {
+        let follow_relations: Vec<FollowRelation> = follows
+            .iter()
+            .map(|(follower, followee)| FollowRelation {
+                id: None,
+                followee_id: user_ids[*followee],
+                follower_id: user_ids[*follower],
+            })
+            .collect();
+        repository
+            .bulk_insert_follow_relations(&follow_relations)
+            .await?;
+        inserted += follows.len();
+        progress("follow_relations", inserted, data.follows.len());
+    }
+
+    let mut inserted = 0;
+    for tweets in data.tweets.chunks(batch_size) {
+        let tweets: Vec<(UserTweet, NaiveDateTime)> = tweets
+            .iter()
+            .map(|tweet| {
+                (
+                    UserTweet {
+                        id: None,
+                        user_id: user_ids[tweet.author],
+                        content: tweet.content.clone(),
+                        in_reply_to_id: None,
+                    },
+                    base_time - Duration::seconds(tweet.seconds_ago),
+                )
+            })
+            .collect();
+        repository.bulk_insert_user_tweets(&tweets).await?;
+        inserted += tweets.len();
+        progress("user_tweets", inserted, data.tweets.len());
+    }
+    Ok(())
+}
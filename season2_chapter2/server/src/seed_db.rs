@@ -0,0 +1,83 @@
+// src/seed_db.rs
+// タイムラインの性能検証用にダミーのユーザ、フォロー関係、ツイートを投入する
+// 使い方: cargo run --release --bin seed_db -- --users 10000 --tweets 100000 --follows 20 --seed 1
+// fan-out-on-writeで検証する場合は投入後にbackfill_timelineを実行する
+use anyhow::{anyhow, bail};
+use chrono::Utc;
+use ruitter::models::{create_tokio_runtime, database_url};
+use ruitter::repositories::connect;
+use ruitter::seed::{generate, insert, SeedConfig};
+use std::io::Write as _;
+use std::time::Instant;
+
+// 1回のINSERTの行数、MySQLとSQLiteのプレースホルダの上限を超えないようにする
+const DEFAULT_BATCH_SIZE: usize = 1_000;
+const USAGE: &str = "usage: seed_db [--users N] [--tweets M] [--follows K] [--seed S] [--prefix NAME] [--batch-size B]";
+
+fn main() -> anyhow::Result<()> {
+    let tokio_rt = create_tokio_runtime();
+    tokio_rt.block_on(run())
+}
+
+async fn run() -> anyhow::Result<()> {
+    let mut config = SeedConfig::default();
+    let mut batch_size = DEFAULT_BATCH_SIZE;
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    for pair in args.chunks(2) {
+        let (flag, value) = match pair {
+            [flag, value] => (flag.as_str(), value),
+            _ => bail!(USAGE),
+        };
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| anyhow!("invalid {}: {}", flag, value))
+        };
+        match flag {
+            "--users" => config.users = number()?,
+            "--tweets" => config.tweets = number()?,
+            "--follows" => config.follows_per_user = number()?,
+            "--seed" => config.seed = number()? as u64,
+            "--prefix" => config.name_prefix = value.clone(),
+            "--batch-size" => batch_size = number()?,
+            _ => bail!(USAGE),
+        }
+    }
+    if config.tweets > 0 && config.users == 0 {
+        bail!("--tweets requires at least one user");
+    }
+
+    // init_dbを実行していないDBにも投入できるようにテーブルを作成する
+    let (repository, session_store) = connect(&database_url()).await?;
+    session_store.migrate().await?;
+    repository.setup_tables().await?;
+
+    let started = Instant::now();
+    let data = generate(&config);
+    println!(
+        "generated {} users, {} follow relations, {} tweets in {:?}",
+        data.user_names.len(),
+        data.follows.len(),
+        data.tweets.len(),
+        started.elapsed()
+    );
+
+    let started = Instant::now();
+    insert(
+        &repository,
+        &data,
+        Utc::now().naive_utc(),
+        batch_size,
+        |table, inserted, total| {
+            // 同じ行を上書きして進捗を表示し、テーブルごとに改行する
+            eprint!("\r{:<16} {:>10}/{:<10}", table, inserted, total);
+            if inserted == total {
+                eprintln!();
+            }
+            std::io::stderr().flush().ok();
+        },
+    )
+    .await?;
+    println!("inserted in {:?}", started.elapsed());
+    Ok(())
+}
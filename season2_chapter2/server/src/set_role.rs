@@ -0,0 +1,34 @@
+// src/set_role.rs
+// ユーザのロールを変更する、最初の管理者はAPIから作れないのでこのコマンドで指定する
+// 使い方: cargo run --bin set_role -- <ユーザ名> <user|moderator|admin>
+use anyhow::{anyhow, bail};
+use ruitter::models::{create_tokio_runtime, database_url, Role};
+use ruitter::repositories::connect;
+
+fn main() -> anyhow::Result<()> {
+    let tokio_rt = create_tokio_runtime();
+    tokio_rt.block_on(run())
+}
+
+async fn run() -> anyhow::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (name, role) = match args.as_slice() {
+        [name, role] => (name, role),
+        _ => bail!("usage: set_role <name> <user|moderator|admin>"),
+    };
+    let role = Role::parse(role).ok_or_else(|| anyhow!("unknown role: {}", role))?;
+    let (repository, _session_store) = connect(&database_url()).await?;
+    let user = repository
+        .find_by_name(name)
+        .await?
+        .ok_or_else(|| anyhow!("user not found: {}", name))?;
+    if !user.can_hold_role(role) {
+        bail!("{} has no password and cannot be {}", name, role.as_str());
+    }
+    // 監査ログには操作者なしで記録される
+    repository
+        .set_user_role(None, user.id.unwrap(), role)
+        .await?;
+    println!("set role of {} to {}", name, role.as_str());
+    Ok(())
+}
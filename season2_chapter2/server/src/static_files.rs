@@ -0,0 +1,62 @@
+// ビルド済みフロントエンドの配信
+// 小規模なデプロイではAPIサーバ1つでフロントエンドも配信できるようにする
+use axum::{
+    body::{boxed, Body},
+    http::{header, HeaderValue, Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::path::PathBuf;
+use tower::ServiceExt as _;
+use tower_http::services::{ServeDir, ServeFile};
+
+// Viteがハッシュ付きのファイル名で出力するディレクトリ、中身が変わればファイル名も変わる
+const HASHED_ASSETS_PREFIX: &str = "/assets/";
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+// index.htmlなどは毎回サーバに更新を確認させる
+const REVALIDATE_CACHE_CONTROL: &str = "no-cache";
+
+// 静的ファイルを返す、存在しないパスはSPAのルーティングに任せるためindex.htmlを返す
+// ただしハッシュ付きのアセットが見つからなければ404を返す、index.htmlを長期キャッシュさせないため
+// 事前に圧縮された.br/.gzファイルがあればAccept-Encodingに応じてそちらを返す
+pub(crate) async fn serve_static(static_dir: PathBuf, request: Request<Body>) -> Response {
+    let path = request.uri().path();
+    // 未定義のAPIはindex.htmlではなく404を返す
+    if path == "/api" || path.starts_with("/api/") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let is_hashed_asset = path.starts_with(HASHED_ASSETS_PREFIX);
+    let serve_dir = ServeDir::new(&static_dir)
+        .precompressed_br()
+        .precompressed_gzip();
+    let result = if is_hashed_asset {
+        serve_dir.oneshot(request).await.map(|res| res.map(boxed))
+    } else {
+        let index_html = ServeFile::new(static_dir.join("index.html"))
+            .precompressed_br()
+            .precompressed_gzip();
+        serve_dir
+            .fallback(index_html)
+            .oneshot(request)
+            .await
+            .map(|res| res.map(boxed))
+    };
+    match result {
+        Ok(mut response) => {
+            // アセットのディレクトリはフォールバックしないので、成功したならアセットそのもの
+            let cache_control = if is_hashed_asset && response.status() == StatusCode::OK {
+                IMMUTABLE_CACHE_CONTROL
+            } else {
+                REVALIDATE_CACHE_CONTROL
+            };
+            let headers = response.headers_mut();
+            headers.insert(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static(cache_control),
+            );
+            // 圧縮済みファイルを出し分けるので中間キャッシュにエンコーディングごとに保存させる
+            headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+            response
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
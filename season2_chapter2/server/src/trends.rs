@@ -0,0 +1,145 @@
+// ハッシュタグのトレンド
+// ツイートのハッシュタグを一定時間ごとのバケットに数えておき、リクエストのたびにツイートを走査しないようにする
+// 期間内の使用回数を、直前の期間の使用回数から見込んだ回数と比べて伸びているタグを求める
+use crate::models::{HashtagBucket, Trend, TrendTweet};
+use crate::render::hashtags;
+use crate::repositories::SharedRepository;
+use std::collections::HashMap;
+
+// バケットの幅(秒)
+pub const BUCKET_SECONDS: i64 = 5 * 60;
+// 見込みの使用回数を求める直前の期間(秒)
+pub const BASELINE_SECONDS: i64 = 7 * 24 * 60 * 60;
+// バケットを残す期間(秒)、最も長い期間とその直前の期間を集計できればよい
+pub const RETENTION_SECONDS: i64 = BASELINE_SECONDS + 24 * 60 * 60 + BUCKET_SECONDS;
+// 1回に読み出して数えるツイートの件数
+const AGGREGATE_BATCH_SIZE: u64 = 1000;
+// 数件の投稿だけで上位にならないよう、期間内にこの回数以上使われたタグだけを返す
+const MIN_TREND_COUNT: u64 = 3;
+// 返すトレンドの件数
+pub const TREND_LIMIT: usize = 10;
+
+// トレンドを求める期間、現在時刻までの直近の期間を比べる
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TrendWindow {
+    #[default]
+    OneHour,
+    OneDay,
+}
+impl TrendWindow {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "1h" => Some(Self::OneHour),
+            "24h" => Some(Self::OneDay),
+            _ => None,
+        }
+    }
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Self::OneHour => 60 * 60,
+            Self::OneDay => 24 * 60 * 60,
+        }
+    }
+}
+
+// 時刻を含むバケットの開始時刻
+pub fn bucket_start(timestamp: i64) -> i64 {
+    timestamp.div_euclid(BUCKET_SECONDS) * BUCKET_SECONDS
+}
+
+// ツイートのハッシュタグをタグとバケットごとに数える
+pub fn count_hashtags(tweets: &[TrendTweet]) -> Vec<HashtagBucket> {
+    let mut counts: HashMap<(String, i64), u64> = HashMap::new();
+    for tweet in tweets {
+        let start = bucket_start(tweet.posted_at);
+        for tag in hashtags(&tweet.content) {
+            *counts.entry((tag, start)).or_default() += 1;
+        }
+    }
+    let mut buckets: Vec<HashtagBucket> = counts
+        .into_iter()
+        .map(|((tag, bucket_start), count)| HashtagBucket {
+            tag,
+            bucket_start,
+            count,
+        })
+        .collect();
+    // 複数のワーカーが同じ行を更新してもデッドロックしないよう、主キーの順に書き込む
+    buckets.sort_by(|a, b| (&a.tag, a.bucket_start).cmp(&(&b.tag, b.bucket_start)));
+    buckets
+}
+
+// 集計済みの位置より後のツイートのハッシュタグをバケットに数え、数えたツイートの件数を返す
+// 位置はツイートIDなので、後から小さいIDでコミットされたツイートは数えないが、トレンドには影響しない程度とみなす
+pub async fn aggregate_hashtags(repository: &SharedRepository) -> Result<u64, sqlx::Error> {
+    let mut aggregated = 0;
+    loop {
+        let cursor = repository.hashtag_cursor().await?;
+        let tweets = repository
+            .trend_tweets(cursor, AGGREGATE_BATCH_SIZE)
+            .await?;
+        let last_id = match tweets.last() {
+            Some(tweet) => tweet.id,
+            None => return Ok(aggregated),
+        };
+        let buckets = count_hashtags(&tweets);
+        // 他のワーカーが先に数えていれば、進んだ位置から読み直す
+        if !repository
+            .add_hashtag_counts(cursor, last_id, &buckets)
+            .await?
+        {
+            continue;
+        }
+        aggregated += tweets.len() as u64;
+        if (tweets.len() as u64) < AGGREGATE_BATCH_SIZE {
+            return Ok(aggregated);
+        }
+    }
+}
+
+// nowまでの期間のトレンドを求める
+pub async fn trends(
+    repository: &SharedRepository,
+    window: TrendWindow,
+    now: i64,
+) -> Result<Vec<Trend>, sqlx::Error> {
+    // 集計中のバケットも含めるので、期間は最大でバケット1つ分短くなる
+    let until = bucket_start(now) + BUCKET_SECONDS;
+    let since = until - window.seconds();
+    let current = repository.hashtag_counts(since, until).await?;
+    let baseline = repository
+        .hashtag_counts(since - BASELINE_SECONDS, since)
+        .await?
+        .into_iter()
+        .collect();
+    Ok(rank_trends(current, &baseline, window))
+}
+
+// 期間内の使用回数と、直前の期間の使用回数から見込んだ回数との差が大きい順に並べる
+pub fn rank_trends(
+    current: Vec<(String, u64)>,
+    baseline: &HashMap<String, u64>,
+    window: TrendWindow,
+) -> Vec<Trend> {
+    let ratio = window.seconds() as f64 / BASELINE_SECONDS as f64;
+    let mut trends: Vec<Trend> = current
+        .into_iter()
+        .filter(|(_, count)| *count >= MIN_TREND_COUNT)
+        .map(|(tag, count)| {
+            let expected = baseline.get(&tag).copied().unwrap_or(0) as f64 * ratio;
+            // 差を見込みの回数の平方根で割り、普段からよく使われるタグが常に上位に来ないようにする
+            let score = (count as f64 - expected) / (expected + 1.0).sqrt();
+            Trend {
+                tag,
+                count,
+                expected,
+                score,
+            }
+        })
+        .filter(|trend| trend.score > 0.0)
+        .collect();
+    trends.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.tag.cmp(&b.tag)));
+    trends.truncate(TREND_LIMIT);
+    trends
+}
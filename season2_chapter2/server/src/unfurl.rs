@@ -0,0 +1,280 @@
+// ツイート中のURLのリンクプレビュー(OpenGraphメタデータ)の取得
+// HTTPの取得はHttpFetcherトレイト経由で行い、テストでは外部に接続しない実装に差し替える
+use crate::models::LinkPreview;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+// 1つのツイートからプレビューを取得するURLの最大件数
+pub const MAX_UNFURL_URLS: usize = 4;
+// 取得するHTMLの最大バイト数、metaタグはhead内にあるので先頭だけで足りる
+const MAX_HTML_BYTES: usize = 512 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REDIRECTS: usize = 3;
+// 保存するメタデータの最大文字数
+const MAX_TITLE_LENGTH: usize = 255;
+const MAX_DESCRIPTION_LENGTH: usize = 1024;
+
+#[axum::async_trait]
+pub trait HttpFetcher {
+    // URLのHTMLを取得する、HTML以外のレスポンスはエラーにする
+    async fn fetch_html(&self, url: &str) -> anyhow::Result<String>;
+}
+
+pub type SharedHttpFetcher = Arc<dyn HttpFetcher + Send + Sync>;
+
+// reqwestで取得する実装
+// ツイートの本文は誰でも書けるので、内部ネットワークのアドレスには接続しない
+pub struct ReqwestFetcher {
+    client: reqwest::Client,
+}
+
+impl ReqwestFetcher {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            // リダイレクト先のアドレスも確かめるため、自動では追わない
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicAddressResolver))
+            .user_agent("ruitter-link-preview")
+            .build()
+            .expect("failed to build http client");
+        Self { client }
+    }
+}
+
+impl Default for ReqwestFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[axum::async_trait]
+impl HttpFetcher for ReqwestFetcher {
+    async fn fetch_html(&self, url: &str) -> anyhow::Result<String> {
+        let mut url = reqwest::Url::parse(url)?;
+        for _ in 0..=MAX_REDIRECTS {
+            ensure_public_host(&url)?;
+            let response = self.client.get(url.clone()).send().await?;
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .ok_or_else(|| anyhow::anyhow!("redirect without location"))?;
+                url = url.join(location)?;
+                continue;
+            }
+            let mut response = response.error_for_status()?;
+            if !response_is_html(&response) {
+                anyhow::bail!("not html");
+            }
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                body.extend_from_slice(&chunk);
+                if body.len() >= MAX_HTML_BYTES {
+                    body.truncate(MAX_HTML_BYTES);
+                    break;
+                }
+            }
+            return Ok(String::from_utf8_lossy(&body).into_owned());
+        }
+        anyhow::bail!("too many redirects")
+    }
+}
+
+fn response_is_html(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"))
+}
+
+// http(s)以外のスキームと、ループバックやプライベートアドレスを直接書いたURLを拒否する
+// ホスト名はPublicAddressResolverが接続時に確かめる
+fn ensure_public_host(url: &reqwest::Url) -> anyhow::Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        anyhow::bail!("unsupported scheme: {}", url.scheme());
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("url without host"))?;
+    // IPv6アドレスは[]で囲まれている
+    if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse() {
+        if !is_public_ip(ip) {
+            anyhow::bail!("non-public address {}", ip);
+        }
+    }
+    Ok(())
+}
+
+// ループバックやプライベートアドレスに解決されるホストを拒否するリゾルバ
+// 確かめたアドレスにそのまま接続するので、確認の後でDNSの応答を変えられても(DNS rebinding)内部に接続しない
+struct PublicAddressResolver;
+
+impl Resolve for PublicAddressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            // ポートは接続時にURLのものに差し替えられる
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+                let message = format!("{} resolves to non-public address {}", name, addr.ip());
+                return Err(message.into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // 100.64.0.0/10(キャリアグレードNAT)
+                || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                // fc00::/7(ユニークローカル)とfe80::/10(リンクローカル)
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(|ip| !is_public_ip(IpAddr::V4(ip))))
+        }
+    }
+}
+
+// HTMLのmetaタグからOpenGraphメタデータを読み出す
+// og:titleがなければtitle要素、og:descriptionがなければdescriptionを使う
+pub fn parse_open_graph(url: &str, html: &str) -> LinkPreview {
+    let mut title = None;
+    let mut fallback_title = None;
+    let mut description = None;
+    let mut fallback_description = None;
+    let mut image_url = None;
+    let lower = html.to_ascii_lowercase();
+    let mut position = 0;
+    while let Some(offset) = lower[position..].find('<') {
+        let start = position + offset;
+        let end = match lower[start..].find('>') {
+            Some(offset) => start + offset,
+            None => break,
+        };
+        let tag = &html[start + 1..end];
+        let name = tag
+            .split(|c: char| c.is_ascii_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "meta" => {
+                let attributes = parse_attributes(tag);
+                let key =
+                    attribute(&attributes, "property").or_else(|| attribute(&attributes, "name"));
+                let content = attribute(&attributes, "content");
+                match (key.map(|key| key.to_ascii_lowercase()).as_deref(), content) {
+                    (Some("og:title"), Some(content)) => title = Some(content),
+                    (Some("og:description"), Some(content)) => description = Some(content),
+                    (Some("description"), Some(content)) => fallback_description = Some(content),
+                    (Some("og:image"), Some(content)) => image_url = Some(content),
+                    _ => {}
+                }
+            }
+            "title" if fallback_title.is_none() => {
+                if let Some(close) = lower[end..].find("</title") {
+                    fallback_title = Some(decode_entities(html[end + 1..end + close].trim()));
+                }
+            }
+            // 本文のmetaタグはOpenGraphではないので読まない
+            "body" => break,
+            _ => {}
+        }
+        position = end + 1;
+    }
+    // og:imageは相対URLのこともあるのでページのURLを基準に解決する
+    let image_url = image_url.and_then(|image_url| {
+        let base = reqwest::Url::parse(url).ok()?;
+        let image_url = base.join(&image_url).ok()?;
+        matches!(image_url.scheme(), "http" | "https").then(|| image_url.to_string())
+    });
+    LinkPreview {
+        url: url.to_string(),
+        title: truncate(title.or(fallback_title), MAX_TITLE_LENGTH),
+        description: truncate(description.or(fallback_description), MAX_DESCRIPTION_LENGTH),
+        image_url,
+    }
+}
+
+// タグ内の属性を(小文字の名前, 値)で返す
+fn parse_attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag
+        .trim_start_matches(|c: char| !c.is_ascii_whitespace())
+        .trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_ascii_whitespace() || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        if let Some(after_equal) = rest.strip_prefix('=') {
+            let after_equal = after_equal.trim_start();
+            let (value, remaining) = match after_equal.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let value_end = after_equal[1..]
+                        .find(quote)
+                        .map_or(after_equal.len(), |i| i + 1);
+                    (
+                        &after_equal[1..value_end],
+                        after_equal.get(value_end + 1..).unwrap_or(""),
+                    )
+                }
+                _ => {
+                    let value_end = after_equal
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or(after_equal.len());
+                    (&after_equal[..value_end], &after_equal[value_end..])
+                }
+            };
+            attributes.push((name, decode_entities(value)));
+            rest = remaining.trim_start();
+        } else {
+            if !name.is_empty() {
+                attributes.push((name, String::new()));
+            }
+            rest = rest.trim_start_matches('/').trim_start();
+        }
+    }
+    attributes
+}
+
+fn attribute(attributes: &[(String, String)], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.clone())
+}
+
+// よく使われる文字参照だけを戻す
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+fn truncate(text: Option<String>, max_length: usize) -> Option<String> {
+    text.map(|text| text.trim().chars().take(max_length).collect::<String>())
+        .filter(|text| !text.is_empty())
+}
@@ -0,0 +1,166 @@
+// 管理者が登録したURLへのイベントのWebhook配信
+// 配信はジョブで行い、失敗すればジョブの再実行の間隔に合わせて再送する
+use crate::models::{DeliverWebhookPayload, Job, JobKind, WebhookDelivery, WebhookEvent};
+use crate::repositories::SharedRepository;
+use chrono::Utc;
+use hmac::{Hmac, Mac as _};
+use rand::RngCore as _;
+use sha2::Sha256;
+use std::time::Duration;
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+// エラーとして保存するレスポンス本文の最大文字数
+const MAX_ERROR_BODY_LENGTH: usize = 255;
+
+pub const EVENT_HEADER: &str = "x-ruitter-event";
+pub const DELIVERY_HEADER: &str = "x-ruitter-delivery";
+pub const TIMESTAMP_HEADER: &str = "x-ruitter-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-ruitter-signature";
+
+// 署名に使う鍵、登録時に一度だけ管理者に返す
+pub fn generate_webhook_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// 受信側が検証する署名、再送を流用されないよう時刻も含めて署名する
+// "sha256=" + hex(HMAC-SHA256(鍵, "{時刻}.{本文}"))
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+// 配信の結果、レスポンスを受け取れなかった場合はstatusがNone
+pub struct DeliveryResult {
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+// reqwestでWebhookを送る
+// 登録できるのは管理者だけなので、unfurlと違って内部ネットワークのアドレスにも送る
+pub struct WebhookClient {
+    client: reqwest::Client,
+}
+
+impl WebhookClient {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .user_agent("ruitter-webhook")
+            .build()
+            .expect("failed to build http client");
+        Self { client }
+    }
+
+    // 2xx以外のレスポンスは失敗として扱う
+    pub async fn deliver(
+        &self,
+        url: &str,
+        secret: &str,
+        delivery_id: u64,
+        event: WebhookEvent,
+        body: &str,
+    ) -> DeliveryResult {
+        let timestamp = Utc::now().timestamp();
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.as_str())
+            .header(DELIVERY_HEADER, delivery_id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, sign(secret, timestamp, body))
+            .body(body.to_string())
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status().is_success() => DeliveryResult {
+                status: Some(response.status().as_u16()),
+                error: None,
+            },
+            Ok(mut response) => {
+                let status = response.status();
+                // 保存するのは先頭だけなので、大きな本文を全部は読まない
+                let mut body = Vec::new();
+                while let Ok(Some(chunk)) = response.chunk().await {
+                    body.extend_from_slice(&chunk);
+                    if body.len() >= MAX_ERROR_BODY_LENGTH {
+                        body.truncate(MAX_ERROR_BODY_LENGTH);
+                        break;
+                    }
+                }
+                let body = String::from_utf8_lossy(&body);
+                DeliveryResult {
+                    status: Some(status.as_u16()),
+                    error: Some(
+                        format!("{}: {}", status, body)
+                            .chars()
+                            .take(MAX_ERROR_BODY_LENGTH)
+                            .collect(),
+                    ),
+                }
+            }
+            Err(e) => DeliveryResult {
+                status: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+impl Default for WebhookClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// イベントを購読しているWebhookごとに配信を記録し、配信ジョブを登録する
+// 元の処理は完了しているので、失敗してもログに出すだけにする
+pub async fn emit(repository: &SharedRepository, event: WebhookEvent, data: serde_json::Value) {
+    let webhooks = match repository.subscribed_webhooks(event).await {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            eprintln!("failed to load webhooks for {}: {}", event.as_str(), e);
+            return;
+        }
+    };
+    if webhooks.is_empty() {
+        return;
+    }
+    let payload = serde_json::json!({
+        "event": event.as_str(),
+        "occurred_at": Utc::now().timestamp(),
+        "data": data,
+    })
+    .to_string();
+    for webhook in webhooks {
+        let delivery = WebhookDelivery {
+            id: None,
+            webhook_id: webhook.id.unwrap(),
+            event,
+            payload: payload.clone(),
+        };
+        let result = match repository.insert_webhook_delivery(&delivery).await {
+            Ok(delivery_id) => {
+                let job = Job::new(
+                    JobKind::DeliverWebhook,
+                    serde_json::to_string(&DeliverWebhookPayload { delivery_id }).unwrap(),
+                    Utc::now().naive_utc(),
+                );
+                repository.enqueue_job(&job).await.map(|_| ())
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!(
+                "failed to enqueue {} for webhook {}: {}",
+                event.as_str(),
+                webhook.id.unwrap(),
+                e
+            );
+        }
+    }
+}
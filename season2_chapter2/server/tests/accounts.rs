@@ -0,0 +1,209 @@
+// パスワード、アカウント削除、データエクスポートのテスト
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use common::{body_json, get_json, log_in, post_json, send, sign_up_and_log_in, test_app};
+
+async fn delete_me(app: &Router, cookie: &str, body: serde_json::Value) -> Response {
+    let request = Request::delete("/api/users/me")
+        .header(header::COOKIE, cookie)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    send(app, request).await
+}
+
+async fn get_status(app: &Router, uri: &str, cookie: &str) -> StatusCode {
+    let request = Request::get(uri)
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap();
+    send(app, request).await.status()
+}
+
+// エクスポートのNDJSONを1行ずつJSONとして読む
+async fn export_lines(response: Response) -> Vec<serde_json::Value> {
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    std::str::from_utf8(&body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+async fn export(app: &Router, cookie: &str) -> Response {
+    let request = Request::get("/api/users/me/export")
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap();
+    send(app, request).await
+}
+
+#[tokio::test]
+async fn password_is_required_once_set() {
+    let app = test_app().await;
+    let body = serde_json::json!({ "name": "alice", "password": "correct horse" });
+    let res = post_json(&app, "/api/users", body, None).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body = serde_json::json!({ "name": "bob", "password": "" });
+    let res = post_json(&app, "/api/users", body, None).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    for body in [
+        serde_json::json!({ "name": "alice" }),
+        serde_json::json!({ "name": "alice", "password": "wrong" }),
+    ] {
+        let res = post_json(&app, "/api/sessions", body, None).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+    log_in(
+        &app,
+        serde_json::json!({ "name": "alice", "password": "correct horse" }),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn deleting_account_removes_data_and_sessions() {
+    let app = test_app().await;
+    let credentials = serde_json::json!({ "name": "alice", "password": "correct horse" });
+    let res = post_json(&app, "/api/users", credentials.clone(), None).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let alice = log_in(&app, credentials.clone()).await;
+    let alice_on_other_device = log_in(&app, credentials.clone()).await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+
+    let tweet = serde_json::json!({ "content": "goodbye" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let follow = serde_json::json!({ "name": "alice" });
+    let res = post_json(&app, "/api/follow_relations", follow, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let res = delete_me(&app, &alice, serde_json::json!({ "password": "wrong" })).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    // 書き込みのできるAPIトークンでも削除はできない
+    let token = serde_json::json!({ "name": "bot", "scopes": ["read", "write"] });
+    let res = post_json(&app, "/api/tokens", token, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let token = body_json(res).await["token"].as_str().unwrap().to_string();
+    let request = Request::delete("/api/users/me")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::json!({ "password": "correct horse" }).to_string(),
+        ))
+        .unwrap();
+    assert_eq!(send(&app, request).await.status(), StatusCode::FORBIDDEN);
+    let res = delete_me(
+        &app,
+        &alice,
+        serde_json::json!({ "password": "correct horse" }),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert!(res.headers()[header::SET_COOKIE]
+        .to_str()
+        .unwrap()
+        .starts_with("axum_session=;"));
+
+    // どの端末のセッションも無効になり、ログインもできない
+    for cookie in [&alice, &alice_on_other_device] {
+        assert_eq!(
+            get_status(&app, "/api/pages/timeline", cookie).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+    let res = post_json(&app, "/api/sessions", credentials, None).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    // ツイートとフォロー関係も消える
+    let timeline = get_json(&app, "/api/pages/timeline", &bob).await;
+    assert_eq!(timeline, serde_json::json!([]));
+    let lines = export_lines(export(&app, &bob).await).await;
+    assert_eq!(
+        lines,
+        vec![serde_json::json!({ "type": "user", "id": 2, "name": "bob" })]
+    );
+}
+
+#[tokio::test]
+async fn account_without_password_cannot_be_deleted() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    // 名前だけでは本人と確認できない
+    let res = delete_me(&app, &alice, serde_json::json!({ "password": "" })).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = delete_me(&app, &alice, serde_json::json!({ "name": "alice" })).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    get_json(&app, "/api/pages/timeline", &alice).await;
+}
+
+#[tokio::test]
+async fn export_contains_tweets_follows_and_likes() {
+    let app = test_app().await;
+    let credentials = serde_json::json!({ "name": "alice", "password": "correct horse" });
+    let res = post_json(&app, "/api/users", credentials.clone(), None).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let alice = log_in(&app, credentials).await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    sign_up_and_log_in(&app, "carol").await;
+
+    let tweet = serde_json::json!({ "content": "from bob" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let reply = serde_json::json!({ "content": "from alice", "in_reply_to_id": 1 });
+    let res = post_json(&app, "/api/user_tweets", reply, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = post_json(
+        &app,
+        "/api/user_tweets/1/likes",
+        serde_json::json!({}),
+        Some(&alice),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    for name in ["bob", "carol"] {
+        let follow = serde_json::json!({ "name": name });
+        let res = post_json(&app, "/api/follow_relations", follow, Some(&alice)).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+    let follow = serde_json::json!({ "name": "alice" });
+    let res = post_json(&app, "/api/follow_relations", follow, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let res = export(&app, &alice).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+    assert_eq!(
+        res.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"ruitter-export-1.ndjson\""
+    );
+    let lines = export_lines(res).await;
+    // パスワードのハッシュは含めない
+    assert_eq!(
+        lines,
+        vec![
+            serde_json::json!({ "type": "user", "id": 1, "name": "alice" }),
+            serde_json::json!({ "type": "tweet", "id": 2, "content": "from alice", "in_reply_to_id": 1 }),
+            serde_json::json!({ "type": "following", "name": "bob" }),
+            serde_json::json!({ "type": "following", "name": "carol" }),
+            serde_json::json!({ "type": "follower", "name": "bob" }),
+            serde_json::json!({ "type": "like", "user_tweet_id": 1 }),
+        ]
+    );
+
+    // 読み取り権限のAPIトークンではエクスポートできない
+    let token = serde_json::json!({ "name": "bot", "scopes": ["read"] });
+    let res = post_json(&app, "/api/tokens", token, Some(&alice)).await;
+    let token = body_json(res).await["token"].as_str().unwrap().to_string();
+    let request = Request::get("/api/users/me/export")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&app, request).await.status(), StatusCode::FORBIDDEN);
+}
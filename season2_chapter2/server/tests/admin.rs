@@ -0,0 +1,246 @@
+// 通報とモデレーションAPIのテスト
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use common::{get_json, log_in, post_json, send, sign_up_and_log_in, test_app_and_repository};
+use ruitter::config::ServerConfig;
+use ruitter::models::Role;
+
+async fn post_status(app: &Router, uri: &str, body: serde_json::Value, cookie: &str) -> StatusCode {
+    post_json(app, uri, body, Some(cookie)).await.status()
+}
+
+async fn get_status(app: &Router, uri: &str, cookie: &str) -> StatusCode {
+    let request = Request::get(uri)
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap();
+    send(app, request).await.status()
+}
+
+#[tokio::test]
+async fn moderators_handle_reports_and_admins_suspend_users() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let admin = sign_up_and_log_in(&app, "alice").await;
+    // モデレーター以上にできるのはパスワードを設定したユーザだけ
+    let credentials = serde_json::json!({ "name": "bob", "password": "secret" });
+    let res = post_json(&app, "/api/users", credentials.clone(), None).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let moderator = log_in(&app, credentials).await;
+    let author = sign_up_and_log_in(&app, "carol").await;
+    let reporter = sign_up_and_log_in(&app, "dave").await;
+    // 最初の管理者はset_roleコマンドと同じくリポジトリから直接指定する
+    assert!(repository
+        .set_user_role(None, 1, Role::Admin)
+        .await
+        .unwrap());
+    let body = serde_json::json!({ "role": "moderator" });
+    let status = post_status(&app, "/api/admin/users/2/role", body.clone(), &moderator).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let status = post_status(&app, "/api/admin/users/3/role", body.clone(), &admin).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let status = post_status(&app, "/api/admin/users/2/role", body, &admin).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let tweet = serde_json::json!({ "content": "spam" });
+    let status = post_status(&app, "/api/user_tweets", tweet, &author).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let follow = serde_json::json!({ "name": "carol" });
+    let status = post_status(&app, "/api/follow_relations", follow, &reporter).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let report = serde_json::json!({ "reason": "spam" });
+    let status = post_status(
+        &app,
+        "/api/user_tweets/1/reports",
+        report.clone(),
+        &reporter,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let status = post_status(
+        &app,
+        "/api/user_tweets/1/reports",
+        report.clone(),
+        &reporter,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let status = post_status(&app, "/api/user_tweets/9/reports", report, &reporter).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let status = get_status(&app, "/api/admin/reports", &reporter).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let reports = get_json(&app, "/api/admin/reports", &moderator).await;
+    assert_eq!(
+        reports,
+        serde_json::json!([{
+            "id": 1,
+            "user_tweet_id": 1,
+            "content": "spam",
+            "author_name": "carol",
+            "reporter_name": "dave",
+            "reason": "spam",
+            "is_hidden": false,
+        }])
+    );
+
+    // 非表示にしたツイートはタイムラインから消える
+    let hide = serde_json::json!({ "hidden": true });
+    let status = post_status(&app, "/api/admin/user_tweets/1/hide", hide, &moderator).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let timeline = get_json(&app, "/api/pages/timeline", &reporter).await;
+    assert_eq!(timeline, serde_json::json!([]));
+    let status = post_status(
+        &app,
+        "/api/admin/reports/1/resolve",
+        serde_json::json!({}),
+        &moderator,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let reports = get_json(&app, "/api/admin/reports", &moderator).await;
+    assert_eq!(reports, serde_json::json!([]));
+
+    // 凍結は管理者のみ、凍結されたユーザは既存のセッションもログインも使えない
+    let suspend = serde_json::json!({ "suspended": true });
+    let status = post_status(
+        &app,
+        "/api/admin/users/3/suspend",
+        suspend.clone(),
+        &moderator,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let status = post_status(&app, "/api/admin/users/3/suspend", suspend.clone(), &admin).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let status = post_status(&app, "/api/admin/users/1/suspend", suspend, &admin).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let tweet = serde_json::json!({ "content": "still here" });
+    let status = post_status(&app, "/api/user_tweets", tweet, &author).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let res = post_json(
+        &app,
+        "/api/sessions",
+        serde_json::json!({ "name": "carol" }),
+        None,
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let unsuspend = serde_json::json!({ "suspended": false });
+    let status = post_status(&app, "/api/admin/users/3/suspend", unsuspend, &admin).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    log_in(&app, serde_json::json!({ "name": "carol" })).await;
+
+    // 全ての操作が監査ログに残る
+    let logs = get_json(&app, "/api/admin/audit_logs", &admin).await;
+    let actions = logs
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|log| {
+            (
+                log["actor_name"].clone(),
+                log["action"].clone(),
+                log["target_id"].clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actions,
+        vec![
+            (
+                serde_json::json!("alice"),
+                serde_json::json!("unsuspend_user"),
+                serde_json::json!(3)
+            ),
+            (
+                serde_json::json!("alice"),
+                serde_json::json!("suspend_user"),
+                serde_json::json!(3)
+            ),
+            (
+                serde_json::json!("bob"),
+                serde_json::json!("resolve_report"),
+                serde_json::json!(1)
+            ),
+            (
+                serde_json::json!("bob"),
+                serde_json::json!("hide_tweet"),
+                serde_json::json!(1)
+            ),
+            (
+                serde_json::json!("alice"),
+                serde_json::json!("change_role"),
+                serde_json::json!(2)
+            ),
+            (
+                serde_json::Value::Null,
+                serde_json::json!("change_role"),
+                serde_json::json!(1)
+            ),
+        ]
+    );
+    assert_eq!(logs[4]["detail"], "moderator");
+}
+
+#[tokio::test]
+async fn passwordless_admin_cannot_log_in_by_name() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    sign_up_and_log_in(&app, "alice").await;
+    // パスワード導入前のユーザがリポジトリから直接管理者にされている場合
+    assert!(repository
+        .set_user_role(None, 1, Role::Admin)
+        .await
+        .unwrap());
+    let res = post_json(
+        &app,
+        "/api/sessions",
+        serde_json::json!({ "name": "alice" }),
+        None,
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn only_visible_tweets_can_be_reported() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let author = sign_up_and_log_in(&app, "alice").await;
+    let follower = sign_up_and_log_in(&app, "bob").await;
+    let stranger = sign_up_and_log_in(&app, "carol").await;
+    let follow = serde_json::json!({ "name": "alice" });
+    let status = post_status(&app, "/api/follow_relations", follow, &follower).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let protect = serde_json::json!({ "protected": true });
+    let status = post_status(&app, "/api/users/me/protected", protect, &author).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    for content in ["protected", "hidden"] {
+        let tweet = serde_json::json!({ "content": content });
+        let status = post_status(&app, "/api/user_tweets", tweet, &author).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    assert!(repository.set_user_tweet_hidden(1, 2, true).await.unwrap());
+
+    // フォロワーでなければ非公開アカウントのツイートは存在しないツイートと区別できない
+    let report = serde_json::json!({ "reason": "spam" });
+    for uri in ["/api/user_tweets/1/reports", "/api/user_tweets/9/reports"] {
+        let status = post_status(&app, uri, report.clone(), &stranger).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+    let status = post_status(
+        &app,
+        "/api/user_tweets/1/reports",
+        report.clone(),
+        &follower,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    // 非表示にされたツイートはフォロワーからも見えない
+    let status = post_status(&app, "/api/user_tweets/2/reports", report, &follower).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,128 @@
+// APIトークンのテスト
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use common::{body_json, get_json, post_json, send, sign_up_and_log_in, test_app};
+
+async fn create_token(app: &Router, cookie: &str, scopes: &[&str]) -> (u64, String) {
+    let body = serde_json::json!({ "name": "bot", "scopes": scopes });
+    let res = post_json(app, "/api/tokens", body, Some(cookie)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body = body_json(res).await;
+    let token = body["token"].as_str().unwrap().to_string();
+    assert!(token.starts_with("rtr_"));
+    (body["id"].as_u64().unwrap(), token)
+}
+
+// Bearerトークンを付けてリクエストを送る
+async fn send_with_token(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    body: Option<serde_json::Value>,
+    token: &str,
+) -> Response {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json");
+    let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+    send(app, request.body(body).unwrap()).await
+}
+
+#[tokio::test]
+async fn token_scopes_limit_requests() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let (_, read_token) = create_token(&app, &alice, &["read"]).await;
+    let (_, write_token) = create_token(&app, &alice, &["write"]).await;
+    let tweet = serde_json::json!({ "content": "hello" });
+
+    // readは参照のみ、writeは参照と更新ができる
+    let res = send_with_token(&app, "GET", "/api/pages/timeline", None, &read_token).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = send_with_token(
+        &app,
+        "POST",
+        "/api/user_tweets",
+        Some(tweet.clone()),
+        &read_token,
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = send_with_token(&app, "POST", "/api/user_tweets", Some(tweet), &write_token).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = send_with_token(&app, "GET", "/api/pages/timeline", None, &write_token).await;
+    assert_eq!(body_json(res).await[0]["content"], "hello");
+
+    let res = send_with_token(&app, "GET", "/api/pages/timeline", None, "rtr_invalid").await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn tokens_are_listed_and_revoked_with_cookie_only() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    let (id, token) = create_token(&app, &alice, &["read", "write"]).await;
+
+    // トークンでトークンを管理することはできない
+    let res = send_with_token(&app, "GET", "/api/tokens", None, &token).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let body = serde_json::json!({ "name": "escalated", "scopes": ["write"] });
+    let res = send_with_token(&app, "POST", "/api/tokens", Some(body), &token).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // 一覧にはトークン本体を含めず、使用日時を記録する
+    let tokens = get_json(&app, "/api/tokens", &alice).await;
+    assert_eq!(tokens.as_array().unwrap().len(), 1);
+    assert_eq!(tokens[0]["name"], "bot");
+    assert_eq!(tokens[0]["scopes"], serde_json::json!(["read", "write"]));
+    assert!(tokens[0]["last_used_at"].is_string());
+    assert!(tokens[0].get("token").is_none());
+    assert_eq!(
+        get_json(&app, "/api/tokens", &bob).await,
+        serde_json::json!([])
+    );
+
+    // 他人のトークンは失効できない
+    let uri = format!("/api/tokens/{}", id);
+    let revoke = |cookie: &str| {
+        Request::delete(&uri)
+            .header(header::COOKIE, cookie)
+            .body(Body::empty())
+            .unwrap()
+    };
+    assert_eq!(
+        send(&app, revoke(&bob)).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        send(&app, revoke(&alice)).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    let res = send_with_token(&app, "GET", "/api/pages/timeline", None, &token).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn invalid_token_params_are_rejected() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    for body in [
+        serde_json::json!({ "name": "", "scopes": ["read"] }),
+        serde_json::json!({ "name": "bot", "scopes": [] }),
+    ] {
+        let res = post_json(&app, "/api/tokens", body, Some(&alice)).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+    let body = serde_json::json!({ "name": "bot" });
+    let res = post_json(&app, "/api/tokens", body, None).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,86 @@
+// 結合テストで共通に使うヘルパ
+// SQLiteのインメモリDBを使うのでMySQLコンテナなしで実行できる
+#![allow(dead_code)]
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use ruitter::config::ServerConfig;
+use ruitter::endpoints::app;
+use ruitter::repositories::{connect, SharedRepository};
+use tower::ServiceExt as _;
+
+pub async fn test_app() -> Router {
+    test_app_with(ServerConfig::default()).await
+}
+
+pub async fn test_app_with(config: ServerConfig) -> Router {
+    test_app_and_repository(config).await.0
+}
+
+// APIを経由せずにデータを用意したいテスト向けに、リポジトリも返す
+pub async fn test_app_and_repository(config: ServerConfig) -> (Router, SharedRepository) {
+    let (repository, session_store) = connect("sqlite::memory:").await.unwrap();
+    session_store.migrate().await.unwrap();
+    repository.setup_tables().await.unwrap();
+    (app(repository.clone(), session_store, config), repository)
+}
+
+// テストごとに独立した一時ディレクトリを作る
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("ruitter_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub async fn send(app: &Router, request: Request<Body>) -> Response {
+    app.clone().oneshot(request).await.unwrap()
+}
+
+// JSONボディ付きのPOSTリクエストを送る
+pub async fn post_json(
+    app: &Router,
+    uri: &str,
+    body: serde_json::Value,
+    cookie: Option<&str>,
+) -> Response {
+    let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+    if let Some(cookie) = cookie {
+        request = request.header(header::COOKIE, cookie);
+    }
+    send(app, request.body(Body::from(body.to_string())).unwrap()).await
+}
+
+// GETリクエストを送り、200が返ることを確認してJSONボディを返す
+pub async fn get_json(app: &Router, uri: &str, cookie: &str) -> serde_json::Value {
+    let request = Request::get(uri)
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap();
+    let res = send(app, request).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    body_json(res).await
+}
+
+pub async fn body_json(response: Response) -> serde_json::Value {
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+// ユーザを作成してログインし、Cookieヘッダの値を返す
+pub async fn sign_up_and_log_in(app: &Router, name: &str) -> String {
+    let body = serde_json::json!({ "name": name });
+    let res = post_json(app, "/api/users", body.clone(), None).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    log_in(app, body).await
+}
+
+// ログインし、Cookieヘッダの値を返す
+pub async fn log_in(app: &Router, body: serde_json::Value) -> String {
+    let res = post_json(app, "/api/sessions", body, None).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let set_cookie = res.headers()[header::SET_COOKIE].to_str().unwrap();
+    set_cookie.split(';').next().unwrap().to_string()
+}
@@ -0,0 +1,186 @@
+// DB接続の再試行と、DBが落ちている状態での起動のテスト
+mod common;
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use common::{get_json, log_in, post_json, send, temp_dir};
+use ruitter::config::{DatabaseConfig, ServerConfig};
+use ruitter::endpoints::app;
+use ruitter::repositories::{connect_with, ReadRetry};
+use sqlx::Executor as _;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+fn fast_retry() -> ReadRetry {
+    ReadRetry {
+        retries: 2,
+        backoff: Duration::from_millis(1),
+    }
+}
+
+#[tokio::test]
+async fn read_retry_retries_only_connection_errors() {
+    // 接続の失敗は回数の上限まで再試行する
+    let attempts = AtomicU32::new(0);
+    let result = fast_retry()
+        .run(|| async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(sqlx::Error::PoolTimedOut),
+                _ => Ok(42),
+            }
+        })
+        .await;
+    assert_eq!(result.unwrap(), 42);
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    let attempts = AtomicU32::new(0);
+    let result: Result<(), _> = fast_retry()
+        .run(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(sqlx::Error::PoolTimedOut)
+        })
+        .await;
+    assert!(matches!(result, Err(sqlx::Error::PoolTimedOut)));
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // 行が見つからないなどのエラーは再試行しない
+    let attempts = AtomicU32::new(0);
+    let result: Result<(), _> = fast_retry()
+        .run(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(sqlx::Error::RowNotFound)
+        })
+        .await;
+    assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn server_starts_degraded_and_recovers() {
+    // まだ存在しないディレクトリのDBファイルは開けないので、DBが落ちている状態として扱う
+    let dir = temp_dir("database").join("not_yet");
+    let url = format!("sqlite:{}", dir.join("ruitter.db").display());
+    let config = DatabaseConfig {
+        acquire_timeout: Duration::from_millis(200),
+        read_retry: fast_retry(),
+        lazy_connect: true,
+        ..DatabaseConfig::default()
+    };
+    let (repository, session_store) = connect_with(&url, None, &config).await.unwrap();
+    let app = app(repository.clone(), session_store, ServerConfig::default());
+
+    let health = || Request::get("/api/health").body(Body::empty()).unwrap();
+    let res = send(&app, health()).await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // DBが戻れば再起動せずに処理できる
+    std::fs::create_dir_all(&dir).unwrap();
+    let res = send(&app, health()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body, serde_json::json!({ "database": "ok" }));
+    repository.setup_tables().await.unwrap();
+    assert!(repository.find_by_name("alice").await.unwrap().is_none());
+}
+
+// リポジトリ導入前のDDLで作ったテーブル
+const BASELINE_SCHEMA: &str = r#"
+  CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(255) NOT NULL
+  );
+  CREATE UNIQUE INDEX users__name ON users (name);
+  CREATE TABLE user_tweets (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    content VARCHAR(140),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+  );
+  CREATE TABLE follow_relations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    followee_id INTEGER NOT NULL,
+    follower_id INTEGER NOT NULL,
+    FOREIGN KEY (followee_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (follower_id) REFERENCES users(id) ON DELETE CASCADE
+  );
+  CREATE UNIQUE INDEX follow_relations__follower_id__followee_id
+    ON follow_relations (follower_id, followee_id);
+  INSERT INTO users (name) VALUES ('alice');
+  INSERT INTO user_tweets (user_id, content) VALUES (1, 'before upgrade');
+"#;
+
+async fn column_names(pool: &sqlx::SqlitePool, table: &str) -> Vec<String> {
+    sqlx::query_scalar("SELECT name FROM pragma_table_info(?);")
+        .bind(table)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn setup_tables_upgrades_baseline_schema() {
+    let path = temp_dir("baseline_schema").join("ruitter.db");
+    let url = format!("sqlite:{}", path.display());
+    let pool = sqlx::SqlitePool::connect_with(
+        url.parse::<sqlx::sqlite::SqliteConnectOptions>()
+            .unwrap()
+            .create_if_missing(true),
+    )
+    .await
+    .unwrap();
+    pool.execute(BASELINE_SCHEMA).await.unwrap();
+
+    let (repository, session_store) = connect_with(&url, None, &DatabaseConfig::default())
+        .await
+        .unwrap();
+    session_store.migrate().await.unwrap();
+    repository.setup_tables().await.unwrap();
+    // 2回目は何も変更しない
+    repository.setup_tables().await.unwrap();
+    assert_eq!(
+        column_names(&pool, "users").await,
+        vec![
+            "id",
+            "name",
+            "password_hash",
+            "role",
+            "is_suspended",
+            "is_protected"
+        ]
+    );
+    assert_eq!(
+        column_names(&pool, "user_tweets").await,
+        vec![
+            "id",
+            "user_id",
+            "content",
+            "in_reply_to_id",
+            "is_hidden",
+            "created_at"
+        ]
+    );
+    let alice = repository.find_by_name("alice").await.unwrap().unwrap();
+    assert!(alice.password_hash.is_none());
+    assert!(!alice.is_suspended);
+
+    let app = app(repository, session_store, ServerConfig::default());
+    let cookie = log_in(&app, serde_json::json!({ "name": "alice" })).await;
+    let tweet = serde_json::json!({ "content": "after upgrade" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&cookie)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let timeline = get_json(&app, "/api/pages/timeline", &cookie).await;
+    let contents: Vec<&str> = timeline
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(contents, vec!["after upgrade", "before upgrade"]);
+    // 追加後に投稿したツイートにも投稿日時が入る
+    let (missing,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM user_tweets WHERE created_at IS NULL;")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(missing, 0);
+}
@@ -0,0 +1,124 @@
+// 相互フォローのユーザ同士のダイレクトメッセージのテスト
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use common::{body_json, get_json, post_json, send, sign_up_and_log_in, test_app};
+
+async fn follow(app: &Router, cookie: &str, name: &str) {
+    let body = serde_json::json!({ "name": name });
+    let res = post_json(app, "/api/follow_relations", body, Some(cookie)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+async fn send_message(app: &Router, cookie: &str, conversation_id: u64, content: &str) {
+    let uri = format!("/api/conversations/{}/messages", conversation_id);
+    let body = serde_json::json!({ "content": content });
+    let res = post_json(app, &uri, body, Some(cookie)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn mutual_followers_can_exchange_messages() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    let carol = sign_up_and_log_in(&app, "carol").await;
+
+    // 片方向のフォローでは会話を始められない
+    follow(&app, &alice, "bob").await;
+    let start = serde_json::json!({ "name": "bob" });
+    let res = post_json(&app, "/api/conversations", start.clone(), Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    follow(&app, &bob, "alice").await;
+    let res = post_json(&app, "/api/conversations", start.clone(), Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let conversation_id = body_json(res).await["id"].as_u64().unwrap();
+    // 相手から始めても同じ会話になる
+    let start_from_bob = serde_json::json!({ "name": "alice" });
+    let res = post_json(&app, "/api/conversations", start_from_bob, Some(&bob)).await;
+    assert_eq!(
+        body_json(res).await["id"].as_u64().unwrap(),
+        conversation_id
+    );
+
+    for i in 0..3 {
+        send_message(&app, &alice, conversation_id, &format!("hello {}", i)).await;
+    }
+    send_message(&app, &bob, conversation_id, "hi alice").await;
+
+    let conversations = get_json(&app, "/api/conversations", &bob).await;
+    assert_eq!(
+        conversations,
+        serde_json::json!([
+            { "id": conversation_id, "partner_name": "alice", "last_message": "hi alice", "unread_count": 3 },
+        ])
+    );
+
+    // ページネーション、新しい順に返る
+    let uri = format!("/api/conversations/{}/messages?limit=2", conversation_id);
+    let page = get_json(&app, &uri, &bob).await;
+    assert_eq!(page[0]["content"], "hi alice");
+    assert_eq!(page[1]["content"], "hello 2");
+    let before_id = page[1]["id"].as_u64().unwrap();
+    let uri = format!(
+        "/api/conversations/{}/messages?limit=2&before_id={}",
+        conversation_id, before_id
+    );
+    let page = get_json(&app, &uri, &bob).await;
+    assert_eq!(page[0]["content"], "hello 1");
+    assert_eq!(page[1]["content"], "hello 0");
+
+    // bobが読んだのでaliceから既読が見える、bobのメッセージはaliceがまだ読んでいない
+    let uri = format!("/api/conversations/{}/messages", conversation_id);
+    let messages = get_json(&app, &uri, &alice).await;
+    assert_eq!(messages[0]["sender_name"], "bob");
+    assert_eq!(messages[0]["is_read"], false);
+    assert_eq!(messages[1]["is_read"], true);
+    let conversations = get_json(&app, "/api/conversations", &bob).await;
+    assert_eq!(conversations[0]["unread_count"], 0);
+
+    // 参加していない会話は見えない
+    let request = Request::get(&uri)
+        .header(header::COOKIE, &carol)
+        .body(Body::empty())
+        .unwrap();
+    let res = send(&app, request).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body = serde_json::json!({ "content": "intruder" });
+    let res = post_json(&app, &uri, body, Some(&carol)).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body = serde_json::json!({ "content": "" });
+    let res = post_json(&app, &uri, body, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn blocked_users_cannot_message_each_other() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    follow(&app, &alice, "bob").await;
+    follow(&app, &bob, "alice").await;
+    let start = serde_json::json!({ "name": "bob" });
+    let res = post_json(&app, "/api/conversations", start.clone(), Some(&alice)).await;
+    let conversation_id = body_json(res).await["id"].as_u64().unwrap();
+    send_message(&app, &alice, conversation_id, "hello").await;
+
+    // 会話を始めた後にブロックされれば、どちらからも送れない
+    let block = serde_json::json!({ "name": "alice" });
+    let res = post_json(&app, "/api/blocks", block, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let uri = format!("/api/conversations/{}/messages", conversation_id);
+    for cookie in [&alice, &bob] {
+        let body = serde_json::json!({ "content": "still there?" });
+        let res = post_json(&app, &uri, body, Some(cookie)).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+    let res = post_json(&app, "/api/conversations", start, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+}
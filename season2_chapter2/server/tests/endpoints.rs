@@ -0,0 +1,142 @@
+// APIの結合テスト
+mod common;
+
+use axum::{http::StatusCode, Router};
+use common::{
+    get_json, post_json, sign_up_and_log_in, test_app, test_app_and_repository, test_app_with,
+};
+use ruitter::config::{ServerConfig, TimelineMode};
+
+#[tokio::test]
+async fn timeline_contains_own_and_followee_tweets() {
+    for timeline_mode in [
+        TimelineMode::FanOutOnRead,
+        TimelineMode::FanOutOnWrite,
+        TimelineMode::Hybrid {
+            follower_threshold: 0,
+        },
+    ] {
+        let config = ServerConfig {
+            timeline_mode,
+            ..ServerConfig::default()
+        };
+        let app = test_app_with(config).await;
+        assert_timeline_contains_own_and_followee_tweets(&app).await;
+    }
+}
+
+async fn assert_timeline_contains_own_and_followee_tweets(app: &Router) {
+    let app = app.clone();
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+
+    let tweet = serde_json::json!({ "content": "hello from bob" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let tweet = serde_json::json!({ "content": "hello from alice" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let follow = serde_json::json!({ "name": "bob" });
+    let res = post_json(&app, "/api/follow_relations", follow, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let tweet = serde_json::json!({ "content": "bob again" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    assert_eq!(
+        timeline,
+        serde_json::json!([
+            {
+                "id": 3,
+                "name": "bob",
+                "content": "bob again",
+                "entities": [],
+                "html": "bob again",
+                "media": [],
+                "link_previews": [],
+            },
+            {
+                "id": 2,
+                "name": "alice",
+                "content": "hello from alice",
+                "entities": [],
+                "html": "hello from alice",
+                "media": [],
+                "link_previews": [],
+            },
+            {
+                "id": 1,
+                "name": "bob",
+                "content": "hello from bob",
+                "entities": [],
+                "html": "hello from bob",
+                "media": [],
+                "link_previews": [],
+            },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn hybrid_timeline_merges_fanned_out_and_popular_tweets() {
+    let config = ServerConfig {
+        timeline_mode: TimelineMode::Hybrid {
+            follower_threshold: 1,
+        },
+        ..ServerConfig::default()
+    };
+    let (app, repository) = test_app_and_repository(config).await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    let carol = sign_up_and_log_in(&app, "carol").await;
+    let dave = sign_up_and_log_in(&app, "dave").await;
+    // bobはフォロワーが1人なので配られ、carolは2人で閾値を超えるので読み出し時に結合される
+    for (follower, name) in [(&alice, "bob"), (&alice, "carol"), (&dave, "carol")] {
+        let follow = serde_json::json!({ "name": name });
+        let res = post_json(&app, "/api/follow_relations", follow, Some(follower)).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+    for (cookie, content) in [(&bob, "from bob"), (&carol, "from carol")] {
+        let tweet = serde_json::json!({ "content": content });
+        let res = post_json(&app, "/api/user_tweets", tweet, Some(cookie)).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    let contents: Vec<&str> = timeline
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tweet| tweet["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(contents, vec!["from carol", "from bob"]);
+    // ホームタイムラインに書き込まれたのはbobのツイートだけ
+    assert_eq!(repository.count_followers(3).await.unwrap(), 2);
+    let stored = repository.home_timeline(1, None).await.unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].content, "from bob");
+    let timeline = get_json(&app, "/api/pages/timeline", &dave).await;
+    assert_eq!(timeline.as_array().unwrap().len(), 1);
+    assert_eq!(timeline[0]["content"], "from carol");
+}
+
+#[tokio::test]
+async fn duplicate_user_and_unknown_login_are_rejected() {
+    let app = test_app().await;
+    sign_up_and_log_in(&app, "alice").await;
+    let body = serde_json::json!({ "name": "alice" });
+    let res = post_json(&app, "/api/users", body, None).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = serde_json::json!({ "name": "nobody" });
+    let res = post_json(&app, "/api/sessions", body, None).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn tweet_requires_login() {
+    let app = test_app().await;
+    let tweet = serde_json::json!({ "content": "anonymous" });
+    let res = post_json(&app, "/api/user_tweets", tweet, None).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
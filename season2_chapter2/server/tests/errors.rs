@@ -0,0 +1,114 @@
+// エラーレスポンスのエラーコードと翻訳のテスト
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use common::{body_json, post_json, send, sign_up_and_log_in, test_app};
+use ruitter::endpoints::errors::ErrorCode;
+use ruitter::i18n::{Locale, Messages};
+
+#[test]
+fn every_error_code_is_translated() {
+    let messages = Messages::load().unwrap();
+    let mut codes: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+    codes.sort_unstable();
+    // 翻訳の抜けと、使われなくなったコードの翻訳の残りを検出する
+    for locale in Locale::ALL {
+        assert_eq!(messages.codes(locale), codes, "{}", locale.as_str());
+    }
+    for code in ErrorCode::ALL {
+        let json = serde_json::to_value(code).unwrap();
+        assert_eq!(json, code.as_str());
+    }
+}
+
+#[test]
+fn locale_is_chosen_from_accept_language() {
+    assert_eq!(Locale::from_accept_language("en-US,en;q=0.9"), Locale::En);
+    assert_eq!(Locale::from_accept_language("ja,en;q=0.8"), Locale::Ja);
+    assert_eq!(
+        Locale::from_accept_language("fr, en;q=0.5, ja;q=0.7"),
+        Locale::Ja
+    );
+    assert_eq!(Locale::from_accept_language("EN;q=0.2, de"), Locale::En);
+    // 対応していない言語だけならデフォルトの日本語
+    assert_eq!(Locale::from_accept_language("fr, de"), Locale::Ja);
+    assert_eq!(Locale::from_accept_language("en;q=0"), Locale::Ja);
+}
+
+async fn error_body(
+    app: &axum::Router,
+    request: Request<Body>,
+    status: StatusCode,
+) -> serde_json::Value {
+    let res = send(app, request).await;
+    assert_eq!(res.status(), status);
+    body_json(res).await
+}
+
+#[tokio::test]
+async fn errors_carry_code_and_localized_message() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+
+    // ハンドラが指定したコード
+    let log_in = |language: Option<&str>| {
+        let mut request =
+            Request::post("/api/sessions").header(header::CONTENT_TYPE, "application/json");
+        if let Some(language) = language {
+            request = request.header(header::ACCEPT_LANGUAGE, language);
+        }
+        request.body(Body::from(r#"{"name":"nobody"}"#)).unwrap()
+    };
+    let body = error_body(&app, log_in(None), StatusCode::BAD_REQUEST).await;
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "code": "invalid_credentials",
+            "message": "ユーザ名またはパスワードが違います",
+        })
+    );
+    let res = send(&app, log_in(Some("en-US,en;q=0.9,ja;q=0.8"))).await;
+    assert_eq!(res.headers()[header::CONTENT_LANGUAGE], "en");
+    assert_eq!(
+        body_json(res).await,
+        serde_json::json!({
+            "code": "invalid_credentials",
+            "message": "The user name or password is incorrect.",
+        })
+    );
+
+    // ステータスコードだけを返すハンドラは汎用のコードになる
+    let request = Request::get("/api/trends")
+        .header(header::ACCEPT_LANGUAGE, "en")
+        .body(Body::empty())
+        .unwrap();
+    let body = error_body(&app, request, StatusCode::UNAUTHORIZED).await;
+    assert_eq!(body["code"], "unauthorized");
+    assert_eq!(body["message"], "You need to log in.");
+
+    // ミドルウェアが返すエラー
+    let tweet = |content: &str| {
+        Request::post("/api/user_tweets")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::COOKIE, &alice)
+            .header("Idempotency-Key", "key-1")
+            .body(Body::from(
+                serde_json::json!({ "content": content }).to_string(),
+            ))
+            .unwrap()
+    };
+    assert_eq!(
+        send(&app, tweet("hello")).await.status(),
+        StatusCode::CREATED
+    );
+    let body = error_body(&app, tweet("another"), StatusCode::UNPROCESSABLE_ENTITY).await;
+    assert_eq!(body["code"], "idempotency_key_reused");
+
+    // axumが返すJSONの解析エラーも同じ形式にする
+    let res = post_json(&app, "/api/users", serde_json::json!({}), None).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body_json(res).await["code"], "unprocessable_entity");
+}
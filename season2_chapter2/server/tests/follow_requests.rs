@@ -0,0 +1,135 @@
+// 非公開アカウントとフォロー申請のテスト
+mod common;
+
+use axum::{http::StatusCode, Router};
+use common::{get_json, post_json, sign_up_and_log_in, test_app, test_app_with};
+use ruitter::config::{ServerConfig, TimelineMode};
+
+async fn protect(app: &Router, cookie: &str) {
+    let body = serde_json::json!({ "protected": true });
+    let res = post_json(app, "/api/users/me/protected", body, Some(cookie)).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+}
+
+async fn follow(app: &Router, cookie: &str, name: &str) -> StatusCode {
+    let body = serde_json::json!({ "name": name });
+    post_json(app, "/api/follow_relations", body, Some(cookie))
+        .await
+        .status()
+}
+
+async fn timeline_contents(app: &Router, cookie: &str) -> Vec<String> {
+    get_json(app, "/api/pages/timeline", cookie)
+        .await
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["content"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn following_protected_account_requires_approval() {
+    for timeline_mode in [TimelineMode::FanOutOnRead, TimelineMode::FanOutOnWrite] {
+        let config = ServerConfig {
+            timeline_mode,
+            ..ServerConfig::default()
+        };
+        let app = test_app_with(config).await;
+        let alice = sign_up_and_log_in(&app, "alice").await;
+        let bob = sign_up_and_log_in(&app, "bob").await;
+        protect(&app, &alice).await;
+        let tweet = serde_json::json!({ "content": "protected tweet" });
+        let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        assert_eq!(follow(&app, &bob, "alice").await, StatusCode::ACCEPTED);
+        // 承認されるまではフォロワーではないのでツイートが見えない
+        assert!(timeline_contents(&app, &bob).await.is_empty());
+        let res = post_json(
+            &app,
+            "/api/user_tweets/1/likes",
+            serde_json::json!({}),
+            Some(&bob),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let reply = serde_json::json!({ "content": "reply", "in_reply_to_id": 1 });
+        let res = post_json(&app, "/api/user_tweets", reply, Some(&bob)).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let requests = get_json(&app, "/api/follow_requests", &alice).await;
+        assert_eq!(requests, serde_json::json!([{ "id": 1, "name": "bob" }]));
+        let res = post_json(
+            &app,
+            "/api/follow_requests/1/approve",
+            serde_json::json!({}),
+            Some(&alice),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            get_json(&app, "/api/follow_requests", &alice).await,
+            serde_json::json!([])
+        );
+
+        // 承認後はフォロー前のツイートも見える
+        assert_eq!(timeline_contents(&app, &bob).await, vec!["protected tweet"]);
+        let res = post_json(
+            &app,
+            "/api/user_tweets/1/likes",
+            serde_json::json!({}),
+            Some(&bob),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(follow(&app, &bob, "alice").await, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn follow_request_can_be_rejected_only_by_target() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    protect(&app, &alice).await;
+    assert_eq!(follow(&app, &bob, "alice").await, StatusCode::ACCEPTED);
+    // 申請の重複は1件にまとめる
+    assert_eq!(follow(&app, &bob, "alice").await, StatusCode::ACCEPTED);
+    assert_eq!(
+        get_json(&app, "/api/follow_requests", &alice)
+            .await
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // 申請した本人は承認も拒否もできない
+    for action in ["approve", "reject"] {
+        let uri = format!("/api/follow_requests/1/{}", action);
+        let res = post_json(&app, &uri, serde_json::json!({}), Some(&bob)).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+    let res = post_json(
+        &app,
+        "/api/follow_requests/1/reject",
+        serde_json::json!({}),
+        Some(&alice),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let res = post_json(
+        &app,
+        "/api/follow_requests/1/approve",
+        serde_json::json!({}),
+        Some(&alice),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // 公開に戻せば申請なしでフォローできる
+    let body = serde_json::json!({ "protected": false });
+    post_json(&app, "/api/users/me/protected", body, Some(&alice)).await;
+    assert_eq!(follow(&app, &bob, "alice").await, StatusCode::CREATED);
+}
@@ -0,0 +1,128 @@
+// Idempotency-Keyヘッダによる再送検出のテスト
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use common::{body_json, get_json, send, sign_up_and_log_in, test_app};
+
+async fn post_with_key(
+    app: &Router,
+    uri: &str,
+    body: serde_json::Value,
+    cookie: &str,
+    key: &str,
+) -> Response {
+    let request = Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::COOKIE, cookie)
+        .header("Idempotency-Key", key)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    send(app, request).await
+}
+
+fn replayed(res: &Response) -> bool {
+    res.headers().contains_key("idempotent-replayed")
+}
+
+#[tokio::test]
+async fn retried_tweet_is_created_once() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let tweet = serde_json::json!({ "content": "hello" });
+
+    let res = post_with_key(&app, "/api/user_tweets", tweet.clone(), &alice, "key-1").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert!(!replayed(&res));
+    let res = post_with_key(&app, "/api/user_tweets", tweet.clone(), &alice, "key-1").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert!(replayed(&res));
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    assert_eq!(timeline.as_array().unwrap().len(), 1);
+
+    // キーが違えば別のリクエストとして処理する
+    let res = post_with_key(&app, "/api/user_tweets", tweet, &alice, "key-2").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    assert_eq!(timeline.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn replayed_response_has_original_body() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let body = serde_json::json!({ "name": "cli", "scopes": ["read"] });
+
+    let res = post_with_key(&app, "/api/tokens", body.clone(), &alice, "token").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let created = body_json(res).await;
+    let res = post_with_key(&app, "/api/tokens", body, &alice, "token").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+    assert_eq!(body_json(res).await, created);
+    let tokens = get_json(&app, "/api/tokens", &alice).await;
+    assert_eq!(tokens.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn replayed_response_has_original_headers() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let body = serde_json::json!({ "name": "alice" });
+
+    // ログインし直すとSet-Cookieで新しいセッションが返る
+    let res = post_with_key(&app, "/api/sessions", body.clone(), &alice, "login").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let cookie = res.headers()[header::SET_COOKIE].clone();
+    let res = post_with_key(&app, "/api/sessions", body, &alice, "login").await;
+    assert!(replayed(&res));
+    assert_eq!(res.headers()[header::SET_COOKIE], cookie);
+    assert!(res.headers().get(header::TRANSFER_ENCODING).is_none());
+}
+
+#[tokio::test]
+async fn reused_key_for_other_request_is_rejected() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    let tweet = serde_json::json!({ "content": "hello" });
+    let res = post_with_key(&app, "/api/user_tweets", tweet, &alice, "key").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let other_tweet = serde_json::json!({ "content": "other" });
+    let res = post_with_key(&app, "/api/user_tweets", other_tweet.clone(), &alice, "key").await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let follow = serde_json::json!({ "name": "bob" });
+    let res = post_with_key(&app, "/api/follow_relations", follow, &alice, "key").await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // クエリ文字列だけが違うリクエストも別のリクエスト
+    let tweet = serde_json::json!({ "content": "hello" });
+    let res = post_with_key(&app, "/api/user_tweets?draft=1", tweet, &alice, "key").await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // キーはユーザごとに別
+    let res = post_with_key(&app, "/api/user_tweets", other_tweet, &bob, "key").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert!(!replayed(&res));
+
+    let res = post_with_key(&app, "/api/user_tweets", serde_json::json!({}), &alice, "").await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn oversized_request_is_rejected_without_reserving_key() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let tweet = serde_json::json!({ "content": "a".repeat(6 * 1024 * 1024) });
+    let res = post_with_key(&app, "/api/user_tweets", tweet, &alice, "key").await;
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let tweet = serde_json::json!({ "content": "hello" });
+    let res = post_with_key(&app, "/api/user_tweets", tweet, &alice, "key").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert!(!replayed(&res));
+}
@@ -0,0 +1,165 @@
+// 予約投稿とバックグラウンドジョブのテスト
+mod common;
+
+use async_session::MemoryStore;
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::{get_json, post_json, sign_up_and_log_in, test_app_and_repository};
+use ruitter::config::{ServerConfig, TimelineMode};
+use ruitter::jobs::JobRunner;
+use ruitter::models::{Job, JobKind, UserTweet};
+use ruitter::repositories::AppSessionStore;
+
+#[tokio::test]
+async fn scheduled_tweet_is_published_once_when_due() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let scheduled_at = Utc::now() + Duration::hours(1);
+    let tweet = serde_json::json!({ "content": "later", "scheduled_at": scheduled_at });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    // 過去の日時を指定した場合は即時に投稿する
+    let past = Utc::now() - Duration::hours(1);
+    let tweet = serde_json::json!({ "content": "now", "scheduled_at": past });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let runner = JobRunner::new(
+        repository.clone(),
+        AppSessionStore::Memory(MemoryStore::new()),
+        TimelineMode::default(),
+    );
+    let now = Utc::now().naive_utc();
+    assert_eq!(runner.run_due_jobs(now).await.unwrap(), 0);
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    assert_eq!(timeline.as_array().unwrap().len(), 1);
+
+    let later = now + Duration::hours(2);
+    assert_eq!(runner.run_due_jobs(later).await.unwrap(), 1);
+    assert_eq!(runner.run_due_jobs(later).await.unwrap(), 0);
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    assert_eq!(
+        timeline[0],
+        serde_json::json!({
+            "id": 2,
+            "name": "alice",
+            "content": "later",
+            "entities": [],
+            "html": "later",
+            "media": [],
+            "link_previews": [],
+        })
+    );
+    assert_eq!(timeline.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn scheduled_tweet_of_suspended_author_is_not_published() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let scheduled_at = Utc::now() + Duration::hours(1);
+    let tweet = serde_json::json!({ "content": "later", "scheduled_at": scheduled_at });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    assert!(repository.set_user_suspended(1, 1, true).await.unwrap());
+
+    let runner = JobRunner::new(
+        repository.clone(),
+        AppSessionStore::Memory(MemoryStore::new()),
+        TimelineMode::default(),
+    );
+    // 公開せずに完了させるので、再実行もされない
+    let later = Utc::now().naive_utc() + Duration::hours(2);
+    assert_eq!(runner.run_due_jobs(later).await.unwrap(), 1);
+    assert_eq!(repository.find_user_tweet(1).await.unwrap(), None);
+    let much_later = later + Duration::days(1);
+    assert_eq!(runner.run_due_jobs(much_later).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn jobs_are_claimed_and_published_only_once() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    sign_up_and_log_in(&app, "alice").await;
+    let now = Utc::now().naive_utc();
+    let job = Job::new(JobKind::PublishScheduledTweet, "{}".to_string(), now);
+    assert!(repository.enqueue_job(&job).await.unwrap());
+
+    let lease = now + Duration::minutes(5);
+    let claimed = repository.claim_due_jobs(now, lease, 10).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].attempts, 1);
+    // 実行中のジョブは他のワーカーから取得できない
+    assert!(repository
+        .claim_due_jobs(now, lease, 10)
+        .await
+        .unwrap()
+        .is_empty());
+    // 期限を過ぎても終わっていなければ、ワーカーが落ちたとみなして再取得できる
+    let reclaimed = repository.claim_due_jobs(lease, lease, 10).await.unwrap();
+    assert_eq!(reclaimed.len(), 1);
+
+    let tweet = UserTweet {
+        id: None,
+        user_id: 1,
+        content: "once".to_string(),
+        in_reply_to_id: None,
+    };
+    // 取得し直されたジョブは、最初に取得したワーカーからは更新できない
+    let stale = repository
+        .publish_scheduled_tweet(&claimed[0], &tweet, &[])
+        .await;
+    assert_eq!(stale.unwrap(), None);
+    assert!(!repository.complete_job(&claimed[0]).await.unwrap());
+    let first = repository
+        .publish_scheduled_tweet(&reclaimed[0], &tweet, &[])
+        .await;
+    assert_eq!(first.unwrap(), Some(1));
+    let second = repository
+        .publish_scheduled_tweet(&reclaimed[0], &tweet, &[])
+        .await;
+    assert_eq!(second.unwrap(), None);
+}
+
+#[tokio::test]
+async fn recurring_jobs_are_deduplicated_by_unique_key() {
+    let (_app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let now = Utc::now().naive_utc();
+    let mut job = Job::new(JobKind::CleanupSessions, "{}".to_string(), now);
+    job.unique_key = Some("cleanup_sessions:0".to_string());
+    assert!(repository.enqueue_job(&job).await.unwrap());
+    assert!(!repository.enqueue_job(&job).await.unwrap());
+
+    // 定期ジョブは実行後に次回の分を登録する
+    let runner = JobRunner::new(
+        repository.clone(),
+        AppSessionStore::Memory(MemoryStore::new()),
+        TimelineMode::default(),
+    );
+    assert_eq!(runner.run_due_jobs(now).await.unwrap(), 1);
+    let next_hour = now + Duration::hours(1);
+    assert_eq!(runner.run_due_jobs(next_hour).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn recurring_job_skips_intervals_missed_while_stopped() {
+    let (_app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let now = Utc::now().naive_utc();
+    // 3時間止まっていた間に実行されなかった5分ごとの集計
+    let job = Job::new(
+        JobKind::AggregateTrends,
+        "{}".to_string(),
+        now - Duration::hours(3),
+    );
+    assert!(repository.enqueue_job(&job).await.unwrap());
+
+    let runner = JobRunner::new(
+        repository.clone(),
+        AppSessionStore::Memory(MemoryStore::new()),
+        TimelineMode::default(),
+    );
+    assert_eq!(runner.run_due_jobs(now).await.unwrap(), 1);
+    // 逃した回を順に実行し直さず、次回はnowより後になる
+    assert_eq!(runner.run_due_jobs(now).await.unwrap(), 0);
+    let next = now + Duration::minutes(5);
+    assert_eq!(runner.run_due_jobs(next).await.unwrap(), 1);
+}
@@ -0,0 +1,141 @@
+// リンクプレビュー取得ジョブのテスト
+// 外部サイトには接続せず、HttpFetcherをスタブに差し替える
+mod common;
+
+use async_session::MemoryStore;
+use axum::http::StatusCode;
+use chrono::Utc;
+use common::{get_json, post_json, sign_up_and_log_in, test_app_and_repository};
+use ruitter::config::{ServerConfig, TimelineMode};
+use ruitter::jobs::JobRunner;
+use ruitter::repositories::{AppSessionStore, SharedRepository};
+use ruitter::unfurl::{HttpFetcher, ReqwestFetcher};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// URLごとに決まったHTMLを返し、取得したURLを記録する
+#[derive(Default)]
+struct StubFetcher {
+    pages: HashMap<String, String>,
+    fetched: Mutex<Vec<String>>,
+}
+
+#[axum::async_trait]
+impl HttpFetcher for StubFetcher {
+    async fn fetch_html(&self, url: &str) -> anyhow::Result<String> {
+        self.fetched.lock().unwrap().push(url.to_string());
+        self.pages
+            .get(url)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("not found"))
+    }
+}
+
+fn job_runner(repository: &SharedRepository, fetcher: Arc<StubFetcher>) -> JobRunner {
+    JobRunner::new(
+        repository.clone(),
+        AppSessionStore::Memory(MemoryStore::new()),
+        TimelineMode::default(),
+    )
+    .with_link_fetcher(fetcher)
+}
+
+#[tokio::test]
+async fn link_previews_are_fetched_and_attached_to_timeline() {
+    let config = ServerConfig {
+        link_previews: true,
+        ..ServerConfig::default()
+    };
+    let (app, repository) = test_app_and_repository(config).await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let fetcher = Arc::new(StubFetcher {
+        pages: HashMap::from([(
+            "https://example.com/post".to_string(),
+            r#"<meta property="og:title" content="Post"><meta property="og:description" content="About">"#
+                .to_string(),
+        )]),
+        ..StubFetcher::default()
+    });
+    let runner = job_runner(&repository, fetcher.clone());
+
+    let content = "see https://example.com/post and https://example.com/post";
+    let tweet = serde_json::json!({ "content": content });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    // 取得前はプレビューなし
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    assert_eq!(timeline[0]["link_previews"], serde_json::json!([]));
+    assert_eq!(timeline[0]["entities"].as_array().unwrap().len(), 2);
+
+    assert_eq!(
+        runner.run_due_jobs(Utc::now().naive_utc()).await.unwrap(),
+        1
+    );
+    // 同じURLは1回だけ取得する
+    assert_eq!(fetcher.fetched.lock().unwrap().len(), 1);
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    assert_eq!(
+        timeline[0]["link_previews"],
+        serde_json::json!([{
+            "url": "https://example.com/post",
+            "title": "Post",
+            "description": "About",
+            "image_url": null,
+        }])
+    );
+
+    // 取得済みのURLは別のツイートでも取得し直さない
+    let tweet = serde_json::json!({ "content": "again https://example.com/post" });
+    post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(
+        runner.run_due_jobs(Utc::now().naive_utc()).await.unwrap(),
+        1
+    );
+    assert_eq!(fetcher.fetched.lock().unwrap().len(), 1);
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    assert_eq!(timeline[0]["link_previews"][0]["title"], "Post");
+}
+
+#[tokio::test]
+async fn link_previews_are_not_fetched_when_disabled() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let fetcher = Arc::new(StubFetcher::default());
+    let runner = job_runner(&repository, fetcher.clone());
+
+    let tweet = serde_json::json!({ "content": "https://example.com/" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert_eq!(
+        runner.run_due_jobs(Utc::now().naive_utc()).await.unwrap(),
+        0
+    );
+    assert!(fetcher.fetched.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn reqwest_fetcher_refuses_internal_addresses() {
+    let router = axum::Router::new().route(
+        "/",
+        axum::routing::get(|| async { axum::response::Html("<title>internal</title>") }),
+    );
+    let server =
+        axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(router.into_make_service());
+    let port = server.local_addr().port();
+    tokio::spawn(server);
+
+    let fetcher = ReqwestFetcher::new();
+    // アドレスを直接書いたURLと、接続時にループバックに解決されるホスト名のどちらも拒否する
+    for url in [
+        format!("http://127.0.0.1:{}/", port),
+        format!("http://localhost:{}/", port),
+    ] {
+        let error = fetcher.fetch_html(&url).await.unwrap_err();
+        assert!(
+            format!("{:?}", error).contains("non-public address"),
+            "{}: {:?}",
+            url,
+            error
+        );
+    }
+}
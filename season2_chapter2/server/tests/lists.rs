@@ -0,0 +1,163 @@
+// リストのテスト
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use common::{body_json, get_json, post_json, send, sign_up_and_log_in, test_app};
+
+async fn tweet(app: &Router, cookie: &str, content: &str) {
+    let body = serde_json::json!({ "content": content });
+    let res = post_json(app, "/api/user_tweets", body, Some(cookie)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+async fn add_member(app: &Router, cookie: &str, list_id: u64, name: &str) -> StatusCode {
+    let uri = format!("/api/lists/{}/members", list_id);
+    let body = serde_json::json!({ "name": name });
+    post_json(app, &uri, body, Some(cookie)).await.status()
+}
+
+async fn list_timeline_contents(app: &Router, cookie: &str, list_id: u64) -> Vec<String> {
+    get_json(app, &format!("/api/lists/{}/timeline", list_id), cookie)
+        .await
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["content"].as_str().unwrap().to_string())
+        .collect()
+}
+
+async fn request(app: &Router, method: &str, uri: &str, body: &str, cookie: &str) -> StatusCode {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::COOKIE, cookie)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    send(app, request).await.status()
+}
+
+#[tokio::test]
+async fn list_crud_and_members() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    sign_up_and_log_in(&app, "carol").await;
+
+    let body = serde_json::json!({ "name": "  " });
+    let res = post_json(&app, "/api/lists", body, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = serde_json::json!({ "name": " friends " });
+    let res = post_json(&app, "/api/lists", body, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let list_id = body_json(res).await["id"].as_u64().unwrap();
+
+    assert_eq!(
+        add_member(&app, &alice, list_id, "bob").await,
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        add_member(&app, &alice, list_id, "bob").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        add_member(&app, &alice, list_id, "carol").await,
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        add_member(&app, &alice, list_id, "nobody").await,
+        StatusCode::BAD_REQUEST
+    );
+    // 他のユーザのリストは存在しないものとして扱う
+    assert_eq!(
+        add_member(&app, &bob, list_id, "carol").await,
+        StatusCode::NOT_FOUND
+    );
+
+    let uri = format!("/api/lists/{}", list_id);
+    assert_eq!(
+        request(&app, "PUT", &uri, r#"{"name":"close friends"}"#, &alice).await,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        request(&app, "PUT", &uri, r#"{"name":"mine"}"#, &bob).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        get_json(&app, "/api/lists", &alice).await,
+        serde_json::json!([{ "id": list_id, "name": "close friends", "member_count": 2 }])
+    );
+    assert_eq!(
+        get_json(&app, "/api/lists", &bob).await,
+        serde_json::json!([])
+    );
+
+    let member_uri = format!("/api/lists/{}/members/carol", list_id);
+    assert_eq!(
+        request(&app, "DELETE", &member_uri, "", &alice).await,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        request(&app, "DELETE", &member_uri, "", &alice).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        get_json(&app, &format!("/api/lists/{}/members", list_id), &alice).await,
+        serde_json::json!([{ "id": 2, "name": "bob" }])
+    );
+
+    assert_eq!(
+        request(&app, "DELETE", &uri, "", &bob).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        request(&app, "DELETE", &uri, "", &alice).await,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        get_json(&app, "/api/lists", &alice).await,
+        serde_json::json!([])
+    );
+}
+
+#[tokio::test]
+async fn list_timeline_shows_members_tweets() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    let carol = sign_up_and_log_in(&app, "carol").await;
+    let dave = sign_up_and_log_in(&app, "dave").await;
+    let body = serde_json::json!({ "protected": true });
+    let res = post_json(&app, "/api/users/me/protected", body, Some(&dave)).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    tweet(&app, &bob, "bob tweet").await;
+    tweet(&app, &carol, "carol tweet").await;
+    tweet(&app, &dave, "dave tweet").await;
+    tweet(&app, &bob, "bob second tweet").await;
+
+    let body = serde_json::json!({ "name": "news" });
+    let res = post_json(&app, "/api/lists", body, Some(&alice)).await;
+    let list_id = body_json(res).await["id"].as_u64().unwrap();
+    add_member(&app, &alice, list_id, "bob").await;
+    add_member(&app, &alice, list_id, "dave").await;
+
+    // フォローしていなくてもメンバーのツイートは見えるが、非公開アカウントは見えない
+    assert_eq!(
+        list_timeline_contents(&app, &alice, list_id).await,
+        vec!["bob second tweet", "bob tweet"]
+    );
+    let res = send(
+        &app,
+        Request::get(format!("/api/lists/{}/timeline", list_id))
+            .header(header::COOKIE, &bob)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
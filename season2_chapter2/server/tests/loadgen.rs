@@ -0,0 +1,69 @@
+// 負荷試験の配分と集計のテスト
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+use ruitter::loadgen::{percentile, summarize, Action, ActionMix, Sample};
+use std::time::Duration;
+
+#[test]
+fn action_mix_parse_and_pick() {
+    assert_eq!(
+        ActionMix::parse("timeline=80,post=15,follow=5").unwrap(),
+        ActionMix::default()
+    );
+    assert!(ActionMix::parse("timeline=1,like=1").is_err());
+    assert!(ActionMix::parse("timeline=1,timeline=2").is_err());
+    assert!(ActionMix::parse("timeline=0").is_err());
+    assert!(ActionMix::parse("timeline").is_err());
+
+    let mix = ActionMix::parse("timeline=3,post=1").unwrap();
+    let mut rng = StdRng::seed_from_u64(1);
+    let picks: Vec<Action> = (0..4000).map(|_| mix.pick(&mut rng)).collect();
+    let timelines = picks.iter().filter(|a| **a == Action::Timeline).count();
+    assert!(!picks.contains(&Action::Follow));
+    assert!((2800..3200).contains(&timelines), "{}", timelines);
+}
+
+#[test]
+fn summarize_reports_percentiles_and_errors() {
+    let ms = Duration::from_millis;
+    let sorted: Vec<Duration> = (1..=100).map(ms).collect();
+    assert_eq!(percentile(&sorted, 50.0), ms(50));
+    assert_eq!(percentile(&sorted, 99.0), ms(99));
+    assert_eq!(percentile(&sorted[..1], 99.0), ms(1));
+    assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+
+    let mut samples: Vec<Sample> = (1..=10)
+        .map(|i| Sample {
+            action: Action::Timeline,
+            latency: ms(i),
+            status: Some(200),
+        })
+        .collect();
+    samples.push(Sample {
+        action: Action::Post,
+        latency: ms(5),
+        status: Some(201),
+    });
+    samples.push(Sample {
+        action: Action::Post,
+        latency: ms(7),
+        status: Some(503),
+    });
+    samples.push(Sample {
+        action: Action::Post,
+        latency: ms(10_000),
+        status: None,
+    });
+
+    let summaries = summarize(&samples);
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(summaries[0].action, Action::Timeline);
+    assert_eq!(summaries[0].count, 10);
+    assert_eq!(summaries[0].errors, 0);
+    assert_eq!(summaries[0].p50, ms(5));
+    assert_eq!(summaries[0].p90, ms(9));
+    assert_eq!(summaries[0].max, ms(10));
+    assert_eq!(summaries[1].errors, 2);
+    assert_eq!(summaries[1].statuses, vec![(0, 1), (201, 1), (503, 1)]);
+    assert!((summaries[1].error_rate() - 2.0 / 3.0).abs() < 1e-9);
+}
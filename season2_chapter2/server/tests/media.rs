@@ -0,0 +1,212 @@
+// メディアのアップロードとツイートへの添付のテスト
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use common::{
+    body_json, get_json, post_json, send, sign_up_and_log_in, temp_dir, test_app_and_repository,
+    test_app_with,
+};
+use ruitter::config::ServerConfig;
+use ruitter::models::Role;
+
+const BOUNDARY: &str = "ruitter-test-boundary";
+
+fn png_bytes() -> Vec<u8> {
+    let image = image::RgbImage::from_pixel(800, 400, image::Rgb([0, 128, 255]));
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+    bytes.into_inner()
+}
+
+async fn upload(
+    app: &Router,
+    cookie: &str,
+    content_type: &str,
+    bytes: &[u8],
+) -> axum::response::Response {
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"upload\"\r\nContent-Type: {}\r\n\r\n",
+        BOUNDARY, content_type
+    )
+    .into_bytes();
+    body.extend_from_slice(bytes);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+    let request = Request::post("/api/media")
+        .header(header::COOKIE, cookie)
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(Body::from(body))
+        .unwrap();
+    send(app, request).await
+}
+
+#[tokio::test]
+async fn uploaded_media_appears_in_timeline() {
+    let media_dir = temp_dir("media");
+    let app = test_app_with(ServerConfig {
+        media_dir: media_dir.clone(),
+        ..ServerConfig::default()
+    })
+    .await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+
+    let res = upload(&app, &alice, "image/png", &png_bytes()).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let media = body_json(res).await;
+    let media_id = media["id"].as_u64().unwrap();
+
+    // サムネイルは長辺320pxに縮小される
+    let request = Request::get(media["thumbnail_url"].as_str().unwrap())
+        .header(header::COOKIE, &alice)
+        .body(Body::empty())
+        .unwrap();
+    let res = send(&app, request).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "image/png");
+    let thumbnail = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let thumbnail = image::load_from_memory(&thumbnail).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (320, 160));
+
+    let tweet = serde_json::json!({ "content": "with image", "media_ids": [media_id] });
+    let res = post_json(&app, "/api/user_tweets", tweet.clone(), Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    // 添付済みのメディアは再利用できない
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let timeline = get_json(&app, "/api/pages/timeline", &alice).await;
+    assert_eq!(timeline[0]["media"], serde_json::json!([media]));
+
+    std::fs::remove_dir_all(media_dir).unwrap();
+}
+
+async fn get_media_status(app: &Router, uri: &str, cookie: Option<&str>) -> StatusCode {
+    let mut request = Request::get(uri);
+    if let Some(cookie) = cookie {
+        request = request.header(header::COOKIE, cookie);
+    }
+    send(app, request.body(Body::empty()).unwrap())
+        .await
+        .status()
+}
+
+#[tokio::test]
+async fn media_follows_visibility_of_its_tweet() {
+    let media_dir = temp_dir("media_visibility");
+    let (app, repository) = test_app_and_repository(ServerConfig {
+        media_dir: media_dir.clone(),
+        ..ServerConfig::default()
+    })
+    .await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    let carol = sign_up_and_log_in(&app, "carol").await;
+    let body = serde_json::json!({ "protected": true });
+    let res = post_json(&app, "/api/users/me/protected", body, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let res = upload(&app, &alice, "image/png", &png_bytes()).await;
+    let media = body_json(res).await;
+    let (url, thumbnail_url) = (
+        media["url"].as_str().unwrap().to_string(),
+        media["thumbnail_url"].as_str().unwrap().to_string(),
+    );
+    // 添付前のメディアは本人しか見られない
+    assert_eq!(
+        get_media_status(&app, &url, Some(&alice)).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        get_media_status(&app, &url, Some(&bob)).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        get_media_status(&app, &url, None).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let tweet = serde_json::json!({ "content": "protected image", "media_ids": [media["id"]] });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    // 非公開アカウントのメディアは承認されたフォロワーだけが見られる
+    let follow = serde_json::json!({ "name": "alice" });
+    post_json(&app, "/api/follow_relations", follow, Some(&bob)).await;
+    let res = post_json(
+        &app,
+        "/api/follow_requests/1/approve",
+        serde_json::json!({}),
+        Some(&alice),
+    )
+    .await;
+    assert!(res.status().is_success());
+    for uri in [&url, &thumbnail_url] {
+        assert_eq!(
+            get_media_status(&app, uri, Some(&bob)).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            get_media_status(&app, uri, Some(&carol)).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    // モデレーターが非表示にしたツイートのメディアはフォロワーにも見せない
+    repository
+        .set_user_role(None, 3, Role::Moderator)
+        .await
+        .unwrap();
+    let res = post_json(
+        &app,
+        "/api/admin/user_tweets/1/hide",
+        serde_json::json!({ "hidden": true }),
+        Some(&carol),
+    )
+    .await;
+    assert!(res.status().is_success());
+    assert_eq!(
+        get_media_status(&app, &url, Some(&bob)).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        get_media_status(&app, &url, Some(&alice)).await,
+        StatusCode::OK
+    );
+
+    std::fs::remove_dir_all(media_dir).unwrap();
+}
+
+#[tokio::test]
+async fn invalid_media_is_rejected() {
+    let media_dir = temp_dir("invalid_media");
+    let app = test_app_with(ServerConfig {
+        media_dir: media_dir.clone(),
+        ..ServerConfig::default()
+    })
+    .await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+
+    let res = upload(&app, &alice, "text/plain", b"hello").await;
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    // MIMEタイプと中身が一致しない
+    let res = upload(&app, &alice, "image/jpeg", &png_bytes()).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    // ファイルは小さくても、展開すると巨大になる画像
+    let image = image::GrayImage::new(20000, 1);
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+    let res = upload(&app, &alice, "image/png", bytes.get_ref()).await;
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    // 5件以上は添付できない
+    let tweet = serde_json::json!({ "content": "too many", "media_ids": [1, 2, 3, 4, 5] });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    std::fs::remove_dir_all(media_dir).unwrap();
+}
@@ -0,0 +1,101 @@
+// フォロー、返信、いいね、メンションの通知のテスト
+mod common;
+
+use axum::http::StatusCode;
+use common::{get_json, post_json, sign_up_and_log_in, test_app};
+use ruitter::endpoints::notifications::mentioned_names;
+
+#[tokio::test]
+async fn notifications_are_grouped_and_marked_read() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    let carol = sign_up_and_log_in(&app, "carol").await;
+
+    let follow = serde_json::json!({ "name": "alice" });
+    let res = post_json(&app, "/api/follow_relations", follow.clone(), Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = post_json(&app, "/api/follow_relations", follow, Some(&carol)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let tweet = serde_json::json!({ "content": "hello @bob" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    // 返信先の投稿者へのメンションは返信通知だけにする
+    let reply = serde_json::json!({ "content": "@alice hi", "in_reply_to_id": 1 });
+    let res = post_json(&app, "/api/user_tweets", reply, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let reply = serde_json::json!({ "content": "to nowhere", "in_reply_to_id": 100 });
+    let res = post_json(&app, "/api/user_tweets", reply, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let empty = serde_json::json!({});
+    let res = post_json(
+        &app,
+        "/api/user_tweets/1/likes",
+        empty.clone(),
+        Some(&carol),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = post_json(&app, "/api/user_tweets/1/likes", empty.clone(), Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    // 2回目のいいねと自分のツイートへのいいねは通知しない
+    let res = post_json(&app, "/api/user_tweets/1/likes", empty.clone(), Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = post_json(
+        &app,
+        "/api/user_tweets/1/likes",
+        empty.clone(),
+        Some(&alice),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = post_json(&app, "/api/user_tweets/100/likes", empty, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let notifications = get_json(&app, "/api/notifications", &alice).await;
+    assert_eq!(
+        notifications,
+        serde_json::json!({
+            "unread_count": 5,
+            "groups": [
+                { "kind": "like", "target_id": 1, "actor_names": ["bob", "carol"], "count": 2, "unread": true, "latest_id": 6 },
+                { "kind": "reply", "target_id": 1, "actor_names": ["bob"], "count": 1, "unread": true, "latest_id": 4 },
+                { "kind": "follow", "target_id": 1, "actor_names": ["carol", "bob"], "count": 2, "unread": true, "latest_id": 2 },
+            ]
+        })
+    );
+    let notifications = get_json(&app, "/api/notifications", &bob).await;
+    assert_eq!(notifications["unread_count"], 1);
+    assert_eq!(notifications["groups"][0]["kind"], "mention");
+
+    // up_to_idまでを既読にする
+    let read = serde_json::json!({ "up_to_id": 4 });
+    let res = post_json(&app, "/api/notifications/read", read, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let notifications = get_json(&app, "/api/notifications", &alice).await;
+    assert_eq!(notifications["unread_count"], 2);
+    assert_eq!(notifications["groups"][0]["unread"], true);
+    assert_eq!(notifications["groups"][1]["unread"], false);
+
+    let res = post_json(
+        &app,
+        "/api/notifications/read",
+        serde_json::json!({}),
+        Some(&alice),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let notifications = get_json(&app, "/api/notifications", &alice).await;
+    assert_eq!(notifications["unread_count"], 0);
+}
+
+#[test]
+fn mentions_are_parsed_from_content() {
+    assert_eq!(
+        mentioned_names("@alice hi @bob_2, mail me at carol@example.com @alice"),
+        vec!["alice", "bob_2"]
+    );
+    assert!(mentioned_names("no mentions @ all").is_empty());
+}
@@ -0,0 +1,250 @@
+// OIDCログインのテスト
+// トークンエンドポイントとJWKSを返すモックのIdPをローカルに立てて認可コードフローを通す
+mod common;
+
+use axum::{
+    body::Body,
+    extract::{Extension, Form},
+    http::{header, Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use common::{post_json, send, test_app_with};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use ruitter::config::ServerConfig;
+use ruitter::oidc::{code_challenge, JwksSource, OidcConfig};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const ISSUER: &str = "http://idp.test";
+const CLIENT_ID: &str = "ruitter";
+
+// IdPでユーザが認証され、認可コードを発行した状態
+struct Authorization {
+    code_challenge: String,
+    nonce: String,
+    sub: String,
+    preferred_username: String,
+}
+
+#[derive(Clone, Default)]
+struct MockIdp {
+    authorizations: Arc<Mutex<HashMap<String, Authorization>>>,
+    // 指定するとIDトークンのaudをこの値にする
+    audience: Arc<Mutex<Option<String>>>,
+}
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/oidc")
+        .join(name)
+}
+
+async fn token_endpoint(
+    Extension(idp): Extension<MockIdp>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let authorization = match idp.authorizations.lock().unwrap().remove(&form["code"]) {
+        Some(authorization) => authorization,
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    // PKCEの検証
+    if code_challenge(&form["code_verifier"]) != authorization.code_challenge {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    let audience = idp
+        .audience
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| CLIENT_ID.to_string());
+    let claims = serde_json::json!({
+        "iss": ISSUER,
+        "aud": audience,
+        "sub": authorization.sub,
+        "exp": jsonwebtoken::get_current_timestamp() + 300,
+        "nonce": authorization.nonce,
+        "preferred_username": authorization.preferred_username,
+    });
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some("test-key".to_string());
+    let key =
+        EncodingKey::from_rsa_pem(&std::fs::read(fixture("private_key.pem")).unwrap()).unwrap();
+    let id_token = encode(&header, &claims, &key).unwrap();
+    Json(serde_json::json!({ "access_token": "unused", "token_type": "Bearer", "id_token": id_token }))
+        .into_response()
+}
+
+async fn jwks_endpoint() -> impl IntoResponse {
+    let jwks: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(fixture("jwks.json")).unwrap()).unwrap();
+    Json(jwks)
+}
+
+// モックのIdPを起動し、IdPの状態とruitterのアプリを返す
+async fn start_idp(jwks_from_file: bool) -> (MockIdp, Router) {
+    let idp = MockIdp::default();
+    let router = Router::new()
+        .route("/token", post(token_endpoint))
+        .route("/jwks", get(jwks_endpoint))
+        .layer(Extension(idp.clone()));
+    let server =
+        axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(router.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    let jwks = if jwks_from_file {
+        JwksSource::File(fixture("jwks.json"))
+    } else {
+        JwksSource::Url(format!("http://{}/jwks", addr))
+    };
+    let config = ServerConfig {
+        oidc: Some(OidcConfig {
+            issuer: ISSUER.to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: Some("secret".to_string()),
+            authorization_endpoint: format!("http://{}/authorize", addr),
+            token_endpoint: format!("http://{}/token", addr),
+            jwks,
+            redirect_uri: "http://localhost:8888/api/oidc/callback".to_string(),
+        }),
+        ..ServerConfig::default()
+    };
+    (idp, test_app_with(config).await)
+}
+
+// /api/oidc/loginからIdPでの認証を経てコールバックを呼び、コールバックのレスポンスを返す
+async fn log_in_with_idp(app: &Router, idp: &MockIdp, sub: &str, username: &str) -> Response {
+    let (cookie, params) = start_login(app).await;
+    let code = format!("code-{}", sub);
+    idp.authorizations.lock().unwrap().insert(
+        code.clone(),
+        Authorization {
+            code_challenge: params["code_challenge"].clone(),
+            nonce: params["nonce"].clone(),
+            sub: sub.to_string(),
+            preferred_username: username.to_string(),
+        },
+    );
+    callback(app, &cookie, &code, &params["state"]).await
+}
+
+// ログインを開始し、認可リクエストのクッキーとIdPへのリダイレクト先のクエリを返す
+async fn start_login(app: &Router) -> (String, HashMap<String, String>) {
+    let request = Request::get("/api/oidc/login").body(Body::empty()).unwrap();
+    let res = send(app, request).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    let location = res.headers()[header::LOCATION].to_str().unwrap();
+    let url = reqwest::Url::parse(location).unwrap();
+    let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    assert_eq!(params["client_id"], CLIENT_ID);
+    assert_eq!(params["code_challenge_method"], "S256");
+    (set_cookie(&res, "ruitter_oidc").unwrap(), params)
+}
+
+async fn callback(app: &Router, cookie: &str, code: &str, state: &str) -> Response {
+    let uri = format!("/api/oidc/callback?code={}&state={}", code, state);
+    let request = Request::get(uri)
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap();
+    send(app, request).await
+}
+
+fn set_cookie(res: &Response, name: &str) -> Option<String> {
+    res.headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|value| {
+            value
+                .to_str()
+                .unwrap()
+                .split(';')
+                .next()
+                .unwrap()
+                .to_string()
+        })
+        .find(|cookie| cookie.starts_with(&format!("{}=", name)))
+}
+
+// エクスポートの1行目がログイン中のユーザ
+async fn current_user(app: &Router, cookie: &str) -> serde_json::Value {
+    let request = Request::get("/api/users/me/export")
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap();
+    let res = send(app, request).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let line = std::str::from_utf8(&body).unwrap().lines().next().unwrap();
+    serde_json::from_str(line).unwrap()
+}
+
+#[tokio::test]
+async fn first_login_creates_linked_user() {
+    let (idp, app) = start_idp(false).await;
+    let res = log_in_with_idp(&app, &idp, "subject-1", "alice").await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(res.headers()[header::LOCATION], "/");
+    let cookie = set_cookie(&res, "axum_session").unwrap();
+    let user = current_user(&app, &cookie).await;
+    assert_eq!(user["name"], "alice");
+
+    // 2回目以降は同じユーザでログインする、IdP側のユーザ名が変わっても紐付けはsubで行う
+    let res = log_in_with_idp(&app, &idp, "subject-1", "alice-renamed").await;
+    let cookie = set_cookie(&res, "axum_session").unwrap();
+    assert_eq!(current_user(&app, &cookie).await, user);
+
+    // OIDCで作成したユーザは名前だけではログインできない
+    let body = serde_json::json!({ "name": "alice" });
+    let res = post_json(&app, "/api/sessions", body, None).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn taken_user_name_gets_suffix() {
+    let (idp, app) = start_idp(true).await;
+    let body = serde_json::json!({ "name": "bob", "password": "secret" });
+    let res = post_json(&app, "/api/users", body, None).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let res = log_in_with_idp(&app, &idp, "subject-2", "bob").await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    let cookie = set_cookie(&res, "axum_session").unwrap();
+    let name = current_user(&app, &cookie).await["name"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(name.starts_with("bob_"), "{}", name);
+}
+
+#[tokio::test]
+async fn invalid_callbacks_are_rejected() {
+    let (idp, app) = start_idp(false).await;
+
+    // stateが一致しない、認可リクエストは1回で破棄する
+    let (cookie, params) = start_login(&app).await;
+    let res = callback(&app, &cookie, "code", "wrong-state").await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = callback(&app, &cookie, "code", &params["state"]).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // IdPが認可コードを発行していない
+    let (cookie, params) = start_login(&app).await;
+    let res = callback(&app, &cookie, "unknown-code", &params["state"]).await;
+    assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+
+    // 別のクライアント向けに発行されたIDトークン
+    *idp.audience.lock().unwrap() = Some("other-client".to_string());
+    let res = log_in_with_idp(&app, &idp, "subject-3", "carol").await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(set_cookie(&res, "axum_session").is_none());
+}
+
+#[tokio::test]
+async fn oidc_login_is_disabled_without_config() {
+    let app = common::test_app().await;
+    let request = Request::get("/api/oidc/login").body(Body::empty()).unwrap();
+    assert_eq!(send(&app, request).await.status(), StatusCode::NOT_FOUND);
+}
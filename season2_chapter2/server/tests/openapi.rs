@@ -0,0 +1,129 @@
+// OpenAPIドキュメントとルーティングの乖離を検出するテスト
+// src以下の`.route("パス", メソッド(ハンドラ))`を読み取り、ドキュメントのパスと突き合わせる
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use ruitter::config::ServerConfig;
+use ruitter::endpoints::app;
+use ruitter::openapi::openapi;
+use ruitter::repositories::connect;
+use std::collections::BTreeSet;
+use std::path::Path;
+use tower::ServiceExt as _;
+
+const METHODS: [&str; 5] = ["get", "post", "put", "delete", "patch"];
+
+// ソースコードからルーティングされている(パス, メソッド)の組を列挙する
+fn routes_in_source() -> BTreeSet<(String, String)> {
+    let mut routes = BTreeSet::new();
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    for source in read_sources(&src) {
+        let mut rest = source.as_str();
+        while let Some(start) = rest.find(".route(") {
+            rest = &rest[start + ".route(".len()..];
+            let args = &rest[..closing_paren(rest)];
+            let path = args.split('"').nth(1).unwrap();
+            for method in methods_in(args) {
+                routes.insert((to_openapi_path(path), method.to_string()));
+            }
+        }
+    }
+    routes
+}
+
+fn read_sources(dir: &Path) -> Vec<String> {
+    let mut sources = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            sources.extend(read_sources(&path));
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            sources.push(std::fs::read_to_string(path).unwrap());
+        }
+    }
+    sources
+}
+
+// `.route(`の直後から対応する閉じ括弧までの長さを返す
+fn closing_paren(text: &str) -> usize {
+    let mut depth = 1;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    panic!("unbalanced .route(");
+}
+
+// `get(handler).post(handler)`のようなメソッドルータからHTTPメソッドを取り出す
+fn methods_in(args: &str) -> Vec<&'static str> {
+    let mut methods = Vec::new();
+    for method in METHODS {
+        let pattern = format!("{}(", method);
+        let mut offset = 0;
+        while let Some(i) = args[offset..].find(&pattern) {
+            let at = offset + i;
+            let preceded_by_ident = args[..at]
+                .chars()
+                .last()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_');
+            if !preceded_by_ident {
+                methods.push(method);
+            }
+            offset = at + pattern.len();
+        }
+    }
+    methods
+}
+
+// axumの`:id`形式のパスパラメータをOpenAPIの`{id}`形式に変換する
+fn to_openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn routes_in_spec(spec: &serde_json::Value) -> BTreeSet<(String, String)> {
+    let mut routes = BTreeSet::new();
+    for (path, operations) in spec["paths"].as_object().unwrap() {
+        for method in operations.as_object().unwrap().keys() {
+            if METHODS.contains(&method.as_str()) {
+                routes.insert((path.clone(), method.clone()));
+            }
+        }
+    }
+    routes
+}
+
+#[test]
+fn spec_matches_routes() {
+    let spec = serde_json::to_value(openapi()).unwrap();
+    assert_eq!(routes_in_spec(&spec), routes_in_source());
+}
+
+#[tokio::test]
+async fn spec_is_served() {
+    let (repository, session_store) = connect("sqlite::memory:").await.unwrap();
+    let app = app(repository, session_store, ServerConfig::default());
+    let request = Request::get("/api/openapi.json")
+        .body(Body::empty())
+        .unwrap();
+    let res = app.oneshot(request).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let spec: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(spec, serde_json::to_value(openapi()).unwrap());
+    assert!(spec["components"]["schemas"]["TimelineItem"].is_object());
+}
@@ -0,0 +1,106 @@
+// 読み取り専用のレプリカへの振り分けのテスト
+// レプリカには別のSQLiteファイルを使い、プライマリへの書き込みが反映されないレプリカとして扱う
+mod common;
+
+use axum::{http::StatusCode, Router};
+use common::{get_json, log_in, post_json, temp_dir};
+use ruitter::config::DatabaseConfig;
+use ruitter::config::{ServerConfig, TimelineMode};
+use ruitter::endpoints::app;
+use ruitter::models::{Role, User};
+use ruitter::repositories::{connect, connect_with, AppSessionStore, SharedRepository};
+use std::time::Duration;
+
+async fn replicated_repository(name: &str) -> (SharedRepository, AppSessionStore) {
+    let dir = temp_dir(name);
+    let primary_url = format!("sqlite:{}", dir.join("primary.db").display());
+    let replica_url = format!("sqlite:{}", dir.join("replica.db").display());
+    // レプリカにはユーザだけが同期済みの状態にする
+    let (replica, _) = connect(&replica_url).await.unwrap();
+    replica.setup_tables().await.unwrap();
+    for name in ["alice", "bob"] {
+        replica
+            .insert_user(&User {
+                id: None,
+                name: name.to_string(),
+                password_hash: None,
+                role: Role::User,
+                is_suspended: false,
+                is_protected: false,
+            })
+            .await
+            .unwrap();
+    }
+    let (repository, session_store) =
+        connect_with(&primary_url, Some(&replica_url), &DatabaseConfig::default())
+            .await
+            .unwrap();
+    session_store.migrate().await.unwrap();
+    repository.setup_tables().await.unwrap();
+    (repository, session_store)
+}
+
+fn app_with_window(
+    repository: &SharedRepository,
+    session_store: &AppSessionStore,
+    timeline_mode: TimelineMode,
+    window: Duration,
+) -> Router {
+    let config = ServerConfig {
+        timeline_mode,
+        sticky_primary_window: window,
+        ..ServerConfig::default()
+    };
+    app(repository.clone(), session_store.clone(), config)
+}
+
+async fn timeline_len(app: &Router, cookie: &str) -> usize {
+    get_json(app, "/api/pages/timeline", cookie)
+        .await
+        .as_array()
+        .unwrap()
+        .len()
+}
+
+#[tokio::test]
+async fn reads_after_write_stick_to_primary() {
+    // ホームタイムラインを読む方式でもレプリカに振り分ける
+    for timeline_mode in [TimelineMode::FanOutOnRead, TimelineMode::FanOutOnWrite] {
+        assert_reads_after_write_stick_to_primary(timeline_mode).await;
+    }
+}
+
+async fn assert_reads_after_write_stick_to_primary(timeline_mode: TimelineMode) {
+    let name = format!("read_replica_{:?}", timeline_mode);
+    let (repository, session_store) = replicated_repository(&name).await;
+    let sticky_app = app_with_window(
+        &repository,
+        &session_store,
+        timeline_mode,
+        Duration::from_secs(60),
+    );
+    let replica_app = app_with_window(&repository, &session_store, timeline_mode, Duration::ZERO);
+    for name in ["alice", "bob"] {
+        let body = serde_json::json!({ "name": name });
+        let res = post_json(&sticky_app, "/api/users", body, None).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+    // ログインのユーザ名の検索はレプリカで行う
+    let alice = log_in(&sticky_app, serde_json::json!({ "name": "alice" })).await;
+    let bob = log_in(&sticky_app, serde_json::json!({ "name": "bob" })).await;
+
+    let body = serde_json::json!({ "content": "hello" });
+    let res = post_json(&sticky_app, "/api/user_tweets", body, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    // 書き込んでいないセッションのタイムラインはレプリカから読むので、まだ見えない
+    assert_eq!(timeline_len(&sticky_app, &alice).await, 0);
+
+    let body = serde_json::json!({ "name": "bob" });
+    let res = post_json(&sticky_app, "/api/follow_relations", body, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    // フォローした直後はプライマリから読む
+    assert_eq!(timeline_len(&sticky_app, &alice).await, 1);
+    assert_eq!(timeline_len(&sticky_app, &bob).await, 1);
+    // 期間を過ぎれば再びレプリカから読む
+    assert_eq!(timeline_len(&replica_app, &alice).await, 0);
+}
@@ -0,0 +1,69 @@
+// おすすめユーザAPIのテスト
+mod common;
+
+use axum::{http::StatusCode, Router};
+use common::{get_json, post_json, sign_up_and_log_in, test_app};
+
+async fn follow(app: &Router, cookie: &str, name: &str) {
+    let body = serde_json::json!({ "name": name });
+    let res = post_json(app, "/api/follow_relations", body, Some(cookie)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+async fn recommended_names(app: &Router, cookie: &str) -> Vec<String> {
+    let users = get_json(app, "/api/recommendations/users", cookie).await;
+    users
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|user| user["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn recommends_friends_of_friends_by_score() {
+    let app = test_app().await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    let carol = sign_up_and_log_in(&app, "carol").await;
+    for name in ["dave", "frank"] {
+        sign_up_and_log_in(&app, name).await;
+    }
+    let erin = sign_up_and_log_in(&app, "erin").await;
+    follow(&app, &alice, "bob").await;
+    follow(&app, &alice, "carol").await;
+    // フォロー済みのユーザと自分自身はおすすめしない
+    follow(&app, &bob, "carol").await;
+    follow(&app, &bob, "alice").await;
+    follow(&app, &bob, "dave").await;
+    follow(&app, &carol, "dave").await;
+    follow(&app, &bob, "erin").await;
+    follow(&app, &carol, "frank").await;
+    // 共通のフォロイー数が同じなら直近に投稿しているユーザを優先する
+    let tweet = serde_json::json!({ "content": "hello" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&erin)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let users = get_json(&app, "/api/recommendations/users", &alice).await;
+    assert_eq!(
+        users[0],
+        serde_json::json!({ "id": 4, "name": "dave", "mutual_count": 2, "recent_tweet_count": 0 })
+    );
+    assert_eq!(
+        recommended_names(&app, &alice).await,
+        vec!["dave", "erin", "frank"]
+    );
+
+    // フォローとブロックをするとキャッシュを捨てて結果に反映する
+    follow(&app, &alice, "dave").await;
+    let block = serde_json::json!({ "name": "frank" });
+    let res = post_json(&app, "/api/blocks", block.clone(), Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = post_json(&app, "/api/blocks", block, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(recommended_names(&app, &alice).await, vec!["erin"]);
+
+    let block = serde_json::json!({ "name": "alice" });
+    let res = post_json(&app, "/api/blocks", block, Some(&alice)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,64 @@
+// ツイート本文のエンティティ抽出とHTML組み立てのテスト
+use ruitter::render::{extract_entities, hashtags, render_html, EntityKind};
+use ruitter::unfurl::parse_open_graph;
+
+#[test]
+fn entities_are_extracted_with_char_offsets() {
+    let content = "こんにちは @alice #Rust https://example.com/a_(b)?q=1. mail@example.com #123";
+    let entities = extract_entities(content);
+    let summary: Vec<_> = entities
+        .iter()
+        .map(|entity| (entity.kind, entity.start, entity.end, entity.value()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (EntityKind::Mention, 6, 12, "alice"),
+            (EntityKind::Hashtag, 13, 18, "Rust"),
+            (EntityKind::Url, 19, 48, "https://example.com/a_(b)?q=1"),
+        ]
+    );
+    assert_eq!(entities[1].href, "/hashtags/rust");
+    // URL中の@や#は抽出しない
+    let entities = extract_entities("https://example.com/@alice#top");
+    assert_eq!(entities.len(), 1);
+    assert_eq!(hashtags("#Rust #rust ＃Axum"), vec!["rust", "axum"]);
+}
+
+#[test]
+fn html_is_escaped_and_linked() {
+    let content = r#"<script>alert("x")</script> & @bob https://example.com/?a=1&b=2"#;
+    let html = render_html(content, &extract_entities(content));
+    assert_eq!(
+        html,
+        concat!(
+            "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; &amp; ",
+            r#"<a href="/users/bob">@bob</a> "#,
+            r#"<a href="https://example.com/?a=1&amp;b=2" target="_blank" rel="nofollow noopener noreferrer">"#,
+            "https://example.com/?a=1&amp;b=2</a>",
+        )
+    );
+}
+
+#[test]
+fn open_graph_metadata_is_parsed() {
+    let html = r#"<html><head>
+        <title>Fallback</title>
+        <meta property="og:title" content="Example &amp; Co">
+        <meta name="description" content='A description'>
+        <meta property="og:image" content="/images/card.png" />
+        </head><body><meta property="og:title" content="ignored"></body></html>"#;
+    let preview = parse_open_graph("https://example.com/articles/1", html);
+    assert_eq!(preview.title.as_deref(), Some("Example & Co"));
+    assert_eq!(preview.description.as_deref(), Some("A description"));
+    assert_eq!(
+        preview.image_url.as_deref(),
+        Some("https://example.com/images/card.png")
+    );
+
+    // og:titleがなければtitle要素を使う
+    let preview = parse_open_graph("https://example.com/", "<title> Only title </title>");
+    assert_eq!(preview.title.as_deref(), Some("Only title"));
+    assert_eq!(preview.description, None);
+    assert_eq!(preview.image_url, None);
+}
@@ -0,0 +1,90 @@
+// ダミーデータの生成と投入のテスト
+mod common;
+
+use chrono::Utc;
+use common::{get_json, log_in, test_app_and_repository};
+use ruitter::config::ServerConfig;
+use ruitter::seed::{generate, insert, SeedConfig};
+
+fn config(seed: u64) -> SeedConfig {
+    SeedConfig {
+        users: 200,
+        tweets: 500,
+        follows_per_user: 5,
+        seed,
+        ..SeedConfig::default()
+    }
+}
+
+#[test]
+fn generate_is_deterministic_and_skewed() {
+    let data = generate(&config(1));
+    assert_eq!(data, generate(&config(1)));
+    assert_ne!(data.follows, generate(&config(2)).follows);
+    assert_eq!(data.user_names.len(), 200);
+    assert_eq!(data.tweets.len(), 500);
+    assert!(data
+        .tweets
+        .windows(2)
+        .all(|pair| pair[0].seconds_ago >= pair[1].seconds_ago));
+    // 自分自身や同じユーザを重複してフォローしない
+    let mut follows = data.follows.clone();
+    follows.sort_unstable();
+    follows.dedup();
+    assert_eq!(follows.len(), data.follows.len());
+    assert!(data
+        .follows
+        .iter()
+        .all(|(follower, followee)| follower != followee));
+
+    // 優先的選択なのでフォロワー数の最大値は平均より大きく偏る
+    let mut followers = vec![0; data.user_names.len()];
+    for (_, followee) in &data.follows {
+        followers[*followee] += 1;
+    }
+    let mean = data.follows.len() / data.user_names.len();
+    assert!(*followers.iter().max().unwrap() > mean * 5);
+}
+
+#[tokio::test]
+async fn insert_seeds_timeline() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let data = generate(&config(3));
+    let mut last_progress = Vec::new();
+    insert(
+        &repository,
+        &data,
+        Utc::now().naive_utc(),
+        64,
+        |table, inserted, total| {
+            if inserted == total {
+                last_progress.push(table.to_string());
+            }
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        last_progress,
+        vec!["users", "follow_relations", "user_tweets"]
+    );
+
+    // 投入したユーザはパスワードなしでログインできる
+    let (follower, _) = data.follows[data.follows.len() - 1];
+    let cookie = log_in(
+        &app,
+        serde_json::json!({ "name": data.user_names[follower] }),
+    )
+    .await;
+    let timeline = get_json(&app, "/api/pages/timeline", &cookie).await;
+    // タイムラインには自分とフォロイーのツイートが並ぶ
+    let expected = data
+        .tweets
+        .iter()
+        .filter(|tweet| {
+            tweet.author == follower || data.follows.contains(&(follower, tweet.author))
+        })
+        .count();
+    assert!(expected > 0);
+    assert_eq!(timeline.as_array().unwrap().len(), expected);
+}
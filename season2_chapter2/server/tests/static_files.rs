@@ -0,0 +1,85 @@
+// フロントエンドの静的ファイル配信とSPAフォールバックのテスト
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use common::{send, temp_dir, test_app_with};
+use ruitter::config::ServerConfig;
+use std::path::PathBuf;
+
+// Viteのビルド結果を模したディレクトリを作る
+fn create_static_dir() -> PathBuf {
+    let dir = temp_dir("static");
+    std::fs::create_dir_all(dir.join("assets")).unwrap();
+    std::fs::write(dir.join("index.html"), "<html>ruitter</html>").unwrap();
+    std::fs::write(
+        dir.join("assets/index.abc123.js"),
+        "console.log('ruitter');",
+    )
+    .unwrap();
+    std::fs::write(dir.join("assets/index.abc123.js.gz"), "gzipped").unwrap();
+    dir
+}
+
+async fn test_app(static_dir: PathBuf) -> Router {
+    let config = ServerConfig {
+        static_dir: Some(static_dir),
+        ..ServerConfig::default()
+    };
+    test_app_with(config).await
+}
+
+async fn get(app: &Router, uri: &str, accept_encoding: Option<&str>) -> Response {
+    let mut request = Request::get(uri);
+    if let Some(accept_encoding) = accept_encoding {
+        request = request.header(header::ACCEPT_ENCODING, accept_encoding);
+    }
+    send(app, request.body(Body::empty()).unwrap()).await
+}
+
+async fn body_text(response: Response) -> String {
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn serves_assets_and_falls_back_to_index_html() {
+    let static_dir = create_static_dir();
+    let app = test_app(static_dir.clone()).await;
+
+    // ハッシュ付きのアセットは長期キャッシュさせ、圧縮済みファイルがあればそれを返す
+    let res = get(&app, "/assets/index.abc123.js", Some("gzip")).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+    assert_eq!(
+        res.headers()[header::CACHE_CONTROL],
+        "public, max-age=31536000, immutable"
+    );
+    assert_eq!(body_text(res).await, "gzipped");
+
+    let res = get(&app, "/assets/index.abc123.js", None).await;
+    assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+    assert_eq!(body_text(res).await, "console.log('ruitter');");
+
+    // フロントエンドのルーティングが扱うパスはindex.htmlを返す
+    let res = get(&app, "/users/alice", None).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CACHE_CONTROL], "no-cache");
+    assert_eq!(body_text(res).await, "<html>ruitter</html>");
+
+    // 存在しないアセットはindex.htmlにフォールバックせず、キャッシュもさせない
+    let res = get(&app, "/assets/index.old456.js", None).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(res.headers()[header::CACHE_CONTROL], "no-cache");
+    assert_ne!(body_text(res).await, "<html>ruitter</html>");
+
+    // 未定義のAPIはindex.htmlにフォールバックしない
+    let res = get(&app, "/api/unknown", None).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    std::fs::remove_dir_all(static_dir).unwrap();
+}
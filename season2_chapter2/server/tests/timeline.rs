@@ -0,0 +1,115 @@
+// タイムラインクエリがフォロイー数によらず正しい結果を返すことのテスト
+// 以前はフォロイーIDをIN句に展開していたため、0件や大量件数で破綻していた
+use ruitter::models::{timeline_item, Role, User, UserTweet};
+use ruitter::repositories::Repository as _;
+use ruitter::repositories::{
+    SqliteRepository, TimelineRepository as _, UserRepository as _, UserTweetRepository as _,
+};
+
+// 1つのINSERT文にまとめる行数
+const CHUNK_SIZE: usize = 500;
+
+async fn setup_repository() -> SqliteRepository {
+    let repository = SqliteRepository::connect("sqlite::memory:").await.unwrap();
+    repository.setup_tables().await.unwrap();
+    repository
+}
+
+// 指定人数のフォロイーを作り、それぞれ1件ずつツイートさせてからフォローする
+// 件数が多いので複数行INSERTでまとめて投入する
+async fn follow_users_with_tweets(repository: &SqliteRepository, follower_id: u64, count: usize) {
+    let names = (0..count)
+        .map(|i| format!("followee{}", i))
+        .collect::<Vec<_>>();
+    for chunk in names.chunks(CHUNK_SIZE) {
+        let sql = format!(
+            "INSERT INTO users (name) VALUES {};",
+            vec!["(?)"; chunk.len()].join(",")
+        );
+        let mut query = sqlx::query(&sql);
+        for name in chunk {
+            query = query.bind(name);
+        }
+        query.execute(repository.pool()).await.unwrap();
+    }
+    sqlx::query(
+        r#"
+          INSERT INTO user_tweets (user_id, content)
+          SELECT id, 'tweet by ' || name FROM users WHERE name LIKE 'followee%' ORDER BY id;
+        "#,
+    )
+    .execute(repository.pool())
+    .await
+    .unwrap();
+    sqlx::query(
+        r#"
+          INSERT INTO follow_relations (followee_id, follower_id)
+          SELECT id, ? FROM users WHERE name LIKE 'followee%';
+        "#,
+    )
+    .bind(follower_id as i64)
+    .execute(repository.pool())
+    .await
+    .unwrap();
+}
+
+async fn insert_user_with_tweet(repository: &SqliteRepository, name: &str) -> u64 {
+    let user_id = repository
+        .insert_user(&User {
+            id: None,
+            name: name.to_string(),
+            password_hash: None,
+            role: Role::User,
+            is_suspended: false,
+            is_protected: false,
+        })
+        .await
+        .unwrap();
+    repository
+        .insert_user_tweet(&UserTweet {
+            id: None,
+            user_id,
+            content: format!("tweet by {}", name),
+            in_reply_to_id: None,
+        })
+        .await
+        .unwrap();
+    user_id
+}
+
+async fn assert_timeline_for_followees(count: usize) {
+    let repository = setup_repository().await;
+    let follower_id = insert_user_with_tweet(&repository, "follower").await;
+    // フォローしていないユーザのツイートは含まれない
+    insert_user_with_tweet(&repository, "stranger").await;
+    follow_users_with_tweets(&repository, follower_id, count).await;
+
+    let timeline = repository.timeline(follower_id).await.unwrap();
+    assert_eq!(timeline.len(), count + 1);
+    assert!(timeline.iter().all(|item| item.name != "stranger"));
+    assert!(timeline.contains(&timeline_item(
+        1,
+        "follower".to_string(),
+        "tweet by follower".to_string(),
+        None
+    )));
+    // 新しいツイートが先頭に来る
+    if count > 0 {
+        assert_eq!(timeline[0].name, format!("followee{}", count - 1));
+    }
+}
+
+#[tokio::test]
+async fn timeline_with_no_followees() {
+    assert_timeline_for_followees(0).await;
+}
+
+#[tokio::test]
+async fn timeline_with_one_followee() {
+    assert_timeline_for_followees(1).await;
+}
+
+#[tokio::test]
+async fn timeline_with_50k_followees() {
+    assert_timeline_for_followees(50_000).await;
+}
@@ -0,0 +1,143 @@
+// ハッシュタグのトレンドのテスト
+mod common;
+
+use async_session::MemoryStore;
+use axum::{body::Body, http::header, http::Request, http::StatusCode, Router};
+use chrono::{Duration, Utc};
+use common::{get_json, post_json, send, sign_up_and_log_in, test_app_and_repository};
+use ruitter::config::{ServerConfig, TimelineMode};
+use ruitter::jobs::JobRunner;
+use ruitter::models::{Job, JobKind, UserTweet};
+use ruitter::repositories::{AppSessionStore, SharedRepository};
+use ruitter::trends::{rank_trends, TrendWindow};
+use std::collections::HashMap;
+
+async fn post_tweets(app: &Router, cookie: &str, content: &str, count: usize) {
+    for _ in 0..count {
+        let body = serde_json::json!({ "content": content });
+        let res = post_json(app, "/api/user_tweets", body, Some(cookie)).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+}
+
+// 定期実行と同じくジョブとして集計する
+async fn aggregate(repository: &SharedRepository) {
+    let runner = JobRunner::new(
+        repository.clone(),
+        AppSessionStore::Memory(MemoryStore::new()),
+        TimelineMode::default(),
+    );
+    let now = Utc::now().naive_utc();
+    let job = Job::new(JobKind::AggregateTrends, "{}".to_string(), now);
+    repository.enqueue_job(&job).await.unwrap();
+    assert_eq!(runner.run_due_jobs(now).await.unwrap(), 1);
+}
+
+fn tags_and_counts(trends: &serde_json::Value) -> Vec<(String, u64)> {
+    trends
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|trend| {
+            (
+                trend["tag"].as_str().unwrap().to_string(),
+                trend["count"].as_u64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn rising_tags_rank_above_steadily_used_tags() {
+    let current = vec![
+        ("steady".to_string(), 50),
+        ("rising".to_string(), 10),
+        ("rare".to_string(), 2),
+    ];
+    // 普段から1時間に50回使われるタグと、普段は使われないタグ
+    let baseline = HashMap::from([("steady".to_string(), 50 * 24 * 7)]);
+    let trends = rank_trends(current, &baseline, TrendWindow::OneHour);
+    let tags: Vec<&str> = trends.iter().map(|trend| trend.tag.as_str()).collect();
+    assert_eq!(tags, vec!["rising"]);
+    assert_eq!(trends[0].expected, 0.0);
+}
+
+#[tokio::test]
+async fn trends_are_aggregated_incrementally() {
+    let config = ServerConfig {
+        trend_cache_ttl: std::time::Duration::ZERO,
+        ..ServerConfig::default()
+    };
+    let (app, repository) = test_app_and_repository(config).await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let user_id = repository.find_by_name("alice").await.unwrap().unwrap().id;
+    // 直前の1週間は#rustが1時間に1回ずつ使われている
+    let now = Utc::now().naive_utc();
+    let past: Vec<(UserTweet, _)> = (2..=168)
+        .map(|hours| {
+            let tweet = UserTweet {
+                id: None,
+                user_id: user_id.unwrap(),
+                content: "learning #Rust".to_string(),
+                in_reply_to_id: None,
+            };
+            (tweet, now - Duration::hours(hours))
+        })
+        .collect();
+    repository.bulk_insert_user_tweets(&past).await.unwrap();
+    post_tweets(&app, &alice, "#rust again", 4).await;
+    post_tweets(&app, &alice, "#newtag launched", 5).await;
+    post_tweets(&app, &alice, "#rare", 2).await;
+
+    aggregate(&repository).await;
+    let trends = get_json(&app, "/api/trends", &alice).await;
+    assert_eq!(
+        tags_and_counts(&trends),
+        vec![("newtag".to_string(), 5), ("rust".to_string(), 4)]
+    );
+    let trends = get_json(&app, "/api/trends?window=24h", &alice).await;
+    assert_eq!(trends[0]["tag"], "newtag");
+
+    // 集計済みのツイートは数え直さず、新しいツイートだけを加える
+    post_tweets(&app, &alice, "#NewTag", 1).await;
+    aggregate(&repository).await;
+    aggregate(&repository).await;
+    let trends = get_json(&app, "/api/trends", &alice).await;
+    assert_eq!(
+        tags_and_counts(&trends),
+        vec![("newtag".to_string(), 6), ("rust".to_string(), 4)]
+    );
+
+    let request = Request::get("/api/trends?window=7d")
+        .header(header::COOKIE, &alice)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn protected_and_suspended_authors_do_not_feed_trends() {
+    let config = ServerConfig {
+        trend_cache_ttl: std::time::Duration::ZERO,
+        ..ServerConfig::default()
+    };
+    let (app, repository) = test_app_and_repository(config).await;
+    let alice = sign_up_and_log_in(&app, "alice").await;
+    let bob = sign_up_and_log_in(&app, "bob").await;
+    let carol = sign_up_and_log_in(&app, "carol").await;
+    let body = serde_json::json!({ "protected": true });
+    let res = post_json(&app, "/api/users/me/protected", body, Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    post_tweets(&app, &bob, "#private", 5).await;
+    post_tweets(&app, &carol, "#spam", 5).await;
+    let carol_id = repository.find_by_name("carol").await.unwrap().unwrap().id;
+    assert!(repository
+        .set_user_suspended(1, carol_id.unwrap(), true)
+        .await
+        .unwrap());
+    post_tweets(&app, &alice, "#public", 5).await;
+
+    aggregate(&repository).await;
+    let trends = get_json(&app, "/api/trends", &alice).await;
+    assert_eq!(tags_and_counts(&trends), vec![("public".to_string(), 5)]);
+}
@@ -0,0 +1,214 @@
+// Webhook配信のテスト
+// 配信を記録する受信サーバをローカルに立て、署名と再試行、デッドレターを確かめる
+mod common;
+
+use async_session::MemoryStore;
+use axum::{
+    body::Bytes,
+    extract::Extension,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Router,
+};
+use chrono::{Duration, Utc};
+use common::{body_json, get_json, post_json, sign_up_and_log_in, test_app_and_repository};
+use ruitter::config::{ServerConfig, TimelineMode};
+use ruitter::jobs::JobRunner;
+use ruitter::models::Role;
+use ruitter::repositories::{AppSessionStore, SharedRepository};
+use ruitter::webhooks::{sign, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use std::sync::{Arc, Mutex};
+
+// 受信したリクエストのヘッダと本文
+type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+async fn receive(Extension(received): Extension<Received>, headers: HeaderMap, body: Bytes) {
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    received.lock().unwrap().push((headers, body));
+}
+
+// 受信サーバを起動し、受信した内容とアドレスを返す
+// /okは常に200、/failは常に500を返す
+fn start_receiver() -> (Received, String) {
+    let received = Received::default();
+    let router = Router::new()
+        .route("/ok", post(receive))
+        .route(
+            "/fail",
+            post(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "unavailable") }),
+        )
+        .layer(Extension(received.clone()));
+    let server =
+        axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(router.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    (received, format!("http://{}", addr))
+}
+
+fn job_runner(repository: &SharedRepository) -> JobRunner {
+    JobRunner::new(
+        repository.clone(),
+        AppSessionStore::Memory(MemoryStore::new()),
+        TimelineMode::default(),
+    )
+}
+
+#[test]
+fn signature_is_hmac_sha256_of_timestamp_and_body() {
+    assert_eq!(
+        sign("secret", 1700000000, r#"{"event":"user.created"}"#),
+        "sha256=a199809c6732c7d9d753b0517b72b0b4f179cd09e964af007aeeccf3ab970f71"
+    );
+}
+
+#[tokio::test]
+async fn events_are_signed_and_delivered() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let (received, receiver_url) = start_receiver();
+    let admin = sign_up_and_log_in(&app, "alice").await;
+    repository
+        .set_user_role(None, 1, Role::Admin)
+        .await
+        .unwrap();
+    let bob = sign_up_and_log_in(&app, "bob").await;
+
+    let body = serde_json::json!({ "url": format!("{}/ok", receiver_url), "events": ["user.created", "follow.created"] });
+    let res = post_json(&app, "/api/admin/webhooks", body.clone(), Some(&bob)).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let invalid = serde_json::json!({ "url": "ftp://example.com/", "events": ["user.created"] });
+    let res = post_json(&app, "/api/admin/webhooks", invalid, Some(&admin)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = post_json(&app, "/api/admin/webhooks", body, Some(&admin)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let created = body_json(res).await;
+    let (id, secret) = (
+        created["id"].as_u64().unwrap(),
+        created["secret"].as_str().unwrap(),
+    );
+
+    // aliceとbobの登録はWebhookの登録前なので配信しない
+    let carol = sign_up_and_log_in(&app, "carol").await;
+    let follow = serde_json::json!({ "name": "bob" });
+    let res = post_json(&app, "/api/follow_relations", follow, Some(&carol)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    // 購読していないイベント
+    let tweet = serde_json::json!({ "content": "hello" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&carol)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let runner = job_runner(&repository);
+    assert_eq!(
+        runner.run_due_jobs(Utc::now().naive_utc()).await.unwrap(),
+        2
+    );
+    let received = received.lock().unwrap().clone();
+    let events: Vec<serde_json::Value> = received
+        .iter()
+        .map(|(headers, body)| {
+            // 受信側と同じく、時刻と本文から署名を計算して比べる
+            let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+            assert_eq!(headers[SIGNATURE_HEADER], sign(secret, timestamp, body));
+            serde_json::from_str(body).unwrap()
+        })
+        .collect();
+    let mut summaries: Vec<(String, serde_json::Value)> = events
+        .iter()
+        .map(|event| {
+            (
+                event["event"].as_str().unwrap().to_string(),
+                event["data"].clone(),
+            )
+        })
+        .collect();
+    summaries.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        summaries,
+        vec![
+            (
+                "follow.created".to_string(),
+                serde_json::json!({ "follower_id": 3, "followee_id": 2 })
+            ),
+            (
+                "user.created".to_string(),
+                serde_json::json!({ "id": 3, "name": "carol" })
+            ),
+        ]
+    );
+
+    let deliveries = get_json(
+        &app,
+        &format!("/api/admin/webhooks/{}/deliveries", id),
+        &admin,
+    )
+    .await;
+    let deliveries = deliveries.as_array().unwrap();
+    assert_eq!(deliveries.len(), 2);
+    for delivery in deliveries {
+        assert_eq!(delivery["status"], "delivered");
+        assert_eq!(delivery["attempts"], 1);
+        assert_eq!(delivery["response_status"], 200);
+        assert!(delivery["delivered_at"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_then_dead_lettered() {
+    let (app, repository) = test_app_and_repository(ServerConfig::default()).await;
+    let (received, receiver_url) = start_receiver();
+    let admin = sign_up_and_log_in(&app, "alice").await;
+    repository
+        .set_user_role(None, 1, Role::Admin)
+        .await
+        .unwrap();
+    let body =
+        serde_json::json!({ "url": format!("{}/fail", receiver_url), "events": ["tweet.posted"] });
+    let res = post_json(&app, "/api/admin/webhooks", body, Some(&admin)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let id = body_json(res).await["id"].as_u64().unwrap();
+    let tweet = serde_json::json!({ "content": "hello" });
+    let res = post_json(&app, "/api/user_tweets", tweet, Some(&admin)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    // 再試行の間隔が延びていくので、時刻を進めながら実行する
+    let runner = job_runner(&repository);
+    let start = Utc::now().naive_utc();
+    let mut runs = 0;
+    for hours in 0..10 {
+        runs += runner
+            .run_due_jobs(start + Duration::hours(hours))
+            .await
+            .unwrap();
+    }
+    assert_eq!(runs, 5);
+    assert!(received.lock().unwrap().is_empty());
+
+    let uri = format!("/api/admin/webhooks/{}/deliveries", id);
+    let deliveries = get_json(&app, &uri, &admin).await;
+    assert_eq!(deliveries[0]["status"], "dead_lettered");
+    assert_eq!(deliveries[0]["attempts"], 5);
+    assert_eq!(deliveries[0]["response_status"], 500);
+    assert!(deliveries[0]["delivered_at"].is_null());
+
+    let dead_letters = get_json(&app, "/api/admin/webhook_dead_letters", &admin).await;
+    let dead_letters = dead_letters.as_array().unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0]["webhook_id"], id);
+    assert_eq!(dead_letters[0]["event"], "tweet.posted");
+    let payload: serde_json::Value =
+        serde_json::from_str(dead_letters[0]["payload"].as_str().unwrap()).unwrap();
+    assert_eq!(payload["data"]["content"], "hello");
+    assert!(dead_letters[0]["last_error"]
+        .as_str()
+        .unwrap()
+        .contains("unavailable"));
+
+    // Webhookを削除してもデッドレターは残る
+    let request = axum::http::Request::delete(format!("/api/admin/webhooks/{}", id))
+        .header(axum::http::header::COOKIE, &admin)
+        .body(axum::body::Body::empty())
+        .unwrap();
+    let res = common::send(&app, request).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let dead_letters = get_json(&app, "/api/admin/webhook_dead_letters", &admin).await;
+    assert_eq!(dead_letters.as_array().unwrap().len(), 1);
+}